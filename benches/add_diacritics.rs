@@ -0,0 +1,57 @@
+/*
+Compare `add_diacritics` with `add_diacritics_into` reusing its output buffer.
+
+The prediction cache is enabled so inference is skipped after the first round,
+which leaves nikud removal, rendering and allocations to measure.
+
+Run with:
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.int8.onnx -O phonikud.onnx
+    wget https://huggingface.co/dicta-il/dictabert-large-char-menaked/raw/main/tokenizer.json -O tokenizer.json
+    cargo bench --bench add_diacritics
+*/
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use phonikud_rs::Phonikud;
+
+const SENTENCES: [&str; 4] = [
+    "הכוח לשנות מתחיל ברגע שבו אתה מאמין שזה אפשרי!",
+    "שלום עולם",
+    "ביום שלישי בבוקר יצאנו לטיול ארוך בהרים שמצפון לעיר",
+    "הספר שקראתי אתמול היה מעניין מאוד",
+];
+
+fn add_diacritics(c: &mut Criterion) {
+    let mut phonikud = Phonikud::new("phonikud.onnx", "tokenizer.json")
+        .expect("No model found, see the top of benches/add_diacritics.rs");
+    phonikud.set_cache_capacity(SENTENCES.len());
+    for sentence in SENTENCES {
+        phonikud.add_diacritics(sentence).unwrap();
+    }
+
+    let mut group = c.benchmark_group("add_diacritics");
+    group.throughput(Throughput::Elements(SENTENCES.len() as u64));
+    group.bench_function("allocating", |b| {
+        b.iter(|| {
+            for sentence in SENTENCES {
+                black_box(phonikud.add_diacritics(black_box(sentence)).unwrap());
+            }
+        })
+    });
+
+    let mut out = String::new();
+    group.bench_function("into", |b| {
+        b.iter(|| {
+            for sentence in SENTENCES {
+                out.clear();
+                phonikud.add_diacritics_into(black_box(sentence), &mut out).unwrap();
+                black_box(&out);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, add_diacritics);
+criterion_main!(benches);
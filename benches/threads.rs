@@ -0,0 +1,77 @@
+/*
+Measure sentences per second at every intra-op thread count, for every model precision
+found, next to the count `Precision::default_intra_threads` picks.
+
+Inference dominates, so the cache is left disabled. The counts past which a precision
+stops gaining are the caps of the default policy.
+
+Run with:
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.onnx
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.int8.onnx
+    wget https://huggingface.co/dicta-il/dictabert-large-char-menaked/raw/main/tokenizer.json -O tokenizer.json
+    cargo bench --bench threads
+*/
+
+use std::{hint::black_box, path::Path, time::Duration};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use phonikud_rs::{Phonikud, Precision};
+
+const MODEL_PATH: &str = "phonikud-1.0.onnx";
+const TOKENIZER_PATH: &str = "tokenizer.json";
+const SENTENCES: [&str; 4] = [
+    "הכוח לשנות מתחיל ברגע שבו אתה מאמין שזה אפשרי!",
+    "ביום שלישי בבוקר יצאנו לטיול ארוך בהרים שמצפון לעיר",
+    "הספר שקראתי אתמול היה מעניין מאוד",
+    "הדייג נצמד לדופן הסירה בזמן הסערה.",
+];
+/// Time spent on every thread count, after a run to warm up
+const BUDGET: Duration = Duration::from_secs(3);
+
+fn threads(c: &mut Criterion) {
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let mut found = false;
+    for precision in [Precision::Fp32, Precision::Int8] {
+        let path = precision.model_path(MODEL_PATH);
+        if !Path::new(&path).exists() {
+            println!("{precision:?}: {path} not found, skipping");
+            continue;
+        }
+        found = true;
+        let default = precision.default_intra_threads();
+        println!("{precision:?} ({path}), default {default} of {cores} cores");
+        let mut group = c.benchmark_group(format!("{precision:?}"));
+        group.measurement_time(BUDGET);
+        group.throughput(Throughput::Elements(SENTENCES.len() as u64));
+        for threads in thread_counts(cores) {
+            let mut phonikud = Phonikud::builder(MODEL_PATH, TOKENIZER_PATH)
+                .with_precision(precision)
+                .with_intra_threads(threads)
+                .build()
+                .unwrap();
+            let name = if threads == default { "default threads" } else { "threads" };
+            group.bench_function(BenchmarkId::new(name, threads), |b| {
+                b.iter(|| {
+                    for sentence in SENTENCES {
+                        black_box(phonikud.add_diacritics(black_box(sentence)).unwrap());
+                    }
+                })
+            });
+        }
+        group.finish();
+    }
+    assert!(found, "No model found, see the top of benches/threads.rs");
+}
+
+/// Powers of two up to `cores`, and `cores` itself
+fn thread_counts(cores: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = (0..)
+        .map(|exponent| 1 << exponent)
+        .take_while(|&threads| threads < cores)
+        .collect();
+    counts.push(cores);
+    counts
+}
+
+criterion_group!(benches, threads);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+/*
+Measure every stage of the pipeline: tokenization, inference at several sequence lengths,
+decoding, and end-to-end sentences per second for every model precision found.
+
+Decoding is timed with the prediction cache enabled, so inference is skipped and the
+reconstruction of the vocalized text from the predictions is left to measure.
+Tokenization and inference report chars per second as elements, decoding and end to end
+report sentences.
+
+Run with:
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.onnx
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.int8.onnx
+    wget https://huggingface.co/dicta-il/dictabert-large-char-menaked/raw/main/tokenizer.json -O tokenizer.json
+    cargo bench --bench throughput
+*/
+
+use std::{hint::black_box, path::Path, time::Duration};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use phonikud_rs::{Phonikud, Precision};
+
+const MODEL_PATH: &str = "phonikud-1.0.onnx";
+const TOKENIZER_PATH: &str = "tokenizer.json";
+const SENTENCES: [&str; 4] = [
+    "הכוח לשנות מתחיל ברגע שבו אתה מאמין שזה אפשרי!",
+    "ביום שלישי בבוקר יצאנו לטיול ארוך בהרים שמצפון לעיר",
+    "הספר שקראתי אתמול היה מעניין מאוד",
+    "הדייג נצמד לדופן הסירה בזמן הסערה.",
+];
+/// Input lengths in chars, the longest fills a whole chunk
+const SEQUENCE_LENGTHS: [usize; 5] = [16, 64, 128, 256, 510];
+/// Time spent on every measurement, after a run to warm up
+const BUDGET: Duration = Duration::from_secs(3);
+
+fn throughput(c: &mut Criterion) {
+    let mut found = false;
+    for precision in [Precision::Fp32, Precision::Int8] {
+        let path = precision.model_path(MODEL_PATH);
+        if !Path::new(&path).exists() {
+            println!("{precision:?}: {path} not found, skipping");
+            continue;
+        }
+        found = true;
+        let mut phonikud = Phonikud::builder(MODEL_PATH, TOKENIZER_PATH)
+            .with_precision(precision)
+            .build()
+            .unwrap();
+        phonikud.warmup().unwrap();
+        stages(c, precision, &mut phonikud);
+    }
+    assert!(found, "No model found, see the top of benches/throughput.rs");
+}
+
+fn stages(c: &mut Criterion, precision: Precision, phonikud: &mut Phonikud) {
+    let paragraph = SENTENCES.join(" ");
+    let paragraph_chars = paragraph.chars().count() as u64;
+    let mut group = c.benchmark_group(format!("{precision:?}"));
+    group.measurement_time(BUDGET);
+
+    group.throughput(Throughput::Elements(paragraph_chars));
+    group.bench_function("tokenize", |b| {
+        b.iter(|| black_box(phonikud.tokenize(black_box(&paragraph)).unwrap()))
+    });
+
+    for length in SEQUENCE_LENGTHS {
+        let input: String = paragraph.chars().cycle().take(length).collect();
+        group.throughput(Throughput::Elements(length as u64));
+        group.bench_with_input(BenchmarkId::new("inference", length), &input, |b, input| {
+            b.iter(|| black_box(phonikud.run_raw(black_box(input)).unwrap()))
+        });
+    }
+
+    group.throughput(Throughput::Elements(SENTENCES.len() as u64));
+    phonikud.set_cache_capacity(SENTENCES.len());
+    group.bench_function("decode", |b| b.iter(|| sentences(phonikud)));
+    phonikud.set_cache_capacity(0);
+    group.bench_function("end to end", |b| b.iter(|| sentences(phonikud)));
+    group.finish();
+}
+
+fn sentences(phonikud: &mut Phonikud) {
+    for sentence in SENTENCES {
+        black_box(phonikud.add_diacritics(black_box(sentence)).unwrap());
+    }
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+fn main() {
+    // gRPC messages and service of proto/phonikud.proto, see src/grpc.rs
+    #[cfg(feature = "grpc")]
+    {
+        // protoc comes with the build dependencies, nothing has to be installed
+        let mut config = prost_build::Config::new();
+        config.protoc_executable(protoc_bin_vendored::protoc_bin_path().unwrap());
+        tonic_build::configure()
+            .compile_protos_with_config(config, &["proto/phonikud.proto"], &["proto"])
+            .unwrap();
+    }
+}
@@ -0,0 +1,25 @@
+/*
+Run with:
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.int8.onnx -O phonikud.onnx
+    wget https://huggingface.co/dicta-il/dictabert-large-char-menaked/raw/main/tokenizer.json -O tokenizer.json
+    cargo run --example confidence
+*/
+
+use phonikud_rs::{DiacriticsOptions, Phonikud};
+
+fn main() -> anyhow::Result<()> {
+    let model_path = "phonikud.onnx";
+    let tokenizer_path = "tokenizer.json";
+
+    let mut phonikud = Phonikud::new(model_path, tokenizer_path)?;
+
+    let text = "הדייג נצמד לדופן הסירה בזמן הסערה.";
+    let report = phonikud.add_diacritics_with_confidence(text, &DiacriticsOptions::default(), 0.9)?;
+
+    println!("Output: {}", report.text);
+    for word in report.low_confidence {
+        println!("Review: {} ({:.2})", &report.text[word.range], word.confidence);
+    }
+
+    Ok(())
+}
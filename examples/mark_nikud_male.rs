@@ -7,7 +7,7 @@ Run with:
 See https://en.wikipedia.org/wiki/Unicode_and_HTML_for_the_Hebrew_alphabet#Compact_table for Hebrew unicode values
 */
 
-use phonikud_rs::Phonikud;
+use phonikud_rs::{DiacriticsOptions, Phonikud};
 
 fn main() -> anyhow::Result<()> {
     let model_path = "phonikud.onnx";
@@ -16,7 +16,11 @@ fn main() -> anyhow::Result<()> {
     let mut phonikud = Phonikud::new(model_path, tokenizer_path)?;
 
     let text = "הדייג נצמד לדופן הסירה בזמן הסערה.";
-    let vocalized = phonikud.add_diacritics_with_options(text, Some("\u{05af}"))?; 
+    let options = DiacriticsOptions {
+        mark_matres_lectionis: Some("\u{05af}".to_string()),
+        ..Default::default()
+    };
+    let vocalized = phonikud.add_diacritics_with_options(text, &options)?;
 
     println!("Input: {}", text);
     println!("Output: {}", vocalized);
@@ -0,0 +1,67 @@
+/*
+Measure the latency of every model precision and how often it agrees with fp32.
+
+Run with:
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.onnx
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.int8.onnx
+    wget https://huggingface.co/dicta-il/dictabert-large-char-menaked/raw/main/tokenizer.json -O tokenizer.json
+    cargo run --release --example precision
+*/
+
+use std::{path::Path, time::Instant};
+
+use phonikud_rs::{Phonikud, Precision};
+
+const SENTENCES: [&str; 4] = [
+    "הכוח לשנות מתחיל ברגע שבו אתה מאמין שזה אפשרי!",
+    "ביום שלישי בבוקר יצאנו לטיול ארוך בהרים שמצפון לעיר",
+    "הספר שקראתי אתמול היה מעניין מאוד",
+    "הדייג נצמד לדופן הסירה בזמן הסערה.",
+];
+
+fn main() -> anyhow::Result<()> {
+    let model_path = "phonikud-1.0.onnx";
+    let tokenizer_path = "tokenizer.json";
+
+    let mut reference = Vec::new();
+    for precision in [Precision::Fp32, Precision::Fp16, Precision::Int8] {
+        let path = precision.model_path(model_path);
+        if !Path::new(&path).exists() {
+            println!("{precision:?}: {path} not found, skipping");
+            continue;
+        }
+        let mut phonikud = Phonikud::builder(model_path, tokenizer_path)
+            .with_precision(precision)
+            .build()?;
+        phonikud.warmup()?;
+
+        let start = Instant::now();
+        let vocalized = SENTENCES
+            .iter()
+            .map(|sentence| phonikud.add_diacritics(sentence))
+            .collect::<Result<Vec<_>, _>>()?;
+        let latency = start.elapsed() / SENTENCES.len() as u32;
+
+        if reference.is_empty() {
+            reference = vocalized.clone();
+        }
+        let agreeing = vocalized
+            .iter()
+            .zip(&reference)
+            .flat_map(|(output, expected)| {
+                output.split_whitespace().zip(expected.split_whitespace())
+            })
+            .filter(|(word, expected)| word == expected)
+            .count();
+        let words: usize = reference
+            .iter()
+            .map(|sentence| sentence.split_whitespace().count())
+            .sum();
+        println!(
+            "{precision:?}: {latency:?} per sentence, {:.1}% of words match the first precision found",
+            100.0 * agreeing as f64 / words as f64
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+/*
+Run with:
+    wget https://huggingface.co/thewh1teagle/phonikud-onnx/resolve/main/phonikud-1.0.int8.onnx -O phonikud.onnx
+    wget https://huggingface.co/dicta-il/dictabert-large-char-menaked/raw/main/tokenizer.json -O tokenizer.json
+    cargo run --example tts --features tts
+
+Prints one line of phonemes per sentence, the input of a Piper voice in phoneme mode.
+With piper-rs, hand every line to the voice instead of printing it.
+*/
+
+use phonikud_rs::{
+    Phonikud,
+    tts::{PhonemeProvider, TtsOptions},
+};
+
+/// Synthesize `text` with any frontend, e.g. to compare phonikud with espeak-ng
+fn speak(frontend: &mut impl PhonemeProvider, text: &str) -> anyhow::Result<()> {
+    for sentence in frontend.phonemize_sentences(text, &TtsOptions::default())? {
+        println!("{}\t{}", &text[sentence.range].trim(), sentence.phonemes);
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut phonikud = Phonikud::new("phonikud.onnx", "tokenizer.json")?;
+    speak(&mut phonikud, "שלום עולם. מה שלומך היום? אני בסדר, תודה!")
+}
@@ -0,0 +1,134 @@
+//! Render mocked predictions over arbitrary text, as the model output is reconstructed.
+//!
+//! The predictions fall on arbitrary byte offsets of the text without nikud, like those
+//! of a tokenizer with byte-level offsets, so slicing between them can hit any byte of
+//! combining marks, bidi controls and other multi-byte chars. They must be moved onto
+//! their char, the text keeping its letters.
+//!
+//! Run with:
+//!     cargo +nightly fuzz run reconstruct
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use phonikud_rs::{
+    DiacriticsOptions, StressPlacement, TokenPrediction, Vocalization,
+    acronym::AcronymMode,
+    text::{MarkOrder, remove_nikud},
+};
+
+/// Marks of the nikud classes, along with matres lectionis
+const NIKUD: &[&str] = &[
+    "",
+    "\u{05b0}",
+    "\u{05b4}",
+    "\u{05b7}",
+    "\u{05b8}",
+    "\u{05b9}",
+    "\u{05bc}",
+    "\u{05bb}",
+    "\u{05bc}\u{05b8}",
+    "\u{05c7}",
+    "<MAT_LECT>",
+];
+const SHIN: &[&str] = &["", "\u{05c1}", "\u{05c2}"];
+const PLACEMENTS: &[StressPlacement] = &[
+    StressPlacement::AfterVowel,
+    StressPlacement::BeforeVowel,
+    StressPlacement::AfterCluster,
+    StressPlacement::VowelLetter,
+];
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    text: String,
+    predictions: Vec<MockPrediction>,
+    options: MockOptions,
+}
+
+#[derive(Debug, Arbitrary)]
+struct MockPrediction {
+    /// Byte offset in the text without nikud, wrapped around its length
+    offset: u16,
+    nikud: u8,
+    shin: Option<u8>,
+    stress: bool,
+    vocal_shva: bool,
+    prefix: bool,
+}
+
+#[derive(Debug, Arbitrary)]
+struct MockOptions {
+    dagesh: bool,
+    ktiv_haser: bool,
+    mark_matres: bool,
+    placement: u8,
+    hebrew_order: bool,
+    protect_acronyms: bool,
+}
+
+impl MockOptions {
+    fn to_options(&self) -> DiacriticsOptions {
+        DiacriticsOptions {
+            dagesh: self.dagesh,
+            ktiv_haser: self.ktiv_haser,
+            mark_matres_lectionis: self.mark_matres.then(|| "\u{05af}".to_string()),
+            stress_placement: PLACEMENTS[self.placement as usize % PLACEMENTS.len()],
+            mark_order: if self.hebrew_order {
+                MarkOrder::Hebrew
+            } else {
+                MarkOrder::Model
+            },
+            acronyms: if self.protect_acronyms {
+                AcronymMode::Protect
+            } else {
+                AcronymMode::Vocalize
+            },
+            ..Default::default()
+        }
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let clean_text = remove_nikud(&input.text);
+    let options = input.options.to_options();
+
+    // Sorted like the predictions of the model, but overlapping and inside chars
+    let mut predictions: Vec<TokenPrediction> = Vec::new();
+    let mut offsets: Vec<(usize, &MockPrediction)> = input
+        .predictions
+        .iter()
+        .map(|mock| (mock.offset as usize % (clean_text.len() + 1), mock))
+        .collect();
+    offsets.sort_by_key(|&(start, _)| start);
+    for (start, mock) in offsets {
+        let char = clean_text.get(start..).and_then(|rest| rest.chars().next());
+        let char = char.unwrap_or('ש');
+        let nikud_class = mock.nikud as usize % NIKUD.len();
+        let shin_class = mock.shin.map(|shin| shin as usize % SHIN.len());
+        predictions.push(TokenPrediction {
+            char,
+            start,
+            end: start + char.len_utf8(),
+            nikud_class,
+            confidence: 1.0,
+            nikud: NIKUD[nikud_class].to_string(),
+            shin_class,
+            shin: shin_class.map(|class| SHIN[class].to_string()),
+            stress: mock.stress,
+            vocal_shva: mock.vocal_shva,
+            prefix: mock.prefix,
+            nikud_logits: vec![0.0; NIKUD.len()],
+            shin_logits: vec![0.0; SHIN.len()],
+            additional_logits: [0.0; 3],
+        });
+    }
+
+    let vocalization = Vocalization::render(&clean_text, predictions, &options);
+    vocalization.offset_mapping(&clean_text);
+    // Only marks are added, unless letters are dropped for the deficient spelling
+    if !options.ktiv_haser {
+        assert_eq!(remove_nikud(&vocalization.text), clean_text);
+    }
+});
@@ -0,0 +1,41 @@
+#ifndef PHONIKUD_H
+#define PHONIKUD_H
+
+/* C API of phonikud-rs, built with `cargo build --release --features ffi`.
+ * All strings are NUL terminated UTF-8. */
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Phonikud Phonikud;
+
+typedef enum PhonikudStatus {
+    PHONIKUD_OK = 0,
+    PHONIKUD_NULL_ARGUMENT = 1,
+    PHONIKUD_INVALID_UTF8 = 2,
+    PHONIKUD_MODEL_LOAD = 3,
+    PHONIKUD_INFERENCE = 4,
+    PHONIKUD_INPUT_TOO_LONG = 5,
+    PHONIKUD_ERROR = 6,
+    PHONIKUD_PANIC = 7,
+} PhonikudStatus;
+
+/* Load a model, `*out` receives the handle on success. */
+PhonikudStatus phonikud_new(const char *model_path, const char *tokenizer_path, Phonikud **out);
+
+/* Vocalize `text`, `*out` receives a string to release with phonikud_string_free. */
+PhonikudStatus phonikud_add_diacritics(Phonikud *phonikud, const char *text, char **out);
+
+/* Message of the last error on the calling thread, or NULL. Valid until the next call. */
+const char *phonikud_last_error(void);
+
+void phonikud_string_free(char *string);
+
+void phonikud_free(Phonikud *phonikud);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,206 @@
+//! Detect Hebrew acronyms written with gershayim, e.g. `צה"ל`, and expand or protect them
+
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use crate::{
+    hebrew::is_hebrew_letter,
+    normalize::{Alignment, GERESH_FORMS as GERESH, GERSHAYIM_FORMS as GERSHAYIM, Normalized},
+    text::{is_nikud, remove_nikud},
+};
+
+/// What to do with acronyms when vocalizing
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AcronymMode {
+    /// Vocalize acronyms like any other word
+    #[default]
+    Vocalize,
+    /// Copy acronyms to the output without nikud
+    Protect,
+    /// Replace known acronyms with their spoken form before vocalizing and protect the rest
+    Expand(Arc<Acronyms>),
+}
+
+/// Maps acronyms to their spoken form
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Acronyms {
+    entries: HashMap<String, String>,
+}
+
+/// Single letter prefixes that may precede an acronym, e.g. `בצה"ל`
+const PREFIX_LETTERS: &[char] = &['ו', 'ה', 'ב', 'כ', 'ל', 'מ', 'ש'];
+
+impl Acronyms {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A small set of very common acronyms
+    pub fn common() -> Self {
+        [
+            ("ד\"ר", "דוקטור"),
+            ("עו\"ד", "עורך דין"),
+            ("רו\"ח", "רואה חשבון"),
+            ("ת\"א", "תל אביב"),
+            ("ארה\"ב", "ארצות הברית"),
+            ("בע\"מ", "בערבון מוגבל"),
+            ("ש\"ח", "שקלים"),
+            ("ק\"מ", "קילומטר"),
+            ("ק\"ג", "קילוגרם"),
+            ("מ\"מ", "מילימטר"),
+            ("ס\"מ", "סנטימטר"),
+            ("וכו'", "וכולי"),
+            ("בס\"ד", "בסייעתא דשמיא"),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    /// Speak `acronym` as `spoken`, any form of gershayim may be used in `acronym`, see
+    /// [`crate::normalize::normalize_geresh`]
+    pub fn insert(&mut self, acronym: &str, spoken: &str) -> Option<String> {
+        self.entries.insert(key(acronym), spoken.to_string())
+    }
+
+    /// Look up `word`, also after removing up to two single letter prefixes
+    pub fn get(&self, word: &str) -> Option<String> {
+        let word = key(word);
+        let mut rest = word.as_str();
+        for _ in 0..3 {
+            if let Some(spoken) = self.entries.get(rest) {
+                return Some(format!("{}{spoken}", &word[..word.len() - rest.len()]));
+            }
+            rest = rest.strip_prefix(PREFIX_LETTERS)?;
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<A: AsRef<str>, S: AsRef<str>> FromIterator<(A, S)> for Acronyms {
+    fn from_iter<I: IntoIterator<Item = (A, S)>>(iter: I) -> Self {
+        let mut acronyms = Self::new();
+        for (acronym, spoken) in iter {
+            acronyms.insert(acronym.as_ref(), spoken.as_ref());
+        }
+        acronyms
+    }
+}
+
+/// Whether `word` looks like an acronym: Hebrew letters with gershayim before the last letter
+pub fn is_acronym(word: &str) -> bool {
+    let word = remove_nikud(word);
+    let Some((head, tail)) = word.split_once(GERSHAYIM) else {
+        return false;
+    };
+    let mut tail = tail.chars();
+    !head.is_empty()
+        && head.chars().all(is_hebrew_letter)
+        && tail.next().is_some_and(is_hebrew_letter)
+        && tail.next().is_none()
+}
+
+/// Replace the acronyms of `text` found in `acronyms` with their spoken form
+pub fn expand_acronyms(text: &str, acronyms: &Acronyms) -> Normalized {
+    let mut result = String::with_capacity(text.len() * 2);
+    let mut alignments = Vec::new();
+    let mut prev_index = 0;
+
+    for original in word_ranges(text) {
+        let Some(spoken) = acronyms.get(&text[original.clone()]) else {
+            continue;
+        };
+        result.push_str(&text[prev_index..original.start]);
+        let start = result.len();
+        result.push_str(&spoken);
+        alignments.push(Alignment {
+            normalized: start..result.len(),
+            original: original.clone(),
+        });
+        prev_index = original.end;
+    }
+    result.push_str(&text[prev_index..]);
+
+    Normalized {
+        text: result,
+        alignments,
+    }
+}
+
+/// Byte ranges of the acronyms in `text`
+#[cfg(feature = "model")]
+pub(crate) fn acronym_ranges(text: &str) -> Vec<Range<usize>> {
+    word_ranges(text)
+        .filter(|range| is_acronym(&text[range.clone()]))
+        .collect()
+}
+
+/// Words made of Hebrew letters, nikud, geresh and gershayim, without leading or trailing quotes
+fn word_ranges(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut word_start = None;
+    text.char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+        .filter_map(move |(idx, ch)| {
+            if is_hebrew_letter(ch)
+                || is_nikud(ch)
+                || GERSHAYIM.contains(&ch)
+                || GERESH.contains(&ch)
+            {
+                word_start.get_or_insert(idx);
+                return None;
+            }
+            let start = word_start.take()?;
+            let word = &text[start..idx];
+            let trimmed = word
+                .trim_start_matches(GERSHAYIM)
+                .trim_start_matches(GERESH);
+            let start = start + word.len() - trimmed.len();
+            // Keep a trailing geresh, it marks abbreviations like `וכו'`
+            let end = start + trimmed.trim_end_matches(GERSHAYIM).len();
+            (start < end).then_some(start..end)
+        })
+}
+
+/// `acronym` without nikud, its geresh and gershayim written in ASCII
+fn key(acronym: &str) -> String {
+    remove_nikud(acronym)
+        .chars()
+        .map(|ch| match ch {
+            ch if GERSHAYIM.contains(&ch) => '"',
+            ch if GERESH.contains(&ch) => '\'',
+            ch => ch,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typographic_gershayim_mark_acronyms() {
+        let acronyms = Acronyms::common();
+        for word in ["צה\"ל", "צה\u{05f4}ל", "צה\u{201d}ל", "צה\u{201c}ל"] {
+            assert!(is_acronym(word), "{word}");
+        }
+        for word in ["עו\u{201d}ד", "לעו\u{05f4}ד", "וכו\u{2019}"] {
+            assert!(acronyms.get(word).is_some(), "{word}");
+        }
+        let expanded = expand_acronyms("פגישה עם עו\u{201d}ד היום", &acronyms);
+        assert_eq!(expanded.text, "פגישה עם עורך דין היום");
+        let text = "\u{201c}צה\u{201d}ל\u{201d}";
+        // Quoted, the quotes around it aren't part of it
+        let words: Vec<&str> = word_ranges(text).map(|range| &text[range]).collect();
+        assert_eq!(words, ["צה\u{201d}ל"]);
+    }
+}
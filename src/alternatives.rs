@@ -0,0 +1,332 @@
+use std::ops::Range;
+
+use crate::{
+    DiacriticsOptions, Phonikud, PhonikudModel, Result, TokenPrediction, constraints,
+    model::{render, replacements, retain_unreplaced, word_range},
+    text::remove_nikud_with_offsets,
+};
+
+/// The most likely vocalizations of a word, best first
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WordAlternatives {
+    /// Byte range of the word in the input text
+    pub range: Range<usize>,
+    pub alternatives: Vec<Alternative>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Alternative {
+    /// The vocalized word
+    pub text: String,
+    /// Joint probability of the nikud and shin classes of every letter, 1 for words the
+    /// lexicon or acronyms replace
+    pub score: f32,
+}
+
+/// A partial vocalization of a word: the chosen nikud and shin class per letter
+#[derive(Clone)]
+struct Beam {
+    classes: Vec<(usize, Option<usize>)>,
+    log_prob: f32,
+}
+
+impl PhonikudModel {
+    /// Return the `k` most likely vocalizations of every word of `text`.
+    ///
+    /// The candidates come from a beam search over the per-letter nikud and
+    /// shin probabilities. Alternatives that render to the same text are merged,
+    /// so a word may have fewer than `k`. Words the lexicon or acronyms of `options`
+    /// replace have their replacement as the only alternative, as
+    /// [`PhonikudModel::vocalize`] renders them.
+    pub fn top_k(
+        &mut self,
+        text: &str,
+        k: usize,
+        options: &DiacriticsOptions,
+    ) -> Result<Vec<WordAlternatives>> {
+        let (clean_text, offset_map) = remove_nikud_with_offsets(text);
+        let mut predictions = self.predict_with_options(&clean_text, options)?;
+        let replacements = replacements(&clean_text, options);
+        retain_unreplaced(&mut predictions, &replacements);
+
+        let mut words = Vec::new();
+        for (range, predictions) in group_words(&clean_text, &predictions, &replacements) {
+            let word = &clean_text[range.clone()];
+            let word_replacements: Vec<(Range<usize>, &str)> = replacements
+                .iter()
+                .filter(|(replaced, _)| range.start <= replaced.start && replaced.end <= range.end)
+                .map(|(replaced, with)| {
+                    (
+                        replaced.start - range.start..replaced.end - range.start,
+                        *with,
+                    )
+                })
+                .collect();
+            let alternatives = if predictions.is_empty() {
+                let (text, _) = render(word, &[], &word_replacements, options);
+                let replaced = Alternative { text, score: 1.0 };
+                std::iter::once(replaced).take(k).collect()
+            } else {
+                self.alternatives(
+                    word,
+                    range.start,
+                    predictions,
+                    &word_replacements,
+                    k,
+                    options,
+                )
+            };
+            words.push(WordAlternatives {
+                range: offset_map[range.start]..offset_map[range.end],
+                alternatives,
+            });
+        }
+
+        Ok(words)
+    }
+
+    /// The `k` best renderings of `word` at `word_start`, from the predictions of its letters
+    fn alternatives(
+        &self,
+        word: &str,
+        word_start: usize,
+        predictions: &[TokenPrediction],
+        replacements: &[(Range<usize>, &str)],
+        k: usize,
+        options: &DiacriticsOptions,
+    ) -> Vec<Alternative> {
+        let classes = self.classes();
+        // The classes `vocalize` may pick under its orthography constraints
+        let allowed = |prediction: &TokenPrediction, class: usize| {
+            let end = prediction.end - word_start;
+            !options.orthography_constraints
+                || classes
+                    .nikud
+                    .get(class)
+                    .is_some_and(|marks| constraints::allows(word, prediction.char, end, marks))
+        };
+        let mut alternatives: Vec<Alternative> = Vec::with_capacity(k);
+        for beam in beam_search(predictions, k.saturating_mul(2), allowed) {
+            let letters: Vec<TokenPrediction> = predictions
+                .iter()
+                .zip(&beam.classes)
+                .map(|(prediction, &(nikud_class, shin_class))| {
+                    self.with_classes(prediction, word_start, nikud_class, shin_class)
+                })
+                .collect();
+            let (text, _) = render(word, &letters, replacements, options);
+            if alternatives.len() < k && alternatives.iter().all(|alt| alt.text != text) {
+                alternatives.push(Alternative {
+                    text,
+                    score: beam.log_prob.exp(),
+                });
+            }
+        }
+        alternatives
+    }
+
+    /// Copy of `prediction` relative to a word starting at `word_start`, with other classes
+    fn with_classes(
+        &self,
+        prediction: &TokenPrediction,
+        word_start: usize,
+        nikud_class: usize,
+        shin_class: Option<usize>,
+    ) -> TokenPrediction {
+        let classes = self.classes();
+        TokenPrediction {
+            start: prediction.start - word_start,
+            end: prediction.end - word_start,
+            nikud_class,
+            nikud: classes.nikud[nikud_class].clone(),
+            shin_class,
+            shin: shin_class.map(|class| classes.shin[class].clone()),
+            ..prediction.clone()
+        }
+    }
+}
+
+impl Phonikud {
+    /// Return the `k` best vocalizations of every word, e.g. to offer alternatives in an editor
+    pub fn top_k_alternatives(&mut self, text: &str, k: usize) -> Result<Vec<WordAlternatives>> {
+        self.inner.top_k(text, k, &DiacriticsOptions::default())
+    }
+}
+
+/// Whitespace delimited words of `text` with a prediction or a replacement, along with
+/// the predictions of their letters
+fn group_words<'a>(
+    text: &str,
+    predictions: &'a [TokenPrediction],
+    replacements: &[(Range<usize>, &str)],
+) -> Vec<(Range<usize>, &'a [TokenPrediction])> {
+    let spans = predictions
+        .iter()
+        .map(|prediction| prediction.start..prediction.end);
+    let mut ranges: Vec<Range<usize>> = spans
+        .chain(replacements.iter().map(|(range, _)| range.clone()))
+        .map(|span| word_range(text, span))
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+    ranges.dedup();
+    ranges
+        .into_iter()
+        .map(|range| {
+            let first = predictions.partition_point(|prediction| prediction.start < range.start);
+            let last = predictions.partition_point(|prediction| prediction.start < range.end);
+            (range, &predictions[first..last])
+        })
+        .collect()
+}
+
+/// Keep the `width` most likely class sequences after every letter of the word, with
+/// the nikud classes `allowed` for the letter
+fn beam_search(
+    predictions: &[TokenPrediction],
+    width: usize,
+    allowed: impl Fn(&TokenPrediction, usize) -> bool,
+) -> Vec<Beam> {
+    let mut beams = vec![Beam {
+        classes: Vec::with_capacity(predictions.len()),
+        log_prob: 0.0,
+    }];
+
+    for prediction in predictions {
+        let nikud = top_classes(&prediction.nikud_logits, width, |class| {
+            allowed(prediction, class)
+        });
+        let shin = match prediction.shin_class {
+            Some(_) => top_classes(&prediction.shin_logits, width, |_| true)
+                .into_iter()
+                .map(|(class, log_prob)| (Some(class), log_prob))
+                .collect(),
+            None => vec![(None, 0.0)],
+        };
+
+        let mut next = Vec::with_capacity(beams.len() * nikud.len() * shin.len());
+        for beam in &beams {
+            for &(nikud_class, nikud_log_prob) in &nikud {
+                for &(shin_class, shin_log_prob) in &shin {
+                    let mut classes = beam.classes.clone();
+                    classes.push((nikud_class, shin_class));
+                    next.push(Beam {
+                        classes,
+                        log_prob: beam.log_prob + nikud_log_prob + shin_log_prob,
+                    });
+                }
+            }
+        }
+        next.sort_by(|a, b| b.log_prob.total_cmp(&a.log_prob));
+        next.truncate(width);
+        beams = next;
+    }
+
+    beams
+}
+
+/// The `n` classes `allowed` with the highest log softmax probability, best first. They
+/// are all kept when none is allowed, as [`constraints::apply`] keeps the prediction.
+fn top_classes(logits: &[f32], n: usize, allowed: impl Fn(usize) -> bool) -> Vec<(usize, f32)> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = logits
+        .iter()
+        .map(|&logit| (logit - max).exp())
+        .sum::<f32>()
+        .ln();
+    let mut classes: Vec<(usize, f32)> = logits
+        .iter()
+        .enumerate()
+        .map(|(class, &logit)| (class, logit - max - log_sum))
+        .collect();
+    if classes.iter().any(|&(class, _)| allowed(class)) {
+        classes.retain(|&(class, _)| allowed(class));
+    }
+    classes.sort_by(|a, b| b.1.total_cmp(&a.1));
+    classes.truncate(n);
+    classes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hebrew::is_hebrew_letter;
+
+    fn prediction(char: char, start: usize) -> TokenPrediction {
+        TokenPrediction {
+            char,
+            start,
+            end: start + char.len_utf8(),
+            nikud_class: 0,
+            confidence: 1.0,
+            nikud: String::new(),
+            shin_class: None,
+            shin: None,
+            stress: false,
+            vocal_shva: false,
+            prefix: false,
+            nikud_logits: Vec::new(),
+            shin_logits: Vec::new(),
+            additional_logits: [0.0; 3],
+        }
+    }
+
+    #[test]
+    fn replaced_words_are_grouped_without_predictions() {
+        let text = "ספר צה\"ל שלום,";
+        let acronym = text.find('צ').unwrap()..text.find(" ש").unwrap();
+        let replacements = [(acronym.clone(), "צה\"ל")];
+        let predictions: Vec<TokenPrediction> = text
+            .char_indices()
+            .filter(|&(idx, ch)| is_hebrew_letter(ch) && !acronym.contains(&idx))
+            .map(|(idx, ch)| prediction(ch, idx))
+            .collect();
+
+        let words = group_words(text, &predictions, &replacements);
+        let found: Vec<(&str, usize)> = words
+            .iter()
+            .map(|(range, predictions)| (&text[range.clone()], predictions.len()))
+            .collect();
+        assert_eq!(found, [("ספר", 3), ("צה\"ל", 0), ("שלום,", 4)]);
+    }
+
+    #[test]
+    fn forbidden_classes_are_left_out_of_the_beams() {
+        let classes = crate::ClassVocab::default();
+        let class = |marks: &str| classes.nikud.iter().position(|c| c == marks).unwrap();
+        let (dagesh, qamats) = (class("\u{05bc}"), class("\u{05b8}"));
+        let logits = |best: usize| {
+            let mut logits = vec![0.0; classes.nikud.len()];
+            logits[best] = 5.0;
+            logits
+        };
+        // A dagesh in a guttural and a vowel under a final mem
+        let word = "עם";
+        let predictions = [
+            TokenPrediction {
+                nikud_logits: logits(dagesh),
+                ..prediction('ע', 0)
+            },
+            TokenPrediction {
+                nikud_logits: logits(qamats),
+                ..prediction('ם', 2)
+            },
+        ];
+
+        let beams = beam_search(&predictions, 4, |_, _| true);
+        assert_eq!(beams[0].classes, [(dagesh, None), (qamats, None)]);
+
+        let allowed = |prediction: &TokenPrediction, class: usize| {
+            constraints::allows(word, prediction.char, prediction.end, &classes.nikud[class])
+        };
+        let beams = beam_search(&predictions, 4, allowed);
+        assert_eq!(beams.len(), 4);
+        assert_eq!(beams[0].classes, [(class(""), None), (class(""), None)]);
+        for beam in &beams {
+            for (prediction, &(nikud_class, _)) in predictions.iter().zip(&beam.classes) {
+                assert!(allowed(prediction, nikud_class));
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::Range;
+
+use crate::{DiacriticsOptions, Phonikud, Result, Vocalization, phonemize::is_mark};
+
+/// A piece of the vocalized text and what put it there, for editors highlighting what
+/// the model added
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnotatedSpan {
+    /// Byte range in the vocalized text
+    pub range: Range<usize>,
+    pub kind: SpanKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SpanKind {
+    /// Text copied from the input, letters and the marks they already had included
+    Untouched,
+    /// Vowel points, dagesh and shin dots predicted by the model, along with the matres
+    /// lectionis mark
+    Nikud,
+    /// The stress mark of [`DiacriticsOptions::stress_mark`]
+    Stress,
+    /// The vocal shva mark of [`DiacriticsOptions::vocal_shva_mark`], or what replaces the
+    /// shva with [`DiacriticsOptions::vocal_shva_style`]
+    VocalShva,
+    /// The prefix boundary of [`DiacriticsOptions::prefix_mark`]
+    Prefix,
+    /// Text written differently from the input without the model, such as the words of
+    /// the lexicon and expanded acronyms
+    Replaced,
+}
+
+impl Vocalization {
+    /// Split the vocalized text into spans telling the text of `input` from the marks the
+    /// model added to it. `input` is the text this vocalization was made from and
+    /// `options` the ones it was rendered with.
+    ///
+    /// Spans are sorted and cover the text without gaps, neighbours of the same kind are
+    /// merged.
+    pub fn annotated_spans(&self, input: &str, options: &DiacriticsOptions) -> Vec<AnnotatedSpan> {
+        let vocal_shva = match options.vocal_shva_style.replacement() {
+            Some(replacement) => Some(replacement),
+            None => options.vocal_shva_mark.as_deref(),
+        };
+        let marks = [
+            (options.stress_mark.as_deref(), SpanKind::Stress),
+            (vocal_shva, SpanKind::VocalShva),
+            (options.prefix_mark.as_deref(), SpanKind::Prefix),
+            (options.mark_matres_lectionis.as_deref(), SpanKind::Nikud),
+        ];
+        let marks: Vec<(&str, SpanKind)> = marks
+            .into_iter()
+            .filter_map(|(mark, kind)| Some((mark.filter(|mark| !mark.is_empty())?, kind)))
+            .collect();
+
+        let mut annotated = Vec::new();
+        let mut spans = self.spans.iter().zip(&self.predictions).peekable();
+        for mapping in self.offset_mapping(input) {
+            let output = mapping.output;
+            // Letters dropped for the deficient spelling have empty spans
+            while spans.next_if(|(span, _)| span.is_empty()).is_some() {}
+            if output.is_empty() {
+                continue;
+            }
+            if let Some((_, prediction)) = spans.next_if(|(span, _)| **span == output) {
+                let vocal_shva = prediction.vocal_shva;
+                annotate_letter(&self.text, output, &marks, vocal_shva, &mut annotated);
+            } else if self.text[output.clone()] == input[mapping.input] {
+                push(&mut annotated, output, SpanKind::Untouched);
+            } else {
+                push(&mut annotated, output, SpanKind::Replaced);
+            }
+        }
+        annotated
+    }
+}
+
+impl Phonikud {
+    /// Vocalize `text` and annotate every piece of the output, see
+    /// [`Vocalization::annotated_spans`]
+    pub fn add_diacritics_with_annotations(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<(String, Vec<AnnotatedSpan>)> {
+        let vocalization = self.vocalize(text, options)?;
+        let annotated = vocalization.annotated_spans(text, options);
+        Ok((vocalization.text, annotated))
+    }
+}
+
+/// Annotate the rendering of a predicted letter, the letter and the marks it carried from
+/// the input being untouched. The vocal shva mark only counts on a letter predicted to
+/// have a `vocal_shva`, a hataf segol written for one is a predicted vowel elsewhere.
+fn annotate_letter(
+    text: &str,
+    span: Range<usize>,
+    marks: &[(&str, SpanKind)],
+    vocal_shva: bool,
+    annotated: &mut Vec<AnnotatedSpan>,
+) {
+    let letter_len = text[span.start..].chars().next().map_or(0, char::len_utf8);
+    push(
+        annotated,
+        span.start..span.start + letter_len,
+        SpanKind::Untouched,
+    );
+    let mut idx = span.start + letter_len;
+    while idx < span.end {
+        let rest = &text[idx..span.end];
+        let mark = marks.iter().find(|&&(mark, kind)| {
+            rest.starts_with(mark) && (kind != SpanKind::VocalShva || vocal_shva)
+        });
+        let (len, kind) = match mark {
+            Some(&(mark, kind)) => (mark.len(), kind),
+            None => {
+                let ch = rest.chars().next().unwrap_or_default();
+                (ch.len_utf8(), mark_kind(ch))
+            }
+        };
+        push(annotated, idx..idx + len, kind);
+        idx += len;
+    }
+}
+
+/// Kind of a mark written after a predicted letter that isn't one of the configured marks.
+/// Points are predicted, the taamim and the marks of other scripts come from the input.
+fn mark_kind(ch: char) -> SpanKind {
+    if is_mark(ch) && ch >= '\u{05b0}' {
+        SpanKind::Nikud
+    } else {
+        SpanKind::Untouched
+    }
+}
+
+/// Append a span, extending the last one when it has the same kind
+fn push(annotated: &mut Vec<AnnotatedSpan>, range: Range<usize>, kind: SpanKind) {
+    match annotated.last_mut() {
+        Some(last) if last.kind == kind && last.range.end == range.start => {
+            last.range.end = range.end
+        }
+        _ => annotated.push(AnnotatedSpan { range, kind }),
+    }
+}
@@ -0,0 +1,103 @@
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+
+use crate::{DiacriticsOptions, Phonikud, PhonikudError, Result, Vocalization};
+
+struct Request {
+    text: String,
+    options: DiacriticsOptions,
+    reply: oneshot::Sender<Result<Vocalization>>,
+}
+
+/// Cloneable async handle to a [`Phonikud`] running on a dedicated worker thread.
+///
+/// Requests wait in a bounded queue, so callers are suspended instead of piling up
+/// when the worker is busy, or rejected with [`AsyncPhonikud::reject_when_full`].
+/// Inference never blocks the async runtime.
+#[derive(Clone)]
+pub struct AsyncPhonikud {
+    sender: mpsc::Sender<Request>,
+    capacity: usize,
+    reject_when_full: bool,
+}
+
+impl AsyncPhonikud {
+    /// Move `phonikud` to a worker thread accepting up to `queue_capacity` pending requests
+    pub fn new(mut phonikud: Phonikud, queue_capacity: usize) -> Self {
+        let capacity = queue_capacity.max(1);
+        let (sender, mut receiver) = mpsc::channel::<Request>(capacity);
+
+        std::thread::spawn(move || {
+            while let Some(request) = receiver.blocking_recv() {
+                let result = phonikud.vocalize(&request.text, &request.options);
+                // The caller may have given up waiting
+                let _ = request.reply.send(result);
+            }
+        });
+
+        Self {
+            sender,
+            capacity,
+            reject_when_full: false,
+        }
+    }
+
+    /// Fail with [`PhonikudError::Overloaded`] instead of waiting when the queue is full,
+    /// so load spikes are shed instead of piling up callers. Applies to the clones of
+    /// the returned handle.
+    pub fn reject_when_full(mut self) -> Self {
+        self.reject_when_full = true;
+        self
+    }
+
+    pub async fn add_diacritics_async(&self, text: impl Into<String>) -> Result<String> {
+        self.add_diacritics_with_options_async(text, DiacriticsOptions::default())
+            .await
+    }
+
+    pub async fn add_diacritics_with_options_async(
+        &self,
+        text: impl Into<String>,
+        options: DiacriticsOptions,
+    ) -> Result<String> {
+        Ok(self.vocalize_async(text, options).await?.text)
+    }
+
+    /// Vocalize `text` and also return the predictions it was rendered from
+    pub async fn vocalize_async(
+        &self,
+        text: impl Into<String>,
+        options: DiacriticsOptions,
+    ) -> Result<Vocalization> {
+        let (reply, response) = oneshot::channel();
+        let request = Request {
+            text: text.into(),
+            options,
+            reply,
+        };
+
+        if self.reject_when_full {
+            self.sender.try_send(request).map_err(|e| match e {
+                TrySendError::Full(_) => PhonikudError::Overloaded {
+                    capacity: self.capacity,
+                },
+                TrySendError::Closed(_) => PhonikudError::WorkerStopped,
+            })?;
+        } else {
+            self.sender
+                .send(request)
+                .await
+                .map_err(|_| PhonikudError::WorkerStopped)?;
+        }
+        response.await.map_err(|_| PhonikudError::WorkerStopped)?
+    }
+}
+
+impl Phonikud {
+    /// Move this instance to a worker thread and return an async handle to it
+    pub fn into_async(self, queue_capacity: usize) -> AsyncPhonikud {
+        AsyncPhonikud::new(self, queue_capacity)
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::oneshot;
+
+use crate::{DiacriticsOptions, Phonikud, PhonikudError, Result, Vocalization};
+
+struct Request {
+    text: String,
+    options: DiacriticsOptions,
+    reply: oneshot::Sender<Result<Vocalization>>,
+}
+
+/// Cloneable handle coalescing the requests of many threads or tasks into batched model runs.
+///
+/// The worker thread waits up to `max_latency` after the first pending request
+/// for more to arrive, then vocalizes up to `max_batch_size` of them with
+/// [`Phonikud::vocalize_batch`] and resolves each caller. Under load one run
+/// serves many requests, a lone request waits at most `max_latency` longer.
+#[derive(Clone)]
+pub struct BatchScheduler {
+    queue: Queue,
+}
+
+#[derive(Clone)]
+enum Queue {
+    Unbounded(mpsc::Sender<Request>),
+    /// Rejects requests past its capacity
+    Bounded(mpsc::SyncSender<Request>, usize),
+}
+
+impl BatchScheduler {
+    /// Move `phonikud` to a worker thread running batches of up to `max_batch_size` requests
+    pub fn new(phonikud: Phonikud, max_batch_size: usize, max_latency: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel::<Request>();
+        spawn_worker(phonikud, max_batch_size, max_latency, receiver);
+        Self {
+            queue: Queue::Unbounded(sender),
+        }
+    }
+
+    /// Like [`BatchScheduler::new`] with up to `queue_capacity` requests waiting for a
+    /// batch. Requests past it fail right away with [`PhonikudError::Overloaded`].
+    pub fn bounded(
+        phonikud: Phonikud,
+        max_batch_size: usize,
+        max_latency: Duration,
+        queue_capacity: usize,
+    ) -> Self {
+        let capacity = queue_capacity.max(1);
+        let (sender, receiver) = mpsc::sync_channel::<Request>(capacity);
+        spawn_worker(phonikud, max_batch_size, max_latency, receiver);
+        Self {
+            queue: Queue::Bounded(sender, capacity),
+        }
+    }
+
+    pub async fn add_diacritics_async(&self, text: impl Into<String>) -> Result<String> {
+        self.add_diacritics_with_options_async(text, DiacriticsOptions::default())
+            .await
+    }
+
+    pub async fn add_diacritics_with_options_async(
+        &self,
+        text: impl Into<String>,
+        options: DiacriticsOptions,
+    ) -> Result<String> {
+        Ok(self.vocalize_async(text, options).await?.text)
+    }
+
+    /// Vocalize `text` and also return the predictions it was rendered from
+    pub async fn vocalize_async(
+        &self,
+        text: impl Into<String>,
+        options: DiacriticsOptions,
+    ) -> Result<Vocalization> {
+        let response = self.submit(text.into(), options)?;
+        response.await.map_err(|_| PhonikudError::WorkerStopped)?
+    }
+
+    /// Like [`BatchScheduler::vocalize_async`], blocking the calling thread.
+    ///
+    /// Panics when called from an async context.
+    pub fn vocalize_blocking(
+        &self,
+        text: impl Into<String>,
+        options: DiacriticsOptions,
+    ) -> Result<Vocalization> {
+        let response = self.submit(text.into(), options)?;
+        response
+            .blocking_recv()
+            .map_err(|_| PhonikudError::WorkerStopped)?
+    }
+
+    fn submit(
+        &self,
+        text: String,
+        options: DiacriticsOptions,
+    ) -> Result<oneshot::Receiver<Result<Vocalization>>> {
+        let (reply, response) = oneshot::channel();
+        let request = Request {
+            text,
+            options,
+            reply,
+        };
+        match &self.queue {
+            Queue::Unbounded(sender) => {
+                sender.send(request).map_err(|_| PhonikudError::WorkerStopped)?;
+            }
+            Queue::Bounded(sender, capacity) => sender.try_send(request).map_err(|e| match e {
+                mpsc::TrySendError::Full(_) => PhonikudError::Overloaded {
+                    capacity: *capacity,
+                },
+                mpsc::TrySendError::Disconnected(_) => PhonikudError::WorkerStopped,
+            })?,
+        }
+        Ok(response)
+    }
+}
+
+fn spawn_worker(
+    mut phonikud: Phonikud,
+    max_batch_size: usize,
+    max_latency: Duration,
+    receiver: mpsc::Receiver<Request>,
+) {
+    let max_batch_size = max_batch_size.max(1);
+    std::thread::spawn(move || {
+        while let Ok(first) = receiver.recv() {
+            let deadline = Instant::now() + max_latency;
+            let mut batch = vec![first];
+            while batch.len() < max_batch_size {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match receiver.recv_timeout(timeout) {
+                    Ok(request) => batch.push(request),
+                    Err(_) => break,
+                }
+            }
+
+            let requests: Vec<(&str, &DiacriticsOptions)> = batch
+                .iter()
+                .map(|request| (request.text.as_str(), &request.options))
+                .collect();
+            let results = phonikud.vocalize_batch(&requests, max_batch_size);
+            for (request, result) in batch.into_iter().zip(results) {
+                // The caller may have given up waiting
+                let _ = request.reply.send(result);
+            }
+        }
+    });
+}
+
+impl Phonikud {
+    /// Move this instance to a worker thread batching the requests sent to the returned handle
+    pub fn into_batch_scheduler(
+        self,
+        max_batch_size: usize,
+        max_latency: Duration,
+    ) -> BatchScheduler {
+        BatchScheduler::new(self, max_batch_size, max_latency)
+    }
+}
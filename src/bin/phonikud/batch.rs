@@ -0,0 +1,199 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use indicatif::{ProgressBar, ProgressStyle};
+use phonikud_rs::{DiacriticsOptions, Phonikud};
+use walkdir::WalkDir;
+
+use crate::OptionArgs;
+
+/// Lists the files written so far, relative to the output directory
+const MANIFEST: &str = ".phonikud-manifest";
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// Directory to read the files from, recursively
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Directory to write the vocalized files to, mirroring the input tree
+    #[arg(long)]
+    output: PathBuf,
+
+    /// Only vocalize files whose name matches this pattern, with `*` and `?` wildcards
+    #[arg(long, default_value = "*.txt")]
+    glob: String,
+
+    /// Number of files vocalized at once, each on its own model session
+    #[arg(short, long, default_value_t = default_jobs())]
+    jobs: usize,
+
+    /// Vocalize every file again instead of resuming from the manifest
+    #[arg(long)]
+    restart: bool,
+
+    #[command(flatten)]
+    options: OptionArgs,
+}
+
+/// Every session already runs its operators on several threads
+fn default_jobs() -> usize {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    #[cfg(feature = "onnx")]
+    let session_threads = phonikud_rs::Precision::Auto.default_intra_threads();
+    #[cfg(not(feature = "onnx"))]
+    let session_threads = 1;
+    (threads / session_threads).max(1)
+}
+
+pub fn run(phonikud: Phonikud, args: BatchArgs) -> Result<()> {
+    let options = args.options.to_options()?;
+    fs::create_dir_all(&args.output)
+        .with_context(|| format!("Failed to create {}", args.output.display()))?;
+
+    // Files listed in the manifest were completed by an interrupted run
+    let manifest_path = args.output.join(MANIFEST);
+    let done: HashSet<PathBuf> = match fs::read_to_string(&manifest_path) {
+        Ok(manifest) if !args.restart => manifest.lines().map(PathBuf::from).collect(),
+        _ => HashSet::new(),
+    };
+
+    let mut files = Vec::new();
+    for entry in WalkDir::new(&args.input).sort_by_file_name() {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy();
+        if !entry.file_type().is_file() || !matches_glob(&args.glob, &name) {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(&args.input)?.to_path_buf();
+        if !done.contains(&relative) {
+            files.push(relative);
+        }
+    }
+    if !done.is_empty() {
+        eprintln!("Resuming, {} files were done by a previous run", done.len());
+    }
+
+    let manifest = OpenOptions::new()
+        .create(true)
+        .append(!args.restart)
+        .write(true)
+        .truncate(args.restart)
+        .open(&manifest_path)
+        .with_context(|| format!("Failed to open {}", manifest_path.display()))?;
+    let manifest = Mutex::new(manifest);
+    let progress = ProgressBar::new(files.len() as u64).with_style(ProgressStyle::with_template(
+        "{bar:40} {pos}/{len} files [{elapsed_precise}, {eta} left] {msg}",
+    )?);
+
+    let jobs = args.jobs.clamp(1, files.len().max(1));
+    let mut workers = Vec::with_capacity(jobs);
+    for _ in 1..jobs {
+        workers.push(phonikud.clone_worker()?);
+    }
+    workers.push(phonikud);
+
+    // Each worker pulls the next file, so large files don't stall the others
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for mut worker in workers {
+            let (files, next, failures) = (&files, &next, &failures);
+            let (manifest, progress, args, options) = (&manifest, &progress, &args, &options);
+            scope.spawn(move || {
+                while let Some(relative) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    progress.set_message(relative.display().to_string());
+                    let input = args.input.join(relative);
+                    let output = args.output.join(relative);
+                    let result =
+                        vocalize_file(&mut worker, &input, &output, options).and_then(|()| {
+                            let mut manifest = manifest.lock().unwrap();
+                            writeln!(manifest, "{}", relative.display())
+                                .context("Failed to update the manifest")
+                        });
+                    if let Err(e) = result {
+                        failures.lock().unwrap().push((relative, e));
+                    }
+                    progress.inc(1);
+                }
+            });
+        }
+    });
+    progress.finish_and_clear();
+
+    let failures = failures.into_inner().unwrap();
+    eprintln!(
+        "Vocalized {} of {} files",
+        files.len() - failures.len(),
+        files.len()
+    );
+    if failures.is_empty() {
+        return Ok(());
+    }
+    for (relative, e) in &failures {
+        eprintln!("  {}: {e:#}", relative.display());
+    }
+    bail!("{} files failed, run again to retry them", failures.len())
+}
+
+pub fn vocalize_file(
+    phonikud: &mut Phonikud,
+    input: &Path,
+    output: &Path,
+    options: &DiacriticsOptions,
+) -> Result<()> {
+    let text =
+        fs::read_to_string(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let vocalized = phonikud.add_diacritics_with_options(&text, options)?;
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Written under another name first, so an interrupted write never looks complete
+    let partial = output.with_extension("part");
+    fs::write(&partial, vocalized)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, output)?;
+    Ok(())
+}
+
+/// Match a file name against a pattern where `*` matches any run of chars and `?` one char
+pub fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it currently matches up to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the `*` match one more char
+                Some((after_star, matched)) => {
+                    star = Some((after_star, matched + 1));
+                    p = after_star;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
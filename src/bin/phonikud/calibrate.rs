@@ -0,0 +1,64 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use phonikud_rs::Phonikud;
+
+#[derive(Args)]
+pub struct CalibrateArgs {
+    /// Vocalized dev set with the phonikud markers, one sentence per line
+    #[arg(long)]
+    dev: PathBuf,
+
+    /// Write the thresholds to a JSON file for `--config` and `DiacriticsOptions::from_file`
+    #[arg(long, value_name = "FILE")]
+    write_config: Option<PathBuf>,
+}
+
+pub fn run(mut phonikud: Phonikud, args: CalibrateArgs) -> Result<()> {
+    let dev_set = fs::read_to_string(&args.dev)
+        .with_context(|| format!("Failed to read {}", args.dev.display()))?;
+    let calibration = phonikud.calibrate_thresholds(&dev_set)?;
+
+    println!("Scored on {} letters", calibration.letters);
+    println!("head        threshold  precision  recall     F1         F1 at 0.5");
+    for (name, head) in [
+        ("stress", &calibration.stress),
+        ("vocal_shva", &calibration.vocal_shva),
+        ("prefix", &calibration.prefix),
+    ] {
+        println!(
+            "{name:<11} {:<10.2} {:<10} {:<10} {:<10} {}",
+            head.threshold,
+            percent(head.counts.precision()),
+            percent(head.counts.recall()),
+            percent(head.counts.f1()),
+            percent(head.default_counts.f1()),
+        );
+    }
+
+    if let Some(path) = &args.write_config {
+        let thresholds = calibration.thresholds;
+        let config = serde_json::json!({
+            "thresholds": {
+                "stress": rounded(thresholds.stress),
+                "vocal_shva": rounded(thresholds.vocal_shva),
+                "prefix": rounded(thresholds.prefix),
+            }
+        });
+        fs::write(path, format!("{config:#}\n"))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        eprintln!("Wrote the thresholds to {}", path.display());
+    }
+    Ok(())
+}
+
+fn percent(value: f64) -> String {
+    format!("{:.2}%", value * 100.0)
+}
+
+/// The thresholds are hundredths, without the noise of their `f32` representation
+fn rounded(threshold: f32) -> f64 {
+    (f64::from(threshold) * 100.0).round() / 100.0
+}
+
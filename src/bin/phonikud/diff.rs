@@ -0,0 +1,91 @@
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use phonikud_rs::eval::{self, DiffSegment};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Vocalized text to review, e.g. the model output
+    predicted: PathBuf,
+
+    /// Reference vocalization of the same text, e.g. the human corrected one
+    gold: PathBuf,
+
+    /// Print every line, not only the ones whose diacritics differ
+    #[arg(long)]
+    all: bool,
+
+    /// When to color the differences, markers like `[-old-]{+new+}` are used otherwise
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Color {
+    /// When stdout is a terminal and NO_COLOR isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+pub fn run(args: DiffArgs) -> Result<()> {
+    let predicted = read(&args.predicted)?;
+    let gold = read(&args.gold)?;
+    let evaluation = eval::evaluate(&predicted, &gold)?;
+    let color = match args.color {
+        Color::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+
+    // The base texts are equal, so their lines pair up
+    let mut stdout = io::stdout().lock();
+    for (line_number, (p, g)) in predicted.lines().zip(gold.lines()).enumerate() {
+        let segments = eval::diff(p, g)?;
+        if !args.all && segments.iter().all(|segment| segment.equal) {
+            continue;
+        }
+        writeln!(stdout, "{}: {}", line_number + 1, render(p, g, &segments, color))?;
+    }
+
+    eprintln!(
+        "Letters {}/{} correct ({:.2}%), words {}/{} correct ({:.2}%)",
+        evaluation.correct_letters,
+        evaluation.letters,
+        evaluation.cha() * 100.0,
+        evaluation.correct_words,
+        evaluation.words,
+        evaluation.wor() * 100.0,
+    );
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// The line with every differing run shown as the prediction followed by the reference
+fn render(predicted: &str, gold: &str, segments: &[DiffSegment], color: bool) -> String {
+    let mut line = String::with_capacity(predicted.len() * 2);
+    for segment in segments {
+        let p = &predicted[segment.predicted_range.clone()];
+        let g = &gold[segment.gold_range.clone()];
+        if segment.equal {
+            line.push_str(p);
+        } else if color {
+            line.push_str(&format!("{RED}{p}{RESET}{GREEN}{g}{RESET}"));
+        } else {
+            line.push_str(&format!("[-{p}-]{{+{g}+}}"));
+        }
+    }
+    line
+}
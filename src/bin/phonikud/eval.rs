@@ -0,0 +1,198 @@
+use std::{fmt::Write as _, fs, io::Write, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use phonikud_rs::{
+    eval::{Evaluation, MarkCounts},
+    text::remove_nikud,
+};
+
+use crate::{ModelArgs, OptionArgs};
+
+/// Number of confusions listed by the text report
+const TOP_CONFUSIONS: usize = 20;
+
+#[derive(Args)]
+pub struct EvalArgs {
+    /// Reference vocalization of the corpus
+    #[arg(long)]
+    gold: PathBuf,
+
+    /// Vocalization to score, the model vocalizes the gold text without its nikud when omitted
+    #[arg(long)]
+    pred: Option<PathBuf>,
+
+    #[command(flatten)]
+    options: OptionArgs,
+
+    /// Report format
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+
+    /// Write the report to a file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    /// A summary with the most frequent vowel confusions
+    Text,
+    /// Every metric and the full confusion matrix as one JSON object
+    Json,
+    /// One `metric,gold,predicted,value` row per metric and confusion matrix cell
+    Csv,
+}
+
+pub fn run(model: &ModelArgs, args: EvalArgs) -> Result<()> {
+    let gold = fs::read_to_string(&args.gold)
+        .with_context(|| format!("Failed to read {}", args.gold.display()))?;
+    let predicted = match &args.pred {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => {
+            let options = args.options.to_options()?;
+            model
+                .load()?
+                .add_diacritics_with_options(&remove_nikud(&gold), &options)?
+        }
+    };
+    let evaluation = phonikud_rs::eval::evaluate(&predicted, &gold)?;
+
+    let report = match args.format {
+        ReportFormat::Text => text_report(&evaluation),
+        ReportFormat::Json => format!("{:#}\n", json_report(&evaluation)),
+        ReportFormat::Csv => csv_report(&evaluation),
+    };
+    match &args.output {
+        Some(path) => fs::write(path, report)
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => std::io::stdout().write_all(report.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// The markers and their names in the reports
+fn markers(evaluation: &Evaluation) -> [(&'static str, &MarkCounts); 3] {
+    [
+        ("stress", &evaluation.stress),
+        ("vocal_shva", &evaluation.vocal_shva),
+        ("prefix", &evaluation.prefix),
+    ]
+}
+
+fn text_report(evaluation: &Evaluation) -> String {
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "Letters  {}/{} correct ({:.2}%)",
+        evaluation.correct_letters,
+        evaluation.letters,
+        evaluation.cha() * 100.0
+    );
+    let _ = writeln!(
+        report,
+        "Words    {}/{} correct ({:.2}%)",
+        evaluation.correct_words,
+        evaluation.words,
+        evaluation.wor() * 100.0
+    );
+    for (name, counts) in markers(evaluation) {
+        let _ = writeln!(
+            report,
+            "{name:<11} precision {:.2}%, recall {:.2}%, F1 {:.2}%",
+            counts.precision() * 100.0,
+            counts.recall() * 100.0,
+            counts.f1() * 100.0
+        );
+    }
+
+    let mut confusions: Vec<_> = evaluation
+        .confusion
+        .iter()
+        .filter(|((gold, predicted), _)| gold != predicted)
+        .collect();
+    confusions.sort_by(|a, b| b.1.cmp(a.1));
+    if !confusions.is_empty() {
+        let _ = writeln!(report, "\nMost frequent vowel confusions (gold -> predicted):");
+    }
+    for ((gold, predicted), count) in confusions.into_iter().take(TOP_CONFUSIONS) {
+        let _ = writeln!(
+            report,
+            "  {count:>6}  {} -> {}",
+            vowel_name(gold),
+            vowel_name(predicted)
+        );
+    }
+    report
+}
+
+fn json_report(evaluation: &Evaluation) -> serde_json::Value {
+    let mut report = serde_json::json!({
+        "letters": evaluation.letters,
+        "correct_letters": evaluation.correct_letters,
+        "cha": evaluation.cha(),
+        "words": evaluation.words,
+        "correct_words": evaluation.correct_words,
+        "wor": evaluation.wor(),
+        "confusion": evaluation
+            .confusion
+            .iter()
+            .map(|((gold, predicted), count)| serde_json::json!({
+                "gold": vowel_name(gold),
+                "predicted": vowel_name(predicted),
+                "count": count,
+            }))
+            .collect::<Vec<_>>(),
+    });
+    for (name, counts) in markers(evaluation) {
+        report[name] = serde_json::json!({
+            "true_positives": counts.true_positives,
+            "false_positives": counts.false_positives,
+            "false_negatives": counts.false_negatives,
+            "precision": counts.precision(),
+            "recall": counts.recall(),
+            "f1": counts.f1(),
+        });
+    }
+    report
+}
+
+fn csv_report(evaluation: &Evaluation) -> String {
+    let mut report = String::from("metric,gold,predicted,value\n");
+    let mut row = |metric: &str, value: f64| {
+        let _ = writeln!(report, "{metric},,,{value}");
+    };
+    row("letters", evaluation.letters as f64);
+    row("correct_letters", evaluation.correct_letters as f64);
+    row("cha", evaluation.cha());
+    row("words", evaluation.words as f64);
+    row("correct_words", evaluation.correct_words as f64);
+    row("wor", evaluation.wor());
+    for (name, counts) in markers(evaluation) {
+        row(&format!("{name}_precision"), counts.precision());
+        row(&format!("{name}_recall"), counts.recall());
+        row(&format!("{name}_f1"), counts.f1());
+    }
+    for ((gold, predicted), count) in &evaluation.confusion {
+        let _ = writeln!(
+            report,
+            "confusion,{},{},{count}",
+            vowel_name(gold),
+            vowel_name(predicted)
+        );
+    }
+    report
+}
+
+/// Code points of the vowel points, e.g. `U+05B7`, since they don't render on their own
+fn vowel_name(vowel: &str) -> String {
+    if vowel.is_empty() {
+        return "none".to_string();
+    }
+    vowel
+        .chars()
+        .map(|ch| format!("U+{:04X}", ch as u32))
+        .collect::<Vec<_>>()
+        .join("+")
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use clap::Args;
+use phonikud_rs::Phonikud;
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// Print the model info as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(phonikud: Phonikud, args: InfoArgs) -> Result<()> {
+    let info = phonikud.model_info();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+    println!("backend        {}", info.backend);
+    for input in &info.inputs {
+        println!("input          {input}");
+    }
+    for output in &info.outputs {
+        println!("output         {output}");
+    }
+    println!("opset          {}", or_unknown(info.opset.map(|opset| opset.to_string())));
+    println!("vocab size     {}", or_unknown(info.vocab_size.map(|size| size.to_string())));
+    println!("quantization   {}", or_unknown(info.quantization.map(|q| format!("{q:?}"))));
+    if !info.providers.is_empty() {
+        println!("providers      {}", info.providers.join(", "));
+    }
+    println!("producer       {}", or_unknown(info.producer));
+    if let Some(version) = info.model_version {
+        println!("model version  {version}");
+    }
+    for (key, value) in &info.metadata {
+        println!("metadata       {key} = {value}");
+    }
+    Ok(())
+}
@@ -0,0 +1,376 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use phonikud_rs::{
+    DiacriticsOptions, Lexicon, Phonikud, TokenPrediction, Vocalization, VocalShvaStyle,
+    export::{
+        self,
+        ExportFormat::{self, Conllu, Tsv},
+    },
+    spelling::SpellingOptions,
+};
+
+mod batch;
+mod calibrate;
+mod diff;
+mod eval;
+mod info;
+mod pipe;
+mod repl;
+#[cfg(feature = "serve")]
+mod serve;
+mod watch;
+
+/// Add diacritics to Hebrew text along with phonetic marks
+#[derive(Parser)]
+#[command(name = "phonikud", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    model: ModelArgs,
+
+    #[command(flatten)]
+    vocalize: VocalizeArgs,
+}
+
+#[derive(Args)]
+struct ModelArgs {
+    /// Path to the ONNX model
+    #[arg(long, env = "PHONIKUD_MODEL", default_value = "phonikud.onnx", global = true)]
+    model: String,
+
+    /// Path to tokenizer.json
+    #[arg(long, env = "PHONIKUD_TOKENIZER", default_value = "tokenizer.json", global = true)]
+    tokenizer: String,
+
+    /// TOML or JSON file with the default options and a `[model]` table of load settings
+    #[arg(long, env = "PHONIKUD_CONFIG", value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+
+    /// Keep the predictions in this file across runs, so the sentences an earlier run
+    /// vocalized skip the model. Entries written by another model are discarded.
+    #[cfg(feature = "disk-cache")]
+    #[arg(long, env = "PHONIKUD_CACHE_FILE", value_name = "FILE", global = true)]
+    cache_file: Option<PathBuf>,
+}
+
+impl ModelArgs {
+    fn load(&self) -> Result<Phonikud> {
+        #[cfg(feature = "onnx")]
+        if let Some(path) = &self.config {
+            let config = phonikud_rs::ModelConfig::from_file(path)?;
+            let builder = phonikud_rs::PhonikudBuilder::new(&self.model, &self.tokenizer);
+            #[cfg(feature = "disk-cache")]
+            let builder = match &self.cache_file {
+                Some(cache_file) => builder.with_disk_cache(cache_file),
+                None => builder,
+            };
+            return Ok(builder.with_config(&config).build()?);
+        }
+        #[cfg_attr(not(feature = "disk-cache"), allow(unused_mut))]
+        let mut phonikud = Phonikud::new(&self.model, &self.tokenizer)?;
+        #[cfg(feature = "disk-cache")]
+        if let Some(cache_file) = &self.cache_file {
+            let cache = phonikud_rs::DiskCache::open_for_model(cache_file, &self.model)
+                .with_context(|| format!("Failed to open {}", cache_file.display()))?;
+            phonikud.set_disk_cache(cache);
+        }
+        Ok(phonikud)
+    }
+
+    /// Load the options of the config file, the flags of every command apply on top of them
+    fn load_config(&self) -> Result<()> {
+        let options = match &self.config {
+            Some(path) => DiacriticsOptions::from_file(path)?,
+            None => DiacriticsOptions::default(),
+        };
+        let _ = CONFIG_OPTIONS.set(options);
+        Ok(())
+    }
+}
+
+/// The options of the `--config` file
+static CONFIG_OPTIONS: OnceLock<DiacriticsOptions> = OnceLock::new();
+
+#[derive(Args)]
+struct VocalizeArgs {
+    /// Input text file, stdin is read when omitted
+    input: Option<PathBuf>,
+
+    /// Vocalize stdin line by line as the lines arrive, flushing every line, for pipelines
+    #[arg(long, conflicts_with = "input")]
+    stdin: bool,
+
+    /// With `--stdin`, lines vocalized at once, each on its own model session. The output
+    /// keeps the order of the input.
+    #[arg(short, long, default_value_t = 1, requires = "stdin")]
+    jobs: usize,
+
+    #[command(flatten)]
+    options: OptionArgs,
+
+    /// Write the output to a file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// The vocalized text
+    Text,
+    /// One JSON object per input line with the vocalized text and per-letter predictions
+    Json,
+    /// The predictions as tab separated values, one row per letter and input line
+    Tsv,
+    /// The predictions in CoNLL-U, one sentence per input line and a token per letter
+    Conllu,
+}
+
+/// Flags shared by every command that vocalizes text
+#[derive(Args)]
+struct OptionArgs {
+    /// Mark matres lectionis with this string (e.g. U+05AF)
+    #[arg(long, value_name = "MARK")]
+    mark_matres: Option<String>,
+
+    /// Drop matres lectionis for the deficient spelling (ktiv haser)
+    #[arg(long)]
+    ktiv_haser: bool,
+
+    /// Only send the Hebrew parts of the text to the model
+    #[arg(long)]
+    hebrew_only: bool,
+
+    /// Don't emit stress marks
+    #[arg(long)]
+    no_stress: bool,
+
+    /// Write this string instead of the shva of a vocal shva, e.g. `ᵉ`, or U+05B1 for a
+    /// hataf segol
+    #[arg(long, value_name = "HINT")]
+    vocal_shva_hint: Option<String>,
+
+    /// Keep the cantillation marks (taamim) of the input
+    #[arg(long)]
+    keep_taamim: bool,
+
+    /// Never predict marks a letter can't take, e.g. a dagesh in a guttural
+    #[arg(long)]
+    orthography_constraints: bool,
+
+    /// Read final letters inside words and regular letters ending them in their right
+    /// form, the output keeps them as written
+    #[arg(long)]
+    fix_final_forms: bool,
+
+    /// Replace Hebrew presentation forms such as U+FB2A with the standard letters before
+    /// vocalizing
+    #[arg(long)]
+    normalize_presentation_forms: bool,
+
+    /// Fix extra yods and vavs and misplaced final letters before vocalizing, the output
+    /// is written with the fixed spelling
+    #[arg(long)]
+    respell: bool,
+
+    /// Lexicon file overriding the vocalization of words, one `word<TAB>vocalized` per line
+    #[arg(long, value_name = "FILE")]
+    lexicon: Option<PathBuf>,
+}
+
+impl OptionArgs {
+    fn to_options(&self) -> Result<DiacriticsOptions> {
+        let mut options = CONFIG_OPTIONS.get().cloned().unwrap_or_default();
+        if let Some(mark) = &self.mark_matres {
+            options.mark_matres_lectionis = Some(mark.clone());
+        }
+        options.ktiv_haser |= self.ktiv_haser;
+        options.hebrew_runs_only |= self.hebrew_only;
+        options.keep_taamim |= self.keep_taamim;
+        options.orthography_constraints |= self.orthography_constraints;
+        options.fix_final_forms |= self.fix_final_forms;
+        options.normalize_presentation_forms |= self.normalize_presentation_forms;
+        if self.respell {
+            options.respell.get_or_insert_with(SpellingOptions::default);
+        }
+        if self.no_stress {
+            options.stress_mark = None;
+        }
+        if let Some(hint) = &self.vocal_shva_hint {
+            options.vocal_shva_style = VocalShvaStyle::Hint(hint.clone());
+        }
+        if let Some(path) = &self.lexicon {
+            let lexicon = Lexicon::from_file(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            options.lexicon = Some(Arc::new(lexicon));
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Vocalize every matching file of a directory in parallel, resuming interrupted runs
+    Batch(batch::BatchArgs),
+    /// Find the stress, vocal shva and prefix thresholds scoring best on a dev set
+    Calibrate(calibrate::CalibrateArgs),
+    /// Compare a vocalization against a reference, highlighting the differing diacritics
+    Diff(diff::DiffArgs),
+    /// Score a vocalization, or the model itself, against a gold corpus
+    Eval(eval::EvalArgs),
+    /// Show the model inputs and outputs, opset, weight type and execution providers
+    Info(info::InfoArgs),
+    /// Vocalize the cue text of an SRT or WebVTT file, keeping numbers, timings and tags
+    Subtitles(VocalizeArgs),
+    /// Vocalize lines as they are typed, changing the options with `:` commands
+    Repl(repl::ReplArgs),
+    /// Serve the model over HTTP
+    #[cfg(feature = "serve")]
+    Serve(serve::ServeArgs),
+    /// Vocalize the matching files of a directory as they are added or modified
+    Watch(watch::WatchArgs),
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    cli.model.load_config()?;
+
+    match cli.command {
+        #[cfg(feature = "serve")]
+        Some(Command::Serve(args)) => serve::run(cli.model.load()?, args),
+        Some(Command::Batch(args)) => batch::run(cli.model.load()?, args),
+        Some(Command::Calibrate(args)) => calibrate::run(cli.model.load()?, args),
+        Some(Command::Diff(args)) => diff::run(args),
+        Some(Command::Eval(args)) => eval::run(&cli.model, args),
+        Some(Command::Info(args)) => info::run(cli.model.load()?, args),
+        Some(Command::Subtitles(args)) => vocalize(cli.model.load()?, args, Input::Subtitles),
+        Some(Command::Repl(args)) => repl::run(cli.model.load()?, args),
+        Some(Command::Watch(args)) => watch::run(cli.model.load()?, args),
+        None => vocalize(cli.model.load()?, cli.vocalize, Input::Text),
+    }
+}
+
+/// How the input is read
+enum Input {
+    Text,
+    Subtitles,
+}
+
+fn vocalize(mut phonikud: Phonikud, args: VocalizeArgs, input: Input) -> Result<()> {
+    if args.stdin {
+        return match input {
+            Input::Text => pipe::run(phonikud, &args),
+            Input::Subtitles => bail!("--stdin only applies to plain text"),
+        };
+    }
+    let text = match &args.input {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+
+    let options = args.options.to_options()?;
+    let vocalized = match (input, args.format) {
+        (Input::Text, Format::Text) => phonikud.add_diacritics_with_options(&text, &options)?,
+        (Input::Text, Format::Json) => json_lines(&mut phonikud, &text, &options)?,
+        (Input::Subtitles, Format::Text) => phonikud.add_diacritics_subtitles(&text, &options)?,
+        (Input::Text, Format::Tsv) => export_lines(&mut phonikud, &text, &options, Tsv)?,
+        (Input::Text, Format::Conllu) => export_lines(&mut phonikud, &text, &options, Conllu)?,
+        (Input::Subtitles, _) => bail!("--format json, tsv and conllu only apply to plain text"),
+    };
+
+    match &args.output {
+        Some(path) => fs::write(path, vocalized)
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => io::stdout().write_all(vocalized.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+/// Vocalize every line of `text` into a JSON object, offsets are byte offsets into the line
+fn json_lines(phonikud: &mut Phonikud, text: &str, options: &DiacriticsOptions) -> Result<String> {
+    let mut output = String::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let vocalization = phonikud.vocalize(line, options)?;
+        output.push_str(&vocalization_json(line_number + 1, line, &vocalization).to_string());
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Export the predictions of every line of `text`, see [`phonikud_rs::export`]
+fn export_lines(
+    phonikud: &mut Phonikud,
+    text: &str,
+    options: &DiacriticsOptions,
+    format: ExportFormat,
+) -> Result<String> {
+    let mut output = Vec::new();
+    export::write_header(&mut output, format)?;
+    for (line_number, line) in text.lines().enumerate() {
+        let vocalization = phonikud.vocalize(line, options)?;
+        export::write_sentence(&mut output, format, line_number + 1, line, &vocalization)?;
+    }
+    Ok(String::from_utf8(output)?)
+}
+
+/// The JSON object of line `line_number` of the input
+fn vocalization_json(
+    line_number: usize,
+    input: &str,
+    vocalization: &Vocalization,
+) -> serde_json::Value {
+    let predictions: Vec<_> = vocalization.predictions.iter().map(prediction_json).collect();
+    let warnings: Vec<_> = vocalization
+        .warnings
+        .iter()
+        .map(|warning| {
+            serde_json::json!({
+                "kind": warning.kind,
+                "start": warning.range.start,
+                "end": warning.range.end,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "line": line_number,
+        "input": input,
+        "text": vocalization.text,
+        "predictions": predictions,
+        "warnings": warnings,
+    })
+}
+
+fn prediction_json(prediction: &TokenPrediction) -> serde_json::Value {
+    serde_json::json!({
+        "char": prediction.char,
+        "start": prediction.start,
+        "end": prediction.end,
+        "nikud_class": prediction.nikud_class,
+        "nikud": prediction.nikud,
+        "dagesh": prediction.has_dagesh(),
+        "confidence": prediction.confidence,
+        "shin_class": prediction.shin_class,
+        "shin": prediction.shin,
+        "stress": prediction.stress,
+        "vocal_shva": prediction.vocal_shva,
+        "prefix": prediction.prefix,
+    })
+}
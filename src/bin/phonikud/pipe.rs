@@ -0,0 +1,127 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, Write},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use phonikud_rs::{
+    DiacriticsOptions, Phonikud,
+    export::{self, ExportFormat},
+};
+
+use crate::{Format, VocalizeArgs, vocalization_json};
+
+/// Lines read ahead of the slowest session
+const LINES_PER_JOB: usize = 4;
+
+/// Vocalize stdin line by line as the lines arrive, on `--jobs` sessions.
+///
+/// Every line is written and flushed as soon as it and the lines before it are done, so
+/// the output keeps the order of the input. The threads are detached: when a line fails,
+/// the error is returned without waiting for stdin to close.
+pub fn run(phonikud: Phonikud, args: &VocalizeArgs) -> Result<()> {
+    let options = Arc::new(args.options.to_options()?);
+    let jobs = args.jobs.max(1);
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+    if args.format == Format::Tsv {
+        export::write_header(&mut output, ExportFormat::Tsv)?;
+    }
+
+    let (line_sender, lines) = mpsc::sync_channel::<(usize, String)>(jobs * LINES_PER_JOB);
+    thread::spawn(move || {
+        for (idx, line) in io::stdin().lock().lines().enumerate() {
+            // A read error ends the input like its end does, the lines so far are written
+            let Ok(line) = line else { break };
+            if line_sender.send((idx, line)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let lines = Arc::new(Mutex::new(lines));
+    let (result_sender, results) = mpsc::channel();
+    let mut workers = Vec::with_capacity(jobs);
+    for _ in 1..jobs {
+        workers.push(phonikud.clone_worker()?);
+    }
+    workers.push(phonikud);
+    for mut worker in workers {
+        let (lines, options, results) = (lines.clone(), options.clone(), result_sender.clone());
+        let format = args.format;
+        thread::spawn(move || {
+            loop {
+                // Released before vocalizing, so the other sessions can take the next lines
+                let next = lines.lock().unwrap().recv();
+                let Ok((idx, line)) = next else { break };
+                let result = vocalize_line(&mut worker, &line, idx + 1, &options, format);
+                if results.send((idx, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(result_sender);
+
+    // Lines finished ahead of an earlier one wait for it
+    let mut done = BTreeMap::new();
+    let mut next = 0;
+    for (idx, result) in results {
+        done.insert(idx, result);
+        while let Some(result) = done.remove(&next) {
+            let line_number = next + 1;
+            let vocalized =
+                result.with_context(|| format!("Failed to vocalize line {line_number}"))?;
+            writeln!(output, "{vocalized}")?;
+            next += 1;
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn vocalize_line(
+    phonikud: &mut Phonikud,
+    line: &str,
+    line_number: usize,
+    options: &DiacriticsOptions,
+    format: Format,
+) -> Result<String> {
+    let vocalized = match format {
+        Format::Text => phonikud.add_diacritics_with_options(line, options)?,
+        Format::Json => {
+            let vocalization = phonikud.vocalize(line, options)?;
+            vocalization_json(line_number, line, &vocalization).to_string()
+        }
+        Format::Tsv => export_line(phonikud, line, line_number, options, ExportFormat::Tsv)?,
+        Format::Conllu => {
+            export_line(phonikud, line, line_number, options, ExportFormat::Conllu)?
+        }
+    };
+    Ok(vocalized)
+}
+
+/// The rows of `line` without the final newline, which is written after every line
+fn export_line(
+    phonikud: &mut Phonikud,
+    line: &str,
+    line_number: usize,
+    options: &DiacriticsOptions,
+    format: ExportFormat,
+) -> Result<String> {
+    let vocalization = phonikud.vocalize(line, options)?;
+    let mut rows = Vec::new();
+    export::write_sentence(&mut rows, format, line_number, line, &vocalization)?;
+    let mut rows = String::from_utf8(rows)?;
+    if rows.ends_with('\n') {
+        rows.pop();
+    }
+    Ok(rows)
+}
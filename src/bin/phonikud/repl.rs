@@ -0,0 +1,133 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use anyhow::{Result, bail};
+use clap::Args;
+use phonikud_rs::{DiacriticsOptions, PREFIX_MARK, Phonikud, STRESS_MARK, VOCAL_SHVA_MARK};
+
+use crate::{OptionArgs, vocalization_json};
+
+const HELP: &str = "\
+:stress on|off      emit stress marks
+:shva on|off        emit vocal shva marks
+:prefix on|off      emit prefix boundaries
+:matres MARK|off    mark matres lectionis with MARK, escapes like \\u05af work
+:haser on|off       drop matres lectionis (ktiv haser)
+:json on|off        print the predictions as JSON
+:help               show this help
+:quit               exit";
+
+#[derive(Args)]
+pub struct ReplArgs {
+    #[command(flatten)]
+    options: OptionArgs,
+}
+
+pub fn run(mut phonikud: Phonikud, args: ReplArgs) -> Result<()> {
+    let mut options = args.options.to_options()?;
+    let mut json = false;
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        eprintln!("Type Hebrew text to vocalize it, :help for the commands");
+    }
+
+    let mut stdout = io::stdout();
+    let mut lines = io::stdin().lock().lines();
+    for line_number in 1.. {
+        if interactive {
+            eprint!("> ");
+            io::stderr().flush()?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line?;
+
+        if let Some(command) = line.trim().strip_prefix(':') {
+            match run_command(command, &mut options, &mut json) {
+                Ok(true) => continue,
+                Ok(false) => break,
+                Err(e) => {
+                    eprintln!("{e}");
+                    continue;
+                }
+            }
+        }
+        // A failed line shouldn't end the session
+        match phonikud.vocalize(&line, &options) {
+            Ok(vocalization) if json => writeln!(
+                stdout,
+                "{}",
+                vocalization_json(line_number, &line, &vocalization)
+            )?,
+            Ok(vocalization) => writeln!(stdout, "{}", vocalization.text)?,
+            Err(e) => eprintln!("Error: {e}"),
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// Apply a `:` command, returns false to end the session
+fn run_command(command: &str, options: &mut DiacriticsOptions, json: &mut bool) -> Result<bool> {
+    let (name, value) = command.split_once(' ').unwrap_or((command, ""));
+    let value = value.trim();
+    let mark = |default: &str| switch(value).map(|on| on.then(|| default.to_string()));
+    match name {
+        "stress" => options.stress_mark = mark(STRESS_MARK)?,
+        "shva" => options.vocal_shva_mark = mark(VOCAL_SHVA_MARK)?,
+        "prefix" => options.prefix_mark = mark(PREFIX_MARK)?,
+        "matres" => {
+            options.mark_matres_lectionis = match value {
+                "" => bail!("Usage: :matres MARK|off"),
+                "off" => None,
+                mark => Some(unescape(mark)?),
+            }
+        }
+        "haser" => options.ktiv_haser = switch(value)?,
+        "json" => *json = switch(value)?,
+        "help" => eprintln!("{HELP}"),
+        "quit" | "q" => return Ok(false),
+        _ => bail!("Unknown command :{name}, see :help"),
+    }
+    Ok(true)
+}
+
+fn switch(value: &str) -> Result<bool> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => bail!("Expected on or off, got {value:?}"),
+    }
+}
+
+/// Replace `\uXXXX` and `\u{X}` escapes with the chars they stand for
+fn unescape(text: &str) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find("\\u") {
+        result.push_str(&rest[..idx]);
+        rest = &rest[idx + 2..];
+        let (hex, len) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => bail!("Unclosed escape in {text:?}"),
+            },
+            None => {
+                let len = rest
+                    .chars()
+                    .take(4)
+                    .take_while(char::is_ascii_hexdigit)
+                    .count();
+                (&rest[..len], len)
+            }
+        };
+        let ch = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| anyhow::anyhow!("Invalid escape \\u{hex} in {text:?}"))?;
+        result.push(ch);
+        rest = &rest[len..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
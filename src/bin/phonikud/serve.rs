@@ -0,0 +1,46 @@
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::Result;
+use clap::Args;
+use phonikud_rs::Phonikud;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: IpAddr,
+
+    /// Port to listen on
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+
+    /// Requests waiting for the model before new ones get 429 Too Many Requests
+    #[arg(long, default_value_t = phonikud_rs::server::DEFAULT_QUEUE_CAPACITY)]
+    queue_capacity: usize,
+
+    /// Serve the gRPC service of proto/phonikud.proto instead of HTTP
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc: bool,
+}
+
+pub fn run(mut phonikud: Phonikud, args: ServeArgs) -> Result<()> {
+    // Don't make the first request pay for the session's first allocations
+    phonikud.warmup()?;
+
+    let addr = SocketAddr::new(args.host, args.port);
+    let runtime = tokio::runtime::Runtime::new()?;
+    #[cfg(feature = "grpc")]
+    if args.grpc {
+        eprintln!("Listening for gRPC on {addr}");
+        let server = phonikud_rs::grpc::serve(phonikud, addr, args.queue_capacity);
+        runtime.block_on(server)?;
+        return Ok(());
+    }
+    eprintln!("Listening on http://{addr}");
+
+    let server =
+        phonikud_rs::server::serve_with_queue_capacity(phonikud, addr, args.queue_capacity);
+    runtime.block_on(server)?;
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use notify::{RecursiveMode, Watcher};
+use phonikud_rs::Phonikud;
+use walkdir::WalkDir;
+
+use crate::{
+    OptionArgs,
+    batch::{matches_glob, vocalize_file},
+};
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Directory to watch for new and modified files, recursively
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Directory to write the vocalized files to, mirroring the input tree
+    #[arg(long)]
+    output: PathBuf,
+
+    /// Only vocalize files whose name matches this pattern, with `*` and `?` wildcards
+    #[arg(long, default_value = "*.txt")]
+    glob: String,
+
+    /// Milliseconds a file must stay unchanged before it's vocalized
+    #[arg(long, default_value_t = 500)]
+    settle: u64,
+
+    #[command(flatten)]
+    options: OptionArgs,
+}
+
+/// Modification time and size of a file, it's vocalized once they stop changing
+type Snapshot = (SystemTime, u64);
+
+/// Watch the input directory and vocalize the files whose output is missing or older,
+/// including those already there on startup. A file is picked up once it didn't change
+/// for `--settle` after its last event, so files still being copied in aren't vocalized
+/// half written.
+pub fn run(mut phonikud: Phonikud, args: WatchArgs) -> Result<()> {
+    let options = args.options.to_options()?;
+    fs::create_dir_all(&args.output)
+        .with_context(|| format!("Failed to create {}", args.output.display()))?;
+    // Events carry the full path of the watched directory
+    let input = fs::canonicalize(&args.input)
+        .with_context(|| format!("Failed to open {}", args.input.display()))?;
+    // The output may be inside the input directory, its files aren't inputs
+    let output = fs::canonicalize(&args.output)?;
+    let settle = Duration::from_millis(args.settle);
+
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher
+        .watch(&input, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", input.display()))?;
+    eprintln!("Watching {} for {}", args.input.display(), args.glob);
+
+    // The last snapshot of every file to vocalize and when it was taken
+    let started = Instant::now();
+    let mut pending: HashMap<PathBuf, (Snapshot, Instant)> =
+        stale_files(&input, &output, &args.glob, &input)
+            .into_iter()
+            .map(|(relative, snapshot)| (relative, (snapshot, started)))
+            .collect();
+    // Retried once they change again
+    let mut failed: HashMap<PathBuf, Snapshot> = HashMap::new();
+    loop {
+        // Sleep until a file changes, or until the first pending one settles
+        let settles = pending.values().map(|&(_, seen)| seen + settle).min();
+        let event = match settles {
+            None => Some(events.recv().context("The watcher stopped")?),
+            Some(settles) => {
+                match events.recv_timeout(settles.saturating_duration_since(Instant::now())) {
+                    Ok(event) => Some(event),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => bail!("The watcher stopped"),
+                }
+            }
+        };
+
+        let now = Instant::now();
+        for event in event.into_iter().chain(events.try_iter()) {
+            let event = event.with_context(|| format!("Failed to watch {}", input.display()))?;
+            for path in event.paths {
+                // A directory moved in brings its files without events of their own
+                let files = if path.is_dir() {
+                    let files = stale_files(&input, &output, &args.glob, &path);
+                    files
+                        .into_iter()
+                        .map(|(relative, snapshot)| (relative, Some(snapshot)))
+                        .collect()
+                } else if let Some(relative) = input_file(&input, &output, &args.glob, &path) {
+                    let snapshot = stale_snapshot(&input, &output, &relative);
+                    vec![(relative, snapshot)]
+                } else {
+                    Vec::new()
+                };
+                for (relative, snapshot) in files {
+                    match snapshot {
+                        Some(snapshot) if failed.get(&relative) == Some(&snapshot) => {}
+                        // Changed since the last event, it has to settle again
+                        Some(snapshot)
+                            if pending.get(&relative).map(|&(seen, _)| seen) != Some(snapshot) =>
+                        {
+                            pending.insert(relative, (snapshot, now));
+                        }
+                        Some(_) => {}
+                        None => {
+                            pending.remove(&relative);
+                        }
+                    }
+                }
+            }
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|&(_, &(_, seen))| now.duration_since(seen) >= settle)
+            .map(|(relative, _)| relative.clone())
+            .collect();
+        for relative in settled {
+            let Some((snapshot, _)) = pending.remove(&relative) else {
+                continue;
+            };
+            // Unchanged since its last event
+            match stale_snapshot(&input, &output, &relative) {
+                Some(current) if current == snapshot => {}
+                Some(current) => {
+                    pending.insert(relative, (current, now));
+                    continue;
+                }
+                None => continue,
+            }
+            let source = input.join(&relative);
+            match vocalize_file(
+                &mut phonikud,
+                &source,
+                &args.output.join(&relative),
+                &options,
+            ) {
+                Ok(()) => eprintln!("Vocalized {}", relative.display()),
+                Err(e) => {
+                    eprintln!("Failed to vocalize {}: {e:#}", relative.display());
+                    failed.insert(relative, snapshot);
+                }
+            }
+        }
+    }
+}
+
+/// The path of `path` relative to the input directory, if it's a matching input file
+fn input_file(input: &Path, output: &Path, glob: &str, path: &Path) -> Option<PathBuf> {
+    if path.starts_with(output) {
+        return None;
+    }
+    let relative = path.strip_prefix(input).ok()?;
+    let name = relative.file_name()?.to_string_lossy();
+    matches_glob(glob, &name).then(|| relative.to_path_buf())
+}
+
+/// Snapshot of the input file at `relative`, unless it's gone or its output is up to date
+fn stale_snapshot(input: &Path, output: &Path, relative: &Path) -> Option<Snapshot> {
+    // Files can be removed before their event is handled
+    let metadata = fs::metadata(input.join(relative))
+        .ok()
+        .filter(|m| m.is_file())?;
+    let modified = metadata.modified().ok()?;
+    let vocalized = fs::metadata(output.join(relative)).and_then(|m| m.modified());
+    if vocalized.is_ok_and(|vocalized| vocalized >= modified) {
+        return None;
+    }
+    Some((modified, metadata.len()))
+}
+
+/// Matching input files under `root` whose output is missing or older than them
+fn stale_files(input: &Path, output: &Path, glob: &str, root: &Path) -> Vec<(PathBuf, Snapshot)> {
+    let entries = WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|entry| !entry.file_type().is_dir() || entry.path() != output);
+    // Files can be removed during the scan
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| input_file(input, output, glob, entry.path()))
+        .filter_map(|relative| {
+            let snapshot = stale_snapshot(input, output, &relative)?;
+            Some((relative, snapshot))
+        })
+        .collect()
+}
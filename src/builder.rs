@@ -0,0 +1,617 @@
+use std::{path::Path, sync::Arc};
+
+use ort::{
+    execution_providers::{
+        CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
+        DirectMLExecutionProvider, ExecutionProvider, ExecutionProviderDispatch,
+        TensorRTExecutionProvider,
+    },
+    session::builder::GraphOptimizationLevel,
+};
+use tokenizers::Tokenizer;
+
+use crate::{
+    ClassVocab, Phonikud, PhonikudError, PhonikudModel, Result, TruncationPolicy,
+    session::{ModelSource, SessionOptions},
+};
+
+/// Configure how the model is loaded before building a [`Phonikud`].
+///
+/// Execution providers are tried in the order they were added. A provider that
+/// is unavailable at runtime is skipped and inference falls back to the CPU.
+pub struct PhonikudBuilder {
+    model: ModelSource,
+    tokenizer: TokenizerSource,
+    options: SessionOptions,
+    cache_capacity: usize,
+    truncation: TruncationPolicy,
+    max_chunk_chars: Option<usize>,
+    precision: Option<Precision>,
+    /// GPU providers requested, [`Precision::Auto`] picks fp16 when one is available
+    accelerators: Vec<Provider>,
+    #[cfg(feature = "mmap")]
+    mmap: bool,
+    #[cfg(feature = "checksum")]
+    checksums: Checksums,
+    #[cfg(feature = "disk-cache")]
+    disk_cache: Option<std::path::PathBuf>,
+}
+
+enum TokenizerSource {
+    File(String),
+    Bytes(Vec<u8>),
+}
+
+/// Expected digests of the model and tokenizer, checked before they are loaded
+#[cfg(feature = "checksum")]
+#[derive(Default)]
+struct Checksums {
+    model: Option<String>,
+    tokenizer: Option<String>,
+    manifest: Option<crate::checksum::Manifest>,
+}
+
+#[cfg(feature = "checksum")]
+impl Checksums {
+    /// The digest given for the file itself, or the one listed in the manifest
+    fn expected<'a>(&'a self, given: &'a Option<String>, path: &str) -> Option<&'a str> {
+        given
+            .as_deref()
+            .or_else(|| self.manifest.as_ref()?.get(path))
+    }
+
+    fn verify_model(&self, model: &ModelSource) -> Result<()> {
+        use crate::checksum::{verify_bytes, verify_file};
+        match model {
+            ModelSource::File(path) => match self.expected(&self.model, path) {
+                Some(expected) => verify_file(path, expected),
+                None => Ok(()),
+            },
+            ModelSource::Bytes(bytes) => match &self.model {
+                Some(expected) => verify_bytes("model", bytes, expected),
+                None => Ok(()),
+            },
+            #[cfg(feature = "mmap")]
+            ModelSource::Mapped(mmap) => match &self.model {
+                Some(expected) => verify_bytes("model", mmap, expected),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn verify_tokenizer(&self, tokenizer: &TokenizerSource) -> Result<()> {
+        use crate::checksum::{verify_bytes, verify_file};
+        match tokenizer {
+            TokenizerSource::File(path) => match self.expected(&self.tokenizer, path) {
+                Some(expected) => verify_file(path, expected),
+                None => Ok(()),
+            },
+            TokenizerSource::Bytes(bytes) => match &self.tokenizer {
+                Some(expected) => verify_bytes("tokenizer.json", bytes, expected),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+impl PhonikudBuilder {
+    /// Chars per chunk of [`Self::with_low_memory`], about a sentence
+    pub const LOW_MEMORY_CHUNK_CHARS: usize = 128;
+
+    pub fn new(model_path: &str, tokenizer_path: &str) -> Self {
+        Self {
+            model: ModelSource::File(model_path.to_string()),
+            tokenizer: TokenizerSource::File(tokenizer_path.to_string()),
+            options: SessionOptions::default(),
+            cache_capacity: 0,
+            truncation: TruncationPolicy::default(),
+            max_chunk_chars: None,
+            precision: None,
+            accelerators: Vec::new(),
+            #[cfg(feature = "mmap")]
+            mmap: false,
+            #[cfg(feature = "checksum")]
+            checksums: Checksums::default(),
+            #[cfg(feature = "disk-cache")]
+            disk_cache: None,
+        }
+    }
+
+    /// Load the model and tokenizer from memory instead of files
+    pub fn from_bytes(model: &[u8], tokenizer_json: &[u8]) -> Self {
+        Self {
+            model: ModelSource::Bytes(Arc::from(model)),
+            tokenizer: TokenizerSource::Bytes(tokenizer_json.to_vec()),
+            options: SessionOptions::default(),
+            cache_capacity: 0,
+            truncation: TruncationPolicy::default(),
+            max_chunk_chars: None,
+            precision: None,
+            accelerators: Vec::new(),
+            #[cfg(feature = "mmap")]
+            mmap: false,
+            #[cfg(feature = "checksum")]
+            checksums: Checksums::default(),
+            #[cfg(feature = "disk-cache")]
+            disk_cache: None,
+        }
+    }
+
+    /// Cache the predictions of up to `capacity` chunks of text, see [`Phonikud::set_cache_capacity`]
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+
+    /// Load the `precision` variant of the model file, see [`Precision::model_path`]
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Keep the predictions in the file at `path` across runs, see [`crate::DiskCache`].
+    /// Its entries are only used with the model that ends up loaded, the one whose
+    /// SHA-256 digest wrote them.
+    #[cfg(feature = "disk-cache")]
+    pub fn with_disk_cache(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.disk_cache = Some(path.into());
+        self
+    }
+
+    /// Memory-map the model file instead of reading it.
+    ///
+    /// Processes loading the same file share its pages, and the whole file is never copied
+    /// to the heap. ORT format models (`.ort`) are run from the mapping directly, ONNX
+    /// models are still parsed into memory owned by the session.
+    #[cfg(feature = "mmap")]
+    pub fn with_mmap(mut self, enable: bool) -> Self {
+        self.mmap = enable;
+        self
+    }
+
+    /// Fail with [`PhonikudError::ChecksumMismatch`] unless the model has the SHA-256
+    /// digest `sha256` (hex). With [`Self::with_precision`], this is the digest of the
+    /// variant that ends up loaded.
+    #[cfg(feature = "checksum")]
+    pub fn with_model_sha256(mut self, sha256: &str) -> Self {
+        self.checksums.model = Some(sha256.to_string());
+        self
+    }
+
+    /// Fail with [`PhonikudError::ChecksumMismatch`] unless tokenizer.json has the
+    /// SHA-256 digest `sha256` (hex)
+    #[cfg(feature = "checksum")]
+    pub fn with_tokenizer_sha256(mut self, sha256: &str) -> Self {
+        self.checksums.tokenizer = Some(sha256.to_string());
+        self
+    }
+
+    /// Check the model and tokenizer files against the digests `manifest` lists for
+    /// their names. Files it doesn't list aren't checked, and the digests given with
+    /// [`Self::with_model_sha256`] and [`Self::with_tokenizer_sha256`] take precedence.
+    #[cfg(feature = "checksum")]
+    pub fn with_manifest(mut self, manifest: crate::checksum::Manifest) -> Self {
+        self.checksums.manifest = Some(manifest);
+        self
+    }
+
+    /// Number of threads used to parallelize a single operator, by default picked from
+    /// the available cores and the precision of the model, see
+    /// [`Precision::default_intra_threads`]
+    pub fn with_intra_threads(mut self, threads: usize) -> Self {
+        self.options.intra_threads = Some(threads);
+        self
+    }
+
+    /// Number of threads used to run independent operators in parallel.
+    ///
+    /// Setting this enables ORT's parallel execution mode.
+    pub fn with_inter_threads(mut self, threads: usize) -> Self {
+        self.options.inter_threads = Some(threads);
+        self
+    }
+
+    /// Pin the intra-op threads to the logical `cores` (numbered from 0), running one
+    /// thread on each of them instead of the count of [`Self::with_intra_threads`].
+    ///
+    /// ORT runs part of every operator on the calling thread, which takes the first core
+    /// but isn't pinned by it. Meant for servers giving each session of a
+    /// [`PhonikudPool`](crate::PhonikudPool) its own cores.
+    pub fn with_thread_affinity(mut self, cores: impl Into<Vec<usize>>) -> Self {
+        self.options.thread_affinity = Some(cores.into());
+        self
+    }
+
+    /// Graph optimization level (default [`GraphOptimizationLevel::Level3`])
+    pub fn with_optimization_level(mut self, level: GraphOptimizationLevel) -> Self {
+        self.options.optimization_level = level.into();
+        self
+    }
+
+    /// Enable or disable ORT's memory pattern optimization
+    pub fn with_memory_pattern(mut self, enable: bool) -> Self {
+        self.options.memory_pattern = Some(enable);
+        self
+    }
+
+    /// Enable or disable the CPU memory arena
+    pub fn with_cpu_arena(mut self, enable: bool) -> Self {
+        self.options.cpu_arena = Some(enable);
+        self
+    }
+
+    /// Return the memory the CPU arena grew to during a run to the system after it,
+    /// instead of keeping it for the next run
+    pub fn with_arena_shrinkage(mut self, enable: bool) -> Self {
+        self.options.arena_shrinkage = enable;
+        self
+    }
+
+    /// Drop the ORT session after every run and load the model again for the next one.
+    ///
+    /// Frees the weights and buffers of the session while the model is idle, at the cost
+    /// of loading it on every call. Weights aren't pre-packed, the container would keep
+    /// them in memory.
+    pub fn with_unload_between_calls(mut self, enable: bool) -> Self {
+        self.options.unload_between_runs = enable;
+        self
+    }
+
+    /// Split texts into chunks of at most `chars` chars, see
+    /// [`PhonikudModel::set_max_chunk_chars`]
+    pub fn with_max_chunk_chars(mut self, chars: usize) -> Self {
+        self.max_chunk_chars = Some(chars);
+        self
+    }
+
+    /// Settings for devices with little memory and few cores, such as a Raspberry Pi:
+    /// a single intra-op thread and no inter-op ones, no memory pattern, an arena shrunk
+    /// after every run and chunks of at most [`Self::LOW_MEMORY_CHUNK_CHARS`] chars.
+    ///
+    /// Settings made after this one override it. [`Self::with_unload_between_calls`]
+    /// saves more between calls when the model is only used now and then.
+    pub fn with_low_memory(mut self) -> Self {
+        self.options.intra_threads = Some(1);
+        self.options.inter_threads = None;
+        self.options.thread_affinity = None;
+        self.with_memory_pattern(false)
+            .with_arena_shrinkage(true)
+            .with_max_chunk_chars(Self::LOW_MEMORY_CHUNK_CHARS)
+    }
+
+    /// What happens to inputs longer than the truncation length of tokenizer.json,
+    /// see [`PhonikudModel::tokenizer_settings`]
+    pub fn with_truncation_policy(mut self, policy: TruncationPolicy) -> Self {
+        self.truncation = policy;
+        self
+    }
+
+    /// Run on the CUDA device `device_id`
+    #[cfg(feature = "cuda")]
+    pub fn with_cuda(mut self, device_id: i32) -> Self {
+        self.accelerators.push(Provider::Cuda);
+        self.options.execution_providers.push(
+            CUDAExecutionProvider::default()
+                .with_device_id(device_id)
+                .build(),
+        );
+        self
+    }
+
+    /// Run with TensorRT on the CUDA device `device_id`
+    #[cfg(feature = "tensorrt")]
+    pub fn with_tensorrt(mut self, device_id: i32) -> Self {
+        self.accelerators.push(Provider::TensorRT);
+        self.options.execution_providers.push(
+            TensorRTExecutionProvider::default()
+                .with_device_id(device_id)
+                .build(),
+        );
+        self
+    }
+
+    /// Run with CoreML (macOS / iOS)
+    #[cfg(feature = "coreml")]
+    pub fn with_coreml(mut self) -> Self {
+        self.accelerators.push(Provider::CoreML);
+        self.options
+            .execution_providers
+            .push(CoreMLExecutionProvider::default().build());
+        self
+    }
+
+    /// Run with DirectML (Windows) on the adapter `device_id`
+    #[cfg(feature = "directml")]
+    pub fn with_directml(mut self, device_id: i32) -> Self {
+        self.accelerators.push(Provider::DirectML);
+        self.options.execution_providers.push(
+            DirectMLExecutionProvider::default()
+                .with_device_id(device_id)
+                .build(),
+        );
+        self
+    }
+
+    /// Add any ORT execution provider
+    pub fn with_execution_provider(
+        mut self,
+        provider: impl Into<ExecutionProviderDispatch>,
+    ) -> Self {
+        self.options.execution_providers.push(provider.into());
+        self
+    }
+
+    /// Apply the `[model]` table of a config file, see [`ModelConfig`](crate::ModelConfig)
+    #[cfg(feature = "config")]
+    pub fn with_config(mut self, config: &crate::ModelConfig) -> Self {
+        if config.low_memory {
+            self = self.with_low_memory();
+        }
+        if config.unload_between_calls {
+            self = self.with_unload_between_calls(true);
+        }
+        if let Some(chars) = config.max_chunk_chars {
+            self = self.with_max_chunk_chars(chars);
+        }
+        for &provider in &config.providers {
+            self = self.with_provider(provider, config.device_id);
+        }
+        if let Some(precision) = config.precision {
+            self = self.with_precision(precision);
+        }
+        if let Some(threads) = config.intra_threads {
+            self = self.with_intra_threads(threads);
+        }
+        if let Some(threads) = config.inter_threads {
+            self = self.with_inter_threads(threads);
+        }
+        if let Some(cores) = &config.thread_affinity {
+            self = self.with_thread_affinity(cores.clone());
+        }
+        self.with_cache_capacity(config.cache_capacity)
+            .with_truncation_policy(config.truncation)
+    }
+
+    #[cfg(feature = "config")]
+    #[cfg_attr(
+        not(any(feature = "cuda", feature = "tensorrt", feature = "directml")),
+        allow(unused_variables)
+    )]
+    fn with_provider(self, provider: Provider, device_id: i32) -> Self {
+        match provider {
+            #[cfg(feature = "cuda")]
+            Provider::Cuda => self.with_cuda(device_id),
+            #[cfg(feature = "tensorrt")]
+            Provider::TensorRT => self.with_tensorrt(device_id),
+            #[cfg(feature = "coreml")]
+            Provider::CoreML => self.with_coreml(),
+            #[cfg(feature = "directml")]
+            Provider::DirectML => self.with_directml(device_id),
+            // Inference falls back to the CPU anyway
+            Provider::Cpu => self,
+            #[allow(unreachable_patterns)]
+            _ => self,
+        }
+    }
+
+    pub fn build(self) -> Result<Phonikud> {
+        #[cfg(feature = "checksum")]
+        self.checksums.verify_tokenizer(&self.tokenizer)?;
+        let tokenizer = match &self.tokenizer {
+            TokenizerSource::File(path) => Tokenizer::from_file(path),
+            TokenizerSource::Bytes(bytes) => Tokenizer::from_bytes(bytes),
+        }
+        .map_err(PhonikudError::TokenizerLoad)?;
+
+        let model = match (self.model, self.precision) {
+            (ModelSource::File(path), Some(precision)) => {
+                let gpu = self.accelerators.iter().any(|provider| provider.is_available());
+                ModelSource::File(precision.resolve(&path, gpu))
+            }
+            (model, _) => model,
+        };
+        #[cfg(feature = "checksum")]
+        self.checksums.verify_model(&model)?;
+        #[cfg(feature = "disk-cache")]
+        let disk_cache = match &self.disk_cache {
+            Some(path) => Some(crate::DiskCache::open(path, &model.sha256()?)?),
+            None => None,
+        };
+        let classes = match &model {
+            ModelSource::File(path) => ClassVocab::from_sidecar(path)?,
+            _ => None,
+        };
+        let source = match model {
+            #[cfg(feature = "mmap")]
+            ModelSource::File(path) if self.mmap => ModelSource::map(&path)?,
+            source => source,
+        };
+        let mut model = PhonikudModel::load(source, Arc::new(tokenizer), self.options, classes)?;
+        model.set_cache_capacity(self.cache_capacity);
+        model.set_truncation_policy(self.truncation);
+        model.set_max_chunk_chars(self.max_chunk_chars);
+        #[cfg_attr(not(feature = "disk-cache"), allow(unused_mut))]
+        let mut phonikud = Phonikud::from_model(model);
+        #[cfg(feature = "disk-cache")]
+        if let Some(disk_cache) = disk_cache {
+            phonikud.set_disk_cache(disk_cache);
+        }
+        Ok(phonikud)
+    }
+}
+
+/// Numeric precision of the model weights, each precision stored in its own file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Precision {
+    /// Quantized weights, the smallest and fastest on the CPU (`*.int8.onnx`)
+    Int8,
+    /// Half precision weights for GPUs (`*.fp16.onnx`)
+    Fp16,
+    /// Full precision, the reference accuracy (`*.onnx`)
+    Fp32,
+    /// fp16 when a requested GPU provider is available and int8 otherwise, keeping
+    /// the given file when that variant doesn't exist
+    #[default]
+    Auto,
+}
+
+impl Precision {
+    /// Suffixes naming the precision in a model file name, fp32 files have none
+    const SUFFIXES: [&str; 3] = [".int8", ".fp16", ".fp32"];
+
+    /// Intra-op threads of an int8 model, the quantized matmuls of a sentence are too
+    /// small to split further. `benches/threads.rs` measures the gain of every count.
+    const MAX_INT8_THREADS: usize = 4;
+    /// Intra-op threads of the fp32 and fp16 models
+    const MAX_FLOAT_THREADS: usize = 8;
+
+    /// Intra-op threads a model of this precision runs on unless
+    /// [`PhonikudBuilder::with_intra_threads`] sets them: every available core, up to 4
+    /// for int8 models and 8 for the others. [`Precision::Auto`] counts as int8, the
+    /// variant it picks on the CPU, and so do models loaded from memory.
+    pub fn default_intra_threads(self) -> usize {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        let max = match self {
+            Precision::Int8 | Precision::Auto => Self::MAX_INT8_THREADS,
+            Precision::Fp16 | Precision::Fp32 => Self::MAX_FLOAT_THREADS,
+        };
+        cores.min(max)
+    }
+
+    /// Precision of the model at `model_path`, named by its suffix
+    pub(crate) fn of_file(model_path: &str) -> Self {
+        let stem = Path::new(model_path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        if stem.ends_with(".int8") {
+            Precision::Int8
+        } else if stem.ends_with(".fp16") {
+            Precision::Fp16
+        } else {
+            Precision::Fp32
+        }
+    }
+
+    /// The file of this precision next to `model_path`, e.g. `phonikud-1.0.fp16.onnx`
+    /// for `phonikud-1.0.int8.onnx`. [`Precision::Auto`] returns `model_path` as is.
+    pub fn model_path(self, model_path: &str) -> String {
+        let suffix = match self {
+            Precision::Int8 => ".int8",
+            Precision::Fp16 => ".fp16",
+            Precision::Fp32 => "",
+            Precision::Auto => return model_path.to_string(),
+        };
+        let path = Path::new(model_path);
+        let (Some(stem), Some(extension)) = (
+            path.file_stem().and_then(|stem| stem.to_str()),
+            path.extension().and_then(|extension| extension.to_str()),
+        ) else {
+            return model_path.to_string();
+        };
+        let base = Self::SUFFIXES
+            .iter()
+            .find_map(|suffix| stem.strip_suffix(suffix))
+            .unwrap_or(stem);
+        path.with_file_name(format!("{base}{suffix}.{extension}"))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Path of the model to load, `gpu` tells whether a GPU provider is available
+    fn resolve(self, model_path: &str, gpu: bool) -> String {
+        if self != Precision::Auto {
+            return self.model_path(model_path);
+        }
+        let precision = if gpu { Precision::Fp16 } else { Precision::Int8 };
+        let path = precision.model_path(model_path);
+        if Path::new(&path).exists() {
+            path
+        } else {
+            model_path.to_string()
+        }
+    }
+}
+
+/// Execution providers known to the builder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Provider {
+    Cpu,
+    Cuda,
+    TensorRT,
+    CoreML,
+    DirectML,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 5] = [
+        Provider::Cpu,
+        Provider::Cuda,
+        Provider::TensorRT,
+        Provider::CoreML,
+        Provider::DirectML,
+    ];
+
+    /// Whether the linked ONNX Runtime supports this provider on the current platform.
+    ///
+    /// Use this to decide up front whether to request a provider or stay on the CPU.
+    pub fn is_available(self) -> bool {
+        let provider = self.execution_provider();
+        provider.supported_by_platform() && provider.is_available().unwrap_or(false)
+    }
+
+    /// Name of the provider in ONNX Runtime, e.g. `CUDAExecutionProvider`
+    pub fn ort_name(self) -> &'static str {
+        self.execution_provider().name()
+    }
+
+    /// The provider ONNX Runtime calls `name`, see [`Provider::ort_name`]
+    pub(crate) fn from_ort_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|provider| provider.ort_name() == name)
+    }
+
+    fn execution_provider(self) -> Box<dyn ExecutionProvider> {
+        match self {
+            Provider::Cpu => Box::new(CPUExecutionProvider::default()),
+            Provider::Cuda => Box::new(CUDAExecutionProvider::default()),
+            Provider::TensorRT => Box::new(TensorRTExecutionProvider::default()),
+            Provider::CoreML => Box::new(CoreMLExecutionProvider::default()),
+            Provider::DirectML => Box::new(DirectMLExecutionProvider::default()),
+        }
+    }
+
+    /// All providers usable in this process
+    pub fn available() -> Vec<Provider> {
+        Self::ALL.into_iter().filter(|p| p.is_available()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_memory_sets_the_session_options() {
+        let builder = PhonikudBuilder::new("model.onnx", "tokenizer.json")
+            .with_intra_threads(4)
+            .with_inter_threads(2)
+            .with_thread_affinity([0, 1, 2, 3])
+            .with_low_memory();
+        assert_eq!(builder.options.intra_threads, Some(1));
+        assert_eq!(builder.options.inter_threads, None);
+        assert_eq!(builder.options.thread_affinity, None);
+        assert_eq!(builder.options.memory_pattern, Some(false));
+        assert!(builder.options.arena_shrinkage);
+        assert!(!builder.options.unload_between_runs);
+        assert_eq!(builder.max_chunk_chars, Some(PhonikudBuilder::LOW_MEMORY_CHUNK_CHARS));
+
+        // Settings made after it override it
+        let builder = builder.with_intra_threads(2).with_max_chunk_chars(256);
+        assert_eq!(builder.options.intra_threads, Some(2));
+        assert_eq!(builder.max_chunk_chars, Some(256));
+    }
+}
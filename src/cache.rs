@@ -0,0 +1,98 @@
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+
+use crate::{Thresholds, TokenPrediction};
+
+/// Hit and miss counters of the prediction cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries currently cached
+    pub len: usize,
+    pub capacity: usize,
+}
+
+/// Cleaned text and the thresholds the binary heads were evaluated with
+#[derive(Hash, PartialEq, Eq)]
+struct CacheKey {
+    text: String,
+    thresholds: [u32; 3],
+}
+
+impl CacheKey {
+    fn new(text: &str, thresholds: &Thresholds) -> Self {
+        Self {
+            text: text.to_string(),
+            thresholds: [
+                thresholds.stress.to_bits(),
+                thresholds.vocal_shva.to_bits(),
+                thresholds.prefix.to_bits(),
+            ],
+        }
+    }
+}
+
+/// LRU cache of the predictions for a cleaned text, shared by every rendering option
+pub(crate) struct PredictionCache {
+    entries: LruCache<CacheKey, Vec<TokenPrediction>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl PredictionCache {
+    pub(crate) fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: LruCache::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Whether `text` is cached, without counting a hit or a miss
+    pub(crate) fn contains(&self, text: &str, thresholds: &Thresholds) -> bool {
+        self.entries.contains(&CacheKey::new(text, thresholds))
+    }
+
+    pub(crate) fn get(
+        &mut self,
+        text: &str,
+        thresholds: &Thresholds,
+    ) -> Option<Vec<TokenPrediction>> {
+        let predictions = self.entries.get(&CacheKey::new(text, thresholds)).cloned();
+        match predictions {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        predictions
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        text: &str,
+        thresholds: &Thresholds,
+        predictions: Vec<TokenPrediction>,
+    ) {
+        self.entries
+            .put(CacheKey::new(text, thresholds), predictions);
+    }
+
+    pub(crate) fn capacity(&self) -> NonZeroUsize {
+        self.entries.cap()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.entries.len(),
+            capacity: self.entries.cap().get(),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{
+    Phonikud, Result, Thresholds,
+    eval::{self, MarkCounts},
+    hebrew::is_hebrew_letter,
+    model::sigmoid,
+    options::{PREFIX_MARK, STRESS_MARK, VOCAL_SHVA_MARK},
+};
+
+/// Thresholds tried for every head, in hundredths
+const CANDIDATES: std::ops::RangeInclusive<u16> = 1..=99;
+
+const DEFAULT_THRESHOLD: f32 = 0.5;
+
+/// Thresholds picked by [`Phonikud::calibrate_thresholds`] and how each head scores with them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    /// The best threshold of every head, ready for [`crate::DiacriticsOptions::thresholds`]
+    pub thresholds: Thresholds,
+    pub stress: HeadCalibration,
+    pub vocal_shva: HeadCalibration,
+    pub prefix: HeadCalibration,
+    /// Hebrew letters of the dev set the heads were scored on
+    pub letters: usize,
+}
+
+/// The operating point of one binary head
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadCalibration {
+    /// Threshold with the best F1 score on the dev set
+    pub threshold: f32,
+    /// Counts at `threshold`
+    pub counts: MarkCounts,
+    /// Counts at the default threshold of 0.5, for comparison
+    pub default_counts: MarkCounts,
+}
+
+impl Phonikud {
+    /// Sweep the stress, vocal shva and prefix thresholds against a labeled dev set and
+    /// pick the one with the best F1 score for every head.
+    ///
+    /// `dev_set` is vocalized text with the phonikud markers, one sentence per line.
+    /// Every line is run through the model once, the thresholds are then tried from
+    /// 0.01 to 0.99. Ties go to the threshold closest to the default.
+    pub fn calibrate_thresholds(&mut self, dev_set: &str) -> Result<Calibration> {
+        // Probability and gold label of every letter, per head
+        let mut samples: [Vec<(f32, bool)>; 3] = Default::default();
+        for line in dev_set.lines() {
+            let gold = eval::units(line);
+            if !gold.iter().any(|unit| is_hebrew_letter(unit.base)) {
+                continue;
+            }
+            let raw = self.run_raw(line)?;
+            // The units line up with the chars of the text without nikud
+            let char_starts: Vec<usize> = raw.text.char_indices().map(|(idx, _)| idx).collect();
+            for (token, range) in raw.offsets.iter().enumerate() {
+                let Ok(idx) = char_starts.binary_search(&range.start) else {
+                    continue;
+                };
+                let Some(unit) = gold.get(idx).filter(|unit| {
+                    !range.is_empty()
+                        && is_hebrew_letter(unit.base)
+                        && raw.text[range.start..].starts_with(unit.base)
+                }) else {
+                    continue;
+                };
+                for (head, mark) in [STRESS_MARK, VOCAL_SHVA_MARK, PREFIX_MARK]
+                    .into_iter()
+                    .enumerate()
+                {
+                    let probability = sigmoid(raw.additional_logits[[token, head]]);
+                    samples[head].push((probability, unit.has(mark)));
+                }
+            }
+        }
+
+        let letters = samples[0].len();
+        let [stress, vocal_shva, prefix] = samples.map(|samples| calibrate(&samples));
+        Ok(Calibration {
+            thresholds: Thresholds {
+                stress: stress.threshold,
+                vocal_shva: vocal_shva.threshold,
+                prefix: prefix.threshold,
+            },
+            stress,
+            vocal_shva,
+            prefix,
+            letters,
+        })
+    }
+}
+
+fn calibrate(samples: &[(f32, bool)]) -> HeadCalibration {
+    let counts_at = |threshold: f32| {
+        let mut counts = MarkCounts::default();
+        for &(probability, gold) in samples {
+            counts.add(probability > threshold, gold);
+        }
+        counts
+    };
+
+    let default_counts = counts_at(DEFAULT_THRESHOLD);
+    let mut best = HeadCalibration {
+        threshold: DEFAULT_THRESHOLD,
+        counts: default_counts,
+        default_counts,
+    };
+    for candidate in CANDIDATES {
+        let threshold = f32::from(candidate) / 100.0;
+        let counts = counts_at(threshold);
+        let (f1, best_f1) = (counts.f1(), best.counts.f1());
+        let closer =
+            (threshold - DEFAULT_THRESHOLD).abs() < (best.threshold - DEFAULT_THRESHOLD).abs();
+        if f1 > best_f1 || f1 == best_f1 && closer {
+            best.threshold = threshold;
+            best.counts = counts;
+        }
+    }
+    best
+}
@@ -0,0 +1,108 @@
+#[cfg(feature = "onnx")]
+use std::sync::Mutex;
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU8, Ordering},
+    },
+};
+
+#[cfg(feature = "onnx")]
+use ort::session::RunOptions;
+
+use crate::error::{PhonikudError, Result};
+
+const RUNNING: u8 = 0;
+const CANCELLED: u8 = 1;
+const TIMED_OUT: u8 = 2;
+
+/// Aborts an inference from another thread.
+///
+/// With ONNX Runtime the running model call is terminated, the tract backend stops
+/// before its next chunk. The call then fails with [`PhonikudError::Cancelled`].
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<State>,
+}
+
+#[derive(Default)]
+struct State {
+    reason: AtomicU8,
+    /// Run options of the ORT call in progress, terminated on cancellation
+    #[cfg(feature = "onnx")]
+    running: Mutex<Option<Arc<RunOptions>>>,
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.stop(CANCELLED);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.reason.load(Ordering::Acquire) != RUNNING
+    }
+
+    /// Cancel because a deadline passed, the call fails with [`PhonikudError::Timeout`]
+    pub(crate) fn expire(&self) {
+        self.stop(TIMED_OUT);
+    }
+
+    fn stop(&self, reason: u8) {
+        // The first reason wins
+        let _ = self.state.reason.compare_exchange(
+            RUNNING,
+            reason,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+        #[cfg(feature = "onnx")]
+        if let Some(run_options) = &*self.state.running.lock().unwrap() {
+            let _ = run_options.terminate();
+        }
+    }
+
+    /// The error to return once cancelled
+    pub(crate) fn check(&self) -> Result<()> {
+        match self.state.reason.load(Ordering::Acquire) {
+            RUNNING => Ok(()),
+            TIMED_OUT => Err(PhonikudError::Timeout),
+            _ => Err(PhonikudError::Cancelled),
+        }
+    }
+
+    /// Run options for the next ORT call, terminated if the token is cancelled meanwhile.
+    /// `run_options` are the ones the session asks for, if any.
+    #[cfg(feature = "onnx")]
+    pub(crate) fn run_options(&self, run_options: Option<RunOptions>) -> Result<Arc<RunOptions>> {
+        let run_options = match run_options {
+            Some(run_options) => run_options,
+            None => RunOptions::new().map_err(PhonikudError::Inference)?,
+        };
+        let run_options = Arc::new(run_options);
+        *self.state.running.lock().unwrap() = Some(run_options.clone());
+        // A cancellation right before registering would otherwise be missed
+        if self.is_cancelled() {
+            let _ = run_options.terminate();
+        }
+        Ok(run_options)
+    }
+
+    /// Forget the run options of the finished ORT call
+    #[cfg(feature = "onnx")]
+    pub(crate) fn finish_run(&self) {
+        self.state.running.lock().unwrap().take();
+    }
+}
@@ -0,0 +1,119 @@
+//! Verify model files against their expected SHA-256 digests
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{PhonikudError, Result};
+
+/// Expected digests of model files, by file name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    digests: HashMap<String, String>,
+}
+
+impl Manifest {
+    /// Parse `sha256sum` output, one `<digest>  <file>` line per file.
+    ///
+    /// Files are looked up by their name, so `models/phonikud.onnx` in the manifest
+    /// matches any `phonikud.onnx`. Blank lines and `#` comments are skipped.
+    pub fn parse(manifest: &str) -> Result<Self> {
+        let mut digests = HashMap::new();
+        for (idx, line) in manifest.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || PhonikudError::InvalidManifest { line: idx + 1 };
+            let (digest, file) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            // `sha256sum --binary` marks the files with `*`
+            let file = file.trim_start().trim_start_matches('*');
+            if !is_sha256(digest) || file.is_empty() {
+                return Err(invalid());
+            }
+            digests.insert(file_name(Path::new(file)), digest.to_lowercase());
+        }
+        Ok(Self { digests })
+    }
+
+    /// Read a manifest written by `sha256sum`, see [`Manifest::parse`]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Expect `sha256` (hex) for the files named like `file`
+    pub fn insert(&mut self, file: &str, sha256: &str) {
+        self.digests.insert(file_name(Path::new(file)), sha256.to_lowercase());
+    }
+
+    /// Expected digest of the file at `path`, if it's listed
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&str> {
+        self.digests.get(&file_name(path.as_ref())).map(String::as_str)
+    }
+
+    /// Check the file at `path` against its digest, files missing from the manifest pass
+    pub fn verify(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        match self.get(path) {
+            Some(expected) => verify_file(path, expected),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fail with [`PhonikudError::ChecksumMismatch`] unless the file at `path` has the
+/// SHA-256 digest `expected` (hex, any case)
+pub fn verify_file(path: impl AsRef<Path>, expected: &str) -> Result<()> {
+    let path = path.as_ref();
+    check(path.to_path_buf(), expected, sha256_file(path)?)
+}
+
+/// Like [`verify_file`] for a file already in memory, `name` stands for its path in
+/// the error
+pub fn verify_bytes(name: &str, bytes: &[u8], expected: &str) -> Result<()> {
+    check(PathBuf::from(name), expected, sha256_bytes(bytes))
+}
+
+fn check(path: PathBuf, expected: &str, actual: String) -> Result<()> {
+    if actual.eq_ignore_ascii_case(expected) {
+        return Ok(());
+    }
+    Err(PhonikudError::ChecksumMismatch {
+        path,
+        expected: expected.to_lowercase(),
+        actual,
+    })
+}
+
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub(crate) fn sha256_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn is_sha256(digest: &str) -> bool {
+    digest.len() == 64 && digest.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+        .into_owned()
+}
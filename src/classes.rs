@@ -0,0 +1,106 @@
+//! Class vocabulary of the nikud and shin heads
+
+use std::{fs, path::Path};
+
+use serde_json::Value;
+
+use crate::error::{PhonikudError, Result};
+
+/// Key of the ONNX metadata entry holding the class vocabulary as JSON
+pub const CLASSES_METADATA_KEY: &str = "phonikud_classes";
+
+/// Marks predicted by each class of the nikud and shin heads.
+///
+/// The 1.0 model vocabulary is the default. Newer models can ship their own,
+/// either under [`CLASSES_METADATA_KEY`] in the ONNX metadata or in a sidecar
+/// JSON file next to the model (`model.onnx` → `model.classes.json`):
+///
+/// ```json
+/// { "nikud_classes": ["", "<MAT_LECT>", "ּ", ...], "shin_classes": ["ׁ", "ׂ"] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassVocab {
+    /// Marks of every nikud class, `<MAT_LECT>` for matres lectionis
+    pub nikud: Vec<String>,
+    /// Shin/sin dot of every shin class
+    pub shin: Vec<String>,
+}
+
+impl Default for ClassVocab {
+    fn default() -> Self {
+        Self {
+            nikud: NIKUD_CLASSES.iter().map(ToString::to_string).collect(),
+            shin: SHIN_CLASSES.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+impl ClassVocab {
+    /// Parse a JSON object with `nikud_classes` and `shin_classes` string arrays
+    pub fn from_json(json: &str) -> Result<Self> {
+        let invalid =
+            |reason: &str| PhonikudError::IncompatibleModel(format!("class vocabulary {reason}"));
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| invalid(&format!("is not valid JSON: {e}")))?;
+
+        let classes = |key: &str| {
+            let array = value
+                .get(key)
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid(&format!("has no {key} array")))?;
+            let classes = array
+                .iter()
+                .map(|class| class.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid(&format!("has a non string entry in {key}")))?;
+            if classes.is_empty() {
+                return Err(invalid(&format!("has an empty {key} array")));
+            }
+            Ok(classes)
+        };
+
+        Ok(Self {
+            nikud: classes("nikud_classes")?,
+            shin: classes("shin_classes")?,
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Read the sidecar file of `model_path` if there is one
+    pub(crate) fn from_sidecar(model_path: &str) -> Result<Option<Self>> {
+        let path = Path::new(model_path).with_extension("classes.json");
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::from_file(path).map(Some)
+    }
+}
+
+// Constants matching Python implementation
+const NIKUD_CLASSES: &[&str] = &[
+    "",
+    "<MAT_LECT>",
+    "\u{05bc}", // dagesh
+    "\u{05b0}", // shva
+    "\u{05b1}", // hataf segol
+    "\u{05b2}", // hataf patah
+    "\u{05b3}", // hataf qamats
+    "\u{05b4}", // hiriq
+    "\u{05b5}", // tsere
+    "\u{05b6}", // segol
+    "\u{05b7}", // patah
+    "\u{05b8}", // qamats
+    "\u{05b9}", // holam
+    "\u{05ba}", // holam haser
+    "\u{05bb}", // qubuts
+    "\u{05bc}\u{05b0}", "\u{05bc}\u{05b1}", "\u{05bc}\u{05b2}", "\u{05bc}\u{05b3}",
+    "\u{05bc}\u{05b4}", "\u{05bc}\u{05b5}", "\u{05bc}\u{05b6}", "\u{05bc}\u{05b7}",
+    "\u{05bc}\u{05b8}", "\u{05bc}\u{05b9}", "\u{05bc}\u{05ba}", "\u{05bc}\u{05bb}",
+    "\u{05c7}",         // qamats qatan
+    "\u{05bc}\u{05c7}", // dagesh + qamats qatan
+];
+
+const SHIN_CLASSES: &[&str] = &["\u{05c1}", "\u{05c2}"]; // shin, sin
@@ -0,0 +1,136 @@
+//! Load the options and model settings shared by services from TOML or JSON files
+
+use std::{fs, path::Path};
+
+use serde_json::Value;
+
+use crate::{DiacriticsOptions, PhonikudError, Result};
+
+impl DiacriticsOptions {
+    /// Load the options from a TOML file, or a JSON file when the extension is `.json`.
+    ///
+    /// Keys are the field names, missing ones keep their default:
+    ///
+    /// ```toml
+    /// stress_mark = "ˈ"
+    /// prefix_mark = "|"
+    /// hebrew_runs_only = true
+    ///
+    /// [thresholds]
+    /// stress = 0.42
+    /// ```
+    ///
+    /// TOML has no null, so an empty string leaves a mark out. A `[model]` table is left
+    /// to [`ModelConfig::from_file`], so one file can describe a whole vocalization profile.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut value = read(path)?;
+        if let Value::Object(map) = &mut value {
+            map.remove(MODEL_TABLE);
+        }
+        deserialize(path, value)
+    }
+}
+
+/// The table of a config file with the model settings
+const MODEL_TABLE: &str = "model";
+
+/// How the model is loaded, the `[model]` table of a config file
+#[cfg(feature = "onnx")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ModelConfig {
+    /// Execution providers to try in order, e.g. `["cuda", "cpu"]`. Providers this
+    /// build wasn't compiled with are skipped, like the ones unavailable at runtime.
+    pub providers: Vec<crate::Provider>,
+    /// Device of the CUDA, TensorRT and DirectML providers
+    pub device_id: i32,
+    pub precision: Option<crate::Precision>,
+    /// See [`crate::PhonikudBuilder::with_cache_capacity`]
+    pub cache_capacity: usize,
+    /// See [`crate::PhonikudBuilder::with_intra_threads`]
+    pub intra_threads: Option<usize>,
+    /// See [`crate::PhonikudBuilder::with_inter_threads`]
+    pub inter_threads: Option<usize>,
+    /// See [`crate::PhonikudBuilder::with_thread_affinity`]
+    pub thread_affinity: Option<Vec<usize>>,
+    /// `"chunk"` or `"error"`, see [`crate::TruncationPolicy`]
+    pub truncation: crate::TruncationPolicy,
+    /// See [`crate::PhonikudBuilder::with_low_memory`], applied before the thread counts
+    pub low_memory: bool,
+    /// See [`crate::PhonikudBuilder::with_unload_between_calls`]
+    pub unload_between_calls: bool,
+    /// See [`crate::PhonikudBuilder::with_max_chunk_chars`]
+    pub max_chunk_chars: Option<usize>,
+}
+
+#[cfg(feature = "onnx")]
+impl ModelConfig {
+    /// Load the `[model]` table of a config file, see [`DiacriticsOptions::from_file`]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let value = match read(path)? {
+            Value::Object(mut map) => map.remove(MODEL_TABLE),
+            _ => None,
+        };
+        deserialize(path, value.unwrap_or_else(|| Value::Object(Default::default())))
+    }
+}
+
+fn read(path: &Path) -> Result<Value> {
+    let source = fs::read_to_string(path)?;
+    let error = |message: String| PhonikudError::Config {
+        path: path.to_path_buf(),
+        message,
+    };
+    if path.extension().is_some_and(|extension| extension == "json") {
+        serde_json::from_str(&source).map_err(|e| error(e.to_string()))
+    } else {
+        let document: toml_edit::DocumentMut =
+            source.parse().map_err(|e: toml_edit::TomlError| error(e.to_string()))?;
+        Ok(table_value(document.as_table().iter()))
+    }
+}
+
+fn deserialize<T: serde::de::DeserializeOwned>(path: &Path, value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(|e| PhonikudError::Config {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+fn table_value<'a>(entries: impl IntoIterator<Item = (&'a str, &'a toml_edit::Item)>) -> Value {
+    let map = entries
+        .into_iter()
+        .filter_map(|(key, item)| Some((key.to_string(), item_value(item)?)))
+        .collect();
+    Value::Object(map)
+}
+
+fn item_value(item: &toml_edit::Item) -> Option<Value> {
+    match item {
+        toml_edit::Item::None => None,
+        toml_edit::Item::Value(value) => Some(toml_value(value)),
+        toml_edit::Item::Table(table) => Some(table_value(table.iter())),
+        toml_edit::Item::ArrayOfTables(tables) => Some(Value::Array(
+            tables.iter().map(|table| table_value(table.iter())).collect(),
+        )),
+    }
+}
+
+fn toml_value(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(string) => Value::String(string.value().clone()),
+        toml_edit::Value::Integer(integer) => Value::from(*integer.value()),
+        toml_edit::Value::Float(float) => Value::from(*float.value()),
+        toml_edit::Value::Boolean(boolean) => Value::Bool(*boolean.value()),
+        toml_edit::Value::Datetime(datetime) => Value::String(datetime.value().to_string()),
+        toml_edit::Value::Array(array) => Value::Array(array.iter().map(toml_value).collect()),
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), toml_value(value)))
+                .collect(),
+        ),
+    }
+}
@@ -0,0 +1,63 @@
+//! Orthography rules masking the nikud classes a letter can't take
+
+use crate::{
+    classes::ClassVocab,
+    hebrew::{is_hebrew_letter, is_matres},
+    model::{DAGESH, MAT_LECT_TOKEN, TokenPrediction},
+};
+
+const SHVA: char = '\u{05b0}';
+const QAMATS: char = '\u{05b8}';
+
+/// Replace every prediction of a forbidden class with the best allowed one.
+///
+/// The class is chosen from the logits kept in the prediction, its confidence
+/// stays the probability the model gave to it.
+pub(crate) fn apply(clean_text: &str, predictions: &mut [TokenPrediction], classes: &ClassVocab) {
+    for prediction in predictions {
+        let (letter, end) = (prediction.char, prediction.end);
+        let allowed = |class: usize| allows(clean_text, letter, end, &classes.nikud[class]);
+        if allowed(prediction.nikud_class) {
+            continue;
+        }
+
+        let scores = &prediction.nikud_logits;
+        let Some(best) = (0..scores.len())
+            .filter(|&class| allowed(class) && !scores[class].is_nan())
+            .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+        else {
+            continue;
+        };
+        let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let sum: f32 = scores.iter().map(|&score| (score - max).exp()).sum();
+        prediction.nikud_class = best;
+        prediction.nikud = classes.nikud[best].clone();
+        prediction.confidence = (scores[best] - max).exp() / sum;
+    }
+}
+
+/// Whether `letter`, ending at byte `end` of `clean_text`, can take the marks of `class`
+pub(crate) fn allows(clean_text: &str, letter: char, end: usize, class: &str) -> bool {
+    let word_final = !clean_text[end..]
+        .chars()
+        .next()
+        .is_some_and(is_hebrew_letter);
+    letter_allows(letter, word_final, class)
+}
+
+fn letter_allows(letter: char, word_final: bool, class: &str) -> bool {
+    if class == MAT_LECT_TOKEN {
+        return is_matres(letter);
+    }
+    let dagesh = class.contains(DAGESH);
+    let mut vowels = class.chars().filter(|&mark| mark != DAGESH);
+    match letter {
+        // Gutturals and resh don't double, a final he takes a mappiq
+        'א' | 'ח' | 'ע' | 'ר' => !dagesh,
+        'ה' => !dagesh || word_final,
+        // Final kaf only takes a shva or a qamats, the other final forms no vowel
+        'ך' => !dagesh && vowels.all(|mark| mark == SHVA || mark == QAMATS),
+        'ם' | 'ן' | 'ף' | 'ץ' => class.is_empty(),
+        _ => true,
+    }
+}
@@ -0,0 +1,190 @@
+//! Predictions kept in a file, so runs over a corpus skip the sentences an earlier
+//! process already vocalized.
+//!
+//! The file starts with a header naming the format version and the SHA-256 digest of the
+//! model, and every other line holds the predictions of one chunk: the hash of the chunk
+//! text and thresholds, a tab and the predictions as JSON. Entries are only appended, and
+//! an index of their offsets is kept in memory.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{PhonikudError, Result, Thresholds, TokenPrediction};
+
+/// Bumped whenever the layout of the file or of [`TokenPrediction`] changes
+const FORMAT_VERSION: u32 = 1;
+const MAGIC: &str = "phonikud-cache";
+
+/// Hash of a chunk and the thresholds it was predicted with
+type Key = [u8; 16];
+
+/// Prediction cache persisted to a file, see
+/// [`Phonikud::set_disk_cache`](crate::Phonikud::set_disk_cache).
+///
+/// A cache written for another model or format version is emptied when it's opened.
+/// Only the main checkpoint is part of the version, clear the cache when the ensemble or
+/// the tokenizer change.
+pub struct DiskCache {
+    path: PathBuf,
+    file: File,
+    header: String,
+    /// Byte offset of the line of every entry
+    index: HashMap<Key, u64>,
+    /// Offset after the last complete entry
+    len: u64,
+}
+
+impl DiskCache {
+    /// Open or create the cache at `path` for the model with the SHA-256 digest
+    /// `model_sha256` (hex)
+    pub fn open(path: impl AsRef<Path>, model_sha256: &str) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let header = format!("{MAGIC} {FORMAT_VERSION} {}\n", model_sha256.to_lowercase());
+        let mut cache = Self {
+            path,
+            file,
+            header,
+            index: HashMap::new(),
+            len: 0,
+        };
+        cache.load()?;
+        Ok(cache)
+    }
+
+    /// Like [`DiskCache::open`], hashing the model file at `model_path`
+    pub fn open_for_model(path: impl AsRef<Path>, model_path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, &crate::checksum::sha256_file(model_path.as_ref())?)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of cached chunks
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Remove every entry, leaving only the header in the file
+    pub fn clear(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(self.header.as_bytes())?;
+        self.index.clear();
+        self.len = self.header.len() as u64;
+        Ok(())
+    }
+
+    /// Index the entries of the file, starting over if it belongs to another model or
+    /// version. A last line cut short, e.g. by a crash while it was written, is dropped.
+    fn load(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let file = self.file.try_clone()?;
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        if line != self.header.as_bytes() {
+            return self.clear();
+        }
+
+        let mut offset = line.len() as u64;
+        let mut index = HashMap::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 || !line.ends_with(b"\n") {
+                break;
+            }
+            let key = line.split(|&byte| byte == b'\t').next();
+            if let Some(key) = key.and_then(|key| parse_key(std::str::from_utf8(key).ok()?)) {
+                index.insert(key, offset);
+            }
+            offset += read as u64;
+        }
+        self.index = index;
+        self.len = offset;
+        self.file.set_len(offset)?;
+        Ok(())
+    }
+
+    pub(crate) fn contains(&self, text: &str, thresholds: &Thresholds) -> bool {
+        self.index.contains_key(&key(text, thresholds))
+    }
+
+    /// The predictions cached for `text`, `None` when missing or unreadable
+    pub(crate) fn get(
+        &mut self,
+        text: &str,
+        thresholds: &Thresholds,
+    ) -> Option<Vec<TokenPrediction>> {
+        let &offset = self.index.get(&key(text, thresholds))?;
+        self.file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut line = String::new();
+        BufReader::new((&self.file).take(self.len - offset))
+            .read_line(&mut line)
+            .ok()?;
+        let (_, predictions) = line.trim_end().split_once('\t')?;
+        serde_json::from_str(predictions).ok()
+    }
+
+    /// Append the predictions of `text`, an entry already cached is replaced
+    pub(crate) fn insert(
+        &mut self,
+        text: &str,
+        thresholds: &Thresholds,
+        predictions: &[TokenPrediction],
+    ) -> Result<()> {
+        let key = key(text, thresholds);
+        let json = serde_json::to_string(predictions)
+            .map_err(|e| PhonikudError::Io(std::io::Error::other(e)))?;
+        let line = format!("{}\t{json}\n", to_hex(&key));
+        self.file.seek(SeekFrom::Start(self.len))?;
+        self.file.write_all(line.as_bytes())?;
+        self.index.insert(key, self.len);
+        self.len += line.len() as u64;
+        Ok(())
+    }
+}
+
+fn key(text: &str, thresholds: &Thresholds) -> Key {
+    let mut hasher = Sha256::new();
+    for threshold in [thresholds.stress, thresholds.vocal_shva, thresholds.prefix] {
+        hasher.update(threshold.to_bits().to_le_bytes());
+    }
+    hasher.update(text.as_bytes());
+    let mut key = Key::default();
+    key.copy_from_slice(&hasher.finalize()[..size_of::<Key>()]);
+    key
+}
+
+fn to_hex(key: &Key) -> String {
+    key.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn parse_key(hex: &str) -> Option<Key> {
+    let mut key = Key::default();
+    if hex.len() != size_of::<Key>() * 2 {
+        return None;
+    }
+    for (idx, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(idx * 2..idx * 2 + 2)?, 16).ok()?;
+    }
+    Some(key)
+}
+
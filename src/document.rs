@@ -0,0 +1,249 @@
+use std::{collections::BTreeMap, ops::Range};
+
+#[cfg(feature = "model")]
+use crate::{DiacriticsOptions, Phonikud, acronym::AcronymMode, segment::sentence_ranges};
+use crate::{
+    PhonikudError, Result,
+    text::{remove_nikud, split_marks},
+};
+
+/// A vocalized text stored as layers: the text without nikud, the marks the model
+/// predicted for its chars and the marks corrected by hand.
+///
+/// Editors keep the corrections apart from the predictions, so running the model again
+/// with [`Phonikud::revocalize_document`] never overwrites them. Offsets are byte offsets
+/// into [`VocalizedDocument::text`], each layer maps the offset of a char to the marks
+/// following it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VocalizedDocument {
+    /// The text without nikud
+    pub text: String,
+    /// Marks predicted by the model
+    pub predicted: BTreeMap<usize, String>,
+    /// Marks set by hand, taking the place of the predicted marks of the same char. An
+    /// empty string leaves the char without marks.
+    pub corrections: BTreeMap<usize, String>,
+}
+
+impl VocalizedDocument {
+    /// A document of `text` without its nikud, with no marks yet
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: remove_nikud(text),
+            ..Self::default()
+        }
+    }
+
+    /// Marks of the char at `offset`, the corrected ones if it was corrected
+    pub fn marks(&self, offset: usize) -> &str {
+        self.corrections
+            .get(&offset)
+            .or_else(|| self.predicted.get(&offset))
+            .map_or("", String::as_str)
+    }
+
+    /// The vocalized text, every char followed by its marks
+    pub fn render(&self) -> String {
+        let marks_len: usize = self.predicted.values().map(String::len).sum();
+        let mut rendered = String::with_capacity(self.text.len() + marks_len);
+        for (idx, ch) in self.text.char_indices() {
+            rendered.push(ch);
+            rendered.push_str(self.marks(idx));
+        }
+        rendered
+    }
+
+    /// Set the marks of the char at `offset` by hand
+    pub fn correct(&mut self, offset: usize, marks: &str) {
+        self.corrections.insert(offset, marks.to_string());
+    }
+
+    /// Correct every char of `range` with the marks of `vocalized`, the same text with
+    /// other marks, e.g. a word retyped in an editor. Chars without marks in `vocalized`
+    /// are corrected to none.
+    ///
+    /// Fails with [`PhonikudError::BaseTextMismatch`], the offset being in `range`, when
+    /// `vocalized` isn't the text of `range` once its marks are removed.
+    pub fn correct_range(&mut self, range: Range<usize>, vocalized: &str) -> Result<()> {
+        let original = &self.text[range.clone()];
+        let (letters, mut marks) = split_marks(vocalized);
+        if letters != original {
+            let offset = letters
+                .char_indices()
+                .zip(original.chars())
+                .find(|&((_, a), b)| a != b)
+                .map_or_else(|| letters.len().min(original.len()), |((idx, _), _)| idx);
+            return Err(PhonikudError::BaseTextMismatch {
+                offset: range.start + offset,
+            });
+        }
+        for (idx, _) in original.char_indices() {
+            let marks = marks.remove(&idx).unwrap_or_default();
+            self.corrections.insert(range.start + idx, marks);
+        }
+        Ok(())
+    }
+
+    /// Drop the corrections of `range`, so the model predicts its marks again
+    pub fn clear_corrections(&mut self, range: Range<usize>) {
+        let corrected: Vec<usize> = self.corrections.range(range).map(|(&idx, _)| idx).collect();
+        for idx in corrected {
+            self.corrections.remove(&idx);
+        }
+    }
+
+    /// Replace `range` of the text with `replacement`, moving the marks after it along.
+    /// The marks of the replaced chars are dropped, and `replacement` is added without its
+    /// marks, which become corrections. No predictions are made, see
+    /// [`Phonikud::edit_document`]. Returns the range of the replacement in the text.
+    ///
+    /// Panics when `range` doesn't fall on char boundaries of the text.
+    pub fn replace_range(&mut self, range: Range<usize>, replacement: &str) -> Range<usize> {
+        let (letters, marks) = split_marks(replacement);
+        self.text.replace_range(range.clone(), &letters);
+        let inserted = range.start..range.start + letters.len();
+        for layer in [&mut self.predicted, &mut self.corrections] {
+            let after = layer.split_off(&range.start);
+            layer.extend(
+                after
+                    .into_iter()
+                    .filter(|&(idx, _)| idx >= range.end)
+                    .map(|(idx, marks)| (idx - range.end + inserted.end, marks)),
+            );
+        }
+        self.corrections
+            .extend(marks.into_iter().map(|(idx, marks)| (inserted.start + idx, marks)));
+        inserted
+    }
+
+    /// Byte ranges of the runs of whitespace delimited words without any correction, what
+    /// [`Phonikud::revocalize_document`] runs the model on
+    pub fn uncorrected_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        // Whether the last word was corrected, a run never spans it
+        let mut after_corrected = true;
+        for word in words(&self.text) {
+            if self.corrections.range(word.clone()).next().is_some() {
+                after_corrected = true;
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(run) if !after_corrected => run.end = word.end,
+                _ => ranges.push(word),
+            }
+            after_corrected = false;
+        }
+        ranges
+    }
+}
+
+/// Byte ranges of the whitespace delimited words of `text`
+fn words(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    text.split_whitespace().map(move |word| {
+        let start = word.as_ptr() as usize - text.as_ptr() as usize;
+        start..start + word.len()
+    })
+}
+
+#[cfg(feature = "model")]
+impl Phonikud {
+    /// Vocalize `text` into a [`VocalizedDocument`], the predictions in its own layer.
+    ///
+    /// The document keeps every char of the text, so the lexicon, ktiv haser and acronym
+    /// expansion of `options` aren't applied.
+    pub fn vocalize_document(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<VocalizedDocument> {
+        let mut document = VocalizedDocument::new(text);
+        self.revocalize_document(&mut document, options)?;
+        Ok(document)
+    }
+
+    /// Run the model again on the words of `document` without corrections, e.g. after
+    /// changing the options or the model. Only [`VocalizedDocument::uncorrected_ranges`]
+    /// go through the model, corrected words and their predictions are kept as they are.
+    pub fn revocalize_document(
+        &mut self,
+        document: &mut VocalizedDocument,
+        options: &DiacriticsOptions,
+    ) -> Result<()> {
+        let all = 0..document.text.len();
+        self.revocalize_within(document, all, options)
+    }
+
+    /// Apply an edit of `document`, replacing `range` of its text with `replacement`, and
+    /// vocalize the sentences it touches again, so live editing doesn't run the whole
+    /// document through the model on every keystroke.
+    ///
+    /// Marks typed in `replacement` become corrections, see
+    /// [`VocalizedDocument::replace_range`]. Returns the byte range of the sentences
+    /// vocalized again, the part of [`VocalizedDocument::render`] that changed along
+    /// with the replacement.
+    pub fn edit_document(
+        &mut self,
+        document: &mut VocalizedDocument,
+        range: Range<usize>,
+        replacement: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<Range<usize>> {
+        let inserted = document.replace_range(range, replacement);
+        // The sentences overlapping the replacement, or holding the point of a deletion
+        let sentences = sentence_ranges(&document.text);
+        let touched: Vec<&Range<usize>> = sentences
+            .iter()
+            .filter(|sentence| sentence.start <= inserted.end && inserted.start <= sentence.end)
+            .collect();
+        let affected = match (touched.first(), touched.last()) {
+            (Some(first), Some(last)) => first.start..last.end,
+            _ => inserted,
+        };
+        self.revocalize_within(document, affected.clone(), options)?;
+        Ok(affected)
+    }
+
+    /// Run the model on the uncorrected words of `document` in `within`
+    fn revocalize_within(
+        &mut self,
+        document: &mut VocalizedDocument,
+        within: Range<usize>,
+        options: &DiacriticsOptions,
+    ) -> Result<()> {
+        let mut options = options.clone();
+        options.lexicon = None;
+        options.ktiv_haser = false;
+        if let AcronymMode::Expand(_) = options.acronyms {
+            options.acronyms = AcronymMode::Protect;
+        }
+
+        let ranges = document.uncorrected_ranges().into_iter().filter_map(|range| {
+            let range = range.start.max(within.start)..range.end.min(within.end);
+            (!range.is_empty()).then_some(range)
+        });
+        for range in ranges.collect::<Vec<_>>() {
+            let vocalization = self.vocalize(&document.text[range.clone()], &options)?;
+            let stale: Vec<usize> = document
+                .predicted
+                .range(range.clone())
+                .map(|(&idx, _)| idx)
+                .collect();
+            for idx in stale {
+                document.predicted.remove(&idx);
+            }
+            // Every span starts with the letter of its prediction
+            for (prediction, span) in vocalization.predictions.iter().zip(&vocalization.spans) {
+                let marks = vocalization.text[span.clone()]
+                    .get(prediction.char.len_utf8()..)
+                    .unwrap_or_default();
+                if !marks.is_empty() {
+                    let offset = range.start + prediction.start;
+                    document.predicted.insert(offset, marks.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
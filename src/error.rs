@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Errors returned by phonikud-rs
+#[derive(Debug, Error)]
+pub enum PhonikudError {
+    /// The ONNX session could not be configured or the model file could not be loaded
+    #[cfg(feature = "onnx")]
+    #[error("Model load error: {0}")]
+    ModelLoad(#[source] ort::Error),
+    #[cfg(feature = "model")]
+    #[error("Tokenizer load error: {0}")]
+    TokenizerLoad(#[source] tokenizers::Error),
+    #[cfg(feature = "model")]
+    #[error("Tokenizer error: {0}")]
+    Tokenize(#[source] tokenizers::Error),
+    /// ONNX Runtime failed while running the model
+    #[cfg(feature = "onnx")]
+    #[error("Inference error: {0}")]
+    Inference(#[source] ort::Error),
+    /// The tract backend failed to load, optimize or run the model
+    #[cfg(feature = "tract")]
+    #[error("Tract error: {0}")]
+    Tract(String),
+    /// A chunk still tokenized to more tokens than the model accepts
+    #[error("Input has {len} tokens but the model accepts at most {max}")]
+    InputTooLong { len: usize, max: usize },
+    /// The model doesn't have the inputs and outputs of a phonikud model
+    #[error("Incompatible model: {0}")]
+    IncompatibleModel(String),
+    /// tokenizer.json has more tokens than the model has embeddings, it belongs to
+    /// another model
+    #[cfg(feature = "model")]
+    #[error(
+        "tokenizer.json has {tokenizer} tokens but the model only embeds {model}, \
+         the tokenizer doesn't belong to this model"
+    )]
+    TokenizerModelMismatch { tokenizer: usize, model: usize },
+    /// An encoding given to [`crate::PhonikudModel::vocalize_pretokenized`] doesn't fit
+    /// its text
+    #[cfg(feature = "model")]
+    #[error("Invalid encoding: {0}")]
+    InvalidEncoding(String),
+    /// The model outputs don't have the expected count, type or shape
+    #[error("Invalid model outputs: {0}")]
+    InvalidModelOutputs(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Download error: {0}")]
+    Download(String),
+    /// A model file doesn't have the expected SHA-256 digest
+    #[error("Checksum mismatch for {}: expected {expected}, got {actual}", path.display())]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    /// A checksum manifest line isn't a digest followed by a file name
+    #[cfg(feature = "checksum")]
+    #[error("Invalid checksum manifest at line {line}")]
+    InvalidManifest { line: usize },
+    /// A config file isn't valid TOML or JSON, or doesn't match the options
+    #[cfg(feature = "config")]
+    #[error("Invalid config {}: {message}", path.display())]
+    Config { path: PathBuf, message: String },
+    /// The predicted and reference texts differ in more than their diacritics
+    #[error("Texts differ at character {offset} after removing nikud")]
+    BaseTextMismatch { offset: usize },
+    /// The call was aborted through its [`crate::CancellationToken`]
+    #[error("Inference cancelled")]
+    Cancelled,
+    /// The call didn't finish before its deadline
+    #[error("Inference timed out")]
+    Timeout,
+    /// The background inference worker is no longer running
+    #[error("Inference worker stopped")]
+    WorkerStopped,
+    /// The queue of pending requests is full, the request was rejected without waiting
+    #[error("Overloaded, {capacity} requests are already pending")]
+    Overloaded { capacity: usize },
+}
+
+pub type Result<T, E = PhonikudError> = std::result::Result<T, E>;
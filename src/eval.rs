@@ -0,0 +1,317 @@
+//! Compare a predicted vocalization against a gold reference
+
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    error::{PhonikudError, Result},
+    hebrew::is_hebrew_letter,
+    options::{PREFIX_MARK, STRESS_MARK, VOCAL_SHVA_MARK},
+    text::is_nikud,
+};
+
+/// Accuracy counts for one or more compared texts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evaluation {
+    /// Hebrew letters compared
+    pub letters: usize,
+    /// Hebrew letters whose diacritics match the reference
+    pub correct_letters: usize,
+    /// Words containing at least one Hebrew letter
+    pub words: usize,
+    /// Words where every letter matches the reference
+    pub correct_words: usize,
+    pub mismatches: Vec<Mismatch>,
+    /// How often each reference vowel was predicted as each vowel, keyed by
+    /// `(gold, predicted)`. Vowels are the vowel points of a letter, empty when it has none.
+    pub confusion: BTreeMap<(String, String), usize>,
+    pub stress: MarkCounts,
+    pub vocal_shva: MarkCounts,
+    pub prefix: MarkCounts,
+}
+
+/// Counts of a mark that a letter either has or not, e.g. stress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MarkCounts {
+    /// Letters with the mark in both texts
+    pub true_positives: usize,
+    /// Letters with the mark in the prediction only
+    pub false_positives: usize,
+    /// Letters with the mark in the reference only
+    pub false_negatives: usize,
+}
+
+impl MarkCounts {
+    /// Share of the predicted marks that are in the reference
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// Share of the reference marks that were predicted
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+
+    pub(crate) fn add(&mut self, predicted: bool, gold: bool) {
+        match (predicted, gold) {
+            (true, true) => self.true_positives += 1,
+            (true, false) => self.false_positives += 1,
+            (false, true) => self.false_negatives += 1,
+            (false, false) => {}
+        }
+    }
+
+    fn merge(&mut self, other: MarkCounts) {
+        self.true_positives += other.true_positives;
+        self.false_positives += other.false_positives;
+        self.false_negatives += other.false_negatives;
+    }
+}
+
+/// A word whose diacritics differ from the reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// Byte range of the word in the predicted text
+    pub predicted_range: Range<usize>,
+    /// Byte range of the word in the reference text
+    pub gold_range: Range<usize>,
+    pub predicted: String,
+    pub gold: String,
+}
+
+impl Evaluation {
+    /// Character level accuracy (CHA), the share of letters with correct diacritics
+    pub fn cha(&self) -> f64 {
+        ratio(self.correct_letters, self.letters)
+    }
+
+    /// Word level accuracy (WOR), the share of words with every letter correct
+    pub fn wor(&self) -> f64 {
+        ratio(self.correct_words, self.words)
+    }
+
+    /// Add the counts of another evaluation, e.g. to score a whole corpus.
+    ///
+    /// Mismatch ranges are kept relative to their own texts.
+    pub fn merge(&mut self, other: Evaluation) {
+        self.letters += other.letters;
+        self.correct_letters += other.correct_letters;
+        self.words += other.words;
+        self.correct_words += other.correct_words;
+        self.mismatches.extend(other.mismatches);
+        for (pair, count) in other.confusion {
+            *self.confusion.entry(pair).or_default() += count;
+        }
+        self.stress.merge(other.stress);
+        self.vocal_shva.merge(other.vocal_shva);
+        self.prefix.merge(other.prefix);
+    }
+}
+
+/// Compare `predicted` against `gold`, letter by letter.
+///
+/// Both texts must be identical once nikud is removed. Every mark is compared,
+/// including stress, vocal shva and prefix markers; strip them first with
+/// [`crate::text::strip_phonikud_markers`] to score standard nikud only.
+/// The order of marks on a letter doesn't matter.
+///
+/// Besides the accuracy, the vowels of every letter are tallied into a
+/// confusion matrix and the stress, vocal shva and prefix markers are counted
+/// for their precision and recall.
+pub fn evaluate(predicted: &str, gold: &str) -> Result<Evaluation> {
+    let (predicted_units, gold_units) = aligned_units(predicted, gold)?;
+
+    let mut evaluation = Evaluation::default();
+    let mut word: Option<(usize, bool, bool)> = None; // (first unit, has letters, all correct)
+
+    let pairs = predicted_units.iter().zip(&gold_units).enumerate();
+    for (idx, (p, g)) in pairs.chain(std::iter::once((gold_units.len(), (&SPACE, &SPACE)))) {
+        if p.base.is_whitespace() {
+            if let Some((start, true, correct)) = word.take() {
+                evaluation.words += 1;
+                if correct {
+                    evaluation.correct_words += 1;
+                } else {
+                    let predicted_range =
+                        predicted_units[start].range.start..predicted_units[idx - 1].range.end;
+                    let gold_range = gold_units[start].range.start..gold_units[idx - 1].range.end;
+                    evaluation.mismatches.push(Mismatch {
+                        predicted: predicted[predicted_range.clone()].to_string(),
+                        gold: gold[gold_range.clone()].to_string(),
+                        predicted_range,
+                        gold_range,
+                    });
+                }
+            }
+            continue;
+        }
+
+        let (_, has_letters, all_correct) = word.get_or_insert((idx, false, true));
+        if is_hebrew_letter(p.base) {
+            let correct = p.marks == g.marks;
+            evaluation.letters += 1;
+            evaluation.correct_letters += usize::from(correct);
+            *has_letters = true;
+            *all_correct &= correct;
+
+            *evaluation.confusion.entry((g.vowel(), p.vowel())).or_default() += 1;
+            for (counts, mark) in [
+                (&mut evaluation.stress, STRESS_MARK),
+                (&mut evaluation.vocal_shva, VOCAL_SHVA_MARK),
+                (&mut evaluation.prefix, PREFIX_MARK),
+            ] {
+                counts.add(p.has(mark), g.has(mark));
+            }
+        }
+    }
+
+    Ok(evaluation)
+}
+
+/// A run of the compared texts whose diacritics either all match or all differ
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffSegment {
+    /// Byte range of the run in the predicted text
+    pub predicted_range: Range<usize>,
+    /// Byte range of the run in the reference text
+    pub gold_range: Range<usize>,
+    /// Whether the marks of every char in the run match the reference
+    pub equal: bool,
+}
+
+/// Split `predicted` and `gold` into runs of chars with matching and differing marks.
+///
+/// The segments cover both texts from start to end, alternating between equal
+/// and differing runs. Unlike [`evaluate`], marks on non Hebrew chars are
+/// compared as well. Both texts must be identical once nikud is removed.
+pub fn diff(predicted: &str, gold: &str) -> Result<Vec<DiffSegment>> {
+    let (predicted_units, gold_units) = aligned_units(predicted, gold)?;
+
+    let mut segments: Vec<DiffSegment> = Vec::new();
+    for (p, g) in predicted_units.iter().zip(&gold_units) {
+        let equal = p.marks == g.marks;
+        match segments.last_mut() {
+            Some(segment) if segment.equal == equal => {
+                segment.predicted_range.end = p.range.end;
+                segment.gold_range.end = g.range.end;
+            }
+            last => {
+                // Start where the previous run ended, so marks without a base are covered
+                let (predicted_start, gold_start) = last.map_or((0, 0), |segment| {
+                    (segment.predicted_range.end, segment.gold_range.end)
+                });
+                segments.push(DiffSegment {
+                    predicted_range: predicted_start..p.range.end,
+                    gold_range: gold_start..g.range.end,
+                    equal,
+                });
+            }
+        }
+    }
+    match segments.last_mut() {
+        Some(segment) => {
+            segment.predicted_range.end = predicted.len();
+            segment.gold_range.end = gold.len();
+        }
+        None if predicted.len() + gold.len() > 0 => segments.push(DiffSegment {
+            predicted_range: 0..predicted.len(),
+            gold_range: 0..gold.len(),
+            equal: predicted == gold,
+        }),
+        None => {}
+    }
+    Ok(segments)
+}
+
+/// The units of both texts, checking that they have the same base chars
+fn aligned_units(predicted: &str, gold: &str) -> Result<(Vec<Unit>, Vec<Unit>)> {
+    let predicted_units = units(predicted);
+    let gold_units = units(gold);
+
+    let mismatch_at = predicted_units
+        .iter()
+        .zip(&gold_units)
+        .position(|(p, g)| p.base != g.base)
+        .or((predicted_units.len() != gold_units.len())
+            .then(|| predicted_units.len().min(gold_units.len())));
+    match mismatch_at {
+        Some(offset) => Err(PhonikudError::BaseTextMismatch { offset }),
+        None => Ok((predicted_units, gold_units)),
+    }
+}
+
+/// A base character and the marks that follow it
+pub(crate) struct Unit {
+    pub(crate) base: char,
+    /// Sorted, so marks typed in a different order still compare equal
+    marks: Vec<char>,
+    range: Range<usize>,
+}
+
+const SPACE: Unit = Unit {
+    base: ' ',
+    marks: Vec::new(),
+    range: 0..0,
+};
+
+impl Unit {
+    /// Whether the marks include the single char `mark`, e.g. [`STRESS_MARK`]
+    pub(crate) fn has(&self, mark: &str) -> bool {
+        self.marks.iter().any(|&ch| mark.chars().eq([ch]))
+    }
+
+    /// The vowel points among the marks, without dagesh, shin dots and phonikud markers
+    fn vowel(&self) -> String {
+        self.marks
+            .iter()
+            .filter(|&&ch| matches!(ch, '\u{05b0}'..='\u{05bb}' | '\u{05c7}'))
+            .collect()
+    }
+}
+
+pub(crate) fn units(text: &str) -> Vec<Unit> {
+    let mut units: Vec<Unit> = Vec::new();
+    for (idx, ch) in text.char_indices() {
+        let end = idx + ch.len_utf8();
+        match units.last_mut() {
+            Some(unit) if is_nikud(ch) => {
+                unit.marks.push(ch);
+                unit.range.end = end;
+            }
+            // A mark without a base character to attach to
+            None if is_nikud(ch) => {}
+            _ => units.push(Unit {
+                base: ch,
+                marks: Vec::new(),
+                range: idx..end,
+            }),
+        }
+    }
+    for unit in &mut units {
+        unit.marks.sort_unstable();
+    }
+    units
+}
+
+
+fn ratio(correct: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        correct as f64 / total as f64
+    }
+}
@@ -0,0 +1,234 @@
+//! Export the predictions of vocalized sentences as tables, one row per letter, to study
+//! the model with pandas, R or the CoNLL-U tools
+//!
+//! Rows hold the index of the sentence and of the word, the letter and its byte range in
+//! the input, the predicted classes and flags of the four heads and their probabilities.
+
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+use crate::{
+    TokenPrediction, Vocalization,
+    model::{sigmoid, softmax_probability},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Tab separated values with a header row, one row per letter
+    Tsv,
+    /// CoNLL-U, with a line per letter grouped under a multiword token line per word.
+    /// The classes and flags are in the FEATS column, the probabilities and the
+    /// byte range in MISC.
+    Conllu,
+}
+
+const TSV_HEADER: &[&str] = &[
+    "sentence",
+    "word",
+    "char",
+    "start",
+    "end",
+    "nikud_class",
+    "nikud",
+    "nikud_probability",
+    "shin_class",
+    "shin",
+    "shin_probability",
+    "stress",
+    "stress_probability",
+    "vocal_shva",
+    "vocal_shva_probability",
+    "prefix",
+    "prefix_probability",
+];
+
+/// Write the predictions of `sentences`, every input text with its vocalization, to `out`.
+///
+/// Sentences and words are numbered from 1, words being the runs of the input between
+/// whitespace.
+pub fn write_predictions<'a, W: Write>(
+    out: &mut W,
+    format: ExportFormat,
+    sentences: impl IntoIterator<Item = (&'a str, &'a Vocalization)>,
+) -> io::Result<()> {
+    write_header(out, format)?;
+    for (idx, (input, vocalization)) in sentences.into_iter().enumerate() {
+        write_sentence(out, format, idx + 1, input, vocalization)?;
+    }
+    Ok(())
+}
+
+/// Write the header row of a TSV file, CoNLL-U files have none
+pub fn write_header<W: Write>(out: &mut W, format: ExportFormat) -> io::Result<()> {
+    match format {
+        ExportFormat::Tsv => writeln!(out, "{}", TSV_HEADER.join("\t")),
+        ExportFormat::Conllu => Ok(()),
+    }
+}
+
+/// Write the rows of sentence number `sentence`, for exports streamed sentence by sentence
+/// after [`write_header`]
+pub fn write_sentence<W: Write>(
+    out: &mut W,
+    format: ExportFormat,
+    sentence: usize,
+    input: &str,
+    vocalization: &Vocalization,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Tsv => write_tsv(out, sentence, input, &vocalization.predictions),
+        ExportFormat::Conllu => write_conllu(out, sentence, input, &vocalization.predictions),
+    }
+}
+
+/// Probabilities of the predicted classes and of the stress, vocal shva and prefix heads
+struct Probabilities {
+    nikud: f32,
+    shin: Option<f32>,
+    stress: f32,
+    vocal_shva: f32,
+    prefix: f32,
+}
+
+impl Probabilities {
+    fn of(prediction: &TokenPrediction) -> Self {
+        let shin = prediction
+            .shin_class
+            .filter(|&class| class < prediction.shin_logits.len())
+            .map(|class| softmax_probability(&prediction.shin_logits, class));
+        let [stress, vocal_shva, prefix] = prediction.additional_logits.map(sigmoid);
+        Self {
+            nikud: prediction.confidence,
+            shin,
+            stress,
+            vocal_shva,
+            prefix,
+        }
+    }
+}
+
+fn write_tsv<W: Write>(
+    out: &mut W,
+    sentence: usize,
+    input: &str,
+    predictions: &[TokenPrediction],
+) -> io::Result<()> {
+    let words = WordIndex::new(input);
+    for prediction in predictions {
+        let probabilities = Probabilities::of(prediction);
+        let row = [
+            sentence.to_string(),
+            words.of(prediction.start).to_string(),
+            prediction.char.to_string(),
+            prediction.start.to_string(),
+            prediction.end.to_string(),
+            prediction.nikud_class.to_string(),
+            prediction.nikud.clone(),
+            probabilities.nikud.to_string(),
+            optional(prediction.shin_class),
+            prediction.shin.clone().unwrap_or_default(),
+            optional(probabilities.shin),
+            flag(prediction.stress),
+            probabilities.stress.to_string(),
+            flag(prediction.vocal_shva),
+            probabilities.vocal_shva.to_string(),
+            flag(prediction.prefix),
+            probabilities.prefix.to_string(),
+        ];
+        writeln!(out, "{}", row.join("\t"))?;
+    }
+    Ok(())
+}
+
+fn write_conllu<W: Write>(
+    out: &mut W,
+    sentence: usize,
+    input: &str,
+    predictions: &[TokenPrediction],
+) -> io::Result<()> {
+    writeln!(out, "# sent_id = {sentence}")?;
+    writeln!(out, "# text = {}", input.replace(['\r', '\n'], " "))?;
+    let words = WordIndex::new(input);
+    let mut id = 1;
+    for word in predictions.chunk_by(|a, b| words.of(a.start) == words.of(b.start)) {
+        // Words of a single letter are one token, without a multiword line
+        if word.len() > 1 {
+            let form = &input[words.range(words.of(word[0].start))];
+            let last = id + word.len() - 1;
+            writeln!(out, "{id}-{last}\t{form}\t_\t_\t_\t_\t_\t_\t_\t_")?;
+        }
+        for prediction in word {
+            let probabilities = Probabilities::of(prediction);
+            let mut feats = vec![format!("NikudClass={}", prediction.nikud_class)];
+            feats.extend(prediction.prefix.then(|| "Prefix=Yes".to_string()));
+            feats.extend(
+                prediction
+                    .shin_class
+                    .map(|class| format!("ShinClass={class}")),
+            );
+            feats.extend(prediction.stress.then(|| "Stress=Yes".to_string()));
+            feats.extend(prediction.vocal_shva.then(|| "VocalShva=Yes".to_string()));
+            let mut misc = vec![format!("NikudProb={}", probabilities.nikud)];
+            misc.push(format!("PrefixProb={}", probabilities.prefix));
+            misc.push(format!("Range={}:{}", prediction.start, prediction.end));
+            misc.extend(probabilities.shin.map(|shin| format!("ShinProb={shin}")));
+            misc.push(format!("StressProb={}", probabilities.stress));
+            misc.push(format!("VocalShvaProb={}", probabilities.vocal_shva));
+            writeln!(
+                out,
+                "{id}\t{}\t_\t_\t_\t{}\t_\t_\t_\t{}",
+                prediction.char,
+                feats.join("|"),
+                misc.join("|"),
+            )?;
+            id += 1;
+        }
+    }
+    writeln!(out)
+}
+
+/// Byte ranges of the whitespace separated words of a text
+struct WordIndex(Vec<Range<usize>>);
+
+impl WordIndex {
+    fn new(text: &str) -> Self {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (idx, ch) in text.char_indices() {
+            match (ch.is_whitespace(), start) {
+                (false, None) => start = Some(idx),
+                (true, Some(word_start)) => {
+                    words.push(word_start..idx);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(word_start) = start {
+            words.push(word_start..text.len());
+        }
+        Self(words)
+    }
+
+    /// Number of the word at byte `offset`, from 1
+    fn of(&self, offset: usize) -> usize {
+        self.0
+            .partition_point(|word| word.end <= offset)
+            .min(self.0.len().saturating_sub(1))
+            + 1
+    }
+
+    fn range(&self, word: usize) -> Range<usize> {
+        self.0.get(word - 1).cloned().unwrap_or_default()
+    }
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn flag(value: bool) -> String {
+    u8::from(value).to_string()
+}
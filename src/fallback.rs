@@ -0,0 +1,206 @@
+//! Rule and dictionary based vocalization, a rough stand-in for the model.
+//!
+//! Common function words are looked up in a small dictionary, with a `ו` prefix
+//! vocalized by rule. Other words only get the marks that hold almost everywhere:
+//! the dagesh of `ב`, `כ` and `פ` at the start of a word and the shin dot.
+
+use std::ops::Range;
+
+use crate::{hebrew::is_hebrew_letter, text::remove_nikud};
+
+const DAGESH: char = '\u{05bc}';
+const SHIN_DOT: char = '\u{05c1}';
+const SHVA: char = '\u{05b0}';
+
+/// Common words and their nikud, sorted by the unvocalized word
+const DICTIONARY: &[(&str, &str)] = &[
+    ("אבל", "אֲבָל"),
+    ("או", "אוֹ"),
+    ("אותה", "אוֹתָה"),
+    ("אותו", "אוֹתוֹ"),
+    ("אז", "אָז"),
+    ("אחד", "אֶחָד"),
+    ("אחת", "אַחַת"),
+    ("איך", "אֵיךְ"),
+    ("אין", "אֵין"),
+    ("אל", "אֶל"),
+    ("אם", "אִם"),
+    ("אנחנו", "אֲנַחְנוּ"),
+    ("אני", "אֲנִי"),
+    ("את", "אֶת"),
+    ("אתה", "אַתָּה"),
+    ("גם", "גַּם"),
+    ("הוא", "הוּא"),
+    ("היא", "הִיא"),
+    ("היה", "הָיָה"),
+    ("הם", "הֵם"),
+    ("הן", "הֵן"),
+    ("זאת", "זֹאת"),
+    ("זה", "זֶה"),
+    ("טוב", "טוֹב"),
+    ("יום", "יוֹם"),
+    ("יש", "יֵשׁ"),
+    ("ישראל", "יִשְׂרָאֵל"),
+    ("כי", "כִּי"),
+    ("כך", "כָּךְ"),
+    ("כל", "כָּל"),
+    ("כן", "כֵּן"),
+    ("לא", "לֹא"),
+    ("למה", "לָמָּה"),
+    ("מאוד", "מְאוֹד"),
+    ("מה", "מָה"),
+    ("מי", "מִי"),
+    ("עוד", "עוֹד"),
+    ("עולם", "עוֹלָם"),
+    ("על", "עַל"),
+    ("עם", "עִם"),
+    ("פה", "פֹּה"),
+    ("רק", "רַק"),
+    ("של", "שֶׁל"),
+    ("שלום", "שָׁלוֹם"),
+    ("שם", "שָׁם"),
+    ("תודה", "תּוֹדָה"),
+];
+
+/// Vocalize `text` without the model, existing nikud is removed first
+pub fn vocalize(text: &str) -> String {
+    vocalize_words(text).0
+}
+
+/// Vocalize `text` and return the byte range of every vocalized word in the result
+pub(crate) fn vocalize_words(text: &str) -> (String, Vec<Range<usize>>) {
+    let clean_text = remove_nikud(text);
+    let mut result = String::with_capacity(clean_text.len() * 2);
+    let mut words = Vec::new();
+    let mut prev_index = 0;
+
+    let mut word_start = None;
+    for (idx, ch) in clean_text
+        .char_indices()
+        .chain(std::iter::once((clean_text.len(), ' ')))
+    {
+        if is_hebrew_letter(ch) {
+            word_start.get_or_insert(idx);
+            continue;
+        }
+        let Some(start) = word_start.take() else {
+            continue;
+        };
+        result.push_str(&clean_text[prev_index..start]);
+        let output_start = result.len();
+        vocalize_word(&clean_text[start..idx], &mut result);
+        words.push(output_start..result.len());
+        prev_index = idx;
+    }
+    result.push_str(&clean_text[prev_index..]);
+
+    (result, words)
+}
+
+fn vocalize_word(word: &str, result: &mut String) {
+    if let Some(vocalized) = lookup(word) {
+        result.push_str(vocalized);
+        return;
+    }
+    // The conjunction: shuruk before labials and shva, shva otherwise
+    if let Some(stem) = word.strip_prefix('ו')
+        && let Some(vocalized) = lookup(stem)
+    {
+        let shuruk = stem.starts_with(['ב', 'מ', 'פ']) || vocalized.chars().nth(1) == Some(SHVA);
+        result.push('ו');
+        result.push(if shuruk { DAGESH } else { SHVA });
+        result.push_str(vocalized);
+        return;
+    }
+
+    for (idx, ch) in word.char_indices() {
+        result.push(ch);
+        if idx == 0 && matches!(ch, 'ב' | 'כ' | 'פ') {
+            result.push(DAGESH);
+        }
+        // Shin is far more common than sin
+        if ch == 'ש' {
+            result.push(SHIN_DOT);
+        }
+    }
+}
+
+fn lookup(word: &str) -> Option<&'static str> {
+    DICTIONARY
+        .binary_search_by_key(&word, |&(word, _)| word)
+        .ok()
+        .map(|idx| DICTIONARY[idx].1)
+}
+
+#[cfg(feature = "model")]
+impl crate::Phonikud {
+    /// Like [`Phonikud::vocalize`](crate::Phonikud::vocalize), vocalizing the text with
+    /// the rules when inference fails
+    pub fn vocalize_or_fallback(
+        &mut self,
+        text: &str,
+        options: &crate::DiacriticsOptions,
+    ) -> crate::Vocalization {
+        match self.vocalize(text, options) {
+            Ok(vocalization) => vocalization,
+            #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, "inference failed, using the fallback vocalizer");
+                fallback_vocalization(text)
+            }
+        }
+    }
+}
+
+/// The model when it could be loaded, the rule based fallback otherwise
+#[cfg(feature = "model")]
+pub enum Vocalizer {
+    Model(Box<crate::Phonikud>),
+    Fallback,
+}
+
+#[cfg(feature = "model")]
+impl Vocalizer {
+    /// Load the model, or use the fallback when the files are missing or invalid
+    pub fn load(model_path: &str, tokenizer_path: &str) -> Self {
+        match crate::Phonikud::new(model_path, tokenizer_path) {
+            Ok(phonikud) => Vocalizer::Model(Box::new(phonikud)),
+            #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, "failed to load the model, using the fallback vocalizer");
+                Vocalizer::Fallback
+            }
+        }
+    }
+
+    /// Vocalize `text` with the model, or the fallback when it's missing or fails
+    pub fn vocalize(
+        &mut self,
+        text: &str,
+        options: &crate::DiacriticsOptions,
+    ) -> crate::Vocalization {
+        match self {
+            Vocalizer::Model(phonikud) => phonikud.vocalize_or_fallback(text, options),
+            Vocalizer::Fallback => fallback_vocalization(text),
+        }
+    }
+
+    pub fn add_diacritics(&mut self, text: &str) -> String {
+        self.vocalize(text, &crate::DiacriticsOptions::default())
+            .text
+    }
+}
+
+#[cfg(feature = "model")]
+fn fallback_vocalization(text: &str) -> crate::Vocalization {
+    let (text, fallback_words) = vocalize_words(text);
+    crate::Vocalization {
+        text,
+        predictions: Vec::new(),
+        spans: Vec::new(),
+        warnings: Vec::new(),
+        fallback_words,
+    }
+}
@@ -0,0 +1,164 @@
+//! C API, see `include/phonikud.h`
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{Phonikud, PhonikudError};
+
+/// Return codes of the C API, mirrored in `include/phonikud.h`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhonikudStatus {
+    Ok = 0,
+    NullArgument = 1,
+    InvalidUtf8 = 2,
+    ModelLoad = 3,
+    Inference = 4,
+    InputTooLong = 5,
+    Error = 6,
+    Panic = 7,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn status_of(error: &PhonikudError) -> PhonikudStatus {
+    match error {
+        #[cfg(feature = "onnx")]
+        PhonikudError::ModelLoad(_) => PhonikudStatus::ModelLoad,
+        #[cfg(feature = "onnx")]
+        PhonikudError::Inference(_) => PhonikudStatus::Inference,
+        PhonikudError::TokenizerLoad(_) | PhonikudError::IncompatibleModel(_) => {
+            PhonikudStatus::ModelLoad
+        }
+        PhonikudError::InputTooLong { .. } => PhonikudStatus::InputTooLong,
+        _ => PhonikudStatus::Error,
+    }
+}
+
+/// Run `f`, turning errors and panics into a status code and the thread's last error
+fn guard(f: impl FnOnce() -> Result<(), PhonikudStatus>) -> PhonikudStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => PhonikudStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => {
+            set_last_error("panic in phonikud".to_string());
+            PhonikudStatus::Panic
+        }
+    }
+}
+
+fn fail(error: PhonikudError) -> PhonikudStatus {
+    let status = status_of(&error);
+    set_last_error(error.to_string());
+    status
+}
+
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL terminated string.
+unsafe fn to_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, PhonikudStatus> {
+    if ptr.is_null() {
+        set_last_error(format!("{name} is null"));
+        return Err(PhonikudStatus::NullArgument);
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().map_err(|_| {
+        set_last_error(format!("{name} is not valid UTF-8"));
+        PhonikudStatus::InvalidUtf8
+    })
+}
+
+/// Load a model, storing the handle in `out` on success.
+///
+/// # Safety
+///
+/// `model_path` and `tokenizer_path` must be NUL terminated strings and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phonikud_new(
+    model_path: *const c_char,
+    tokenizer_path: *const c_char,
+    out: *mut *mut Phonikud,
+) -> PhonikudStatus {
+    guard(|| {
+        if out.is_null() {
+            set_last_error("out is null".to_string());
+            return Err(PhonikudStatus::NullArgument);
+        }
+        let model_path = unsafe { to_str(model_path, "model_path") }?;
+        let tokenizer_path = unsafe { to_str(tokenizer_path, "tokenizer_path") }?;
+        let phonikud = Phonikud::new(model_path, tokenizer_path).map_err(fail)?;
+        unsafe { *out = Box::into_raw(Box::new(phonikud)) };
+        Ok(())
+    })
+}
+
+/// Vocalize `text`, storing a new string in `out` that must be released with
+/// `phonikud_string_free`.
+///
+/// # Safety
+///
+/// `phonikud` must come from `phonikud_new`, `text` must be a NUL terminated string
+/// and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phonikud_add_diacritics(
+    phonikud: *mut Phonikud,
+    text: *const c_char,
+    out: *mut *mut c_char,
+) -> PhonikudStatus {
+    guard(|| {
+        if phonikud.is_null() || out.is_null() {
+            set_last_error("phonikud or out is null".to_string());
+            return Err(PhonikudStatus::NullArgument);
+        }
+        let text = unsafe { to_str(text, "text") }?;
+        let phonikud = unsafe { &mut *phonikud };
+        let vocalized = phonikud.add_diacritics(text).map_err(fail)?;
+        // The input had no NUL bytes, and the model never adds any
+        let vocalized = CString::new(vocalized).map_err(|e| {
+            set_last_error(e.to_string());
+            PhonikudStatus::Error
+        })?;
+        unsafe { *out = vocalized.into_raw() };
+        Ok(())
+    })
+}
+
+/// Message of the last error on the calling thread, or null. Valid until the next call.
+#[unsafe(no_mangle)]
+pub extern "C" fn phonikud_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// # Safety
+///
+/// `string` must be null or come from `phonikud_add_diacritics`, and not be freed twice.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phonikud_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// # Safety
+///
+/// `phonikud` must be null or come from `phonikud_new`, and not be freed twice.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phonikud_free(phonikud: *mut Phonikud) {
+    if !phonikud.is_null() {
+        drop(unsafe { Box::from_raw(phonikud) });
+    }
+}
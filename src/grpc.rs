@@ -0,0 +1,234 @@
+//! gRPC service of `proto/phonikud.proto`, running its calls through a [`BatchScheduler`]
+
+use std::{net::SocketAddr, time::Duration};
+
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::{
+    BatchScheduler, DecodeWarning, Phonikud, PhonikudError, QamatsQatan, TokenPrediction,
+    VocalShvaStyle, Vocalization, WarningKind, server::RequestOptions,
+};
+
+/// Messages, client and server generated from `proto/phonikud.proto`
+pub mod proto {
+    tonic::include_proto!("phonikud.v1");
+}
+
+use proto::{
+    phonikud_server::PhonikudServer, qamats_qatan::Mark, vocal_shva_style::Style, warning::Kind,
+};
+
+/// Requests vocalized together in one model run
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 16;
+/// Time the first request of a batch waits for others
+pub const DEFAULT_MAX_LATENCY: Duration = Duration::from_millis(5);
+
+/// The `phonikud.v1.Phonikud` service. Concurrent calls share the model runs of one
+/// scheduler, including those of other services holding a clone of it.
+#[derive(Clone)]
+pub struct PhonikudService {
+    scheduler: BatchScheduler,
+}
+
+impl PhonikudService {
+    pub fn new(scheduler: BatchScheduler) -> Self {
+        Self { scheduler }
+    }
+
+    /// The service to add to a [`tonic::transport::Server`]
+    pub fn into_server(self) -> PhonikudServer<Self> {
+        PhonikudServer::new(self)
+    }
+
+    async fn vocalize_text(
+        &self,
+        text: String,
+        options: Option<proto::RequestOptions>,
+    ) -> Result<proto::VocalizeResponse, Status> {
+        let options = RequestOptions::from(options.unwrap_or_default());
+        let vocalization = self
+            .scheduler
+            .vocalize_async(text, options.into())
+            .await
+            .map_err(status)?;
+        Ok(vocalization.into())
+    }
+}
+
+#[tonic::async_trait]
+impl proto::phonikud_server::Phonikud for PhonikudService {
+    async fn vocalize(
+        &self,
+        request: Request<proto::VocalizeRequest>,
+    ) -> Result<Response<proto::VocalizeResponse>, Status> {
+        let request = request.into_inner();
+        let response = self.vocalize_text(request.text, request.options).await?;
+        Ok(Response::new(response))
+    }
+
+    async fn vocalize_document(
+        &self,
+        request: Request<Streaming<proto::VocalizeRequest>>,
+    ) -> Result<Response<proto::VocalizeResponse>, Status> {
+        let mut parts = request.into_inner();
+        let mut text = String::new();
+        let mut options = None;
+        while let Some(part) = parts.message().await? {
+            // The options of the first part apply to the whole document
+            options.get_or_insert(part.options);
+            text.push_str(&part.text);
+        }
+        let response = self.vocalize_text(text, options.flatten()).await?;
+        Ok(Response::new(response))
+    }
+}
+
+/// The status a call fails with, like the status codes of the HTTP server
+fn status(error: PhonikudError) -> Status {
+    let message = error.to_string();
+    match error {
+        PhonikudError::InputTooLong { .. } => Status::invalid_argument(message),
+        PhonikudError::WorkerStopped => Status::unavailable(message),
+        PhonikudError::Overloaded { .. } => Status::resource_exhausted(message),
+        PhonikudError::Timeout => Status::deadline_exceeded(message),
+        _ => Status::internal(message),
+    }
+}
+
+impl From<proto::RequestOptions> for RequestOptions {
+    fn from(request: proto::RequestOptions) -> Self {
+        let qamats_qatan = request.qamats_qatan.and_then(|style| style.mark);
+        let vocal_shva_style = request.vocal_shva_style.and_then(|style| style.style);
+        Self {
+            nikud: request.nikud,
+            dagesh: request.dagesh,
+            shin_dot: request.shin_dot,
+            stress: request.stress,
+            vocal_shva: request.vocal_shva,
+            prefix: request.prefix,
+            mark_matres_lectionis: request.mark_matres_lectionis,
+            ktiv_haser: request.ktiv_haser,
+            preserve_existing: request.preserve_existing,
+            keep_taamim: request.keep_taamim,
+            hebrew_runs_only: request.hebrew_runs_only,
+            orthography_constraints: request.orthography_constraints,
+            fix_final_forms: request.fix_final_forms,
+            normalize_presentation_forms: request.normalize_presentation_forms,
+            respell: request.respell,
+            qamats_qatan: qamats_qatan.map(|mark| match mark {
+                Mark::Dedicated(_) => QamatsQatan::Dedicated,
+                Mark::Qamats(_) => QamatsQatan::Qamats,
+                Mark::Marked(marker) => QamatsQatan::Marked(marker),
+            }),
+            vocal_shva_style: vocal_shva_style.map(|style| match style {
+                Style::Marked(_) => VocalShvaStyle::Marked,
+                Style::HatafSegol(_) => VocalShvaStyle::HatafSegol,
+                Style::Hint(hint) => VocalShvaStyle::Hint(hint),
+            }),
+            stress_threshold: request.stress_threshold,
+            vocal_shva_threshold: request.vocal_shva_threshold,
+            prefix_threshold: request.prefix_threshold,
+        }
+    }
+}
+
+impl From<Vocalization> for proto::VocalizeResponse {
+    fn from(vocalization: Vocalization) -> Self {
+        Self {
+            predictions: vocalization.predictions.iter().map(Into::into).collect(),
+            warnings: vocalization.warnings.iter().map(Into::into).collect(),
+            text: vocalization.text,
+        }
+    }
+}
+
+impl From<&TokenPrediction> for proto::Prediction {
+    fn from(prediction: &TokenPrediction) -> Self {
+        Self {
+            char: prediction.char.to_string(),
+            start: prediction.start as u32,
+            end: prediction.end as u32,
+            nikud_class: prediction.nikud_class as u32,
+            nikud: prediction.nikud.clone(),
+            dagesh: prediction.has_dagesh(),
+            confidence: prediction.confidence,
+            shin_class: prediction.shin_class.map(|class| class as u32),
+            stress: prediction.stress,
+            vocal_shva: prediction.vocal_shva,
+            prefix: prediction.prefix,
+        }
+    }
+}
+
+impl From<&DecodeWarning> for proto::Warning {
+    fn from(warning: &DecodeWarning) -> Self {
+        let kind = match warning.kind {
+            WarningKind::NoToken => Kind::NoToken,
+            WarningKind::SpecialToken => Kind::SpecialToken,
+            WarningKind::OutOfRange => Kind::OutOfRange,
+            WarningKind::DroppedPrediction => Kind::DroppedPrediction,
+        };
+        Self {
+            kind: kind.into(),
+            start: warning.range.start as u32,
+            end: warning.range.end as u32,
+        }
+    }
+}
+
+/// Serve `phonikud` over gRPC on `addr` until the process is stopped, with up to
+/// `queue_capacity` calls waiting for the model. Calls past it fail right away with
+/// `RESOURCE_EXHAUSTED`.
+pub async fn serve(
+    phonikud: Phonikud,
+    addr: SocketAddr,
+    queue_capacity: usize,
+) -> Result<(), tonic::transport::Error> {
+    let scheduler = BatchScheduler::bounded(
+        phonikud,
+        DEFAULT_MAX_BATCH_SIZE,
+        DEFAULT_MAX_LATENCY,
+        queue_capacity,
+    );
+    tonic::transport::Server::builder()
+        .add_service(PhonikudService::new(scheduler).into_server())
+        .serve(addr)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiacriticsOptions;
+
+    #[test]
+    fn options_keep_the_defaults_when_omitted() {
+        let options = RequestOptions::from(proto::RequestOptions::default());
+        assert_eq!(DiacriticsOptions::from(options), DiacriticsOptions::default());
+
+        let request = proto::RequestOptions {
+            stress: Some(false),
+            qamats_qatan: Some(proto::QamatsQatan {
+                mark: Some(Mark::Marked("|".into())),
+            }),
+            vocal_shva_style: Some(proto::VocalShvaStyle {
+                style: Some(Style::HatafSegol(true)),
+            }),
+            prefix_threshold: Some(0.9),
+            ..Default::default()
+        };
+        let options = DiacriticsOptions::from(RequestOptions::from(request));
+        assert_eq!(options.stress_mark, None);
+        assert_eq!(options.qamats_qatan, QamatsQatan::Marked("|".into()));
+        assert_eq!(options.vocal_shva_style, VocalShvaStyle::HatafSegol);
+        assert_eq!(options.thresholds.prefix, 0.9);
+    }
+
+    #[test]
+    fn errors_map_to_the_status_codes_of_the_server() {
+        let overloaded = status(PhonikudError::Overloaded { capacity: 4 });
+        assert_eq!(overloaded.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status(PhonikudError::WorkerStopped).code(), tonic::Code::Unavailable);
+        assert_eq!(status(PhonikudError::Timeout).code(), tonic::Code::DeadlineExceeded);
+    }
+}
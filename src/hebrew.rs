@@ -0,0 +1,109 @@
+//! Classify Hebrew letters and convert them between their final and regular forms
+
+/// The letters written differently at the end of a word, with their final form
+pub const FINAL_FORMS: [(char, char); 5] =
+    [('כ', 'ך'), ('מ', 'ם'), ('נ', 'ן'), ('פ', 'ף'), ('צ', 'ץ')];
+
+/// Whether `ch` is one of the 27 Hebrew letters, final forms included (U+05D0 to U+05EA)
+pub fn is_hebrew_letter(ch: char) -> bool {
+    ('א'..='ת').contains(&ch)
+}
+
+/// Whether `ch` is the final form of a letter, `ך`, `ם`, `ן`, `ף` or `ץ`
+pub fn is_final_form(ch: char) -> bool {
+    FINAL_FORMS.iter().any(|&(_, final_form)| final_form == ch)
+}
+
+/// Whether `ch` is the regular form of a letter with a final form
+pub fn has_final_form(ch: char) -> bool {
+    FINAL_FORMS.iter().any(|&(regular, _)| regular == ch)
+}
+
+/// Whether `ch` is a guttural, `א`, `ה`, `ח` or `ע`. Resh shares their rules on dagesh
+/// but isn't one.
+pub fn is_guttural(ch: char) -> bool {
+    matches!(ch, 'א' | 'ה' | 'ח' | 'ע')
+}
+
+/// Whether `ch` is one of the begadkefat letters, whose sound changes with a dagesh
+/// kal, the final forms of kaf and pe included
+pub fn is_begadkefat(ch: char) -> bool {
+    matches!(ch, 'ב' | 'ג' | 'ד' | 'כ' | 'ך' | 'פ' | 'ף' | 'ת')
+}
+
+/// Whether `ch` is a letter the full spelling (ktiv male) adds as a mater lectionis,
+/// `א`, `ו` or `י`. A final he also only marks a vowel, but it's written in both
+/// spellings.
+pub fn is_matres(ch: char) -> bool {
+    matches!(ch, 'א' | 'ו' | 'י')
+}
+
+/// The final form of `ch`, `ch` itself for letters without one
+pub fn to_final_form(ch: char) -> char {
+    FINAL_FORMS
+        .iter()
+        .find(|&&(regular, _)| regular == ch)
+        .map_or(ch, |&(_, final_form)| final_form)
+}
+
+/// The regular form of a final letter, `ch` itself for the other chars
+pub fn to_regular_form(ch: char) -> char {
+    FINAL_FORMS
+        .iter()
+        .find(|&&(_, final_form)| final_form == ch)
+        .map_or(ch, |&(regular, _)| regular)
+}
+
+/// Whether `ch` is one of the Hebrew presentation forms (U+FB1D to U+FB4F), letters
+/// precomposed with their nikud, wide letters and the alef lamed ligature
+pub fn is_presentation_form(ch: char) -> bool {
+    ('\u{fb1d}'..='\u{fb4f}').contains(&ch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LETTERS: &str = "אבגדהוזחטיךכלםמןנסעףפץצקרשת";
+
+    /// The chars of the Hebrew block, the presentation forms and their neighbours
+    fn chars() -> impl Iterator<Item = char> {
+        ('\u{0590}'..='\u{05ff}').chain('\u{fb1c}'..='\u{fb50}').chain(['a', ' ', '|'])
+    }
+
+    #[test]
+    fn predicates_match_their_letters() {
+        let letters: Vec<char> = ('\u{05d0}'..='\u{05ea}').collect();
+        assert_eq!(letters, LETTERS.chars().collect::<Vec<_>>());
+        for ch in chars() {
+            let letter = LETTERS.contains(ch);
+            assert_eq!(is_hebrew_letter(ch), letter, "{ch:?}");
+            assert_eq!(is_final_form(ch), "ךםןףץ".contains(ch), "{ch:?}");
+            assert_eq!(has_final_form(ch), "כמנפצ".contains(ch), "{ch:?}");
+            assert_eq!(is_guttural(ch), "אהחע".contains(ch), "{ch:?}");
+            assert_eq!(is_begadkefat(ch), "בגדכךפףת".contains(ch), "{ch:?}");
+            assert_eq!(is_matres(ch), "אוי".contains(ch), "{ch:?}");
+            let presentation_form = ('\u{fb1d}'..='\u{fb4f}').contains(&ch);
+            assert_eq!(is_presentation_form(ch), presentation_form, "{ch:?}");
+            assert!(!(letter && presentation_form));
+        }
+    }
+
+    #[test]
+    fn final_forms_round_trip() {
+        for (regular, final_form) in FINAL_FORMS {
+            assert_eq!(to_final_form(regular), final_form);
+            assert_eq!(to_regular_form(final_form), regular);
+            assert_eq!(to_final_form(final_form), final_form);
+            assert_eq!(to_regular_form(regular), regular);
+        }
+        for ch in chars().filter(|&ch| !has_final_form(ch) && !is_final_form(ch)) {
+            assert_eq!(to_final_form(ch), ch, "{ch:?}");
+            assert_eq!(to_regular_form(ch), ch, "{ch:?}");
+        }
+        for ch in chars() {
+            assert_eq!(to_final_form(to_regular_form(ch)), to_final_form(ch), "{ch:?}");
+            assert_eq!(to_regular_form(to_final_form(ch)), to_regular_form(ch), "{ch:?}");
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Vocalize the text of HTML and XHTML documents, e.g. web pages and EPUB chapters
+
+use std::ops::Range;
+
+/// Elements whose content is code rather than text
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+/// Byte ranges of the text nodes of `html`.
+///
+/// Tags with their attributes, comments, CDATA sections, doctypes, processing
+/// instructions and the content of `<script>` and `<style>` are left out.
+/// Entities are part of the text nodes, a `<` that doesn't start a tag as well.
+pub fn text_ranges(html: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut text_start = 0;
+    let mut idx = 0;
+
+    while let Some(offset) = html[idx..].find('<') {
+        let start = idx + offset;
+        let rest = &html[start..];
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| start + end + 3)
+        } else if rest.starts_with("<![CDATA[") {
+            rest.find("]]>").map(|end| start + end + 3)
+        } else if rest[1..].starts_with(|ch: char| ch.is_ascii_alphabetic() || "/!?".contains(ch)) {
+            tag_end(html, start)
+        } else {
+            idx = start + 1;
+            continue;
+        };
+        let end = end.unwrap_or(html.len());
+
+        if text_start < start {
+            ranges.push(text_start..start);
+        }
+        // The content of raw text elements ends at their closing tag
+        let tag = &html[start..end];
+        let name = if tag.ends_with("/>") {
+            ""
+        } else {
+            tag_name(tag)
+        };
+        idx = match RAW_TEXT_ELEMENTS
+            .iter()
+            .find(|raw| name.eq_ignore_ascii_case(raw))
+        {
+            Some(raw) => html[end..]
+                .to_ascii_lowercase()
+                .find(&format!("</{raw}"))
+                .map_or(html.len(), |close| end + close),
+            None => end,
+        };
+        text_start = idx;
+    }
+    if text_start < html.len() {
+        ranges.push(text_start..html.len());
+    }
+
+    ranges
+}
+
+/// End of the tag starting at `start`, skipping `>` inside quoted attribute values
+fn tag_end(html: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (idx, ch) in html[start..].char_indices() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '>') => return Some(start + idx + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Name of an opening tag, empty for closing tags, comments and declarations
+fn tag_name(tag: &str) -> &str {
+    let name = &tag[1..];
+    let end = name
+        .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '-')
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+#[cfg(feature = "model")]
+impl crate::Phonikud {
+    /// Vocalize the text nodes of `html`, keeping everything else byte for byte
+    pub fn add_diacritics_html(
+        &mut self,
+        html: &str,
+        options: &crate::DiacriticsOptions,
+    ) -> crate::Result<String> {
+        self.add_diacritics_in_ranges(html, &text_ranges(html), options)
+    }
+}
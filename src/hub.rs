@@ -0,0 +1,210 @@
+//! Download model files from the Hugging Face Hub into a local cache
+
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use sha1::{Digest, Sha1};
+
+use crate::{PhonikudError, Result, checksum::sha256_file};
+
+pub const DEFAULT_MODEL_FILE: &str = "phonikud-1.0.int8.onnx";
+pub const TOKENIZER_REPO: &str = "dicta-il/dictabert-large-char-menaked";
+pub const TOKENIZER_FILE: &str = "tokenizer.json";
+
+/// A file in a Hugging Face repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HubFile {
+    pub repo: String,
+    pub file: String,
+    pub revision: String,
+    /// Expected SHA-256 digest (lowercase hex), checked after download and on every reuse.
+    /// Without it, a download is checked against the digest the Hub publishes.
+    pub sha256: Option<String>,
+}
+
+impl HubFile {
+    pub fn new(repo: &str, file: &str) -> Self {
+        Self {
+            repo: repo.to_string(),
+            file: file.to_string(),
+            revision: "main".to_string(),
+            sha256: None,
+        }
+    }
+
+    pub fn with_sha256(mut self, sha256: &str) -> Self {
+        self.sha256 = Some(sha256.to_lowercase());
+        self
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "https://huggingface.co/{}/resolve/{}/{}",
+            self.repo, self.revision, self.file
+        )
+    }
+
+    fn cache_path(&self, cache_dir: &Path) -> PathBuf {
+        cache_dir
+            .join(self.repo.replace('/', "--"))
+            .join(&self.revision)
+            .join(&self.file)
+    }
+}
+
+/// Cache directory, `PHONIKUD_CACHE` or the platform cache dir
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("PHONIKUD_CACHE")
+        .map(PathBuf::from)
+        .or_else(|| dirs::cache_dir().map(|dir| dir.join("phonikud")))
+        .unwrap_or_else(|| PathBuf::from(".phonikud-cache"))
+}
+
+/// Return the cached path of `file`, downloading it first when missing or corrupt.
+///
+/// A cached file is reused without network access when its digest matches the
+/// expected one, or the one recorded when it was downloaded.
+///
+/// Downloads are checked against [`HubFile::sha256`], or without it the digest the Hub
+/// publishes for the file, and fail with [`PhonikudError::ChecksumMismatch`].
+pub fn fetch(file: &HubFile) -> Result<PathBuf> {
+    fetch_into(file, &cache_dir())
+}
+
+pub fn fetch_into(file: &HubFile, cache_dir: &Path) -> Result<PathBuf> {
+    let path = file.cache_path(cache_dir);
+    let digest_path = path.with_extension(format!(
+        "{}.sha256",
+        path.extension().and_then(|e| e.to_str()).unwrap_or_default()
+    ));
+
+    if path.exists() {
+        let expected = match &file.sha256 {
+            Some(sha256) => Some(sha256.clone()),
+            None => fs::read_to_string(&digest_path).ok().map(|d| d.trim().to_string()),
+        };
+        if let Some(expected) = expected
+            && sha256_file(&path)? == expected
+        {
+            return Ok(path);
+        }
+    }
+
+    let url = file.url();
+    let expected = match &file.sha256 {
+        Some(sha256) => Published::Sha256(sha256.clone()),
+        None => published_digest(&url)?,
+    };
+    download(&url, &path)?;
+    let actual = sha256_file(&path)?;
+    let (expected, downloaded) = match expected {
+        Published::Sha256(expected) => (expected, actual.clone()),
+        Published::GitSha1(expected) => (expected, git_blob_sha1(&path)?),
+    };
+    if downloaded != expected {
+        let _ = fs::remove_file(&path);
+        return Err(PhonikudError::ChecksumMismatch {
+            path,
+            expected,
+            actual: downloaded,
+        });
+    }
+    fs::write(&digest_path, &actual)?;
+
+    Ok(path)
+}
+
+/// Digest the Hub publishes for a file
+enum Published {
+    /// SHA-256 of a file stored with Git LFS, such as the models
+    Sha256(String),
+    /// Git blob SHA-1 of a file stored in the repository, such as tokenizer.json
+    GitSha1(String),
+}
+
+/// Read the digest of the file at `url` from the headers of its resolve URL. The Hub
+/// redirects LFS files to their storage, so the redirect itself is kept: its
+/// `X-Linked-Etag` is the SHA-256 of the file, the `ETag` of other files their blob SHA-1.
+fn published_digest(url: &str) -> Result<Published> {
+    let response = ureq::AgentBuilder::new()
+        .redirects(0)
+        .build()
+        .head(url)
+        .call()
+        .map_err(|e| PhonikudError::Download(format!("{url}: {e}")))?;
+    let header = |name: &str| {
+        let value = response.header(name)?;
+        let value = value.trim_start_matches("W/").trim_matches('"').to_lowercase();
+        Some(value).filter(|value| value.bytes().all(|byte| byte.is_ascii_hexdigit()))
+    };
+    match (header("x-linked-etag"), header("etag")) {
+        (Some(sha256), _) | (None, Some(sha256)) if sha256.len() == 64 => {
+            Ok(Published::Sha256(sha256))
+        }
+        (None, Some(sha1)) if sha1.len() == 40 => Ok(Published::GitSha1(sha1)),
+        _ => Err(PhonikudError::Download(format!(
+            "{url}: the Hub published no digest to check the download against"
+        ))),
+    }
+}
+
+/// SHA-1 of the file at `path` as a git blob, the `ETag` the Hub gives files outside LFS
+fn git_blob_sha1(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", file.metadata()?.len()));
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Download to a temporary file first so an interrupted download never looks complete
+fn download(url: &str, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension("part");
+
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| PhonikudError::Download(format!("{url}: {e}")))?;
+    let mut file = fs::File::create(&partial)?;
+    io::copy(&mut response.into_reader(), &mut file)?;
+    file.sync_all()?;
+    fs::rename(&partial, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_blob_sha1_matches_git() {
+        let dir = std::env::temp_dir().join(format!("phonikud-hub-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("blob");
+        // `git hash-object` of an empty file and of "hello\n"
+        fs::write(&path, "").unwrap();
+        assert_eq!(
+            git_blob_sha1(&path).unwrap(),
+            "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+        );
+        fs::write(&path, "hello\n").unwrap();
+        assert_eq!(
+            git_blob_sha1(&path).unwrap(),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+//! Describe a loaded model, see [`PhonikudModel::model_info`](crate::PhonikudModel::model_info)
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use crate::model::TensorInfo;
+
+/// ONNX `TensorProto.DataType` values of the weights
+const UINT8: u64 = 2;
+const INT8: u64 = 3;
+const FLOAT16: u64 = 10;
+/// The default operator set domain, also written as an empty string
+const ONNX_DOMAIN: &str = "ai.onnx";
+/// Suffix of the name of the token embedding matrix exported from BERT models
+const WORD_EMBEDDINGS: &str = "word_embeddings.weight";
+
+/// What was loaded and how it runs, for diagnostics and deployment checks
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ModelInfo {
+    /// `"onnxruntime"` or `"tract"`
+    pub backend: &'static str,
+    pub inputs: Vec<TensorInfo>,
+    pub outputs: Vec<TensorInfo>,
+    /// Version of the default ONNX operator set the model was exported with
+    pub opset: Option<i64>,
+    /// Rows of the token embedding matrix, the ids the tokenizer can produce
+    pub vocab_size: Option<usize>,
+    /// ONNX Runtime execution providers of the session in order of preference, those
+    /// unavailable at runtime left out. The CPU is always last, it runs whatever the
+    /// others don't support. Empty with tract.
+    pub providers: Vec<String>,
+    /// Type of the weights, `None` when the model file couldn't be read again
+    pub quantization: Option<Quantization>,
+    /// The exporter, e.g. `pytorch 2.4.0`
+    pub producer: Option<String>,
+    pub model_version: Option<i64>,
+    /// Custom metadata entries of the model, such as
+    /// [`ClassVocab`](crate::ClassVocab)'s `phonikud_classes`
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Numeric type of the model weights, read from its initializers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Quantization {
+    /// 8 bit integer weights, dynamically or statically quantized
+    Int8,
+    Fp16,
+    Fp32,
+}
+
+/// The top level fields of an ONNX `ModelProto`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ModelHeader {
+    pub(crate) opset: Option<i64>,
+    pub(crate) vocab_size: Option<usize>,
+    pub(crate) quantization: Option<Quantization>,
+    pub(crate) producer: Option<String>,
+    pub(crate) model_version: Option<i64>,
+    pub(crate) metadata: BTreeMap<String, String>,
+}
+
+impl ModelHeader {
+    /// Read the header of the ONNX model in `reader`, seeking past the weights
+    pub(crate) fn read(mut reader: impl Read + Seek) -> io::Result<Self> {
+        let mut header = Self::default();
+        let (mut producer_name, mut producer_version) = (None, None);
+        let mut initializers = Vec::new();
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        while reader.stream_position()? < end {
+            match field(&mut reader)? {
+                (2, WireType::Len(len)) => producer_name = Some(string(&mut reader, len)?),
+                (3, WireType::Len(len)) => producer_version = Some(string(&mut reader, len)?),
+                (5, WireType::Varint(version)) => header.model_version = Some(version as i64),
+                (7, WireType::Len(len)) => initializers = graph_initializers(&mut reader, len)?,
+                (8, WireType::Len(len)) => {
+                    let (domain, version) = opset_import(&mut reader, len)?;
+                    if domain.is_empty() || domain == ONNX_DOMAIN {
+                        header.opset = Some(version);
+                    }
+                }
+                (14, WireType::Len(len)) => {
+                    let (key, value) = string_entry(&mut reader, len)?;
+                    header.metadata.insert(key, value);
+                }
+                (_, wire_type) => wire_type.skip(&mut reader)?,
+            }
+        }
+
+        header.producer = match (producer_name, producer_version) {
+            (Some(name), Some(version)) if !version.is_empty() => Some(format!("{name} {version}")),
+            (name, _) => name,
+        };
+        header.vocab_size = initializers
+            .iter()
+            .find(|initializer| {
+                // Quantized exports rename the matrix `..._quantized`
+                let name = initializer.name.trim_end_matches("_quantized");
+                name.ends_with(WORD_EMBEDDINGS)
+            })
+            .and_then(|initializer| initializer.dims.first())
+            .and_then(|&rows| usize::try_from(rows).ok());
+        let has_type = |data_type: u64| {
+            initializers
+                .iter()
+                .any(|initializer| initializer.data_type == data_type)
+        };
+        header.quantization = Some(if has_type(INT8) || has_type(UINT8) {
+            Quantization::Int8
+        } else if has_type(FLOAT16) {
+            Quantization::Fp16
+        } else {
+            Quantization::Fp32
+        });
+        Ok(header)
+    }
+}
+
+/// The header of a `TensorProto` holding weights
+struct Initializer {
+    name: String,
+    data_type: u64,
+    dims: Vec<u64>,
+}
+
+/// A protobuf field value, length delimited ones are left in the reader
+enum WireType {
+    Varint(u64),
+    Fixed64,
+    Len(u64),
+    Fixed32,
+}
+
+impl WireType {
+    fn skip(self, reader: &mut impl Seek) -> io::Result<()> {
+        let len = match self {
+            WireType::Varint(_) => return Ok(()),
+            WireType::Fixed64 => 8,
+            WireType::Len(len) => len,
+            WireType::Fixed32 => 4,
+        };
+        let len = i64::try_from(len).map_err(|_| invalid("field too long"))?;
+        reader.seek(SeekFrom::Current(len))?;
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid ONNX model: {message}"))
+}
+
+fn varint(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint too long"))
+}
+
+/// The number and value of the next field
+fn field(reader: &mut impl Read) -> io::Result<(u64, WireType)> {
+    let key = varint(reader)?;
+    let wire_type = match key & 0x7 {
+        0 => WireType::Varint(varint(reader)?),
+        1 => WireType::Fixed64,
+        2 => WireType::Len(varint(reader)?),
+        5 => WireType::Fixed32,
+        _ => return Err(invalid("unsupported wire type")),
+    };
+    Ok((key >> 3, wire_type))
+}
+
+fn string(reader: &mut impl Read, len: u64) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid("string isn't UTF-8"))
+}
+
+/// Run `read_field` on every field of the message of `len` bytes at the reader position
+fn message<R: Read + Seek>(
+    reader: &mut R,
+    len: u64,
+    mut read_field: impl FnMut(&mut R, u64, WireType) -> io::Result<()>,
+) -> io::Result<()> {
+    let end = reader.stream_position()? + len;
+    while reader.stream_position()? < end {
+        let (number, wire_type) = field(reader)?;
+        read_field(reader, number, wire_type)?;
+    }
+    Ok(())
+}
+
+/// `OperatorSetIdProto`, the domain and version of an operator set
+fn opset_import(reader: &mut (impl Read + Seek), len: u64) -> io::Result<(String, i64)> {
+    let (mut domain, mut version) = (String::new(), 0);
+    message(reader, len, |reader, number, wire_type| {
+        match (number, wire_type) {
+            (1, WireType::Len(len)) => domain = string(reader, len)?,
+            (2, WireType::Varint(value)) => version = value as i64,
+            (_, wire_type) => wire_type.skip(reader)?,
+        }
+        Ok(())
+    })?;
+    Ok((domain, version))
+}
+
+/// `StringStringEntryProto`, a metadata entry
+fn string_entry(reader: &mut (impl Read + Seek), len: u64) -> io::Result<(String, String)> {
+    let (mut key, mut value) = (String::new(), String::new());
+    message(reader, len, |reader, number, wire_type| {
+        match (number, wire_type) {
+            (1, WireType::Len(len)) => key = string(reader, len)?,
+            (2, WireType::Len(len)) => value = string(reader, len)?,
+            (_, wire_type) => wire_type.skip(reader)?,
+        }
+        Ok(())
+    })?;
+    Ok((key, value))
+}
+
+/// Every initializer of the `GraphProto`, without its data
+fn graph_initializers(
+    reader: &mut (impl Read + Seek),
+    len: u64,
+) -> io::Result<Vec<Initializer>> {
+    let mut initializers = Vec::new();
+    message(reader, len, |reader, number, wire_type| {
+        match (number, wire_type) {
+            (5, WireType::Len(len)) => initializers.push(initializer(reader, len)?),
+            (_, wire_type) => wire_type.skip(reader)?,
+        }
+        Ok(())
+    })?;
+    Ok(initializers)
+}
+
+fn initializer(reader: &mut (impl Read + Seek), len: u64) -> io::Result<Initializer> {
+    let mut initializer = Initializer {
+        name: String::new(),
+        data_type: 0,
+        dims: Vec::new(),
+    };
+    message(reader, len, |reader, number, wire_type| {
+        match (number, wire_type) {
+            (1, WireType::Varint(dim)) => initializer.dims.push(dim),
+            // Packed by proto3 writers
+            (1, WireType::Len(len)) => {
+                let mut packed = reader.take(len);
+                while packed.limit() > 0 {
+                    initializer.dims.push(varint(&mut packed)?);
+                }
+            }
+            (2, WireType::Varint(data_type)) => initializer.data_type = data_type,
+            (8, WireType::Len(len)) => initializer.name = string(reader, len)?,
+            (_, wire_type) => wire_type.skip(reader)?,
+        }
+        Ok(())
+    })?;
+    Ok(initializer)
+}
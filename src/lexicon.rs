@@ -0,0 +1,102 @@
+//! User supplied vocalizations that take precedence over the model
+
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{error::Result, text::remove_nikud};
+
+/// Maps bare words to the vocalized form that replaces the model prediction
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Lexicon {
+    entries: HashMap<String, String>,
+}
+
+impl Lexicon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load entries from a file with one entry per line, either `vocalized` or
+    /// `word<TAB>vocalized`. Empty lines and lines starting with `#` are skipped.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Parse entries in the [`Lexicon::from_file`] format
+    pub fn parse(source: &str) -> Self {
+        let mut lexicon = Self::new();
+        for line in source.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('\t') {
+                Some((word, vocalized)) => lexicon.insert(word.trim(), vocalized.trim()),
+                None => lexicon.insert(line, line),
+            };
+        }
+        lexicon
+    }
+
+    /// Vocalize `word` as `vocalized`. Any nikud in `word` is ignored when matching.
+    pub fn insert(&mut self, word: &str, vocalized: &str) -> Option<String> {
+        self.entries
+            .insert(remove_nikud(word), vocalized.to_string())
+    }
+
+    pub fn get(&self, word: &str) -> Option<&str> {
+        self.entries.get(&remove_nikud(word)).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Byte ranges of the words of `text` that have an entry, with their replacement.
+    ///
+    /// Words already carrying nikud are never matched.
+    #[cfg(feature = "model")]
+    pub(crate) fn matches<'a>(&'a self, text: &str) -> Vec<(std::ops::Range<usize>, &'a str)> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        let mut word_start = None;
+        for (idx, ch) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), ' ')))
+        {
+            if is_word_char(ch) {
+                word_start.get_or_insert(idx);
+            } else if let Some(start) = word_start.take()
+                && let Some(vocalized) = self.entries.get(&text[start..idx])
+            {
+                matches.push((start..idx, vocalized.as_str()));
+            }
+        }
+        matches
+    }
+}
+
+impl<W: AsRef<str>, V: AsRef<str>> FromIterator<(W, V)> for Lexicon {
+    fn from_iter<I: IntoIterator<Item = (W, V)>>(iter: I) -> Self {
+        let mut lexicon = Self::new();
+        for (word, vocalized) in iter {
+            lexicon.insert(word.as_ref(), vocalized.as_ref());
+        }
+        lexicon
+    }
+}
+
+/// Hebrew letters, their marks and the Hebrew geresh/gershayim
+#[cfg(feature = "model")]
+fn is_word_char(ch: char) -> bool {
+    crate::hebrew::is_hebrew_letter(ch)
+        || crate::text::is_nikud(ch)
+        || matches!(ch, '\u{05f3}' | '\u{05f4}')
+}
@@ -1,24 +1,565 @@
+#[cfg(all(feature = "model", not(any(feature = "onnx", feature = "tract"))))]
+compile_error!("the `model` feature needs an inference backend, enable `onnx` or `tract`");
+
+pub mod acronym;
+#[cfg(feature = "model")]
+mod alternatives;
+#[cfg(feature = "model")]
+mod annotations;
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "async")]
+mod batching;
+#[cfg(feature = "onnx")]
+mod builder;
+#[cfg(feature = "model")]
+mod cache;
+#[cfg(feature = "model")]
+mod calibration;
+#[cfg(feature = "model")]
+mod cancel;
+#[cfg(feature = "checksum")]
+pub mod checksum;
+#[cfg(feature = "model")]
+mod classes;
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "model")]
+mod constraints;
+#[cfg(feature = "disk-cache")]
+mod disk_cache;
+mod document;
+mod error;
+pub mod eval;
+#[cfg(feature = "model")]
+pub mod export;
+#[cfg(feature = "fallback")]
+pub mod fallback;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hebrew;
+#[cfg(feature = "download")]
+pub mod hub;
+pub mod html;
+#[cfg(feature = "model")]
+mod info;
+mod lexicon;
+pub mod markdown;
+#[cfg(feature = "model")]
+mod markup;
+#[cfg(feature = "model")]
+mod metrics;
+#[cfg(feature = "model")]
 mod model;
+pub mod normalize;
+#[cfg(feature = "model")]
+mod offsets;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+pub mod phonemize;
+#[cfg(feature = "model")]
+mod pool;
+#[cfg(feature = "model")]
+mod post_process;
+pub mod prosody;
+#[cfg(feature = "python")]
+mod python;
+pub mod segment;
+#[cfg(feature = "model")]
+mod second_opinion;
+pub mod spelling;
+#[cfg(feature = "serve")]
+pub mod server;
+#[cfg(feature = "onnx")]
+mod session;
+pub mod ssml;
+#[cfg(feature = "model")]
+mod stream;
+pub mod subtitles;
+pub mod syllable;
+pub mod text;
+#[cfg(feature = "tract")]
+mod tract;
+pub mod translit;
+#[cfg(feature = "tts")]
+pub mod tts;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-use anyhow::Result;
-pub use model::PhonikudModel;
+#[cfg(feature = "async")]
+pub use asynchronous::AsyncPhonikud;
+#[cfg(feature = "async")]
+pub use batching::BatchScheduler;
+#[cfg(feature = "model")]
+pub use alternatives::{Alternative, WordAlternatives};
+#[cfg(feature = "model")]
+pub use annotations::{AnnotatedSpan, SpanKind};
+#[cfg(feature = "onnx")]
+pub use builder::{PhonikudBuilder, Precision, Provider};
+#[cfg(feature = "model")]
+pub use cache::CacheStats;
+#[cfg(feature = "model")]
+pub use calibration::{Calibration, HeadCalibration};
+#[cfg(feature = "model")]
+pub use cancel::CancellationToken;
+#[cfg(feature = "model")]
+pub use classes::{CLASSES_METADATA_KEY, ClassVocab};
+#[cfg(all(feature = "config", feature = "onnx"))]
+pub use config::ModelConfig;
+#[cfg(feature = "disk-cache")]
+pub use disk_cache::DiskCache;
+pub use document::VocalizedDocument;
+pub use error::{PhonikudError, Result};
+#[cfg(feature = "model")]
+pub use info::{ModelInfo, Quantization};
+pub use lexicon::Lexicon;
+#[cfg(feature = "model")]
+pub use metrics::{InferenceMetrics, MetricsRecorder};
+#[cfg(feature = "model")]
+pub use offsets::OffsetMapping;
+#[cfg(feature = "model")]
+pub use pool::{ModelManager, PhonikudPool};
+#[cfg(feature = "model")]
+pub use post_process::PostProcessor;
+#[cfg(feature = "model")]
+pub use second_opinion::{ConsultedWord, HybridVocalization, Opinion, OpinionError, SecondOpinion};
+#[cfg(feature = "model")]
+pub use stream::DiacriticsStream;
+#[cfg(feature = "onnx")]
+pub use ort::session::builder::GraphOptimizationLevel;
+#[cfg(feature = "model")]
+pub use tokenizers::Encoding;
+pub use options::{
+    DiacriticsOptions, EnsembleMode, PREFIX_MARK, QamatsQatan, STRESS_MARK, StressPlacement,
+    Thresholds, TruncationPolicy, VOCAL_SHVA_MARK, VocalShvaStyle,
+};
+#[cfg(feature = "model")]
+pub use model::{
+    ConfidenceReport, DecodeWarning, LowConfidenceWord, PhonikudModel, ProgressCallback,
+    RawOutputs, TensorInfo, TokenPrediction, TokenSpan, TokenizerSettings, Vocalization,
+    VocalizedResult, VocalizedWord, WarningKind, WordInfo, WordSegmentation,
+};
 
+#[cfg(feature = "model")]
 pub struct Phonikud {
     inner: PhonikudModel,
+    /// Extra sessions used by the parallel API
+    #[cfg(feature = "rayon")]
+    workers: Vec<PhonikudModel>,
 }
 
+#[cfg(feature = "model")]
 impl Phonikud {
     pub fn new(model_path: &str, tokenizer_path: &str) -> Result<Self> {
-        Ok(Self {
-            inner: PhonikudModel::new(model_path, tokenizer_path)?,
-        })
+        Ok(Self::from_model(PhonikudModel::new(model_path, tokenizer_path)?))
+    }
+
+    /// Load the model and tokenizer from memory, e.g. from `include_bytes!`
+    pub fn from_bytes(model: &[u8], tokenizer_json: &[u8]) -> Result<Self> {
+        Ok(Self::from_model(PhonikudModel::from_bytes(model, tokenizer_json)?))
+    }
+
+    /// Load the model with the pure Rust tract backend instead of ONNX Runtime
+    #[cfg(feature = "tract")]
+    pub fn new_tract(model_path: &str, tokenizer_path: &str) -> Result<Self> {
+        Ok(Self::from_model(PhonikudModel::new_tract(model_path, tokenizer_path)?))
+    }
+
+    /// Like [`Phonikud::from_bytes`] with the tract backend
+    #[cfg(feature = "tract")]
+    pub fn from_bytes_tract(model: &[u8], tokenizer_json: &[u8]) -> Result<Self> {
+        Ok(Self::from_model(PhonikudModel::from_bytes_tract(model, tokenizer_json)?))
+    }
+
+    /// Download the model from the Hugging Face repository `repo` (e.g. `thewh1teagle/phonikud-onnx`)
+    /// and the matching tokenizer, reusing cached files when available. Downloads are
+    /// checked against the digests the Hub publishes, see [`hub::fetch`].
+    #[cfg(feature = "download")]
+    pub fn from_pretrained(repo: &str) -> Result<Self> {
+        let model = hub::fetch(&hub::HubFile::new(repo, hub::DEFAULT_MODEL_FILE))?;
+        let tokenizer = hub::fetch(&hub::HubFile::new(hub::TOKENIZER_REPO, hub::TOKENIZER_FILE))?;
+        Self::new(&model.to_string_lossy(), &tokenizer.to_string_lossy())
+    }
+
+    /// Wrap an already loaded model
+    pub fn from_model(inner: PhonikudModel) -> Self {
+        Self {
+            inner,
+            #[cfg(feature = "rayon")]
+            workers: Vec::new(),
+        }
+    }
+
+    /// Start configuring execution providers and session options
+    #[cfg(feature = "onnx")]
+    pub fn builder(model_path: &str, tokenizer_path: &str) -> PhonikudBuilder {
+        PhonikudBuilder::new(model_path, tokenizer_path)
+    }
+
+    /// Create another instance of the same model, e.g. one per worker thread.
+    ///
+    /// The tokenizer and class vocabulary are shared, and with ONNX Runtime so are the
+    /// weights pre-packed for the CPU, which makes this cheaper than loading the model again.
+    /// The instance has its own empty cache of the same capacity and no parallel workers.
+    pub fn clone_worker(&self) -> Result<Self> {
+        Ok(Self::from_model(self.inner.spawn_worker()?))
+    }
+
+    /// Swap in the model at `model_path`, e.g. a newer fine-tuned export, keeping the
+    /// tokenizer and settings of the current one.
+    ///
+    /// The current model stays in place if the new one fails to load. See
+    /// [`ModelManager`] to reload a model shared by many threads without stopping them.
+    pub fn reload_model(&mut self, model_path: &str) -> Result<()> {
+        *self = self.load_replacement(model_path)?;
+        Ok(())
+    }
+
+    /// Combine the logits of the checkpoint at `model_path` with those of the loaded
+    /// model(s) before decoding, e.g. an int8 and an fp32 export of the same model.
+    ///
+    /// The checkpoint is loaded with the same settings and must predict the same classes.
+    /// Workers created later by the parallel API run the whole ensemble.
+    pub fn add_ensemble_member(&mut self, model_path: &str) -> Result<()> {
+        self.inner.add_ensemble_member(model_path)?;
+        #[cfg(feature = "rayon")]
+        self.workers.clear();
+        Ok(())
+    }
+
+    /// Average the logits of the ensemble (the default) or keep the highest ones
+    pub fn set_ensemble_mode(&mut self, mode: EnsembleMode) {
+        self.inner.set_ensemble_mode(mode);
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.set_ensemble_mode(mode);
+        }
+    }
+
+    /// Load the model at `model_path` with the settings of this instance
+    pub(crate) fn load_replacement(&self, model_path: &str) -> Result<Self> {
+        Ok(Self::from_model(self.inner.load_replacement(model_path)?))
     }
 
     pub fn add_diacritics(&mut self, text: &str) -> Result<String> {
-        self.add_diacritics_with_options(text, None)
+        self.add_diacritics_with_options(text, &DiacriticsOptions::default())
+    }
+
+    pub fn add_diacritics_with_options(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<String> {
+        self.inner.run_inference(text, options)
+    }
+
+    /// Append the vocalized `text` to `out`, reusing internal buffers between calls.
+    ///
+    /// Clear and reuse `out` in high throughput pipelines to avoid allocating a
+    /// new string for every text.
+    pub fn add_diacritics_into(&mut self, text: &str, out: &mut String) -> Result<()> {
+        self.add_diacritics_with_options_into(text, &DiacriticsOptions::default(), out)
+    }
+
+    /// Like [`Phonikud::add_diacritics_into`] with custom options
+    pub fn add_diacritics_with_options_into(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        out: &mut String,
+    ) -> Result<()> {
+        self.inner.vocalize_into(text, options, out)
+    }
+
+    /// Vocalize a single word, returning it with the index of its stressed letter.
+    /// Cheaper than [`Phonikud::add_diacritics`] for the short inputs of dictionary
+    /// and flash card apps, see [`PhonikudModel::vocalize_word`].
+    pub fn add_diacritics_word(&mut self, word: &str) -> Result<VocalizedWord> {
+        self.add_diacritics_word_with_options(word, &DiacriticsOptions::default())
+    }
+
+    /// Like [`Phonikud::add_diacritics_word`] with custom options
+    pub fn add_diacritics_word_with_options(
+        &mut self,
+        word: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<VocalizedWord> {
+        self.inner.vocalize_word(word, options)
+    }
+
+    /// Vocalize `original_text` from an `encoding` of it the caller already made, skipping
+    /// the tokenizer pass, see [`PhonikudModel::vocalize_pretokenized`]
+    pub fn add_diacritics_pretokenized(
+        &mut self,
+        encoding: &Encoding,
+        original_text: &str,
+    ) -> Result<String> {
+        let options = DiacriticsOptions::default();
+        Ok(self.inner.vocalize_pretokenized(encoding, original_text, &options)?.text)
+    }
+
+    /// Vocalize `text` and also return the predictions it was rendered from
+    pub fn vocalize(&mut self, text: &str, options: &DiacriticsOptions) -> Result<Vocalization> {
+        self.inner.vocalize(text, options)
+    }
+
+    /// Vocalize many texts, running the model on the chunks of up to `max_batch_size`
+    /// of them at once. `BatchScheduler` batches the requests of many threads this way.
+    pub fn vocalize_batch(
+        &mut self,
+        requests: &[(&str, &DiacriticsOptions)],
+        max_batch_size: usize,
+    ) -> Vec<Result<Vocalization>> {
+        self.inner.vocalize_batch(requests, max_batch_size)
+    }
+
+    /// Like [`Phonikud::add_diacritics_with_options`], aborted when `token` is cancelled
+    /// from another thread
+    pub fn add_diacritics_with_cancellation(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        token: &CancellationToken,
+    ) -> Result<String> {
+        Ok(self.inner.vocalize_cancellable(text, options, token)?.text)
+    }
+
+    /// Like [`Phonikud::add_diacritics_with_options`], failing with
+    /// [`PhonikudError::Timeout`] if the result isn't ready by `deadline`
+    pub fn add_diacritics_with_deadline(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        deadline: std::time::Instant,
+    ) -> Result<String> {
+        let token = CancellationToken::new();
+        let (done, finished) = std::sync::mpsc::channel::<()>();
+        let watchdog = token.clone();
+        std::thread::spawn(move || {
+            let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+            // Returns early when `done` is dropped at the end of the call
+            if finished.recv_timeout(timeout) == Err(std::sync::mpsc::RecvTimeoutError::Timeout) {
+                watchdog.expire();
+            }
+        });
+
+        let result = self.add_diacritics_with_cancellation(text, options, &token);
+        drop(done);
+        result
+    }
+
+    /// Vocalize `text` and list the words whose letters have a confidence below `threshold`
+    pub fn add_diacritics_with_confidence(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        threshold: f32,
+    ) -> Result<ConfidenceReport> {
+        self.inner
+            .run_inference_with_confidence(text, options, threshold)
+    }
+
+    /// Run a few dummy inferences of short to maximal length, so the first real
+    /// request doesn't pay for the session's first allocations. Also warms the
+    /// sessions already created by the parallel API.
+    pub fn warmup(&mut self) -> Result<()> {
+        self.warmup_with_lengths(&model::WARMUP_LENGTHS)
     }
-    
-    pub fn add_diacritics_with_options(&mut self, text: &str, mark_matres_lectionis: Option<&str>) -> Result<String> {
-        self.inner.run_inference(text, mark_matres_lectionis)
+
+    /// Like [`Phonikud::warmup`] with inputs of `lengths` chars
+    pub fn warmup_with_lengths(&mut self, lengths: &[usize]) -> Result<()> {
+        self.inner.warmup(lengths)?;
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.warmup(lengths)?;
+        }
+        Ok(())
+    }
+
+    /// Call `callback` with the chunks processed so far and the chunks of the text while
+    /// long texts are vocalized, see [`PhonikudModel::set_progress`]. The parallel API
+    /// doesn't report.
+    pub fn set_progress(&mut self, callback: impl FnMut(usize, usize) + Send + Sync + 'static) {
+        self.inner.set_progress(Some(Box::new(callback)));
+    }
+
+    /// Stop reporting progress
+    pub fn clear_progress(&mut self) {
+        self.inner.set_progress(None);
+    }
+
+    /// Report the tokenize, inference and decode time of every chunk to `recorder`,
+    /// including those of the sessions used by the parallel API
+    pub fn set_metrics(&mut self, recorder: impl MetricsRecorder + 'static) {
+        let recorder: std::sync::Arc<dyn MetricsRecorder> = std::sync::Arc::new(recorder);
+        self.inner.set_metrics(Some(recorder.clone()));
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.set_metrics(Some(recorder.clone()));
+        }
+    }
+
+    /// Run `processor` on the predictions of every text before they are rendered, e.g.
+    /// to apply a house style. Passes run in the order they were added, on the sessions
+    /// of the parallel API too.
+    pub fn add_post_processor(&mut self, processor: impl PostProcessor + 'static) {
+        let processor: std::sync::Arc<dyn PostProcessor> = std::sync::Arc::new(processor);
+        self.inner.add_post_processor(processor.clone());
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.add_post_processor(processor.clone());
+        }
+    }
+
+    pub fn clear_post_processors(&mut self) {
+        self.inner.clear_post_processors();
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.clear_post_processors();
+        }
+    }
+
+    /// Cache the predictions of up to `capacity` chunks of text, so repeated
+    /// sentences skip inference. `0` disables the cache, which is the default.
+    pub fn set_cache_capacity(&mut self, capacity: usize) {
+        self.inner.set_cache_capacity(capacity);
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.set_cache_capacity(capacity);
+        }
+    }
+
+    /// Split texts into chunks of at most `chars` chars, see
+    /// [`PhonikudModel::set_max_chunk_chars`]
+    pub fn set_max_chunk_chars(&mut self, chars: Option<usize>) {
+        self.inner.set_max_chunk_chars(chars);
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.set_max_chunk_chars(chars);
+        }
+    }
+
+    /// Keep the predictions of every chunk in `cache` too, so they outlive the process.
+    /// The sessions of the parallel API share it.
+    #[cfg(feature = "disk-cache")]
+    pub fn set_disk_cache(&mut self, cache: DiskCache) {
+        let cache = std::sync::Arc::new(std::sync::Mutex::new(cache));
+        self.inner.set_disk_cache(Some(cache.clone()));
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.set_disk_cache(Some(cache.clone()));
+        }
+    }
+
+    /// Stop using the disk cache, leaving its file as it is
+    #[cfg(feature = "disk-cache")]
+    pub fn clear_disk_cache(&mut self) {
+        self.inner.set_disk_cache(None);
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.set_disk_cache(None);
+        }
+    }
+
+    /// Cache statistics of the main session
+    pub fn cache_stats(&self) -> CacheStats {
+        self.inner.cache_stats()
+    }
+
+    pub fn clear_cache(&mut self) {
+        self.inner.clear_cache();
+        #[cfg(feature = "rayon")]
+        for worker in &mut self.workers {
+            worker.clear_cache();
+        }
+    }
+
+    /// Vocalize `text` and convert it into IPA phonemes
+    pub fn phonemize(&mut self, text: &str) -> Result<String> {
+        let vocalized = self.add_diacritics(text)?;
+        Ok(phonemize::phonemize(&vocalized))
+    }
+
+    /// Like [`Phonikud::phonemize`], with the phonemes in `alphabet`, e.g.
+    /// [`phonemize::PhonemeAlphabet::Kirshenbaum`] for espeak-ng
+    pub fn phonemize_with_alphabet(
+        &mut self,
+        text: &str,
+        alphabet: phonemize::PhonemeAlphabet,
+    ) -> Result<String> {
+        let vocalized = self.add_diacritics(text)?;
+        Ok(phonemize::phonemize_with_alphabet(&vocalized, alphabet))
+    }
+
+    /// Vocalize `text` and transliterate it into Latin letters, see
+    /// [`translit::transliterate`]
+    pub fn transliterate(
+        &mut self,
+        text: &str,
+        options: &translit::TranslitOptions,
+    ) -> Result<String> {
+        let vocalized = self.add_diacritics(text)?;
+        Ok(translit::transliterate(&vocalized, options))
+    }
+
+    /// Vocalize `text` and transcribe it into phonemes in one inference pass, along with
+    /// the range and phonemes of every word. See [`PhonikudModel::vocalize_with_phonemes`].
+    pub fn vocalize_with_phonemes(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<VocalizedResult> {
+        self.inner.vocalize_with_phonemes(text, options)
+    }
+
+    /// Vocalize `text` and split its words into syllables, see [`syllable::syllabify`]
+    pub fn syllabify(&mut self, text: &str) -> Result<Vec<syllable::Word>> {
+        let vocalized = self.add_diacritics(text)?;
+        Ok(syllable::syllabify(&vocalized))
+    }
+
+    /// Split the words of `text` into prefixes and stem instead of marking them with `|`
+    pub fn segment_prefixes(&mut self, text: &str) -> Result<Vec<WordSegmentation>> {
+        self.inner.segment_prefixes(text, &Thresholds::default())
+    }
+
+    /// Tokenize `text` with the loaded tokenizer exactly like it is fed to the model,
+    /// see [`PhonikudModel::tokenize`]
+    pub fn tokenize(&self, text: &str) -> Result<Vec<TokenSpan>> {
+        self.inner.tokenize(text)
+    }
+
+    /// Describe the loaded model and the execution providers it runs on, see
+    /// [`PhonikudModel::model_info`]
+    pub fn model_info(&self) -> ModelInfo {
+        self.inner.model_info()
+    }
+
+    /// Truncation and padding of tokenizer.json, see [`PhonikudModel::tokenizer_settings`]
+    pub fn tokenizer_settings(&self) -> &TokenizerSettings {
+        self.inner.tokenizer_settings()
+    }
+
+    /// Run the model on `text` and return the logits of every token, see
+    /// [`PhonikudModel::run_raw`]
+    pub fn run_raw(&mut self, text: &str) -> Result<RawOutputs> {
+        self.inner.run_raw(text)
+    }
+
+    /// Return the per-letter predictions instead of the vocalized string
+    pub fn add_diacritics_detailed(&mut self, text: &str) -> Result<Vec<TokenPrediction>> {
+        self.add_diacritics_detailed_with_thresholds(text, &Thresholds::default())
+    }
+
+    /// Like [`Phonikud::add_diacritics_detailed`] with custom binary head thresholds
+    pub fn add_diacritics_detailed_with_thresholds(
+        &mut self,
+        text: &str,
+        thresholds: &Thresholds,
+    ) -> Result<Vec<TokenPrediction>> {
+        self.inner.run_inference_detailed(text, thresholds)
     }
 }
@@ -0,0 +1,184 @@
+//! Vocalize the prose of Markdown documents
+
+use std::ops::Range;
+
+/// Byte ranges of the prose of `markdown`.
+///
+/// Fenced and indented code blocks, inline code, link and image targets,
+/// reference definitions, URLs and inline HTML tags are left out, so are the
+/// backslash escapes. Link texts and image descriptions are prose.
+pub fn prose_ranges(markdown: &str) -> Vec<Range<usize>> {
+    let mut skipped: Vec<Range<usize>> = Vec::new();
+    // Char and length of the fence of the open code block
+    let mut fence: Option<(char, usize)> = None;
+    let mut prev_blank = true;
+    let mut in_indented = false;
+    let mut line_start = 0;
+
+    for line in markdown.split_inclusive('\n') {
+        let range = line_start..line_start + line.len();
+        line_start = range.end;
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start_matches(' ');
+        let indent = content.len() - trimmed.len();
+
+        if let Some((ch, len)) = fence {
+            if indent < 4 && is_fence_close(trimmed, ch, len) {
+                fence = None;
+            }
+            skipped.push(range);
+            continue;
+        }
+        if indent < 4
+            && let Some(open) = fence_open(trimmed)
+        {
+            fence = Some(open);
+            skipped.push(range);
+            continue;
+        }
+
+        let blank = trimmed.trim().is_empty();
+        let indented_code = !blank
+            && (content.starts_with("    ") || content.starts_with('\t'))
+            && (prev_blank || in_indented);
+        in_indented = indented_code || in_indented && blank;
+        prev_blank = blank;
+        if indented_code || is_reference_definition(trimmed) {
+            skipped.push(range);
+            continue;
+        }
+        skip_inline(content, range.start, &mut skipped);
+    }
+
+    // Everything between the skipped ranges is prose
+    let mut ranges = Vec::new();
+    let mut prev_index = 0;
+    for range in skipped {
+        if prev_index < range.start {
+            ranges.push(prev_index..range.start);
+        }
+        prev_index = prev_index.max(range.end);
+    }
+    if prev_index < markdown.len() {
+        ranges.push(prev_index..markdown.len());
+    }
+    ranges
+}
+
+/// Collect the code spans, link targets, URLs and tags of a line starting at `offset`
+fn skip_inline(line: &str, offset: usize, skipped: &mut Vec<Range<usize>>) {
+    let mut idx = 0;
+    while let Some(ch) = line[idx..].chars().next() {
+        let rest = &line[idx..];
+        let end = if ch == '\\' {
+            // The escaped char is kept from the model along with the backslash
+            Some(idx + 1 + rest[1..].chars().next().map_or(0, char::len_utf8))
+        } else if ch == '`' {
+            let run = rest.len() - rest.trim_start_matches('`').len();
+            match find_run(&rest[run..], run) {
+                Some(close) => Some(idx + run + close + run),
+                None => {
+                    idx += run;
+                    continue;
+                }
+            }
+        } else if rest.starts_with("](") {
+            // Skip the target but not the closing bracket of the link text
+            idx += 1;
+            matching_paren(&rest[1..]).map(|close| idx + close + 1)
+        } else if let Some(label) = rest.strip_prefix("][") {
+            idx += 1;
+            label.find(']').map(|close| idx + close + 2)
+        } else if ch == '<'
+            && rest[1..].starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '/')
+        {
+            rest.find('>').map(|close| idx + close + 1)
+        } else if is_url_start(line, idx) {
+            let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let url = rest[..len].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']);
+            Some(idx + url.len())
+        } else {
+            None
+        };
+
+        match end {
+            Some(end) => {
+                skipped.push(offset + idx..offset + end);
+                idx = end;
+            }
+            None => idx += ch.len_utf8(),
+        }
+    }
+}
+
+/// Char and length of a code fence opening a block
+fn fence_open(line: &str) -> Option<(char, usize)> {
+    let ch = line.chars().next().filter(|&ch| ch == '`' || ch == '~')?;
+    let len = line.len() - line.trim_start_matches(ch).len();
+    // The info string of a backtick fence can't contain backticks
+    let valid = len >= 3 && (ch == '~' || !line[len..].contains('`'));
+    valid.then_some((ch, len))
+}
+
+fn is_fence_close(line: &str, ch: char, len: usize) -> bool {
+    let rest = line.trim_start_matches(ch);
+    line.len() - rest.len() >= len && rest.trim().is_empty()
+}
+
+/// `[label]: target`, footnote definitions (`[^1]: ...`) are prose
+fn is_reference_definition(line: &str) -> bool {
+    line.starts_with('[')
+        && !line.starts_with("[^")
+        && line
+            .find("]:")
+            .is_some_and(|close| !line[1..close].contains(']'))
+}
+
+/// Offset of the next run of exactly `len` backticks in `text`
+fn find_run(text: &str, len: usize) -> Option<usize> {
+    let mut search = 0;
+    while let Some(pos) = text[search..].find('`') {
+        let start = search + pos;
+        let run = text[start..].len() - text[start..].trim_start_matches('`').len();
+        if run == len {
+            return Some(start);
+        }
+        search = start + run;
+    }
+    None
+}
+
+/// Offset of the `)` closing the `(` that `text` starts with
+fn matching_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth == 1 => return Some(idx),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_url_start(line: &str, idx: usize) -> bool {
+    let rest = &line[idx..];
+    let at_word_start = !line[..idx].ends_with(|ch: char| ch.is_alphanumeric());
+    at_word_start
+        && ["https://", "http://", "www."]
+            .iter()
+            .any(|prefix| rest.starts_with(prefix))
+}
+
+#[cfg(feature = "model")]
+impl crate::Phonikud {
+    /// Vocalize the prose of `markdown`, keeping code, URLs and link targets byte for byte
+    pub fn add_diacritics_markdown(
+        &mut self,
+        markdown: &str,
+        options: &crate::DiacriticsOptions,
+    ) -> crate::Result<String> {
+        self.add_diacritics_in_ranges(markdown, &prose_ranges(markdown), options)
+    }
+}
@@ -0,0 +1,57 @@
+use std::ops::Range;
+
+use crate::{DiacriticsOptions, Phonikud, Result, hebrew::is_hebrew_letter};
+
+impl Phonikud {
+    /// Vocalize the sorted, non overlapping `ranges` of `text` and copy the rest byte for byte.
+    ///
+    /// The ranges are vocalized in one call joined by newlines, so neighbouring
+    /// pieces of a sentence still give the model some context.
+    pub(crate) fn add_diacritics_in_ranges(
+        &mut self,
+        text: &str,
+        ranges: &[Range<usize>],
+        options: &DiacriticsOptions,
+    ) -> Result<String> {
+        let ranges: Vec<&Range<usize>> = ranges
+            .iter()
+            .filter(|range| text[(*range).clone()].chars().any(is_hebrew_letter))
+            .collect();
+        let joined = ranges
+            .iter()
+            .map(|range| &text[(*range).clone()])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let vocalized = self.add_diacritics_with_options(&joined, options)?;
+
+        // Text outside Hebrew letters is copied as is, so the newlines line up
+        let mut pieces = Vec::with_capacity(ranges.len());
+        if vocalized.matches('\n').count() == joined.matches('\n').count() {
+            let mut rest = vocalized.as_str();
+            for range in &ranges {
+                let lines = text[(*range).clone()].matches('\n').count();
+                let end = rest
+                    .match_indices('\n')
+                    .nth(lines)
+                    .map_or(rest.len(), |(idx, _)| idx);
+                pieces.push(rest[..end].to_string());
+                rest = rest.get(end + 1..).unwrap_or_default();
+            }
+        } else {
+            for range in &ranges {
+                pieces.push(self.add_diacritics_with_options(&text[(*range).clone()], options)?);
+            }
+        }
+
+        let mut result = String::with_capacity(vocalized.len() + text.len());
+        let mut prev_index = 0;
+        for (range, piece) in ranges.iter().zip(pieces) {
+            result.push_str(&text[prev_index..range.start]);
+            result.push_str(&piece);
+            prev_index = range.end;
+        }
+        result.push_str(&text[prev_index..]);
+        Ok(result)
+    }
+}
+
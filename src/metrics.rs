@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+/// Timings of one model call, or of a chunk answered from the cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InferenceMetrics {
+    pub tokenize: Duration,
+    /// Time spent in the inference backend
+    pub inference: Duration,
+    /// Time spent turning the logits into predictions
+    pub decode: Duration,
+    /// Number of tokens fed to the model, special tokens included
+    pub sequence_length: usize,
+    /// Chunk characters, including those without a prediction
+    pub chars: usize,
+    /// The chunk was found in the prediction cache, no other field but `chars` is set
+    pub cache_hit: bool,
+}
+
+/// Receives the [`InferenceMetrics`] of every chunk, e.g. to export them to Prometheus.
+///
+/// Implemented for closures taking `&InferenceMetrics`.
+pub trait MetricsRecorder: Send + Sync {
+    fn record(&self, metrics: &InferenceMetrics);
+}
+
+impl<F: Fn(&InferenceMetrics) + Send + Sync> MetricsRecorder for F {
+    fn record(&self, metrics: &InferenceMetrics) {
+        self(metrics)
+    }
+}
+
+/// Measure consecutive stages, only reading the clock when metrics are recorded
+pub(crate) struct Stopwatch(Option<Instant>);
+
+impl Stopwatch {
+    pub(crate) fn start(enabled: bool) -> Self {
+        Self(enabled.then(Instant::now))
+    }
+
+    /// Time since the start or the previous lap
+    pub(crate) fn lap(&mut self) -> Duration {
+        let Some(start) = &mut self.0 else {
+            return Duration::ZERO;
+        };
+        let now = Instant::now();
+        let elapsed = now - *start;
+        *start = now;
+        elapsed
+    }
+}
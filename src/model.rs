@@ -1,249 +1,3362 @@
-use anyhow::Result;
-use ort::{
-    session::{
-        builder::{GraphOptimizationLevel, SessionBuilder},
-        Session,
+use tokenizers::{Encoding, PaddingParams, PostProcessor as _, Tokenizer, TruncationParams};
+use ndarray::{Array2, ArrayD, ArrayView2, ArrayViewD, Ix2, s};
+use std::{borrow::Cow, collections::HashMap, fmt, num::NonZeroUsize, ops::Range, sync::Arc};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[cfg(feature = "disk-cache")]
+use crate::disk_cache::DiskCache;
+#[cfg(feature = "onnx")]
+use crate::session::{ModelSource, OnnxSession, SessionOptions};
+#[cfg(feature = "tract")]
+use crate::tract::TractSession;
+use crate::{
+    acronym::{AcronymMode, acronym_ranges, expand_acronyms},
+    cache::{CacheStats, PredictionCache},
+    cancel::CancellationToken,
+    constraints,
+    classes::{CLASSES_METADATA_KEY, ClassVocab},
+    error::{PhonikudError, Result},
+    hebrew::{is_hebrew_letter, is_matres, is_presentation_form},
+    info::{ModelHeader, ModelInfo},
+    metrics::{InferenceMetrics, MetricsRecorder, Stopwatch},
+    normalize::{Normalized, is_geresh_form, normalize_geresh, normalize_presentation_forms},
+    options::{
+        DiacriticsOptions, EnsembleMode, QamatsQatan, StressPlacement, Thresholds,
+        TruncationPolicy, VocalShvaStyle,
+    },
+    phonemize::{is_mark, phonemize},
+    post_process::PostProcessor,
+    spelling::{Respelled, fix_final_forms, respell},
+    text::{
+        CharOffsets, MarkCategories, MarkOrder, has_marks, is_nikud, remove_nikud_into,
+        remove_nikud_with_offsets, reorder_marks, strip_marks_with_offsets,
     },
-    value::Value,
 };
-use tokenizers::Tokenizer;
-use ndarray::Array;
-use std::{collections::HashMap, sync::Arc};
-use regex::Regex;
+
+/// The marks stripped from the input when [`DiacriticsOptions::keep_taamim`] is set
+const ALL_BUT_TAAMIM: MarkCategories = MarkCategories {
+    taamim: false,
+    ..MarkCategories::ALL
+};
 
 /// Hebrew diacritization model wrapper (internal)
 pub struct PhonikudModel {
-    pub session: Session,
+    backend: Backend,
     pub tokenizer: Arc<Tokenizer>,
+    cache: Option<PredictionCache>,
+    /// Shared with the workers, see [`PhonikudModel::set_disk_cache`]
+    #[cfg(feature = "disk-cache")]
+    disk_cache: Option<Arc<std::sync::Mutex<DiskCache>>>,
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+    /// Run in order on the predictions of every text, see
+    /// [`PhonikudModel::add_post_processor`]
+    post_processors: Vec<Arc<dyn PostProcessor>>,
+    /// Token of the call in progress, see [`PhonikudModel::vocalize_cancellable`]
+    cancellation: Option<CancellationToken>,
+    /// See [`PhonikudModel::set_progress`]
+    progress: Option<ProgressCallback>,
+    classes: Arc<ClassVocab>,
+    /// Index of the nikud, shin and additional outputs among the model outputs
+    output_order: [usize; 3],
+    scratch: Scratch,
+    /// Chunk predictions already run by [`PhonikudModel::vocalize_batch`]
+    prefetched: HashMap<String, Vec<TokenPrediction>>,
+    /// Letters of the call in progress left without a prediction, see
+    /// [`Vocalization::warnings`]
+    skipped: Vec<DecodeWarning>,
+    /// Other checkpoints whose logits are combined with those of `backend`
+    ensemble: Vec<EnsembleMember>,
+    ensemble_mode: EnsembleMode,
+    /// Truncation and padding tokenizer.json came with, both turned off in `tokenizer`
+    tokenizer_settings: TokenizerSettings,
+    truncation: TruncationPolicy,
+    /// See [`PhonikudModel::set_max_chunk_chars`]
+    chunk_limit: Option<usize>,
+}
+
+/// Called with the chunks processed so far and the chunks of the whole call, see
+/// [`PhonikudModel::set_progress`]
+pub type ProgressCallback = Box<dyn FnMut(usize, usize) + Send + Sync>;
+
+/// Truncation and padding configured in tokenizer.json, see
+/// [`PhonikudModel::tokenizer_settings`]
+#[derive(Debug, Clone, Default)]
+pub struct TokenizerSettings {
+    /// Truncation of the tokenizer, `None` when it doesn't truncate
+    pub truncation: Option<TruncationParams>,
+    /// Padding of the tokenizer, batches are padded by the model wrapper instead
+    pub padding: Option<PaddingParams>,
+    /// Tokens added around every input by the post-processor, e.g. `[CLS]` and `[SEP]`
+    pub special_tokens: usize,
+}
+
+/// A checkpoint of an ensemble, with the index of each head among its outputs
+struct EnsembleMember {
+    backend: Backend,
+    output_order: [usize; 3],
+}
+
+/// Buffers kept between calls of [`PhonikudModel::vocalize_into`]
+#[derive(Default)]
+struct Scratch {
+    clean_text: String,
+    spans: Vec<Range<usize>>,
+}
+
+/// The inference engine running the ONNX graph
+enum Backend {
+    #[cfg(feature = "onnx")]
+    Onnx(OnnxSession),
+    #[cfg(feature = "tract")]
+    Tract(TractSession),
+}
+
+/// Name and shape of a model input or output, `None` for dynamic dimensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TensorInfo {
+    pub name: String,
+    pub shape: Vec<Option<usize>>,
+}
+
+impl fmt::Display for TensorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dims: Vec<String> = self
+            .shape
+            .iter()
+            .map(|dim| dim.map_or("?".to_string(), |dim| dim.to_string()))
+            .collect();
+        write!(f, "{} [{}]", self.name, dims.join(", "))
+    }
+}
+
+/// Tokenizer outputs fed to the model, `batch_size` rows of one value per token
+#[derive(Clone)]
+pub(crate) struct ModelInputs {
+    pub(crate) batch_size: usize,
+    pub(crate) input_ids: Vec<i64>,
+    pub(crate) attention_mask: Vec<i64>,
+    pub(crate) token_type_ids: Vec<i64>,
+}
+
+impl ModelInputs {
+    /// Length of every row, shorter sequences are padded to the longest one
+    pub(crate) fn seq_len(&self) -> usize {
+        self.input_ids.len() / self.batch_size.max(1)
+    }
 }
 
 impl PhonikudModel {
+    /// Load the model with ONNX Runtime, or with tract when the `onnx` feature is disabled
     pub fn new(model_path: &str, tokenizer_path: &str) -> Result<Self> {
-        let session = SessionBuilder::new()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(model_path)?;
+        #[cfg(feature = "onnx")]
+        return Self::load(
+            ModelSource::File(model_path.to_string()),
+            Arc::new(load_tokenizer_file(tokenizer_path)?),
+            SessionOptions::default(),
+            ClassVocab::from_sidecar(model_path)?,
+        );
+        #[cfg(not(feature = "onnx"))]
+        Self::new_tract(model_path, tokenizer_path)
+    }
+
+    /// Load the model and tokenizer from memory, e.g. from `include_bytes!`.
+    ///
+    /// A copy of `model` is kept so that [`PhonikudModel::spawn_worker`] can create more sessions.
+    pub fn from_bytes(model: &[u8], tokenizer_json: &[u8]) -> Result<Self> {
+        #[cfg(feature = "onnx")]
+        return Self::load(
+            ModelSource::Bytes(Arc::from(model)),
+            Arc::new(load_tokenizer_bytes(tokenizer_json)?),
+            SessionOptions::default(),
+            None,
+        );
+        #[cfg(not(feature = "onnx"))]
+        Self::from_bytes_tract(model, tokenizer_json)
+    }
+
+    #[cfg(feature = "onnx")]
+    pub(crate) fn load(
+        source: ModelSource,
+        tokenizer: Arc<Tokenizer>,
+        options: SessionOptions,
+        classes: Option<ClassVocab>,
+    ) -> Result<Self> {
+        let session = OnnxSession::load(source, options)?;
+        Self::with_backend(Backend::Onnx(session), tokenizer, classes)
+    }
+
+    /// Load the model with the pure Rust tract backend
+    #[cfg(feature = "tract")]
+    pub fn new_tract(model_path: &str, tokenizer_path: &str) -> Result<Self> {
+        Self::with_backend(
+            Backend::Tract(TractSession::from_file(model_path)?),
+            Arc::new(load_tokenizer_file(tokenizer_path)?),
+            ClassVocab::from_sidecar(model_path)?,
+        )
+    }
 
-        let tokenizer = Tokenizer::from_file(tokenizer_path)
-            .map_err(|e| anyhow::anyhow!("Tokenizer load error: {:?}", e))?;
+    /// Load the model with the pure Rust tract backend from memory
+    #[cfg(feature = "tract")]
+    pub fn from_bytes_tract(model: &[u8], tokenizer_json: &[u8]) -> Result<Self> {
+        Self::with_backend(
+            Backend::Tract(TractSession::from_bytes(model)?),
+            Arc::new(load_tokenizer_bytes(tokenizer_json)?),
+            None,
+        )
+    }
+
+    /// Check the model signature before accepting it.
+    ///
+    /// The class vocabulary comes from `classes` (a sidecar file), then from the
+    /// model metadata, and defaults to the 1.0 model classes.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "load_model", skip_all))]
+    fn with_backend(
+        backend: Backend,
+        tokenizer: Arc<Tokenizer>,
+        classes: Option<ClassVocab>,
+    ) -> Result<Self> {
+        let (classes, output_order) = backend.validate(classes)?;
+        backend.check_vocab(&tokenizer)?;
+        let (tokenizer, tokenizer_settings) = untruncated(tokenizer)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            nikud_classes = classes.nikud.len(),
+            shin_classes = classes.shin.len(),
+            ?output_order,
+            "model signature validated"
+        );
 
         Ok(Self {
-            session,
-            tokenizer: Arc::new(tokenizer),
+            backend,
+            tokenizer,
+            cache: None,
+            #[cfg(feature = "disk-cache")]
+            disk_cache: None,
+            metrics: None,
+            post_processors: Vec::new(),
+            cancellation: None,
+            progress: None,
+            classes: Arc::new(classes),
+            output_order,
+            scratch: Scratch::default(),
+            prefetched: HashMap::new(),
+            skipped: Vec::new(),
+            ensemble: Vec::new(),
+            ensemble_mode: EnsembleMode::default(),
+            tokenizer_settings,
+            truncation: TruncationPolicy::default(),
+            chunk_limit: None,
         })
     }
 
-    pub fn run_inference(&mut self, text: &str, mark_matres_lectionis: Option<&str>) -> Result<String> {
-        // Remove nikud from input text first (like Python version)
-        let clean_text = remove_nikud(text);
-        
-        // 1. Tokenize
-        let encoding = self
-            .tokenizer
-            .encode(clean_text.as_str(), true)
-            .map_err(|e| anyhow::anyhow!("Tokenizer error: {:?}", e))?;
-
-        let input_ids: Vec<i64> = encoding.get_ids().iter().map(|&x| x as i64).collect();
-        let attention_mask: Vec<i64> =
-            encoding.get_attention_mask().iter().map(|&x| x as i64).collect();
-        let token_type_ids: Vec<i64> =
-            encoding.get_type_ids().iter().map(|&x| x as i64).collect();
-
-        let seq_len = input_ids.len();
-
-        // 2. Build input tensors
-        let input_ids_tensor = Value::from_array(Array::from_shape_vec((1, seq_len), input_ids)?)?;
-        let attention_mask_tensor =
-            Value::from_array(Array::from_shape_vec((1, seq_len), attention_mask)?)?;
-        let token_type_ids_tensor =
-            Value::from_array(Array::from_shape_vec((1, seq_len), token_type_ids)?)?;
-
-        // 3. Prepare input map
-        let mut inputs = HashMap::new();
-        inputs.insert("input_ids".to_string(), input_ids_tensor);
-        inputs.insert("attention_mask".to_string(), attention_mask_tensor);
-        inputs.insert("token_type_ids".to_string(), token_type_ids_tensor);
-
-        // 4. Run inference
-        let outputs = self.session.run(inputs)?;
-
-        // 5. Extract logits - access by index
-        let nikud_logits = outputs[0].try_extract_array::<f32>()?;
-        let shin_logits = outputs[1].try_extract_array::<f32>()?;
-        let additional_logits = outputs[2].try_extract_array::<f32>()?;
-
-        // 6. Get predictions
-        let nikud_preds: Vec<usize> = nikud_logits
-            .slice(ndarray::s![0, .., ..])
-            .outer_iter()
-            .map(|token| {
-                token
-                    .iter()
-                    .enumerate()
-                    .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-                    .unwrap()
-                    .0
+    /// Truncation and padding of tokenizer.json, read before they were turned off
+    pub fn tokenizer_settings(&self) -> &TokenizerSettings {
+        &self.tokenizer_settings
+    }
+
+    /// What happens to inputs longer than the truncation length of tokenizer.json.
+    /// Without truncation in tokenizer.json, the model length is the only limit.
+    pub fn set_truncation_policy(&mut self, policy: TruncationPolicy) {
+        self.truncation = policy;
+        self.clear_cache();
+    }
+
+    /// Split texts into chunks of at most `chars` chars instead of the longest the model
+    /// and tokenizer.json accept, `None` to go back to those. Shorter chunks keep the
+    /// attention matrices and the arena small on devices with little memory, but words
+    /// near the boundaries see less context.
+    pub fn set_max_chunk_chars(&mut self, chars: Option<usize>) {
+        self.chunk_limit = chars.map(|chars| chars.max(1));
+        self.clear_cache();
+    }
+
+    /// Chars per chunk, fewer when tokenizer.json truncates shorter than the model accepts
+    /// or [`PhonikudModel::set_max_chunk_chars`] sets a lower limit
+    pub(crate) fn max_chunk_chars(&self) -> usize {
+        chunk_chars(self.truncation, &self.tokenizer_settings, self.chunk_limit)
+    }
+
+    /// Tokens per chunk above which [`PhonikudError::InputTooLong`] is returned
+    fn max_seq_len(&self) -> usize {
+        match (self.truncation, &self.tokenizer_settings.truncation) {
+            (TruncationPolicy::Error, Some(truncation)) => truncation.max_length.min(MAX_SEQ_LEN),
+            _ => MAX_SEQ_LEN,
+        }
+    }
+
+    /// The inputs, outputs, opset, execution providers, weight type and metadata of the
+    /// model. The opset and weight type are read from the model file, which is opened
+    /// again with ONNX Runtime.
+    pub fn model_info(&self) -> ModelInfo {
+        let (inputs, outputs) = self.backend.signature();
+        let (backend, providers) = match &self.backend {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(session) => ("onnxruntime", session.providers()),
+            #[cfg(feature = "tract")]
+            Backend::Tract(_) => ("tract", Vec::new()),
+        };
+        let header = self.backend.header().unwrap_or_default();
+        ModelInfo {
+            backend,
+            inputs,
+            outputs,
+            opset: header.opset,
+            vocab_size: header.vocab_size,
+            providers,
+            quantization: header.quantization,
+            producer: header.producer,
+            model_version: header.model_version,
+            metadata: header.metadata,
+        }
+    }
+
+    /// Marks predicted by each class of the nikud and shin heads
+    pub fn classes(&self) -> &ClassVocab {
+        &self.classes
+    }
+
+    /// Create another session of the same model with the same settings, sharing the tokenizer
+    /// and, with ONNX Runtime, the pre-packed weights.
+    ///
+    /// The worker gets its own empty cache of the same capacity, and shares the disk cache
+    /// and the metrics recorder.
+    pub fn spawn_worker(&self) -> Result<Self> {
+        let ensemble = self
+            .ensemble
+            .iter()
+            .map(|member| {
+                Ok(EnsembleMember {
+                    backend: member.backend.spawn()?,
+                    output_order: member.output_order,
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            backend: self.backend.spawn()?,
+            ensemble,
+            ensemble_mode: self.ensemble_mode,
+            tokenizer: self.tokenizer.clone(),
+            tokenizer_settings: self.tokenizer_settings.clone(),
+            truncation: self.truncation,
+            chunk_limit: self.chunk_limit,
+            classes: self.classes.clone(),
+            metrics: self.metrics.clone(),
+            post_processors: self.post_processors.clone(),
+            cancellation: None,
+            progress: None,
+            output_order: self.output_order,
+            scratch: Scratch::default(),
+            prefetched: HashMap::new(),
+            skipped: Vec::new(),
+            cache: self
+                .cache
+                .as_ref()
+                .map(|cache| PredictionCache::new(cache.capacity())),
+            #[cfg(feature = "disk-cache")]
+            disk_cache: self.disk_cache.clone(),
+        })
+    }
+
+    /// Load the model at `model_path` with the settings of this one.
+    ///
+    /// The tokenizer is shared, the cache has the same capacity and starts empty, and
+    /// the metrics go to the same recorder. The disk cache belongs to the current model
+    /// and isn't kept. The class vocabulary is read again for the
+    /// new model, which replaces the ensemble if there is one.
+    pub fn load_replacement(&self, model_path: &str) -> Result<Self> {
+        let backend = self.backend.load_other(model_path)?;
+        let classes = ClassVocab::from_sidecar(model_path)?;
+        let mut model = Self::with_backend(backend, self.tokenizer.clone(), classes)?;
+        model.tokenizer_settings = self.tokenizer_settings.clone();
+        model.truncation = self.truncation;
+        model.chunk_limit = self.chunk_limit;
+        model.metrics = self.metrics.clone();
+        model.post_processors = self.post_processors.clone();
+        model.cache = self
+            .cache
+            .as_ref()
+            .map(|cache| PredictionCache::new(cache.capacity()));
+        Ok(model)
+    }
+
+    /// Add the checkpoint at `model_path` to the ensemble, loaded with the settings of this
+    /// model. Its logits are combined with those of the other checkpoints before decoding.
+    ///
+    /// Fails with [`PhonikudError::IncompatibleModel`] unless the checkpoint predicts the
+    /// same classes, or [`PhonikudError::TokenizerModelMismatch`] unless it embeds every
+    /// token of the tokenizer. Cached predictions are dropped.
+    pub fn add_ensemble_member(&mut self, model_path: &str) -> Result<()> {
+        let backend = self.backend.load_other(model_path)?;
+        let (classes, output_order) = backend.validate(ClassVocab::from_sidecar(model_path)?)?;
+        backend.check_vocab(&self.tokenizer)?;
+        if classes != *self.classes {
+            return Err(PhonikudError::IncompatibleModel(format!(
+                "{model_path} predicts other classes than the first checkpoint of the ensemble"
+            )));
+        }
+        self.ensemble.push(EnsembleMember {
+            backend,
+            output_order,
+        });
+        self.clear_cache();
+        Ok(())
+    }
+
+    /// How the logits of the ensemble checkpoints are combined
+    pub fn set_ensemble_mode(&mut self, mode: EnsembleMode) {
+        self.ensemble_mode = mode;
+        self.clear_cache();
+    }
 
-        let shin_preds: Vec<usize> = shin_logits
-            .slice(ndarray::s![0, .., ..])
-            .outer_iter()
-            .map(|token| {
-                token
+    /// Run the model on dummy text of `lengths` chars (capped to one chunk), so the
+    /// allocations and kernel selection for those shapes happen ahead of real requests.
+    ///
+    /// The cache is bypassed.
+    pub fn warmup(&mut self, lengths: &[usize]) -> Result<()> {
+        for &len in lengths {
+            let text: String = WARMUP_TEXT.chars().cycle().take(len.min(self.max_chunk_chars())).collect();
+            self.predict_chunk(&text, &Thresholds::default())?;
+        }
+        Ok(())
+    }
+
+    /// Call `callback` after every chunk of a long text with the chunks processed so far
+    /// and the chunks of the text, `None` stops reporting.
+    ///
+    /// Chunks without Hebrew letters and cached ones count as processed right away.
+    /// [`PhonikudModel::vocalize_batch`] reports once per text, counting the chunks of
+    /// every text of the batch. Workers created with [`PhonikudModel::spawn_worker`]
+    /// don't report. To stop mid-document, cancel the token of
+    /// [`PhonikudModel::vocalize_cancellable`] from the callback.
+    pub fn set_progress(&mut self, callback: Option<ProgressCallback>) {
+        self.progress = callback;
+    }
+
+    /// Report the timings of every chunk to `recorder`, `None` stops reporting
+    pub fn set_metrics(&mut self, recorder: Option<Arc<dyn MetricsRecorder>>) {
+        self.metrics = recorder;
+    }
+
+    /// Run `processor` on the predictions of every text before they are rendered, after
+    /// the passes added before it
+    pub fn add_post_processor(&mut self, processor: Arc<dyn PostProcessor>) {
+        self.post_processors.push(processor);
+    }
+
+    pub fn clear_post_processors(&mut self) {
+        self.post_processors.clear();
+    }
+
+    /// Run the post-processors on the `predictions` made over `text`, returning the
+    /// warnings of those they left out of order or past the end of `text`
+    fn post_process(
+        &self,
+        text: &str,
+        predictions: &mut Vec<TokenPrediction>,
+        options: &DiacriticsOptions,
+    ) -> Vec<DecodeWarning> {
+        if self.post_processors.is_empty() {
+            return Vec::new();
+        }
+        for processor in &self.post_processors {
+            processor.process(text, predictions, options);
+        }
+        predictions.sort_by_key(|prediction| prediction.start);
+        align_predictions(text, predictions)
+    }
+
+    /// Cache the predictions of up to `capacity` chunks of text, `0` disables the cache
+    pub fn set_cache_capacity(&mut self, capacity: usize) {
+        self.cache = NonZeroUsize::new(capacity).map(PredictionCache::new);
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache
+            .as_ref()
+            .map(PredictionCache::stats)
+            .unwrap_or_default()
+    }
+
+    pub fn clear_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Also look up the chunks in `cache` and store their predictions in it, so they
+    /// survive the process. `None` stops using it.
+    #[cfg(feature = "disk-cache")]
+    pub fn set_disk_cache(&mut self, cache: Option<Arc<std::sync::Mutex<DiskCache>>>) {
+        self.disk_cache = cache;
+    }
+
+    /// Whether the predictions of `chunk` are cached in memory or on disk
+    fn is_cached(&self, chunk: &str, thresholds: &Thresholds) -> bool {
+        if self.cache.as_ref().is_some_and(|cache| cache.contains(chunk, thresholds)) {
+            return true;
+        }
+        #[cfg(feature = "disk-cache")]
+        if let Some(disk_cache) = &self.disk_cache {
+            return lock(disk_cache).contains(chunk, thresholds);
+        }
+        false
+    }
+
+    /// The cached predictions of `chunk`, those found on disk are kept in memory too
+    fn cached(&mut self, chunk: &str, thresholds: &Thresholds) -> Option<Vec<TokenPrediction>> {
+        let cached = self.cache.as_mut().and_then(|cache| cache.get(chunk, thresholds));
+        #[cfg(feature = "disk-cache")]
+        if cached.is_none() {
+            let disk_cache = self.disk_cache.as_ref()?;
+            let predictions = lock(disk_cache).get(chunk, thresholds)?;
+            if let Some(cache) = &mut self.cache {
+                cache.insert(chunk, thresholds, predictions.clone());
+            }
+            return Some(predictions);
+        }
+        cached
+    }
+
+    /// Cache the predictions of `chunk` in memory and on disk. Failing to write the disk
+    /// cache only costs a later inference, so it isn't an error.
+    fn store(&mut self, chunk: &str, thresholds: &Thresholds, predictions: &[TokenPrediction]) {
+        if let Some(cache) = &mut self.cache {
+            cache.insert(chunk, thresholds, predictions.to_vec());
+        }
+        #[cfg(feature = "disk-cache")]
+        if let Some(disk_cache) = &self.disk_cache {
+            #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+            if let Err(e) = lock(disk_cache).insert(chunk, thresholds, predictions) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, "failed to write the disk cache");
+            }
+        }
+    }
+
+    pub fn run_inference(&mut self, text: &str, options: &DiacriticsOptions) -> Result<String> {
+        Ok(self.vocalize(text, options)?.text)
+    }
+
+    /// Run the model and report the words containing a letter with confidence below `threshold`
+    pub fn run_inference_with_confidence(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        threshold: f32,
+    ) -> Result<ConfidenceReport> {
+        let Vocalization {
+            text,
+            predictions,
+            spans,
+            ..
+        } = self.vocalize(text, options)?;
+
+        let mut low_confidence: Vec<LowConfidenceWord> = Vec::new();
+        for (prediction, span) in predictions.iter().zip(spans) {
+            if prediction.confidence >= threshold {
+                continue;
+            }
+            let range = word_range(&text, span);
+            match low_confidence.last_mut() {
+                Some(word) if word.range == range => {
+                    word.confidence = word.confidence.min(prediction.confidence)
+                }
+                _ => low_confidence.push(LowConfidenceWord {
+                    range,
+                    confidence: prediction.confidence,
+                }),
+            }
+        }
+
+        Ok(ConfidenceReport { text, low_confidence })
+    }
+
+    /// Predict and render `text` according to `options`
+    pub fn vocalize(&mut self, text: &str, options: &DiacriticsOptions) -> Result<Vocalization> {
+        if !has_presentation_forms(text, options) {
+            return self.vocalize_respelled(text, options);
+        }
+
+        // Letters of a presentation form point at the precomposed char they came from
+        let normalized = normalize_presentation_forms(text);
+        let mut vocalization = self.vocalize_respelled(&normalized.text, options)?;
+        map_to_original(&mut vocalization, text, |offset| normalized.original_offset(offset));
+        Ok(vocalization)
+    }
+
+    /// Like [`PhonikudModel::vocalize`], once the presentation forms are normalized
+    fn vocalize_respelled(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<Vocalization> {
+        let Some(respelled) = respelled(text, options) else {
+            return self.vocalize_expanded(text, options);
+        };
+
+        // Letters of a fixed word point at the letters they replaced
+        let mut vocalization = self.vocalize_expanded(&respelled.text, options)?;
+        map_to_original(&mut vocalization, text, |offset| respelled.original_offset(offset));
+        Ok(vocalization)
+    }
+
+    /// Like [`PhonikudModel::vocalize`], once the presentation forms are normalized and
+    /// the typing variants fixed
+    fn vocalize_expanded(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<Vocalization> {
+        let AcronymMode::Expand(acronyms) = &options.acronyms else {
+            return self.vocalize_text(text, options);
+        };
+
+        // Letters of an expansion point at the start of the acronym they replaced
+        let expanded = expand_acronyms(text, acronyms);
+        let mut vocalization = self.vocalize_text(&expanded.text, options)?;
+        map_to_original(&mut vocalization, text, |offset| expanded.original_offset(offset));
+        Ok(vocalization)
+    }
+
+    /// Append the vocalized `text` to `out`.
+    ///
+    /// The nikud-free copy of the input and the rendering buffers are reused
+    /// between calls, so a pipeline reusing `out` only allocates the predictions.
+    pub fn vocalize_into(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        out: &mut String,
+    ) -> Result<()> {
+        if options.preserve_existing
+            || options.keep_taamim
+            || matches!(options.acronyms, AcronymMode::Expand(_))
+            || has_presentation_forms(text, options)
+            || options.respell.is_some()
+        {
+            out.push_str(&self.vocalize(text, options)?.text);
+            return Ok(());
+        }
+
+        let Scratch {
+            mut clean_text,
+            mut spans,
+        } = std::mem::take(&mut self.scratch);
+        remove_nikud_into(text, &mut clean_text);
+        let result = self
+            .predict_with_options(&clean_text, options)
+            .map(|mut predictions| {
+                let replacements = replacements(&clean_text, options);
+                retain_unreplaced(&mut predictions, &replacements);
+                self.post_process(&clean_text, &mut predictions, options);
+                spans.clear();
+                render_into(&clean_text, &predictions, &replacements, options, out, &mut spans);
+            });
+        self.scratch = Scratch { clean_text, spans };
+        result
+    }
+
+    /// Vocalize a single word, skipping the chunking and offset mapping of
+    /// [`PhonikudModel::vocalize`].
+    ///
+    /// Meant for dictionary lookups and the like calling the model word by word. The
+    /// cache is used when enabled. Words longer than the model input fail with
+    /// [`PhonikudError::InputTooLong`].
+    pub fn vocalize_word(
+        &mut self,
+        word: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<VocalizedWord> {
+        let Scratch {
+            mut clean_text,
+            mut spans,
+        } = std::mem::take(&mut self.scratch);
+        let word = match has_presentation_forms(word, options) {
+            true => Cow::Owned(normalize_presentation_forms(word).text),
+            false => Cow::Borrowed(word),
+        };
+        let word = match respelled(&word, options) {
+            Some(respelled) => Cow::Owned(respelled.text),
+            None => word,
+        };
+        remove_nikud_into(&word, &mut clean_text);
+        let input = ModelInput::new(&clean_text, options.fix_final_forms);
+        let result = self
+            .predict_word(input.text(), &options.thresholds)
+            .map(|mut predictions| {
+                let constraints = options.orthography_constraints.then_some(&*self.classes);
+                input.restore(&mut predictions, &mut [], constraints);
+                let replacements = replacements(&clean_text, options);
+                retain_unreplaced(&mut predictions, &replacements);
+                self.post_process(&clean_text, &mut predictions, options);
+                let stress = predictions
                     .iter()
-                    .enumerate()
-                    .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-                    .unwrap()
-                    .0
-            })
-            .collect();
+                    .find(|prediction| prediction.stress)
+                    .map(|prediction| clean_text[..prediction.start].chars().count());
+                let mut text = String::with_capacity(clean_text.len() * 2);
+                spans.clear();
+                render_into(
+                    &clean_text,
+                    &predictions,
+                    &replacements,
+                    options,
+                    &mut text,
+                    &mut spans,
+                );
+                VocalizedWord { text, stress }
+            });
+        self.scratch = Scratch { clean_text, spans };
+        result
+    }
 
-        // Additional predictions: stress, vocal_shva, prefix
-        let stress_preds: Vec<bool> = additional_logits
-            .slice(ndarray::s![0, .., 0])
-            .iter()
-            .map(|&x| x > 0.0)
-            .collect();
-            
-        let vocal_shva_preds: Vec<bool> = additional_logits
-            .slice(ndarray::s![0, .., 1])
-            .iter()
-            .map(|&x| x > 0.0)
-            .collect();
-            
-        let prefix_preds: Vec<bool> = additional_logits
-            .slice(ndarray::s![0, .., 2])
-            .iter()
-            .map(|&x| x > 0.0)
-            .collect();
+    /// Like [`PhonikudModel::vocalize`], failing with [`PhonikudError::Cancelled`] or
+    /// [`PhonikudError::Timeout`] as soon as `token` is cancelled
+    pub fn vocalize_cancellable(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        token: &CancellationToken,
+    ) -> Result<Vocalization> {
+        self.cancellation = Some(token.clone());
+        let result = self.vocalize(text, options);
+        self.cancellation = None;
+        result
+    }
+
+    /// Vocalize many texts, running the model on the chunks of up to `max_batch_size`
+    /// of them at once.
+    ///
+    /// Shorter chunks are padded to the longest one of their batch. The results are
+    /// the same as calling [`PhonikudModel::vocalize`] on each text, a failed text
+    /// doesn't fail the others. The tract backend runs one chunk at a time.
+    pub fn vocalize_batch(
+        &mut self,
+        requests: &[(&str, &DiacriticsOptions)],
+        max_batch_size: usize,
+    ) -> Vec<Result<Vocalization>> {
+        // The tract plan is optimized for a single sequence
+        let limit = match self.backend {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(_) => usize::MAX,
+            #[cfg(feature = "tract")]
+            Backend::Tract(_) => 1,
+        };
+        let max_batch_size = max_batch_size.clamp(1, limit);
+        let max_chunk_chars = self.max_chunk_chars();
+        let mut results: Vec<Option<Result<Vocalization>>> =
+            requests.iter().map(|_| None).collect();
+        // The texts report their chunks as a whole instead of one by one
+        let mut progress = self.progress.take();
+        let chunk_counts: Vec<usize> = if progress.is_some() {
+            requests
+                .iter()
+                .map(|&(text, options)| {
+                    split_into_chunks(&model_text(text, options), max_chunk_chars).len()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let total_chunks = chunk_counts.iter().sum();
+        let mut processed_chunks = 0;
+
+        // Predictions depend on the thresholds, so each batch shares them
+        let mut pending: Vec<usize> = (0..requests.len()).collect();
+        while let Some(&first) = pending.first() {
+            let thresholds = requests[first].1.thresholds;
+            let (group, rest): (Vec<usize>, Vec<usize>) = pending
+                .into_iter()
+                .partition(|&idx| requests[idx].1.thresholds == thresholds);
+            pending = rest;
+
+            let model_texts: Vec<String> = group
+                .iter()
+                .map(|&idx| model_text(requests[idx].0, requests[idx].1))
+                .collect();
+            let mut chunks: Vec<&str> = Vec::new();
+            for text in &model_texts {
+                for chunk in split_into_chunks(text, max_chunk_chars) {
+                    let cached = self.is_cached(chunk, &thresholds);
+                    if chunk.chars().any(is_hebrew_letter) && !cached && !chunks.contains(&chunk) {
+                        chunks.push(chunk);
+                    }
+                }
+            }
+            // On failure each text runs on its own and reports its own error
+            for batch in chunks.chunks(max_batch_size) {
+                let Ok(predictions) = self.predict_batch(batch, &thresholds) else {
+                    continue;
+                };
+                for (chunk, (chunk_predictions, skipped)) in batch.iter().zip(predictions) {
+                    // Run again with the text, to report the letters it skipped
+                    if !skipped.is_empty() {
+                        continue;
+                    }
+                    self.store(chunk, &thresholds, &chunk_predictions);
+                    self.prefetched.insert(chunk.to_string(), chunk_predictions);
+                }
+            }
+
+            for idx in group {
+                let (text, options) = requests[idx];
+                results[idx] = Some(self.vocalize(text, options));
+                if let Some(callback) = &mut progress {
+                    processed_chunks += chunk_counts[idx];
+                    callback(processed_chunks, total_chunks);
+                }
+            }
+            self.prefetched.clear();
+        }
+        self.progress = progress;
+
+        results.into_iter().flatten().collect()
+    }
+
+    fn vocalize_text(&mut self, text: &str, options: &DiacriticsOptions) -> Result<Vocalization> {
+        if options.preserve_existing {
+            let vocalized_words = vocalized_word_ranges(text, options);
+            let (clean_text, offset_map) = remove_nikud_with_offsets(text);
+            let (mut predictions, mut warnings) =
+                self.predict_with_warnings(&clean_text, options)?;
+            map_offsets(&mut predictions, &offset_map);
+            map_warning_offsets(&mut warnings, &offset_map);
+            let unvocalized = |start: usize| {
+                let idx = vocalized_words.partition_point(|word| word.end <= start);
+                vocalized_words.get(idx).is_none_or(|word| word.start > start)
+            };
+            predictions.retain(|prediction| unvocalized(prediction.start));
+            warnings.retain(|warning| unvocalized(warning.range.start));
+
+            // Predictions now point into `text`, so vocalized words are copied verbatim
+            let replacements = replacements(text, options);
+            retain_unreplaced(&mut predictions, &replacements);
+            warnings.retain(|warning| !is_replaced(warning.range.start, &replacements));
+            warnings.extend(self.post_process(text, &mut predictions, options));
+            let (text, spans) = render(text, &predictions, &replacements, options);
+            return Ok(Vocalization {
+                text,
+                predictions,
+                spans,
+                warnings,
+                #[cfg(feature = "fallback")]
+                fallback_words: Vec::new(),
+            });
+        }
+
+        if options.keep_taamim {
+            // Rendered over the input without its other marks, the taamim stay with their letter
+            let (taamim_text, taamim_map) = strip_marks_with_offsets(text, ALL_BUT_TAAMIM);
+            let (clean_text, offset_map) = remove_nikud_with_offsets(&taamim_text);
+            let (mut predictions, mut warnings) =
+                self.predict_with_warnings(&clean_text, options)?;
+            map_offsets(&mut predictions, &offset_map);
+            map_warning_offsets(&mut warnings, &offset_map);
+            let replacements = replacements(&taamim_text, options);
+            retain_unreplaced(&mut predictions, &replacements);
+            warnings.retain(|warning| !is_replaced(warning.range.start, &replacements));
+            warnings.extend(self.post_process(&taamim_text, &mut predictions, options));
+            let (text, spans) = render(&taamim_text, &predictions, &replacements, options);
+            map_offsets(&mut predictions, &taamim_map);
+            map_warning_offsets(&mut warnings, &taamim_map);
+            return Ok(Vocalization {
+                text,
+                predictions,
+                spans,
+                warnings,
+                #[cfg(feature = "fallback")]
+                fallback_words: Vec::new(),
+            });
+        }
+
+        // Remove nikud from input text first (like Python version)
+        let (clean_text, offset_map) = remove_nikud_with_offsets(text);
+        let (mut predictions, mut warnings) = self.predict_with_warnings(&clean_text, options)?;
+        let replacements = replacements(&clean_text, options);
+        retain_unreplaced(&mut predictions, &replacements);
+        warnings.retain(|warning| !is_replaced(warning.range.start, &replacements));
+        warnings.extend(self.post_process(&clean_text, &mut predictions, options));
+        let (text, spans) = render(&clean_text, &predictions, &replacements, options);
+        map_offsets(&mut predictions, &offset_map);
+        map_warning_offsets(&mut warnings, &offset_map);
+        Ok(Vocalization {
+            text,
+            predictions,
+            spans,
+            warnings,
+            #[cfg(feature = "fallback")]
+            fallback_words: Vec::new(),
+        })
+    }
+
+    /// Vocalize `text` and transcribe it into IPA phonemes from the same predictions.
+    ///
+    /// The phonemes are read from the nikud, stress and vocal shva predictions whatever
+    /// marks `options` renders. Every letter is predicted, as if `preserve_existing` and
+    /// `keep_taamim` were off, and expanded acronyms are kept as written.
+    pub fn vocalize_with_phonemes(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<VocalizedResult> {
+        let (clean_text, offset_map) = remove_nikud_with_offsets(text);
+        let mut predictions = self.predict_with_options(&clean_text, options)?;
+        let replacements = replacements(&clean_text, options);
+        retain_unreplaced(&mut predictions, &replacements);
+        self.post_process(&clean_text, &mut predictions, options);
+        let (vocalized, spans) = render(&clean_text, &predictions, &replacements, options);
+        let phonetic_options = DiacriticsOptions::default();
+        let (phonetic, phonetic_spans) =
+            render(&clean_text, &predictions, &replacements, &phonetic_options);
 
-        // 7. Reconstruct Hebrew string using offset mapping
-        let offsets = encoding.get_offsets();
-        let mut result = String::new();
+        let mut offsets = RenderedOffsets::new(&predictions, &spans, &replacements);
+        let mut phonetic_offsets =
+            RenderedOffsets::new(&predictions, &phonetic_spans, &replacements);
+        let mut phonemes = String::with_capacity(phonetic.len());
+        let mut words = Vec::new();
         let mut prev_index = 0;
-        
-        for (idx, &(start, end)) in offsets.iter().enumerate() {
-            // Add anything we missed
-            if start > prev_index {
-                result.push_str(&clean_text[prev_index..start]);
-            }
-            
-            // Skip if this token spans more than one character or is empty
-            if end <= start {
-                continue;
+        for word in whitespace_words(&clean_text) {
+            let range = offsets.map(word.start)..offsets.map(word.end);
+            let phonetic_range = phonetic_offsets.map(word.start)..phonetic_offsets.map(word.end);
+            let word_phonemes = phonemize(&phonetic[phonetic_range.clone()]);
+            phonemes.push_str(&phonetic[prev_index..phonetic_range.start]);
+            phonemes.push_str(&word_phonemes);
+            prev_index = phonetic_range.end;
+            words.push(WordInfo {
+                range,
+                input_range: offset_map[word.start]..offset_map[word.end],
+                phonemes: word_phonemes,
+            });
+        }
+        phonemes.push_str(&phonetic[prev_index..]);
+
+        Ok(VocalizedResult {
+            text: vocalized,
+            phonemes,
+            words,
+        })
+    }
+
+    /// Run the model and return the predictions with byte offsets into the original `text`
+    pub fn run_inference_detailed(
+        &mut self,
+        text: &str,
+        thresholds: &Thresholds,
+    ) -> Result<Vec<TokenPrediction>> {
+        let (clean_text, offset_map) = remove_nikud_with_offsets(text);
+        let input = ModelInput::new(&clean_text, false);
+        let mut predictions = self.predict(input.text(), thresholds)?;
+        input.restore(&mut predictions, &mut [], None);
+        map_offsets(&mut predictions, &offset_map);
+        Ok(predictions)
+    }
+
+    /// Split the words of `text` at the prefix boundaries predicted by the model
+    pub fn segment_prefixes(
+        &mut self,
+        text: &str,
+        thresholds: &Thresholds,
+    ) -> Result<Vec<WordSegmentation>> {
+        let predictions = self.run_inference_detailed(text, thresholds)?;
+        Ok(segment_words(text, &predictions))
+    }
+
+    /// Like [`PhonikudModel::predict`], only sending the Hebrew runs of `clean_text`
+    /// to the model when `options.hebrew_runs_only` is set, fixing the final letters
+    /// when `options.fix_final_forms` is set, and applying the orthography constraints
+    /// when `options.orthography_constraints` is set
+    pub(crate) fn predict_with_options(
+        &mut self,
+        clean_text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<Vec<TokenPrediction>> {
+        Ok(self.predict_with_warnings(clean_text, options)?.0)
+    }
+
+    /// Like [`PhonikudModel::predict_with_options`], also returning the letters left
+    /// without a prediction
+    fn predict_with_warnings(
+        &mut self,
+        clean_text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<(Vec<TokenPrediction>, Vec<DecodeWarning>)> {
+        self.skipped.clear();
+        let input = ModelInput::new(clean_text, options.fix_final_forms);
+        let mut predictions = if options.hebrew_runs_only {
+            self.predict_hebrew_runs(input.text(), &options.thresholds)?
+        } else {
+            self.predict(input.text(), &options.thresholds)?
+        };
+        let mut skipped = std::mem::take(&mut self.skipped);
+        let constraints = options.orthography_constraints.then_some(&*self.classes);
+        input.restore(&mut predictions, &mut skipped, constraints);
+        Ok((predictions, skipped))
+    }
+
+    /// Predict the Hebrew runs of `clean_text` only, offsets are relative to `clean_text`
+    fn predict_hebrew_runs(
+        &mut self,
+        clean_text: &str,
+        thresholds: &Thresholds,
+    ) -> Result<Vec<TokenPrediction>> {
+        let (packed, runs) = pack_hebrew_runs(clean_text);
+        let skipped_before = self.skipped.len();
+        let mut predictions = self.predict(&packed, thresholds)?;
+        let unpack = |offset: usize| {
+            let idx = runs.partition_point(|&(packed_start, _)| packed_start <= offset);
+            let (packed_start, start) = runs[idx - 1];
+            start + offset - packed_start
+        };
+        for prediction in &mut predictions {
+            prediction.start = unpack(prediction.start);
+            prediction.end = prediction.start + prediction.char.len_utf8();
+        }
+        for warning in &mut self.skipped[skipped_before..] {
+            let len = warning.range.len();
+            warning.range.start = unpack(warning.range.start);
+            warning.range.end = warning.range.start + len;
+        }
+        Ok(predictions)
+    }
+
+    /// Predict every Hebrew letter of `clean_text`, offsets are relative to `clean_text`
+    pub(crate) fn predict(
+        &mut self,
+        clean_text: &str,
+        thresholds: &Thresholds,
+    ) -> Result<Vec<TokenPrediction>> {
+        // Long inputs are split into chunks that fit the model and stitched back together
+        let mut predictions = Vec::with_capacity(clean_text.len());
+        let mut chunk_start = 0;
+        let chunks = split_into_chunks(clean_text, self.max_chunk_chars());
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = clean_text.len(), chunks = chunks.len(), "split input into chunks");
+        let total_chunks = chunks.len();
+        for (idx, chunk) in chunks.into_iter().enumerate() {
+            // Reported before the chunk is run, as the previous ones are done, so a
+            // token cancelled by the callback stops right away
+            if idx > 0
+                && let Some(callback) = &mut self.progress
+            {
+                callback(idx, total_chunks);
             }
-            
-            // Get the token text
-            let token_text = &clean_text[start..end];
-            
-            // Skip special tokens and multi-character tokens for now
-            if token_text.chars().count() != 1 {
-                result.push_str(token_text);
-                prev_index = end;
+            if let Some(token) = &self.cancellation {
+                token.check()?;
+            }
+            // Empty, whitespace only and non Hebrew text has nothing to predict
+            if !chunk.chars().any(is_hebrew_letter) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(chunk_start, "skipping chunk without Hebrew letters");
+                chunk_start += chunk.len();
                 continue;
             }
-            
-            let char = token_text.chars().next().unwrap();
-            prev_index = end;
-            
-            if !is_hebrew_letter(char) {
-                result.push(char);
+            // Run ahead of time in a batch, the metrics were recorded then
+            if let Some(chunk_predictions) = self.prefetched.get(chunk) {
+                predictions.extend(chunk_predictions.iter().map(|prediction| TokenPrediction {
+                    start: prediction.start + chunk_start,
+                    end: prediction.end + chunk_start,
+                    ..prediction.clone()
+                }));
+                chunk_start += chunk.len();
                 continue;
             }
-            
-            result.push(char);
-            
-            // Add shin/sin dot if it's a shin
-            if char == 'ש' && idx < shin_preds.len() {
-                let shin_mark = SHIN_CLASSES[shin_preds[idx]];
-                result.push_str(shin_mark);
-            }
-            
-            // Add nikud
-            if idx < nikud_preds.len() {
-                let nikud = NIKUD_CLASSES[nikud_preds[idx]];
-                
-                // Handle matres lectionis
-                if nikud == MAT_LECT_TOKEN {
-                    if is_matres_letter(char) {
-                        if let Some(mark) = mark_matres_lectionis {
-                            result.push_str(mark);
-                        }
-                        // If no mark specified, skip adding anything for matres lectionis
+            let cached = self.cached(chunk, thresholds);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(chunk_start, bytes = chunk.len(), cached = cached.is_some(), "chunk");
+            let chunk_predictions = match cached {
+                Some(chunk_predictions) => {
+                    if let Some(metrics) = &self.metrics {
+                        metrics.record(&InferenceMetrics {
+                            chars: chunk.chars().count(),
+                            cache_hit: true,
+                            ..Default::default()
+                        });
                     }
-                    // Don't allow matres on irrelevant letters
-                } else {
-                    result.push_str(nikud);
+                    chunk_predictions
+                }
+                None => {
+                    let (chunk_predictions, skipped) = self.predict_chunk(chunk, thresholds)?;
+                    // Chunks with skipped letters run again, so the reason is known
+                    if skipped.is_empty() {
+                        self.store(chunk, thresholds, &chunk_predictions);
+                    }
+                    self.skipped.extend(skipped.into_iter().map(|mut warning| {
+                        warning.range = chunk_start + warning.range.start
+                            ..chunk_start + warning.range.end;
+                        warning
+                    }));
+                    chunk_predictions
+                }
+            };
+            predictions.extend(chunk_predictions.into_iter().map(|mut prediction| {
+                prediction.start += chunk_start;
+                prediction.end += chunk_start;
+                prediction
+            }));
+            chunk_start += chunk.len();
+        }
+        if let Some(callback) = &mut self.progress {
+            callback(total_chunks, total_chunks);
+        }
+        Ok(predictions)
+    }
+
+    /// Predict `clean_text` as a single chunk, through the cache
+    fn predict_word(
+        &mut self,
+        clean_text: &str,
+        thresholds: &Thresholds,
+    ) -> Result<Vec<TokenPrediction>> {
+        if !clean_text.chars().any(is_hebrew_letter) {
+            return Ok(Vec::new());
+        }
+        let cached = self.cached(clean_text, thresholds);
+        if let Some(predictions) = cached {
+            if let Some(metrics) = &self.metrics {
+                metrics.record(&InferenceMetrics {
+                    chars: clean_text.chars().count(),
+                    cache_hit: true,
+                    ..Default::default()
+                });
+            }
+            return Ok(predictions);
+        }
+        let (predictions, skipped) = self.predict_chunk(clean_text, thresholds)?;
+        if skipped.is_empty() {
+            self.store(clean_text, thresholds, &predictions);
+        }
+        Ok(predictions)
+    }
+
+    /// Predict a single chunk, offsets are relative to the chunk
+    fn predict_chunk(
+        &mut self,
+        clean_text: &str,
+        thresholds: &Thresholds,
+    ) -> Result<(Vec<TokenPrediction>, Vec<DecodeWarning>)> {
+        let mut predictions = self.predict_batch(&[clean_text], thresholds)?;
+        Ok(predictions.pop().unwrap_or_default())
+    }
+
+    /// Run the model on `text` without nikud and return its raw logits, e.g. to decode them
+    /// differently. The text must fit in one chunk.
+    pub fn run_raw(&mut self, text: &str) -> Result<RawOutputs> {
+        let text = crate::text::remove_nikud(text);
+        let (encoding, _) = self.tokenize_letters(&text)?;
+        let inputs = self.model_inputs(std::iter::once(&encoding))?;
+        let seq_len = inputs.seq_len();
+        let outputs = self.run_backend(inputs)?;
+
+        let head = |head: usize| {
+            self.logits(&outputs, head, 1, seq_len)?
+                .slice(s![0, ..seq_len, ..])
+                .to_owned()
+                .into_dimensionality::<Ix2>()
+                .map_err(|e| PhonikudError::InvalidModelOutputs(e.to_string()))
+        };
+        Ok(RawOutputs {
+            nikud_logits: head(0)?,
+            shin_logits: head(1)?,
+            additional_logits: head(2)?,
+            offsets: encoding.get_offsets().iter().map(|&(start, end)| start..end).collect(),
+            text,
+        })
+    }
+
+    /// Vocalize `text` from an `encoding` of it made by the caller, e.g. shared with other
+    /// models it's aligned with, instead of tokenizing it again. The encoding must come
+    /// from the tokenizer of the model, or at least its vocabulary, with a token for every
+    /// letter and offsets into `text`, which has no nikud. It's run as a single chunk.
+    ///
+    /// The options changing the text sent to the model, such as `hebrew_runs_only`,
+    /// `preserve_existing`, `keep_taamim`, `fix_final_forms`, `respell` and acronym expansion,
+    /// aren't applied. Fails with [`PhonikudError::InvalidEncoding`] when the encoding
+    /// doesn't fit `text`.
+    pub fn vocalize_pretokenized(
+        &mut self,
+        encoding: &Encoding,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<Vocalization> {
+        let outside = encoding.get_offsets().iter().find(|&&(_, end)| end > text.len());
+        if let Some((start, end)) = outside {
+            return Err(PhonikudError::InvalidEncoding(format!(
+                "token offsets {start}..{end} are past the {} bytes of text",
+                text.len()
+            )));
+        }
+        if text.chars().any(is_nikud) {
+            return Err(PhonikudError::InvalidEncoding(
+                "the text has nikud, encode it without".to_string(),
+            ));
+        }
+        let letters = letter_tokens(encoding, text).ok_or_else(|| {
+            PhonikudError::InvalidEncoding("a token holds several letters".to_string())
+        })?;
+
+        let inputs = self.model_inputs(std::iter::once(encoding))?;
+        let seq_len = inputs.seq_len();
+        let outputs = self.run_backend(inputs)?;
+        let (letters, special) = self.split_special(encoding, letters);
+        let tokenized: Vec<usize> = letters.iter().map(|letter| letter.start).collect();
+        let mut predictions =
+            self.decode(&outputs, 0, 1, letters, seq_len, &options.thresholds)?;
+        let mut skipped = skipped_letters(text, &tokenized, &special, &predictions);
+        if options.orthography_constraints {
+            constraints::apply(text, &mut predictions, &self.classes);
+        }
+        let replacements = replacements(text, options);
+        retain_unreplaced(&mut predictions, &replacements);
+        skipped.extend(self.post_process(text, &mut predictions, options));
+        let mut vocalization = Vocalization::render(text, predictions, options);
+        skipped.retain(|warning| !is_replaced(warning.range.start, &replacements));
+        vocalization.warnings.extend(skipped);
+        Ok(vocalization)
+    }
+
+    /// Tokenize `text` exactly like it is fed to the model: without nikud, with its geresh
+    /// and gershayim normalized, split into chunks and with one token per Hebrew letter.
+    ///
+    /// Chunks without Hebrew letters are never run and have no tokens. Ranges are
+    /// byte offsets into `text`.
+    pub fn tokenize(&self, text: &str) -> Result<Vec<TokenSpan>> {
+        let (clean_text, offset_map) = remove_nikud_with_offsets(text);
+        let input = ModelInput::new(&clean_text, false);
+        // Offsets past a replaced geresh move back to `clean_text`, the char boundaries
+        // around it stay boundaries
+        let unprotect = |offset: usize| input.original_offset(offset);
+        let mut spans = Vec::new();
+        let mut chunk_start = 0;
+        let chunks = split_into_chunks(input.text(), self.max_chunk_chars());
+        for (chunk_idx, chunk) in chunks.into_iter().enumerate() {
+            if chunk.chars().any(is_hebrew_letter) {
+                let (encoding, _) = self.tokenize_letters(chunk)?;
+                // Tokens of a part of a char cover the whole char
+                let chars = CharOffsets::new(chunk);
+                let tokens = encoding.get_ids().iter().zip(encoding.get_tokens());
+                for ((&id, token), &(start, end)) in tokens.zip(encoding.get_offsets()) {
+                    let end = if start < end { chars.ceil(end) } else { chars.floor(start) };
+                    let start = unprotect(chunk_start + chars.floor(start));
+                    let end = unprotect(chunk_start + end);
+                    // The input range of the last char, not of the nikud after it
+                    let range = match clean_text[..end].chars().next_back() {
+                        Some(ch) if start < end => {
+                            offset_map[start]..offset_map[end - ch.len_utf8()] + ch.len_utf8()
+                        }
+                        _ => {
+                            let chunk_start = offset_map[unprotect(chunk_start)];
+                            chunk_start..chunk_start
+                        }
+                    };
+                    spans.push(TokenSpan {
+                        id,
+                        token: token.clone(),
+                        range,
+                        chunk: chunk_idx,
+                    });
+                }
+            }
+            chunk_start += chunk.len();
+        }
+        Ok(spans)
+    }
+
+    /// Predict `chunks` in one model run, along with the letters left without a
+    /// prediction. Offsets are relative to each chunk.
+    fn predict_batch(
+        &mut self,
+        chunks: &[&str],
+        thresholds: &Thresholds,
+    ) -> Result<Vec<(Vec<TokenPrediction>, Vec<DecodeWarning>)>> {
+        let mut stopwatch = Stopwatch::start(self.metrics.is_some());
+
+        // 1. Tokenize, with one token per letter
+        let encoded = chunks
+            .iter()
+            .map(|chunk| self.tokenize_letters(chunk))
+            .collect::<Result<Vec<_>>>()?;
+        let tokenize = stopwatch.lap();
+
+        let inputs = self.model_inputs(encoded.iter().map(|(encoding, _)| encoding))?;
+        let seq_len = inputs.seq_len();
+
+        // 2. Run inference
+        let outputs = self.run_backend(inputs)?;
+        let inference = stopwatch.lap();
+
+        // 3. Turn the logits of every row into predictions
+        let rows = chunks.len();
+        let predictions = encoded
+            .into_iter()
+            .zip(chunks)
+            .enumerate()
+            .map(|(row, ((encoding, letters), chunk))| {
+                let (letters, special) = self.split_special(&encoding, letters);
+                let tokenized: Vec<usize> = letters.iter().map(|letter| letter.start).collect();
+                let predictions = self.decode(&outputs, row, rows, letters, seq_len, thresholds)?;
+                let skipped = skipped_letters(chunk, &tokenized, &special, &predictions);
+                Ok((predictions, skipped))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(metrics) = &self.metrics {
+            // The run is shared equally by the chunks of the batch
+            let (decode, share) = (stopwatch.lap(), rows as u32);
+            for chunk in chunks {
+                metrics.record(&InferenceMetrics {
+                    tokenize: tokenize / share,
+                    inference: inference / share,
+                    decode: decode / share,
+                    sequence_length: seq_len,
+                    chars: chunk.chars().count(),
+                    cache_hit: false,
+                });
+            }
+        }
+        Ok(predictions)
+    }
+
+    /// Pad the `encodings` to the longest one and stack them into a batch
+    fn model_inputs<'a>(
+        &self,
+        encodings: impl ExactSizeIterator<Item = &'a Encoding> + Clone,
+    ) -> Result<ModelInputs> {
+        let seq_len = encodings.clone().map(Encoding::len).max().unwrap_or(0);
+        let max_seq_len = self.max_seq_len();
+        if seq_len > max_seq_len {
+            return Err(PhonikudError::InputTooLong {
+                len: seq_len,
+                max: max_seq_len,
+            });
+        }
+
+        // Padding tokens are masked out, so they don't change the other predictions
+        let padding = self.tokenizer_settings.padding.as_ref();
+        let pad_id = padding.map_or(0, |padding| padding.pad_id) as i64;
+        let capacity = encodings.len() * seq_len;
+        let mut inputs = ModelInputs {
+            batch_size: encodings.len(),
+            input_ids: Vec::with_capacity(capacity),
+            attention_mask: Vec::with_capacity(capacity),
+            token_type_ids: Vec::with_capacity(capacity),
+        };
+        for encoding in encodings {
+            let padding = seq_len - encoding.len();
+            let ids = encoding.get_ids().iter().map(|&x| x as i64);
+            inputs.input_ids.extend(ids.chain(std::iter::repeat_n(pad_id, padding)));
+            let mask = encoding.get_attention_mask().iter().map(|&x| x as i64);
+            inputs.attention_mask.extend(mask.chain(std::iter::repeat_n(0, padding)));
+            let type_ids = encoding.get_type_ids().iter().map(|&x| x as i64);
+            inputs.token_type_ids.extend(type_ids.chain(std::iter::repeat_n(0, padding)));
+        }
+        Ok(inputs)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "run",
+            level = "debug",
+            skip_all,
+            fields(batch_size = inputs.batch_size, seq_len = inputs.seq_len())
+        )
+    )]
+    fn run_backend(&mut self, inputs: ModelInputs) -> Result<Vec<ArrayD<f32>>> {
+        let cancellation = self.cancellation.as_ref();
+        if self.ensemble.is_empty() {
+            return self.backend.run(inputs, cancellation);
+        }
+
+        let mut outputs = self.backend.run(inputs.clone(), cancellation)?;
+        for member in &mut self.ensemble {
+            let member_outputs = member.backend.run(inputs.clone(), cancellation)?;
+            let heads = OUTPUT_NAMES.iter().zip(self.output_order).zip(member.output_order);
+            for ((name, idx), member_idx) in heads {
+                let missing =
+                    || PhonikudError::InvalidModelOutputs(format!("missing output {name}"));
+                let logits = outputs.get_mut(idx).ok_or_else(missing)?;
+                let other = member_outputs.get(member_idx).ok_or_else(missing)?;
+                if logits.shape() != other.shape() {
+                    return Err(PhonikudError::InvalidModelOutputs(format!(
+                        "{name} logits of the ensemble have shapes {:?} and {:?}",
+                        logits.shape(),
+                        other.shape()
+                    )));
+                }
+                match self.ensemble_mode {
+                    EnsembleMode::Mean => *logits += other,
+                    EnsembleMode::Max => logits.zip_mut_with(other, |logit, &other| {
+                        *logit = logit.max(other);
+                    }),
                 }
             }
-            
-            // Add stress mark
-            if idx < stress_preds.len() && stress_preds[idx] {
-                result.push_str(STRESS_CHAR);
-            }
-            
-            // Add vocal shva mark
-            if idx < vocal_shva_preds.len() && vocal_shva_preds[idx] {
-                result.push_str(VOCAL_SHVA_CHAR);
-            }
-            
-            // Add prefix mark
-            if idx < prefix_preds.len() && prefix_preds[idx] {
-                result.push_str(PREFIX_CHAR);
-            }
-        }
-        
-        // Add any remaining text
-        result.push_str(&clean_text[prev_index..]);
-        
-        Ok(result)
-    }
-}
-
-// Constants matching Python implementation
-const NIKUD_CLASSES: &[&str] = &[
-    "",
-    "<MAT_LECT>",
-    "\u{05bc}", // dagesh
-    "\u{05b0}", // shva
-    "\u{05b1}", // hataf segol
-    "\u{05b2}", // hataf patah
-    "\u{05b3}", // hataf qamats
-    "\u{05b4}", // hiriq
-    "\u{05b5}", // tsere
-    "\u{05b6}", // segol
-    "\u{05b7}", // patah
-    "\u{05b8}", // qamats
-    "\u{05b9}", // holam
-    "\u{05ba}", // holam haser
-    "\u{05bb}", // qubuts
-    "\u{05bc}\u{05b0}", "\u{05bc}\u{05b1}", "\u{05bc}\u{05b2}", "\u{05bc}\u{05b3}",
-    "\u{05bc}\u{05b4}", "\u{05bc}\u{05b5}", "\u{05bc}\u{05b6}", "\u{05bc}\u{05b7}",
-    "\u{05bc}\u{05b8}", "\u{05bc}\u{05b9}", "\u{05bc}\u{05ba}", "\u{05bc}\u{05bb}",
-    "\u{05c7}",         // qamats qatan
-    "\u{05bc}\u{05c7}", // dagesh + qamats qatan
-];
-
-const SHIN_CLASSES: &[&str] = &["\u{05c1}", "\u{05c2}"]; // shin, sin
-const MAT_LECT_TOKEN: &str = "<MAT_LECT>";
-const MATRES_LETTERS: &[char] = &['א', 'ו', 'י'];
-const ALEF_ORD: u32 = 'א' as u32;
-const TAF_ORD: u32 = 'ת' as u32;
-const STRESS_CHAR: &str = "\u{05ab}"; // "ole" symbol marks stress
-const VOCAL_SHVA_CHAR: &str = "\u{05bd}"; // "meteg" symbol marks Vocal Shva
-const PREFIX_CHAR: &str = "|";
-
-fn is_hebrew_letter(ch: char) -> bool {
-    let ord = ch as u32;
-    ALEF_ORD <= ord && ord <= TAF_ORD
-}
-
-fn is_matres_letter(ch: char) -> bool {
-    MATRES_LETTERS.contains(&ch)
-}
-
-fn remove_nikud(text: &str) -> String {
-    let nikud_pattern = Regex::new(r"[\u{0590}-\u{05C7}|]").unwrap();
-    nikud_pattern.replace_all(text, "").to_string()
+        }
+        if self.ensemble_mode == EnsembleMode::Mean {
+            let checkpoints = (self.ensemble.len() + 1) as f32;
+            for &idx in &self.output_order {
+                outputs[idx] /= checkpoints;
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Collect a prediction for every letter of batch row `row` from the model outputs
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(letters = letters.len()))
+    )]
+    fn decode(
+        &self,
+        outputs: &[ArrayD<f32>],
+        row: usize,
+        rows: usize,
+        letters: Vec<Letter>,
+        seq_len: usize,
+        thresholds: &Thresholds,
+    ) -> Result<Vec<TokenPrediction>> {
+        let nikud_logits = self.logits(outputs, 0, rows, seq_len)?;
+        let shin_logits = self.logits(outputs, 1, rows, seq_len)?;
+        let additional_logits = self.logits(outputs, 2, rows, seq_len)?;
+        let logits = [
+            nikud_logits.slice(s![row, .., ..]),
+            shin_logits.slice(s![row, .., ..]),
+            additional_logits.slice(s![row, .., ..]),
+        ];
+        decode_letters(&self.classes, logits, letters, thresholds)
+    }
+
+    /// The logits of `head` among `outputs`, in the order found when the model was loaded
+    fn logits<'a>(
+        &self,
+        outputs: &'a [ArrayD<f32>],
+        head: usize,
+        rows: usize,
+        seq_len: usize,
+    ) -> Result<&'a ArrayD<f32>> {
+        let idx = self.output_order[head];
+        let logits = outputs.get(idx).ok_or_else(|| {
+            PhonikudError::InvalidModelOutputs(format!("missing output {}", OUTPUT_NAMES[head]))
+        })?;
+        let classes = [self.classes.nikud.len(), self.classes.shin.len(), ADDITIONAL_CLASSES];
+        check_logits_shape(idx, &logits.view(), rows, seq_len, classes[head])?;
+        Ok(logits)
+    }
+
+    /// Set apart the letters encoded as special tokens, such as `[UNK]`, which the model
+    /// can't vocalize
+    fn split_special(
+        &self,
+        encoding: &Encoding,
+        letters: Vec<Letter>,
+    ) -> (Vec<Letter>, Vec<usize>) {
+        let mask = encoding.get_special_tokens_mask();
+        let added = self.tokenizer.get_added_vocabulary();
+        let (special, letters): (Vec<Letter>, Vec<Letter>) =
+            letters.into_iter().partition(|letter| {
+                mask.get(letter.token) == Some(&1)
+                    || encoding
+                        .get_tokens()
+                        .get(letter.token)
+                        .is_some_and(|token| added.is_special_token(token))
+            });
+        (letters, special.into_iter().map(|letter| letter.start).collect())
+    }
+
+    /// Encode `clean_text` and find the token predicting each Hebrew letter.
+    ///
+    /// The char level tokenizer of the 1.0 model maps every letter to its own
+    /// token. When a tokenizer merges letters into one token, the text is encoded
+    /// again with every char as a separate word, and each letter is predicted
+    /// by the first token of its word.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "tokenize",
+            level = "debug",
+            skip_all,
+            fields(bytes = clean_text.len())
+        )
+    )]
+    fn tokenize_letters(&self, clean_text: &str) -> Result<(Encoding, Vec<Letter>)> {
+        encode_letters(&self.tokenizer, clean_text)
+    }
+}
+
+/// [`PhonikudModel::tokenize_letters`] with any tokenizer
+fn encode_letters(tokenizer: &Tokenizer, clean_text: &str) -> Result<(Encoding, Vec<Letter>)> {
+    let encoding = tokenizer
+        .encode(clean_text, true)
+        .map_err(PhonikudError::Tokenize)?;
+    if let Some(letters) = letter_tokens(&encoding, clean_text) {
+        return Ok((encoding, letters));
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!("tokenizer merged letters, encoding every char as a word");
+
+    let (starts, chars): (Vec<usize>, Vec<&str>) = clean_text
+        .char_indices()
+        .map(|(start, char)| (start, &clean_text[start..start + char.len_utf8()]))
+        .unzip();
+    let encoding = tokenizer
+        .encode(chars.as_slice(), true)
+        .map_err(PhonikudError::Tokenize)?;
+
+    let mut letters = Vec::with_capacity(encoding.len());
+    let mut prev_word = None;
+    for (token, word) in encoding.get_word_ids().iter().enumerate() {
+        let Some(word) = word.map(|word| word as usize) else {
+            continue;
+        };
+        if prev_word == Some(word) {
+            continue;
+        }
+        prev_word = Some(word);
+        if let Some(char) = chars[word].chars().next().filter(|&c| is_hebrew_letter(c)) {
+            letters.push(Letter {
+                token,
+                char,
+                start: starts[word],
+            });
+        }
+    }
+    Ok((encoding, letters))
+}
+
+/// A prediction for every letter from the nikud, shin and additional `logits` of its row,
+/// one row of scores per token
+fn decode_letters(
+    classes: &ClassVocab,
+    logits: [ArrayView2<f32>; 3],
+    letters: Vec<Letter>,
+    thresholds: &Thresholds,
+) -> Result<Vec<TokenPrediction>> {
+    let (nikud_classes, shin_classes) = (&classes.nikud, &classes.shin);
+    let [nikud_logits, shin_logits, additional_logits] = logits;
+    let mut predictions = Vec::with_capacity(letters.len());
+
+    for Letter { token: idx, char, start } in letters {
+        let end = start + char.len_utf8();
+        if idx >= nikud_logits.nrows() {
+            continue;
+        }
+
+        // Outputs may have more classes than the vocabulary, those are never predicted
+        let nikud_scores = nikud_logits.slice(s![idx, ..nikud_classes.len()]).to_vec();
+        let shin_scores = shin_logits.slice(s![idx, ..shin_classes.len()]).to_vec();
+        let additional = additional_logits.row(idx);
+        // Additional predictions: stress, vocal_shva, prefix
+        let additional_scores = [additional[0], additional[1], additional[2]];
+        let mut scores = nikud_scores.iter().chain(&shin_scores).chain(&additional_scores);
+        if scores.any(|score| score.is_nan()) {
+            return Err(PhonikudError::InvalidModelOutputs(format!(
+                "NaN logits for the letter at byte {start}"
+            )));
+        }
+
+        let nikud_class = argmax(&nikud_scores);
+        let confidence = softmax_probability(&nikud_scores, nikud_class);
+        let shin_class = (char == 'ש').then(|| argmax(&shin_scores));
+
+        predictions.push(TokenPrediction {
+            char,
+            start,
+            end,
+            nikud_class,
+            confidence,
+            nikud: nikud_classes[nikud_class].clone(),
+            shin_class,
+            shin: shin_class.map(|class| shin_classes[class].clone()),
+            stress: sigmoid(additional_scores[0]) > thresholds.stress,
+            vocal_shva: sigmoid(additional_scores[1]) > thresholds.vocal_shva,
+            prefix: sigmoid(additional_scores[2]) > thresholds.prefix,
+            nikud_logits: nikud_scores,
+            shin_logits: shin_scores,
+            additional_logits: additional_scores,
+        });
+    }
+
+    Ok(predictions)
+}
+
+/// The token of every Hebrew letter of `clean_text` in its `encoding`, `None` when a token
+/// holds several chars including a letter.
+///
+/// Offsets inside a char, as byte level tokenizers make for multi-byte chars, are widened
+/// to the whole char, and its first token predicts the letter.
+fn letter_tokens(encoding: &Encoding, clean_text: &str) -> Option<Vec<Letter>> {
+    let chars = CharOffsets::new(clean_text);
+    let mut letters: Vec<Letter> = Vec::with_capacity(encoding.len());
+    for (token, &(start, end)) in encoding.get_offsets().iter().enumerate() {
+        // Special tokens are empty
+        if end <= start {
+            continue;
+        }
+        let (start, end) = (chars.floor(start), chars.ceil(end));
+        let mut token_chars = clean_text[start..end].chars();
+        match (token_chars.next(), token_chars.next()) {
+            // The other tokens of the letter follow its first one
+            (Some(_), None) if letters.last().is_some_and(|letter| letter.start == start) => {}
+            (Some(char), None) if is_hebrew_letter(char) => {
+                letters.push(Letter { token, char, start })
+            }
+            (Some(_), Some(_)) if clean_text[start..end].chars().any(is_hebrew_letter) => {
+                return None;
+            }
+            _ => {}
+        }
+    }
+    Some(letters)
+}
+
+/// The letters of `chunk` without a prediction and why, `tokenized` and `special` are the
+/// starts of the letters given a token and of those encoded as special tokens
+fn skipped_letters(
+    chunk: &str,
+    tokenized: &[usize],
+    special: &[usize],
+    predictions: &[TokenPrediction],
+) -> Vec<DecodeWarning> {
+    if predictions.len() == tokenized.len() && special.is_empty() {
+        return Vec::new();
+    }
+    let mut skipped = Vec::new();
+    let mut predictions = predictions.iter().peekable();
+    for (start, char) in chunk.char_indices().filter(|&(_, ch)| is_hebrew_letter(ch)) {
+        if predictions.next_if(|prediction| prediction.start == start).is_some() {
+            continue;
+        }
+        let kind = if special.binary_search(&start).is_ok() {
+            WarningKind::SpecialToken
+        } else if tokenized.binary_search(&start).is_ok() {
+            WarningKind::OutOfRange
+        } else {
+            WarningKind::NoToken
+        };
+        skipped.push(DecodeWarning {
+            kind,
+            range: start..start + char.len_utf8(),
+        });
+    }
+    skipped
+}
+
+impl Backend {
+    /// Names and shapes of the model inputs and outputs
+    fn signature(&self) -> (Vec<TensorInfo>, Vec<TensorInfo>) {
+        match self {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(session) => session.signature(),
+            #[cfg(feature = "tract")]
+            Backend::Tract(session) => session.signature(),
+        }
+    }
+
+    /// Check the signature, returning the class vocabulary and the index of each head
+    fn validate(&self, classes: Option<ClassVocab>) -> Result<(ClassVocab, [usize; 3])> {
+        let (inputs, outputs, metadata) = match self {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(session) => {
+                let (inputs, outputs) = session.signature();
+                (inputs, outputs, session.metadata(CLASSES_METADATA_KEY))
+            }
+            #[cfg(feature = "tract")]
+            Backend::Tract(session) => {
+                let (inputs, outputs) = session.signature();
+                (inputs, outputs, session.metadata(CLASSES_METADATA_KEY))
+            }
+        };
+        let classes = match (classes, metadata) {
+            (Some(classes), _) => classes,
+            (None, Some(json)) => ClassVocab::from_json(&json)?,
+            (None, None) => ClassVocab::default(),
+        };
+        let output_order = validate_signature(&inputs, &outputs, &classes)?;
+        Ok((classes, output_order))
+    }
+
+    /// Opset, weight types, vocabulary size and metadata of the model file
+    fn header(&self) -> Option<ModelHeader> {
+        match self {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(session) => session.header(),
+            #[cfg(feature = "tract")]
+            Backend::Tract(session) => session.header(),
+        }
+    }
+
+    /// Check that the model has an embedding for every token id of `tokenizer`.
+    ///
+    /// Models often pad their embeddings past the vocabulary, so only a tokenizer
+    /// larger than the model is rejected. Models without a recognizable embedding
+    /// matrix aren't checked.
+    fn check_vocab(&self, tokenizer: &Tokenizer) -> Result<()> {
+        let Some(model) = self.header().and_then(|header| header.vocab_size) else {
+            return Ok(());
+        };
+        let tokenizer = tokenizer.get_vocab_size(true);
+        if tokenizer > model {
+            return Err(PhonikudError::TokenizerModelMismatch { tokenizer, model });
+        }
+        Ok(())
+    }
+
+    /// Create another session of the same model with the same settings
+    fn spawn(&self) -> Result<Self> {
+        Ok(match self {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(session) => Backend::Onnx(session.spawn()?),
+            #[cfg(feature = "tract")]
+            Backend::Tract(session) => Backend::Tract(session.clone()),
+        })
+    }
+
+    /// Load the model at `model_path` with the same engine and settings
+    fn load_other(&self, model_path: &str) -> Result<Self> {
+        Ok(match self {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(session) => {
+                Backend::Onnx(session.load_other(ModelSource::File(model_path.to_string()))?)
+            }
+            #[cfg(feature = "tract")]
+            Backend::Tract(_) => Backend::Tract(TractSession::from_file(model_path)?),
+        })
+    }
+
+    /// Run the model, terminated early when `cancellation` is cancelled
+    #[cfg_attr(not(feature = "onnx"), allow(unused_variables))]
+    fn run(
+        &mut self,
+        inputs: ModelInputs,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<ArrayD<f32>>> {
+        match self {
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(session) => {
+                let run_options = session.run_options()?;
+                let Some(token) = cancellation else {
+                    return session.run(inputs, run_options.as_ref());
+                };
+                let run_options = token.run_options(run_options)?;
+                let result = session.run(inputs, Some(&run_options));
+                token.finish_run();
+                // A terminated run fails with an ORT error, report the cancellation instead
+                token.check()?;
+                result
+            }
+            #[cfg(feature = "tract")]
+            Backend::Tract(session) => session.run(inputs),
+        }
+    }
+}
+
+/// A Hebrew letter of the input and the token predicting it
+struct Letter {
+    token: usize,
+    char: char,
+    /// Byte offset of the letter in the text
+    start: usize,
+}
+
+/// Model prediction for a single Hebrew letter
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenPrediction {
+    /// The Hebrew letter
+    pub char: char,
+    /// Byte offset where the letter starts in the input text
+    pub start: usize,
+    /// Byte offset where the letter ends in the input text
+    pub end: usize,
+    /// Index of the predicted nikud class
+    pub nikud_class: usize,
+    /// Softmax probability of the predicted nikud class
+    pub confidence: f32,
+    /// Predicted nikud marks, `<MAT_LECT>` for matres lectionis
+    pub nikud: String,
+    /// Index of the predicted shin/sin class, only set for `ש`
+    pub shin_class: Option<usize>,
+    /// Predicted shin/sin dot, only set for `ש`
+    pub shin: Option<String>,
+    pub stress: bool,
+    pub vocal_shva: bool,
+    pub prefix: bool,
+    /// Raw nikud head scores, one per nikud class
+    pub nikud_logits: Vec<f32>,
+    /// Raw shin head scores, one per shin class
+    pub shin_logits: Vec<f32>,
+    /// Raw stress, vocal shva and prefix head scores
+    pub additional_logits: [f32; 3],
+}
+
+impl TokenPrediction {
+    /// Whether the model predicted this letter to be a silent mater lectionis
+    pub fn is_matres_lectionis(&self) -> bool {
+        self.nikud == MAT_LECT_TOKEN
+    }
+
+    /// Whether the predicted marks include a dagesh, the mappiq of final he and the
+    /// dot of shuruk included
+    pub fn has_dagesh(&self) -> bool {
+        self.nikud.contains(DAGESH)
+    }
+
+    /// The predicted marks without the dagesh, empty for matres lectionis
+    pub fn vowel(&self) -> String {
+        if self.is_matres_lectionis() {
+            return String::new();
+        }
+        self.nikud.chars().filter(|&mark| mark != DAGESH).collect()
+    }
+}
+
+/// Vocalized text together with the words the model is unsure about
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfidenceReport {
+    pub text: String,
+    /// Words of `text` containing at least one low confidence letter, in order
+    pub low_confidence: Vec<LowConfidenceWord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LowConfidenceWord {
+    /// Byte range of the word in the vocalized text
+    pub range: Range<usize>,
+    /// Lowest letter confidence in the word
+    pub confidence: f32,
+}
+
+/// Logits of the three heads for every token, see [`PhonikudModel::run_raw`].
+///
+/// Outputs may have more classes than the vocabulary, those past its length are
+/// never predicted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawOutputs {
+    /// The text the model saw, the input without nikud
+    pub text: String,
+    /// `[tokens, nikud classes]`, see [`ClassVocab::nikud`]
+    pub nikud_logits: Array2<f32>,
+    /// `[tokens, shin classes]`, see [`ClassVocab::shin`]
+    pub shin_logits: Array2<f32>,
+    /// `[tokens, 3]` logits of the stress, vocal shva and prefix heads
+    pub additional_logits: Array2<f32>,
+    /// Byte range of every token in `text`, empty for special tokens
+    pub offsets: Vec<Range<usize>>,
+}
+
+/// A token of the model input, see [`PhonikudModel::tokenize`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenSpan {
+    /// Id in the tokenizer vocabulary
+    pub id: u32,
+    /// The token as written in the vocabulary, e.g. `[CLS]`
+    pub token: String,
+    /// Byte range of the token in the input text, empty at the start of its chunk
+    /// for special tokens
+    pub range: Range<usize>,
+    /// Index of the chunk the token belongs to, every chunk is a separate model input
+    pub chunk: usize,
+}
+
+/// Vocalized text along with the predictions it was rendered from
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vocalization {
+    pub text: String,
+    /// Predictions with byte offsets into the input text
+    pub predictions: Vec<TokenPrediction>,
+    /// Byte range of every prediction (letter and marks) in `text`
+    pub spans: Vec<Range<usize>>,
+    /// Hebrew letters left without nikud because the model couldn't predict them, in
+    /// order. Empty when every letter outside the lexicon and acronyms was predicted.
+    pub warnings: Vec<DecodeWarning>,
+    /// Byte range of every word of `text` vocalized by the rule based fallback instead
+    /// of the model, see [`Phonikud::vocalize_or_fallback`](crate::Phonikud::vocalize_or_fallback)
+    #[cfg(feature = "fallback")]
+    pub fallback_words: Vec<Range<usize>>,
+}
+
+/// A Hebrew letter the model left without nikud, see [`Vocalization::warnings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodeWarning {
+    pub kind: WarningKind,
+    /// Byte range of the letter in the input text
+    pub range: Range<usize>,
+}
+
+/// Why a letter wasn't vocalized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum WarningKind {
+    /// The tokenizer gave the letter no token of its own, it dropped the letter or merged
+    /// it with other chars
+    NoToken,
+    /// The letter was encoded as a special token such as `[UNK]`
+    SpecialToken,
+    /// The token of the letter is past the sequence of the model outputs
+    OutOfRange,
+    /// A prediction given to [`Vocalization::render`] starts past the end of the text or
+    /// inside the previous one, `range` is the one of the prediction
+    DroppedPrediction,
+}
+
+impl Vocalization {
+    /// Render `predictions` over `clean_text`, the text they were made for without nikud,
+    /// e.g. predictions kept from an earlier run or edited by hand. The lexicon and
+    /// acronyms of `options` replace their words like they do on inference.
+    ///
+    /// Predictions are expected in order, each on its own char. A prediction starting
+    /// inside a char is moved to the start of the char and takes its letter, and those
+    /// past the end of the text or overlapping the previous one are dropped with a
+    /// [`WarningKind::DroppedPrediction`] warning.
+    pub fn render(
+        clean_text: &str,
+        mut predictions: Vec<TokenPrediction>,
+        options: &DiacriticsOptions,
+    ) -> Self {
+        let warnings = align_predictions(clean_text, &mut predictions);
+        let replacements = replacements(clean_text, options);
+        retain_unreplaced(&mut predictions, &replacements);
+        let (text, spans) = render(clean_text, &predictions, &replacements, options);
+        Self {
+            text,
+            predictions,
+            spans,
+            warnings,
+            #[cfg(feature = "fallback")]
+            fallback_words: Vec::new(),
+        }
+    }
+}
+
+/// Vocalized text along with its phoneme transcription, see
+/// [`PhonikudModel::vocalize_with_phonemes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VocalizedResult {
+    pub text: String,
+    /// IPA transcription of `text`, see [`crate::phonemize::phonemize`]
+    pub phonemes: String,
+    /// Every whitespace delimited word, in order
+    pub words: Vec<WordInfo>,
+}
+
+/// A word of a [`VocalizedResult`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WordInfo {
+    /// Byte range of the word in the vocalized text
+    pub range: Range<usize>,
+    /// Byte range of the word in the input text
+    pub input_range: Range<usize>,
+    /// IPA transcription of the word
+    pub phonemes: String,
+}
+
+/// A word vocalized by [`PhonikudModel::vocalize_word`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VocalizedWord {
+    pub text: String,
+    /// Index of the stressed letter among the chars of the word without nikud, whether
+    /// or not the stress mark is rendered
+    pub stress: Option<usize>,
+}
+
+/// A word split into its prefixes (e.g. `ו`, `ש`, `ב`) and stem
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WordSegmentation {
+    /// Byte range of the word in the input text
+    pub range: Range<usize>,
+    /// Byte range of every prefix in the input text, in order
+    pub prefixes: Vec<Range<usize>>,
+    /// Byte range of the rest of the word
+    pub stem: Range<usize>,
+}
+
+/// Reconstruct the vocalized Hebrew string from the predictions over `clean_text`.
+///
+/// Text between the predicted letters is copied as is, except for the sorted,
+/// non overlapping `replacements` which are substituted. Also returns the byte
+/// range each prediction (letter and marks) covers in the output.
+pub(crate) fn render(
+    clean_text: &str,
+    predictions: &[TokenPrediction],
+    replacements: &[(Range<usize>, &str)],
+    options: &DiacriticsOptions,
+) -> (String, Vec<Range<usize>>) {
+    let mut result = String::with_capacity(clean_text.len() * 2);
+    let mut spans = Vec::with_capacity(predictions.len());
+    render_into(clean_text, predictions, replacements, options, &mut result, &mut spans);
+    (result, spans)
+}
+
+/// Like [`render`], appending to `result` and `spans`, with spans relative to `result`
+fn render_into(
+    clean_text: &str,
+    predictions: &[TokenPrediction],
+    replacements: &[(Range<usize>, &str)],
+    options: &DiacriticsOptions,
+    result: &mut String,
+    spans: &mut Vec<Range<usize>>,
+) {
+    let base = result.len();
+    let mut prev_index = 0;
+    let mut replacements = replacements.iter().peekable();
+
+    let mut replace_until = |result: &mut String, prev_index: &mut usize, until: usize| {
+        while let Some((range, replacement)) =
+            replacements.next_if(|(range, _)| range.start < until)
+        {
+            result.push_str(&clean_text[*prev_index..range.start]);
+            result.push_str(replacement);
+            *prev_index = range.end;
+        }
+    };
+
+    let stressed = stressed_letters(predictions, options.stress_placement);
+    for (idx, (prediction, &stressed)) in predictions.iter().zip(&stressed).enumerate() {
+        // Taken once the stress mark is written
+        let mut stress_mark = options.stress_mark.as_deref().filter(|_| stressed);
+        // Taken once it replaces the shva
+        let mut shva_replacement = options
+            .vocal_shva_style
+            .replacement()
+            .filter(|_| prediction.vocal_shva);
+
+        // Add anything we missed
+        replace_until(result, &mut prev_index, prediction.start);
+        result.push_str(&clean_text[prev_index..prediction.start]);
+        prev_index = prediction.end;
+        let span_start = result.len();
+
+        let char = prediction.char;
+        if options.ktiv_haser && prediction.is_matres_lectionis() && is_matres(char) {
+            spans.push(span_start..span_start);
+            continue;
+        }
+        result.push(char);
+
+        // Add shin/sin dot if it's a shin
+        if options.shin_dot
+            && let Some(shin_mark) = &prediction.shin
+        {
+            result.push_str(shin_mark);
+        }
+
+        // Handle matres lectionis
+        if prediction.is_matres_lectionis() {
+            // If no mark specified, skip adding anything for matres lectionis.
+            // Don't allow matres on irrelevant letters
+            if is_matres(char)
+                && let Some(mark) = &options.mark_matres_lectionis
+            {
+                result.push_str(mark);
+            }
+        } else if options.nikud {
+            let mut nikud = qamats_qatan(&prediction.nikud, &options.qamats_qatan);
+            // Shuruk is a vowel rather than a dagesh, it stays
+            let shuruk = char == 'ו' && nikud.chars().eq([DAGESH]);
+            if !options.dagesh && !shuruk && nikud.contains(DAGESH) {
+                nikud = Cow::Owned(nikud.replace(DAGESH, ""));
+            }
+            if let Some(replacement) = shva_replacement.take_if(|_| nikud.contains(SHVA)) {
+                nikud = Cow::Owned(nikud.replace(SHVA, replacement));
+            }
+            let nikud = nikud.as_ref();
+            let before_vowel = options.stress_placement == StressPlacement::BeforeVowel;
+            match stress_mark.take_if(|_| before_vowel) {
+                Some(mark) => {
+                    // The dagesh belongs to the consonant, the stress goes right before the vowel
+                    let vowel_start = nikud.strip_prefix(DAGESH).map_or(0, |_| DAGESH.len_utf8());
+                    result.push_str(&nikud[..vowel_start]);
+                    result.push_str(mark);
+                    result.push_str(&nikud[vowel_start..]);
+                }
+                None => result.push_str(nikud),
+            }
+        }
+
+        // Add stress mark
+        if options.stress_placement != StressPlacement::AfterCluster
+            && let Some(mark) = stress_mark.take()
+        {
+            result.push_str(mark);
+        }
+
+        // Add vocal shva mark, or the replacement of a shva the letter didn't have
+        let vocal_shva_mark = match options.vocal_shva_style {
+            VocalShvaStyle::Marked => options.vocal_shva_mark.as_deref(),
+            _ => shva_replacement,
+        };
+        if prediction.vocal_shva
+            && let Some(mark) = vocal_shva_mark
+        {
+            result.push_str(mark);
+        }
+
+        // Or after the whole cluster
+        if let Some(mark) = stress_mark {
+            result.push_str(mark);
+        }
+
+        // The rest of the letter's grapheme cluster, such as the taamim kept with
+        // `keep_taamim` or a combining mark of another script, stays before the prefix mark
+        let cluster = clean_text[prediction.start..].graphemes(true).next();
+        let cluster_end = prediction.start + cluster.map_or(0, str::len);
+        let next_start = predictions
+            .get(idx + 1)
+            .map_or(clean_text.len(), |next| next.start);
+        let carried = cluster_end.min(next_start).max(prev_index);
+        result.push_str(&clean_text[prev_index..carried]);
+        prev_index = carried;
+
+        // Add prefix mark, only between letters where `remove_nikud` takes it out again
+        let before_letter = clean_text[prev_index..].starts_with(is_hebrew_letter);
+        let after_letter = result.chars().rev().find(|&ch| !is_mark(ch));
+        if prediction.prefix
+            && before_letter
+            && after_letter.is_some_and(is_hebrew_letter)
+            && let Some(mark) = &options.prefix_mark
+        {
+            result.push_str(mark);
+        }
+
+        spans.push(span_start..result.len());
+    }
+
+    // Add any remaining text
+    replace_until(result, &mut prev_index, clean_text.len());
+    result.push_str(&clean_text[prev_index..]);
+
+    // Reordering keeps every mark after its letter, so the spans stay valid
+    let mark_order = match options.mark_order {
+        MarkOrder::Model if options.keep_taamim => MarkOrder::Hebrew,
+        order => order,
+    };
+    if mark_order != MarkOrder::Model {
+        let reordered = reorder_marks(&result[base..], mark_order);
+        result.truncate(base);
+        result.push_str(&reordered);
+    }
+}
+
+/// Write the qamats qatan of `nikud` as `style` asks
+fn qamats_qatan<'a>(nikud: &'a str, style: &QamatsQatan) -> Cow<'a, str> {
+    if !nikud.contains(QAMATS_QATAN) {
+        return Cow::Borrowed(nikud);
+    }
+    match style {
+        QamatsQatan::Dedicated => Cow::Borrowed(nikud),
+        QamatsQatan::Qamats => Cow::Owned(nikud.replace(QAMATS_QATAN, QAMATS)),
+        QamatsQatan::Marked(marker) => {
+            Cow::Owned(nikud.replace(QAMATS_QATAN, &format!("{QAMATS}{marker}")))
+        }
+    }
+}
+
+/// Whether the stress mark goes on each prediction under `placement`.
+///
+/// With [`StressPlacement::VowelLetter`] the stress of a consonant whose vowel
+/// is a following holam male or shuruk moves to that vav.
+fn stressed_letters(predictions: &[TokenPrediction], placement: StressPlacement) -> Vec<bool> {
+    let mut stressed: Vec<bool> = predictions.iter().map(|prediction| prediction.stress).collect();
+    if placement != StressPlacement::VowelLetter {
+        return stressed;
+    }
+
+    for idx in 0..predictions.len().saturating_sub(1) {
+        let (letter, next) = (&predictions[idx], &predictions[idx + 1]);
+        let has_vowel = letter.nikud.chars().any(|mark| mark != DAGESH);
+        // Holam male or shuruk
+        let vowel_vav = next.char == 'ו' && matches!(next.nikud.as_str(), "\u{05b9}" | "\u{05bc}");
+        if stressed[idx] && !has_vowel && vowel_vav && next.start == letter.end {
+            stressed[idx] = false;
+            stressed[idx + 1] = true;
+        }
+    }
+    stressed
+}
+
+/// Words copied or substituted instead of rendering their predictions, sorted and non overlapping
+pub(crate) fn replacements<'a>(
+    text: &'a str,
+    options: &'a DiacriticsOptions,
+) -> Vec<(Range<usize>, &'a str)> {
+    let mut replacements = options
+        .lexicon
+        .as_ref()
+        .map(|lexicon| lexicon.matches(text))
+        .unwrap_or_default();
+
+    if options.acronyms != AcronymMode::Vocalize {
+        let acronyms = acronym_ranges(text);
+        replacements.extend(acronyms.into_iter().map(|range| (range.clone(), &text[range])));
+        replacements.sort_by_key(|(range, _)| range.start);
+        replacements.dedup_by(|next, prev| next.0.start < prev.0.end);
+    }
+
+    replacements
+}
+
+/// Put every prediction on the char of `clean_text` holding its start, returning the
+/// warnings of those dropped, see [`Vocalization::render`]
+fn align_predictions(
+    clean_text: &str,
+    predictions: &mut Vec<TokenPrediction>,
+) -> Vec<DecodeWarning> {
+    let chars = CharOffsets::new(clean_text);
+    let mut prev_end = 0;
+    let mut warnings = Vec::new();
+    predictions.retain_mut(|prediction| {
+        let start = chars.floor(prediction.start);
+        if start < prev_end || start >= clean_text.len() {
+            warnings.push(DecodeWarning {
+                kind: WarningKind::DroppedPrediction,
+                range: prediction.start..prediction.end.max(prediction.start),
+            });
+            return false;
+        }
+        if start != prediction.start {
+            prediction.char = clean_text[start..].chars().next().unwrap_or(prediction.char);
+        }
+        prediction.start = start;
+        prediction.end = chars.ceil(start + 1);
+        prev_end = prediction.end;
+        true
+    });
+    warnings
+}
+
+/// Drop the predictions of letters inside replaced words
+pub(crate) fn retain_unreplaced(
+    predictions: &mut Vec<TokenPrediction>,
+    replacements: &[(Range<usize>, &str)],
+) {
+    if replacements.is_empty() {
+        return;
+    }
+    predictions.retain(|prediction| !is_replaced(prediction.start, replacements));
+}
+
+/// Whether the char at `start` is inside a replaced word
+fn is_replaced(start: usize, replacements: &[(Range<usize>, &str)]) -> bool {
+    let idx = replacements.partition_point(|(range, _)| range.end <= start);
+    replacements
+        .get(idx)
+        .is_some_and(|(range, _)| range.start <= start)
+}
+
+/// Maps increasing offsets of the clean text to the output of [`render`]
+struct RenderedOffsets<'a> {
+    predictions: &'a [TokenPrediction],
+    spans: &'a [Range<usize>],
+    replacements: &'a [(Range<usize>, &'a str)],
+    /// Predictions and replacements before the last offset mapped
+    prediction: usize,
+    replacement: usize,
+    /// Bytes the output grew by until the last offset mapped
+    delta: isize,
+}
+
+impl<'a> RenderedOffsets<'a> {
+    fn new(
+        predictions: &'a [TokenPrediction],
+        spans: &'a [Range<usize>],
+        replacements: &'a [(Range<usize>, &'a str)],
+    ) -> Self {
+        Self {
+            predictions,
+            spans,
+            replacements,
+            prediction: 0,
+            replacement: 0,
+            delta: 0,
+        }
+    }
+
+    /// The output offset of `offset`, which can't be inside a letter or a replacement
+    fn map(&mut self, offset: usize) -> usize {
+        while let Some(prediction) = self
+            .predictions
+            .get(self.prediction)
+            .filter(|prediction| prediction.start < offset)
+        {
+            let rendered = self.spans[self.prediction].len();
+            self.delta += rendered as isize - (prediction.end - prediction.start) as isize;
+            self.prediction += 1;
+        }
+        while let Some((range, replacement)) = self
+            .replacements
+            .get(self.replacement)
+            .filter(|(range, _)| range.start < offset)
+        {
+            self.delta += replacement.len() as isize - range.len() as isize;
+            self.replacement += 1;
+        }
+        offset.saturating_add_signed(self.delta)
+    }
+}
+
+/// Byte ranges of the whitespace delimited words of `text`
+fn whitespace_words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+    for (idx, ch) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if !ch.is_whitespace() {
+            word_start.get_or_insert(idx);
+        } else if let Some(start) = word_start.take() {
+            words.push(start..idx);
+        }
+    }
+    words
+}
+
+/// Move prediction offsets from the clean text to the original text
+fn map_offsets(predictions: &mut [TokenPrediction], offset_map: &[usize]) {
+    for prediction in predictions {
+        prediction.start = offset_map[prediction.start];
+        prediction.end = prediction.start + prediction.char.len_utf8();
+    }
+}
+
+/// Like [`map_offsets`] for the letters of the warnings
+fn map_warning_offsets(warnings: &mut [DecodeWarning], offset_map: &[usize]) {
+    for warning in warnings {
+        let len = warning.range.len();
+        warning.range.start = offset_map[warning.range.start];
+        warning.range.end = warning.range.start + len;
+    }
+}
+
+/// Split every run of Hebrew letters and nikud in `text`, along with the geresh and
+/// gershayim between its letters, after the letters ending a prefix
+fn segment_words(text: &str, predictions: &[TokenPrediction]) -> Vec<WordSegmentation> {
+    let mut words = Vec::new();
+    let mut predictions = predictions.iter().peekable();
+    let mut word_start = None;
+
+    let is_word_char = |ch: char| is_hebrew_letter(ch) || is_nikud(ch) && ch != '|';
+    let mut chars = text.char_indices().chain(std::iter::once((text.len(), ' '))).peekable();
+    while let Some((idx, ch)) = chars.next() {
+        // A geresh or gershayim between letters, as in `ז'אנר` or `צה"ל`, is inside the word
+        let inside_word = word_start.is_some()
+            && is_geresh_form(ch)
+            && chars.peek().is_some_and(|&(_, next)| is_hebrew_letter(next));
+        if is_word_char(ch) || inside_word {
+            word_start.get_or_insert(idx);
+            continue;
+        }
+        let Some(start) = word_start.take() else {
+            continue;
+        };
+
+        let mut prefixes = Vec::new();
+        let mut stem_start = start;
+        let mut prefix_end = false;
+        while let Some(prediction) = predictions.next_if(|prediction| prediction.start < idx) {
+            if prediction.start < start {
+                continue;
+            }
+            // The prefix ends with the marks of its last letter, right before the next letter
+            if prefix_end {
+                prefixes.push(stem_start..prediction.start);
+                stem_start = prediction.start;
+            }
+            prefix_end = prediction.prefix;
+        }
+        words.push(WordSegmentation {
+            range: start..idx,
+            prefixes,
+            stem: stem_start..idx,
+        });
+    }
+
+    words
+}
+
+/// Lock the disk cache, even if another worker panicked while holding it
+#[cfg(feature = "disk-cache")]
+fn lock(cache: &std::sync::Mutex<DiskCache>) -> std::sync::MutexGuard<'_, DiskCache> {
+    cache.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Expand `span` to the whitespace delimited word around it
+pub(crate) fn word_range(text: &str, span: Range<usize>) -> Range<usize> {
+    let start = text[..span.start]
+        .rfind(char::is_whitespace)
+        .map(|idx| idx + text[idx..].chars().next().unwrap().len_utf8())
+        .unwrap_or(0);
+    let end = text[span.end..]
+        .find(char::is_whitespace)
+        .map(|idx| span.end + idx)
+        .unwrap_or(text.len());
+    start..end
+}
+
+/// Make sure the model takes the tokenizer outputs and produces the three heads.
+///
+/// Returns the index of each head among the outputs, looked up by name. Models
+/// exported without output names fall back to the order of the 1.0 export.
+fn validate_signature(
+    inputs: &[TensorInfo],
+    outputs: &[TensorInfo],
+    classes: &ClassVocab,
+) -> Result<[usize; 3]> {
+    let incompatible = |reason: String| {
+        let list = |infos: &[TensorInfo]| {
+            infos.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        };
+        PhonikudError::IncompatibleModel(format!(
+            "{reason}; found inputs [{}] and outputs [{}]",
+            list(inputs),
+            list(outputs)
+        ))
+    };
+
+    for name in INPUT_NAMES {
+        match inputs.iter().find(|input| input.name == name) {
+            Some(input) if input.shape.len() == 2 => {}
+            Some(input) => {
+                return Err(incompatible(format!("input {input} is not [batch, sequence]")));
+            }
+            None => return Err(incompatible(format!("missing input {name}"))),
+        }
+    }
+
+    let by_name = OUTPUT_NAMES.map(|name| outputs.iter().position(|output| output.name == name));
+    let order = match by_name {
+        [Some(nikud), Some(shin), Some(additional)] => [nikud, shin, additional],
+        [None, None, None] if outputs.len() == 3 => [0, 1, 2],
+        _ => {
+            let missing: Vec<&str> = OUTPUT_NAMES
+                .iter()
+                .zip(by_name)
+                .filter(|(_, idx)| idx.is_none())
+                .map(|(name, _)| *name)
+                .collect();
+            return Err(incompatible(format!("missing outputs {}", missing.join(", "))));
+        }
+    };
+
+    let classes = [classes.nikud.len(), classes.shin.len(), ADDITIONAL_CLASSES];
+    for (head, (&idx, classes)) in order.iter().zip(classes).enumerate() {
+        let output = &outputs[idx];
+        let valid = match output.shape.as_slice() {
+            [_, _, Some(n)] => *n >= classes,
+            [_, _, None] => true,
+            _ => false,
+        };
+        if !valid {
+            return Err(incompatible(format!(
+                "output {output} for {} is not [batch, sequence, {classes}]",
+                OUTPUT_NAMES[head]
+            )));
+        }
+    }
+
+    Ok(order)
+}
+
+fn load_tokenizer_file(path: &str) -> Result<Tokenizer> {
+    Tokenizer::from_file(path).map_err(PhonikudError::TokenizerLoad)
+}
+
+fn load_tokenizer_bytes(json: &[u8]) -> Result<Tokenizer> {
+    Tokenizer::from_bytes(json).map_err(PhonikudError::TokenizerLoad)
+}
+
+/// Turn off the truncation and padding of `tokenizer`, returning what they were.
+///
+/// Truncation silently drops the tail of long chunks, and padding every encoding
+/// to a fixed length wastes compute. Batches are padded by [`PhonikudModel`] instead.
+fn untruncated(tokenizer: Arc<Tokenizer>) -> Result<(Arc<Tokenizer>, TokenizerSettings)> {
+    let settings = TokenizerSettings {
+        truncation: tokenizer.get_truncation().cloned(),
+        padding: tokenizer.get_padding().cloned(),
+        special_tokens: tokenizer
+            .get_post_processor()
+            .map_or(0, |processor| processor.added_tokens(false)),
+    };
+    if settings.truncation.is_none() && settings.padding.is_none() {
+        return Ok((tokenizer, settings));
+    }
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        truncation = ?settings.truncation.as_ref().map(|truncation| truncation.max_length),
+        padding = settings.padding.is_some(),
+        "tokenizer.json truncates or pads its inputs, turning it off"
+    );
+
+    let mut tokenizer = Arc::unwrap_or_clone(tokenizer);
+    tokenizer
+        .with_truncation(None)
+        .map_err(PhonikudError::TokenizerLoad)?
+        .with_padding(None);
+    Ok((Arc::new(tokenizer), settings))
+}
+
+pub(crate) fn sigmoid(logit: f32) -> f32 {
+    1.0 / (1.0 + (-logit).exp())
+}
+
+/// Softmax probability of `scores[idx]`
+pub(crate) fn softmax_probability(scores: &[f32], idx: usize) -> f32 {
+    let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = scores.iter().map(|&score| (score - max).exp()).sum();
+    (scores[idx] - max).exp() / sum
+}
+
+/// Make sure a logits output is `[rows, seq_len, classes]` so indexing it can't panic
+fn check_logits_shape(
+    idx: usize,
+    logits: &ArrayViewD<f32>,
+    rows: usize,
+    seq_len: usize,
+    classes: usize,
+) -> Result<()> {
+    match logits.shape() {
+        &[batch, len, n] if batch == rows && len >= seq_len && n >= classes => Ok(()),
+        shape => Err(PhonikudError::InvalidModelOutputs(format!(
+            "output {idx} has shape {shape:?}, expected [{rows}, {seq_len}, {classes}]"
+        ))),
+    }
+}
+
+/// Index of the highest score, the first one on ties. NaN never wins.
+fn argmax(scores: &[f32]) -> usize {
+    let mut best = 0;
+    let mut best_score = f32::NEG_INFINITY;
+    for (idx, &score) in scores.iter().enumerate() {
+        if score > best_score {
+            best = idx;
+            best_score = score;
+        }
+    }
+    best
+}
+
+/// Stress, vocal shva and prefix
+const ADDITIONAL_CLASSES: usize = 3;
+pub(crate) const INPUT_NAMES: [&str; 3] = ["input_ids", "attention_mask", "token_type_ids"];
+const OUTPUT_NAMES: [&str; 3] = ["nikud_logits", "shin_logits", "additional_logits"];
+pub(crate) const DAGESH: char = '\u{05bc}';
+const QAMATS: &str = "\u{05b8}";
+const SHVA: char = '\u{05b0}';
+const QAMATS_QATAN: &str = "\u{05c7}";
+pub(crate) const MAT_LECT_TOKEN: &str = "<MAT_LECT>";
+/// Maximum sequence length of the model, including the [CLS] and [SEP] tokens
+const MAX_SEQ_LEN: usize = 512;
+/// The tokenizer is character level, so a chunk of this many chars always fits
+pub(crate) const MAX_CHUNK_CHARS: usize = MAX_SEQ_LEN - 2;
+/// Sequence lengths used by [`crate::Phonikud::warmup`]
+pub(crate) const WARMUP_LENGTHS: [usize; 3] = [16, 128, MAX_CHUNK_CHARS];
+const WARMUP_TEXT: &str = "שלום עולם, מה שלומך היום? ";
+/// Where a chunk ends when no sentence ends in it
+const CLAUSE_TERMINATORS: &[char] = &[':', ';'];
+
+/// The text [`PhonikudModel::vocalize`] sends to the model for `text`
+fn model_text(text: &str, options: &DiacriticsOptions) -> String {
+    let text = match has_presentation_forms(text, options) {
+        true => Cow::Owned(normalize_presentation_forms(text).text),
+        false => Cow::Borrowed(text),
+    };
+    let text = match respelled(&text, options) {
+        Some(respelled) => Cow::Owned(respelled.text),
+        None => text,
+    };
+    let text = match &options.acronyms {
+        AcronymMode::Expand(acronyms) => Cow::Owned(expand_acronyms(&text, acronyms).text),
+        _ => text,
+    };
+    let mut clean_text = String::with_capacity(text.len());
+    remove_nikud_into(&text, &mut clean_text);
+    let input = ModelInput::new(&clean_text, options.fix_final_forms);
+    if options.hebrew_runs_only {
+        pack_hebrew_runs(input.text()).0
+    } else {
+        input.text().to_string()
+    }
+}
+
+/// Whether `text` is normalized before inference, see
+/// [`DiacriticsOptions::normalize_presentation_forms`]
+fn has_presentation_forms(text: &str, options: &DiacriticsOptions) -> bool {
+    options.normalize_presentation_forms && text.chars().any(is_presentation_form)
+}
+
+/// `text` with the typing variants of [`DiacriticsOptions::respell`] fixed, `None` when
+/// it's kept as written
+fn respelled(text: &str, options: &DiacriticsOptions) -> Option<Respelled> {
+    let respelled = respell(text, options.respell.as_ref()?);
+    (!respelled.fixes.is_empty()).then_some(respelled)
+}
+
+/// Map the offsets of a vocalization of a normalized copy of `text` back to `text`, with
+/// the `original_offset` of the normalization.
+///
+/// A letter of an expansion or decomposed char points at the first char it replaced, a
+/// precomposed letter is covered whole and an added letter covers the char following it.
+fn map_to_original(
+    vocalization: &mut Vocalization,
+    text: &str,
+    original_offset: impl Fn(usize) -> usize,
+) {
+    let original = |range: Range<usize>| {
+        let start = original_offset(range.start);
+        let char_len = text[start..].chars().next().map_or(0, char::len_utf8);
+        start..original_offset(range.end).max(start + char_len)
+    };
+    for prediction in &mut vocalization.predictions {
+        let range = original(prediction.start..prediction.end);
+        prediction.start = range.start;
+        prediction.end = range.end;
+    }
+    for warning in &mut vocalization.warnings {
+        warning.range = original(warning.range.clone());
+    }
+}
+
+/// `clean_text` the way it's sent to the model: with its final letters fixed when asked
+/// and its geresh and gershayim normalized. The letters keep their offsets.
+struct ModelInput<'a> {
+    clean_text: &'a str,
+    letters: Cow<'a, str>,
+    protected: Option<Normalized>,
+}
+
+impl<'a> ModelInput<'a> {
+    /// `fix_finals` as [`DiacriticsOptions::fix_final_forms`]
+    fn new(clean_text: &'a str, fix_finals: bool) -> Self {
+        let fixed = fix_finals
+            .then(|| fix_final_forms(clean_text))
+            .filter(|fixed| !fixed.fixes.is_empty());
+        let letters = fixed.map_or(Cow::Borrowed(clean_text), |fixed| Cow::Owned(fixed.text));
+        let protected = protect_geresh(&letters);
+        Self {
+            clean_text,
+            letters,
+            protected,
+        }
+    }
+
+    /// The text the model is run on
+    fn text(&self) -> &str {
+        self.protected
+            .as_ref()
+            .map_or(&self.letters, |protected| &protected.text)
+    }
+
+    /// Map a byte offset of [`Self::text`] back to the clean text
+    fn original_offset(&self, offset: usize) -> usize {
+        self.protected
+            .as_ref()
+            .map_or(offset, |protected| protected.original_offset(offset))
+    }
+
+    /// Move the predictions and warnings made over [`Self::text`] back onto the clean
+    /// text, applying the orthography constraints first when given the `constraints`
+    /// classes
+    fn restore(
+        &self,
+        predictions: &mut [TokenPrediction],
+        warnings: &mut [DecodeWarning],
+        constraints: Option<&ClassVocab>,
+    ) {
+        if let Some(classes) = constraints {
+            constraints::apply(self.text(), predictions, classes);
+        }
+        if let Some(protected) = &self.protected {
+            restore_geresh(protected, predictions, warnings);
+        }
+        if let Cow::Owned(_) = self.letters {
+            restore_letters(self.clean_text, predictions);
+        }
+    }
+}
+
+/// Put the letters of `clean_text` back into predictions made over a copy of it with its
+/// final letters fixed, the letters being at the same offsets
+fn restore_letters(clean_text: &str, predictions: &mut [TokenPrediction]) {
+    for prediction in predictions {
+        if let Some(ch) = clean_text[prediction.start..].chars().next() {
+            prediction.char = ch;
+        }
+    }
+}
+
+/// `clean_text` with its geresh and gershayim written the way the model knows them,
+/// `None` when they already are, see [`normalize_geresh`]
+fn protect_geresh(clean_text: &str) -> Option<Normalized> {
+    if !clean_text.chars().any(is_geresh_form) {
+        return None;
+    }
+    let protected = normalize_geresh(clean_text);
+    (!protected.alignments.is_empty()).then_some(protected)
+}
+
+/// Move the predictions and warnings made over `protected.text` back to the text it was
+/// made from. Only punctuation is replaced, so the letters keep their length.
+fn restore_geresh(
+    protected: &Normalized,
+    predictions: &mut [TokenPrediction],
+    warnings: &mut [DecodeWarning],
+) {
+    for prediction in predictions {
+        prediction.start = protected.original_offset(prediction.start);
+        prediction.end = prediction.start + prediction.char.len_utf8();
+    }
+    for warning in warnings {
+        let len = warning.range.len();
+        warning.range.start = protected.original_offset(warning.range.start);
+        warning.range.end = warning.range.start + len;
+    }
+}
+
+/// Join the Hebrew runs of `clean_text` with a space, remembering where each one came from.
+///
+/// Returns the packed text and the start of every run in it and in `clean_text`.
+fn pack_hebrew_runs(clean_text: &str) -> (String, Vec<(usize, usize)>) {
+    let mut packed = String::with_capacity(clean_text.len());
+    let mut runs = Vec::new();
+    for run in hebrew_runs(clean_text) {
+        if !packed.is_empty() {
+            packed.push(' ');
+        }
+        runs.push((packed.len(), run.start));
+        packed.push_str(&clean_text[run]);
+    }
+    (packed, runs)
+}
+
+/// Byte ranges of the runs of whitespace delimited words containing a Hebrew letter.
+///
+/// Words without one, such as English names, numbers and URLs, separate the runs.
+fn hebrew_runs(text: &str) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut word_start = None;
+    // Whether the words since the end of the last run are all whitespace
+    let mut contiguous = false;
+
+    for (idx, ch) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if !ch.is_whitespace() {
+            word_start.get_or_insert(idx);
+            continue;
+        }
+        let Some(start) = word_start.take() else {
+            continue;
+        };
+        if !text[start..idx].chars().any(is_hebrew_letter) {
+            contiguous = false;
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if contiguous => run.end = idx,
+            _ => runs.push(start..idx),
+        }
+        contiguous = true;
+    }
+
+    runs
+}
+
+/// Byte ranges of the whitespace delimited words that contain nikud or phonikud marks,
+/// taamim alone don't count with [`DiacriticsOptions::keep_taamim`]
+fn vocalized_word_ranges(text: &str, options: &DiacriticsOptions) -> Vec<Range<usize>> {
+    let categories = if options.keep_taamim {
+        ALL_BUT_TAAMIM
+    } else {
+        MarkCategories::ALL
+    };
+    let mut ranges = Vec::new();
+    let mut word_start = None;
+
+    for (idx, ch) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if !ch.is_whitespace() {
+            word_start.get_or_insert(idx);
+        } else if let Some(start) = word_start.take()
+            && has_marks(&text[start..idx], categories)
+        {
+            ranges.push(start..idx);
+        }
+    }
+
+    ranges
+}
+
+/// Chars per chunk under `truncation` of a tokenizer with `settings`, at most `limit`
+fn chunk_chars(
+    truncation: TruncationPolicy,
+    settings: &TokenizerSettings,
+    limit: Option<usize>,
+) -> usize {
+    let max = match (truncation, &settings.truncation) {
+        (TruncationPolicy::Chunk, Some(truncation)) => truncation
+            .max_length
+            .saturating_sub(settings.special_tokens)
+            .clamp(1, MAX_CHUNK_CHARS),
+        _ => MAX_CHUNK_CHARS,
+    };
+    limit.map_or(max, |limit| limit.min(max))
+}
+
+/// Split text into consecutive chunks of at most `max_chars` characters.
+///
+/// Chunks end at a sentence boundary when possible (see [`crate::segment`]), then
+/// at a clause or word boundary, and only as a last resort in the middle of a word.
+/// Concatenating the chunks always yields the original text.
+fn split_into_chunks(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    let sentence_ends = crate::segment::sentence_ends(text);
+
+    while rest.chars().count() > max_chars {
+        // Byte index right after the last char that still fits
+        let window_end = rest
+            .char_indices()
+            .nth(max_chars)
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        let window = &rest[..window_end];
+
+        let start = text.len() - rest.len();
+        let sentences = sentence_ends.partition_point(|&end| end <= start + window_end);
+        let split_at = sentence_ends[..sentences]
+            .last()
+            .map(|&end| end - start)
+            .filter(|&end| end > 0)
+            .map(|end| end + whitespace_len(&window[end..]))
+            .or_else(|| last_boundary(window, |c| CLAUSE_TERMINATORS.contains(&c)))
+            .or_else(|| last_boundary(window, char::is_whitespace))
+            .unwrap_or(window_end);
+
+        let (chunk, tail) = rest.split_at(split_at);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// Byte index right after the last char matching `pred`, trailing whitespace included
+fn last_boundary(window: &str, pred: impl Fn(char) -> bool) -> Option<usize> {
+    let (idx, ch) = window.char_indices().rev().find(|&(_, c)| pred(c))?;
+    let end = idx + ch.len_utf8();
+    Some(end + whitespace_len(&window[end..]))
+}
+
+fn whitespace_len(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{spelling::SpellingOptions, text::remove_nikud};
+
+    /// A WordPiece tokenizer merging `של`, `##ום`, `עו` and `##לם` into single tokens
+    fn merging_tokenizer() -> Tokenizer {
+        let vocab = [
+            "[UNK]", "[CLS]", "[SEP]", "של", "##ום", "עו", "##לם", "ש", "ל", "ו", "ם", "ע",
+        ];
+        let vocab: serde_json::Map<String, serde_json::Value> = vocab
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id.into()))
+            .collect();
+        let json = serde_json::json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [],
+            "normalizer": null,
+            "pre_tokenizer": {"type": "BertPreTokenizer"},
+            "post_processor": {
+                "type": "BertProcessing",
+                "sep": ["[SEP]", 2],
+                "cls": ["[CLS]", 1],
+            },
+            "decoder": null,
+            "model": {
+                "type": "WordPiece",
+                "unk_token": "[UNK]",
+                "continuing_subword_prefix": "##",
+                "max_input_chars_per_word": 100,
+                "vocab": vocab,
+            },
+        });
+        Tokenizer::from_str(&json.to_string()).unwrap()
+    }
+
+    /// Logits of `tokens` tokens, every score 0
+    fn zero_logits(tokens: usize, classes: &ClassVocab) -> [Array2<f32>; 3] {
+        [
+            Array2::zeros((tokens, classes.nikud.len())),
+            Array2::zeros((tokens, classes.shin.len())),
+            Array2::zeros((tokens, ADDITIONAL_CLASSES)),
+        ]
+    }
+
+    fn views(logits: &[Array2<f32>; 3]) -> [ArrayView2<'_, f32>; 3] {
+        [logits[0].view(), logits[1].view(), logits[2].view()]
+    }
+
+    #[test]
+    fn merged_tokens_are_encoded_letter_by_letter() {
+        let tokenizer = merging_tokenizer();
+        let text = "שלום עולם";
+        let merged = tokenizer.encode(text, true).unwrap();
+        assert_eq!(merged.get_tokens(), ["[CLS]", "של", "##ום", "עו", "##לם", "[SEP]"]);
+        assert!(letter_tokens(&merged, text).is_none());
+
+        let (encoding, letters) = encode_letters(&tokenizer, text).unwrap();
+        let expected: Vec<(usize, char)> = text
+            .char_indices()
+            .filter(|&(_, ch)| is_hebrew_letter(ch))
+            .collect();
+        let found: Vec<(usize, char)> = letters.iter().map(|l| (l.start, l.char)).collect();
+        assert_eq!(found, expected);
+        for letter in &letters {
+            assert_eq!(encoding.get_tokens()[letter.token], letter.char.to_string());
+        }
+
+        let classes = ClassVocab::default();
+        let logits = zero_logits(encoding.len(), &classes);
+        let predictions =
+            decode_letters(&classes, views(&logits), letters, &Thresholds::default()).unwrap();
+        let predicted: Vec<(usize, char)> =
+            predictions.iter().map(|p| (p.start, p.char)).collect();
+        assert_eq!(predicted, expected);
+    }
+
+    #[test]
+    fn argmax_prefers_the_lowest_index_on_ties() {
+        assert_eq!(argmax(&[1.0, 3.0, 3.0, 2.0]), 1);
+        assert_eq!(argmax(&[0.0, 0.0, 0.0]), 0);
+        assert_eq!(argmax(&[f32::NEG_INFINITY, f32::NEG_INFINITY]), 0);
+    }
+
+    #[test]
+    fn argmax_never_picks_nan() {
+        assert_eq!(argmax(&[f32::NAN, 1.0, f32::NAN, 2.0]), 3);
+        assert_eq!(argmax(&[-5.0, f32::NAN]), 0);
+        assert_eq!(argmax(&[f32::NAN, f32::NEG_INFINITY, -1.0]), 2);
+    }
+
+    #[test]
+    fn nan_logits_fail_to_decode() {
+        let classes = ClassVocab::default();
+        let letters = || {
+            vec![
+                Letter { token: 1, char: 'ש', start: 0 },
+                Letter { token: 2, char: 'ל', start: 2 },
+            ]
+        };
+        for head in 0..3 {
+            let mut logits = zero_logits(4, &classes);
+            logits[head][[2, 0]] = f32::NAN;
+            let result =
+                decode_letters(&classes, views(&logits), letters(), &Thresholds::default());
+            match result {
+                Err(PhonikudError::InvalidModelOutputs(message)) => {
+                    assert!(message.contains("byte 2"), "{message}")
+                }
+                other => panic!("expected InvalidModelOutputs, got {other:?}"),
+            }
+        }
+
+        // NaN scores of classes beyond the vocabulary are never read
+        let mut logits = zero_logits(4, &classes);
+        logits[0] = Array2::zeros((4, classes.nikud.len() + 1));
+        logits[0][[1, classes.nikud.len()]] = f32::NAN;
+        let predictions =
+            decode_letters(&classes, views(&logits), letters(), &Thresholds::default()).unwrap();
+        assert_eq!(predictions.len(), 2);
+    }
+
+    /// An encoding of tokens at the byte `offsets`, as byte level tokenizers make them
+    fn byte_level_encoding(offsets: &[(usize, usize)]) -> Encoding {
+        let tokens = offsets
+            .iter()
+            .enumerate()
+            .map(|(id, &offsets)| tokenizers::Token::new(id as u32, String::new(), offsets))
+            .collect();
+        Encoding::from_tokens(tokens, 0)
+    }
+
+    fn found_letters(encoding: &Encoding, text: &str) -> Option<Vec<(usize, char, usize)>> {
+        let letters = letter_tokens(encoding, text)?;
+        Some(letters.iter().map(|l| (l.token, l.char, l.start)).collect())
+    }
+
+    #[test]
+    fn byte_level_offsets_give_each_letter_its_first_token() {
+        // Every byte of `ש😀ל` its own token, between special tokens
+        let offsets = [(0, 0), (0, 1), (1, 2), (2, 4), (4, 6), (6, 7), (7, 8), (0, 0)];
+        let encoding = byte_level_encoding(&offsets);
+        assert_eq!(found_letters(&encoding, "ש😀ל").unwrap(), [(1, 'ש', 0), (5, 'ל', 6)]);
+
+        // A token across the end of one letter and the start of the next holds both
+        let encoding = byte_level_encoding(&[(0, 1), (1, 3), (3, 4)]);
+        assert!(found_letters(&encoding, "של").is_none());
+
+        // Across two emoji it holds no letter
+        let encoding = byte_level_encoding(&[(0, 3), (3, 5), (5, 8), (8, 10)]);
+        assert_eq!(found_letters(&encoding, "😀😀ש").unwrap(), [(3, 'ש', 8)]);
+    }
+
+    #[test]
+    fn predictions_inside_chars_are_moved_or_dropped() {
+        let clean_text = "ש😀ל";
+        let mut predictions = vec![
+            prediction('ש', 1),
+            prediction('ל', 3),
+            // Inside the emoji taken by the previous prediction
+            prediction('ל', 5),
+            prediction('ל', 6),
+            prediction('ל', 7),
+            prediction('ל', 8),
+        ];
+        let warnings = align_predictions(clean_text, &mut predictions);
+        let aligned: Vec<(usize, char, usize)> =
+            predictions.iter().map(|p| (p.start, p.char, p.end)).collect();
+        assert_eq!(aligned, [(0, 'ש', 2), (2, '😀', 6), (6, 'ל', 8)]);
+        let dropped = |range| DecodeWarning {
+            kind: WarningKind::DroppedPrediction,
+            range,
+        };
+        assert_eq!(warnings, [dropped(5..7), dropped(7..9), dropped(8..10)]);
+    }
+
+    #[test]
+    fn rendering_predictions_at_any_offsets_never_panics() {
+        let options = DiacriticsOptions::default();
+        for text in ["ש😀ל", "שָׁ|לוֹם 👍🏽", "צה\"ל\u{200f}א"] {
+            let clean_text = remove_nikud(text);
+            let starts = 0..clean_text.len() + 2;
+            // The char of a prediction on a char boundary is taken as is
+            let at = |start: usize| {
+                let char = clean_text.get(start..).and_then(|rest| rest.chars().next());
+                prediction(char.unwrap_or('ש'), start)
+            };
+            let pairs = starts.clone().flat_map(|a| starts.clone().map(move |b| (a, b)));
+            for (first, second) in pairs {
+                let predictions = vec![
+                    TokenPrediction {
+                        nikud: "\u{05b8}".to_string(),
+                        stress: true,
+                        ..at(first)
+                    },
+                    at(second),
+                ];
+                let vocalization = Vocalization::render(&clean_text, predictions, &options);
+                assert_eq!(remove_nikud(&vocalization.text), clean_text);
+                for prediction in &vocalization.predictions {
+                    assert!(clean_text.is_char_boundary(prediction.start));
+                    assert!(clean_text.is_char_boundary(prediction.end));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn predictions_over_a_protected_geresh_move_back() {
+        assert!(protect_geresh("ג'ירפה צה\"ל").is_none());
+        assert!(protect_geresh("שלום").is_none());
+
+        let text = "צה\u{2019}\u{2019}ל ז\u{05f3}אנר";
+        let protected = protect_geresh(text).unwrap();
+        assert_eq!(protected.text, "צה\"ל ז'אנר");
+        let letters = |text: &str| -> Vec<(usize, char)> {
+            text.char_indices().filter(|&(_, ch)| is_hebrew_letter(ch)).collect()
+        };
+        let mut predictions: Vec<TokenPrediction> = letters(&protected.text)
+            .into_iter()
+            .map(|(start, char)| prediction(char, start))
+            .collect();
+        // The lamed after the gershayim
+        let mut warnings = [DecodeWarning {
+            kind: WarningKind::NoToken,
+            range: 5..7,
+        }];
+        restore_geresh(&protected, &mut predictions, &mut warnings);
+        let restored: Vec<(usize, char)> = predictions.iter().map(|p| (p.start, p.char)).collect();
+        assert_eq!(restored, letters(text));
+        for prediction in &predictions {
+            assert_eq!(prediction.end, prediction.start + prediction.char.len_utf8());
+        }
+        assert_eq!(warnings[0].range, 10..12);
+    }
+
+    #[test]
+    fn model_input_predictions_land_on_the_clean_text() {
+        let clean_text = "שלומ צה\u{2019}\u{2019}ל";
+        let input = ModelInput::new(clean_text, true);
+        assert_eq!(input.text(), "שלום צה\"ל");
+        assert_eq!(ModelInput::new(clean_text, false).text(), "שלומ צה\"ל");
+        assert_eq!(ModelInput::new("שלום", true).text(), "שלום");
+
+        let letters = |text: &str| -> Vec<(usize, char)> {
+            text.char_indices().filter(|&(_, ch)| is_hebrew_letter(ch)).collect()
+        };
+        let mut predictions: Vec<TokenPrediction> = letters(input.text())
+            .into_iter()
+            .map(|(start, char)| prediction(char, start))
+            .collect();
+        input.restore(&mut predictions, &mut [], None);
+        let restored: Vec<(usize, char)> = predictions.iter().map(|p| (p.start, p.char)).collect();
+        assert_eq!(restored, letters(clean_text));
+    }
+
+    #[test]
+    fn model_text_normalizes_the_presentation_forms_like_vocalize() {
+        let text = "\u{fb2a}לום \u{fb4b}";
+        let options = DiacriticsOptions {
+            normalize_presentation_forms: true,
+            ..DiacriticsOptions::default()
+        };
+        assert_eq!(model_text(text, &options), "שלום ו");
+        let options = DiacriticsOptions {
+            normalize_presentation_forms: false,
+            ..options
+        };
+        assert_eq!(model_text(text, &options), text);
+    }
+
+    #[test]
+    fn model_text_is_respelled_when_asked() {
+        let text = "שלומ גדוווול";
+        let options = DiacriticsOptions {
+            respell: Some(SpellingOptions::default()),
+            ..DiacriticsOptions::default()
+        };
+        assert_eq!(model_text(text, &options), "שלום גדוול");
+        assert_eq!(model_text(text, &DiacriticsOptions::default()), text);
+    }
+
+    #[test]
+    fn chunk_limit_caps_the_chunks() {
+        let untruncated = TokenizerSettings {
+            truncation: None,
+            padding: None,
+            special_tokens: 2,
+        };
+        let truncated = TokenizerSettings {
+            truncation: Some(TruncationParams {
+                max_length: 66,
+                ..TruncationParams::default()
+            }),
+            padding: None,
+            special_tokens: 2,
+        };
+        let chunk = TruncationPolicy::Chunk;
+        assert_eq!(chunk_chars(chunk, &untruncated, None), MAX_CHUNK_CHARS);
+        assert_eq!(chunk_chars(chunk, &untruncated, Some(128)), 128);
+        assert_eq!(chunk_chars(chunk, &truncated, None), 64);
+        assert_eq!(chunk_chars(chunk, &truncated, Some(128)), 64);
+        assert_eq!(chunk_chars(chunk, &truncated, Some(16)), 16);
+        assert_eq!(chunk_chars(TruncationPolicy::Error, &truncated, None), MAX_CHUNK_CHARS);
+
+        let text = WARMUP_TEXT.repeat(40);
+        let unlimited = split_into_chunks(&text, chunk_chars(chunk, &untruncated, None));
+        let limited = split_into_chunks(&text, chunk_chars(chunk, &untruncated, Some(128)));
+        assert!(limited.len() > unlimited.len());
+        assert!(limited.iter().all(|chunk| chunk.chars().count() <= 128));
+        assert_eq!(limited.concat(), text);
+    }
+
+    /// A prediction of `char` at `start` with no marks
+    fn prediction(char: char, start: usize) -> TokenPrediction {
+        TokenPrediction {
+            char,
+            start,
+            end: start + char.len_utf8(),
+            nikud_class: 0,
+            confidence: 1.0,
+            nikud: String::new(),
+            shin_class: None,
+            shin: None,
+            stress: false,
+            vocal_shva: false,
+            prefix: false,
+            nikud_logits: Vec::new(),
+            shin_logits: Vec::new(),
+            additional_logits: [0.0; 3],
+        }
+    }
+
+    /// Hebrew letters and marks, the prefix marker, and the chars that must get
+    /// through rendering unchanged around them
+    fn text_char() -> impl Strategy<Value = char> {
+        let others = vec![
+            ' ', '\t', '\n', '\r', '|', '.', 'a', '׳', '״', '־', '׃', '\u{0301}', '\u{200c}',
+            '\u{200d}', '\u{200e}', '\u{200f}', '\u{202a}', '\u{202b}', '\u{202c}', '\u{202d}',
+            '\u{202e}', '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}', '\u{feff}', '😀', '👍',
+            '🏽',
+        ];
+        prop_oneof![
+            4 => prop::char::range('א', 'ת'),
+            2 => prop::char::range('\u{0591}', '\u{05c7}'),
+            2 => prop::sample::select(others),
+            1 => any::<char>(),
+        ]
+    }
+
+    /// Predicted marks of one letter, or none for a letter without a prediction
+    fn mocked_prediction() -> impl Strategy<Value = Option<(&'static str, bool, [bool; 3])>> {
+        let nikud = vec![
+            "",
+            "\u{05b0}",
+            "\u{05b4}",
+            "\u{05b7}",
+            "\u{05b8}",
+            "\u{05b9}",
+            "\u{05bc}",
+            "\u{05bc}\u{05b8}",
+            "\u{05c7}",
+            MAT_LECT_TOKEN,
+        ];
+        let marks = (prop::sample::select(nikud), any::<bool>(), any::<[bool; 3]>());
+        prop::option::weighted(0.9, marks)
+    }
+
+    fn mocked_options() -> impl Strategy<Value = DiacriticsOptions> {
+        let placements = vec![
+            StressPlacement::AfterVowel,
+            StressPlacement::BeforeVowel,
+            StressPlacement::AfterCluster,
+            StressPlacement::VowelLetter,
+        ];
+        let styles = vec![VocalShvaStyle::Marked, VocalShvaStyle::HatafSegol];
+        let orders = vec![MarkOrder::Model, MarkOrder::Nfc, MarkOrder::Hebrew];
+        (
+            prop::sample::select(placements),
+            prop::sample::select(styles),
+            prop::sample::select(orders),
+            any::<[bool; 4]>(),
+        )
+            .prop_map(|(stress_placement, vocal_shva_style, mark_order, flags)| {
+                let [nikud, dagesh, shin_dot, keep_taamim] = flags;
+                DiacriticsOptions {
+                    nikud,
+                    dagesh,
+                    shin_dot,
+                    keep_taamim,
+                    stress_placement,
+                    vocal_shva_style,
+                    mark_order,
+                    ..DiacriticsOptions::default()
+                }
+            })
+    }
+
+    /// The inputs of `fuzz/fuzz_targets/reconstruct.rs`, generated: predictions at any
+    /// byte of the text, inside multi-byte chars, overlapping or past its end
+    #[test]
+    fn predictions_off_char_boundaries_are_moved_or_dropped() {
+        let pool: Vec<char> =
+            "אבגשׁםת \u{05b0}\u{05b8}\u{05bc}|\u{0301}\u{200d}\u{200f}\u{202b}😀👍🏽'\""
+                .chars()
+                .collect();
+        let nikud = ["", "\u{05b0}", "\u{05b4}", "\u{05bc}\u{05b8}", MAT_LECT_TOKEN];
+        let placements = [
+            StressPlacement::AfterVowel,
+            StressPlacement::BeforeVowel,
+            StressPlacement::AfterCluster,
+            StressPlacement::VowelLetter,
+        ];
+        // xorshift, so every run checks the same inputs
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        for _ in 0..20_000 {
+            let text: String = (0..next(12)).map(|_| pool[next(pool.len())]).collect();
+            let clean_text = remove_nikud(&text);
+            let mut starts: Vec<usize> = (0..next(8)).map(|_| next(clean_text.len() + 3)).collect();
+            starts.sort();
+            let predictions: Vec<TokenPrediction> = starts
+                .iter()
+                .map(|&start| {
+                    let char = clean_text.get(start..).and_then(|rest| rest.chars().next());
+                    TokenPrediction {
+                        nikud: nikud[next(nikud.len())].to_string(),
+                        stress: next(2) == 0,
+                        vocal_shva: next(2) == 0,
+                        prefix: next(2) == 0,
+                        ..prediction(char.unwrap_or('ש'), start)
+                    }
+                })
+                .collect();
+            let options = DiacriticsOptions {
+                ktiv_haser: next(4) == 0,
+                mark_matres_lectionis: (next(2) == 0).then(|| "\u{05af}".to_string()),
+                stress_placement: placements[next(placements.len())],
+                mark_order: [MarkOrder::Model, MarkOrder::Hebrew][next(2)],
+                acronyms: if next(2) == 0 {
+                    AcronymMode::Protect
+                } else {
+                    AcronymMode::Vocalize
+                },
+                ..DiacriticsOptions::default()
+            };
+
+            let vocalization = Vocalization::render(&clean_text, predictions, &options);
+            vocalization.offset_mapping(&clean_text);
+            let dropped = vocalization.warnings.len();
+            assert!(vocalization.predictions.len() + dropped <= starts.len(), "{text:?}");
+            for prediction in &vocalization.predictions {
+                assert!(clean_text.is_char_boundary(prediction.start), "{text:?}");
+                assert!(clean_text[prediction.start..].starts_with(prediction.char), "{text:?}");
+            }
+            if !options.ktiv_haser {
+                assert_eq!(remove_nikud(&vocalization.text), clean_text, "{starts:?}");
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn removing_nikud_recovers_the_rendered_text(
+            text in prop::collection::vec(text_char(), 0..48).prop_map(String::from_iter),
+            mocked in prop::collection::vec(mocked_prediction(), 48),
+            options in mocked_options(),
+        ) {
+            let clean_text = remove_nikud(&text);
+            let letters = clean_text.char_indices().filter(|&(_, ch)| is_hebrew_letter(ch));
+            let predictions = letters
+                .zip(mocked)
+                .filter_map(|((start, char), mocked)| {
+                    let (nikud, sin, [stress, vocal_shva, prefix]) = mocked?;
+                    let shin = (char == 'ש').then_some(if sin { "\u{05c2}" } else { "\u{05c1}" });
+                    Some(TokenPrediction {
+                        nikud: nikud.to_string(),
+                        shin: shin.map(str::to_string),
+                        stress,
+                        vocal_shva,
+                        prefix,
+                        ..prediction(char, start)
+                    })
+                })
+                .collect();
+            let vocalization = Vocalization::render(&clean_text, predictions, &options);
+            prop_assert!(vocalization.warnings.is_empty());
+            prop_assert_eq!(remove_nikud(&vocalization.text), clean_text);
+        }
+    }
 }
@@ -0,0 +1,589 @@
+//! Expand numbers, times and dates into Hebrew words before diacritization
+
+use std::{ops::Range, sync::LazyLock};
+
+use regex::{Captures, Regex};
+use unicode_normalization::char::decompose_compatible;
+
+use crate::hebrew::{is_hebrew_letter, is_presentation_form};
+
+/// Grammatical gender of spelled out numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Gender {
+    Masculine,
+    /// Used when counting and for bare numbers
+    #[default]
+    Feminine,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizeOptions {
+    /// Gender of plain numbers, times are always feminine and days masculine
+    pub gender: Gender,
+    /// Expand `DD/MM/YYYY` and `DD.MM.YYYY` dates
+    pub dates: bool,
+    /// Expand `HH:MM` times
+    pub times: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            gender: Gender::Feminine,
+            dates: true,
+            times: true,
+        }
+    }
+}
+
+/// Normalized text with the position of every expansion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalized {
+    pub text: String,
+    /// Expanded spans in order, text outside them is unchanged
+    pub alignments: Vec<Alignment>,
+}
+
+/// A span of the input text replaced in the normalized text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alignment {
+    /// Byte range in the input text
+    pub original: Range<usize>,
+    /// Byte range of the replacement in the normalized text
+    pub normalized: Range<usize>,
+}
+
+impl Normalized {
+    /// Map a byte offset in the normalized text back to the input text.
+    ///
+    /// Offsets inside an expansion map to the start of the original span.
+    pub fn original_offset(&self, offset: usize) -> usize {
+        original_offset(&self.alignments, offset)
+    }
+}
+
+/// Map a byte offset in a normalized text back to the input text, given the `alignments`
+/// of the spans replaced in it, in order. Offsets inside a replacement map to the start
+/// of the span it replaced.
+pub(crate) fn original_offset<'a>(
+    alignments: impl IntoIterator<Item = &'a Alignment>,
+    offset: usize,
+) -> usize {
+    let mut shift = 0isize;
+    for alignment in alignments {
+        if offset < alignment.normalized.start {
+            break;
+        }
+        if offset < alignment.normalized.end {
+            return alignment.original.start;
+        }
+        shift = alignment.original.end as isize - alignment.normalized.end as isize;
+    }
+    (offset as isize + shift) as usize
+}
+
+static NUMBER_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"(?P<date>(?P<day>[0-9]{1,2})[./](?P<month>[0-9]{1,2})[./](?P<year>[0-9]{4}))",
+        r"|(?P<time>(?P<hour>[0-9]{1,2}):(?P<minute>[0-5][0-9]))",
+        r"|(?P<decimal>(?P<whole>[0-9]+)\.(?P<fraction>[0-9]+))",
+        r"|(?P<number>[0-9]{1,3}(?:,[0-9]{3})+|[0-9]+)(?P<percent>%?)",
+    ))
+    .unwrap()
+});
+
+const MONTHS: &[&str] = &[
+    "ינואר",
+    "פברואר",
+    "מרץ",
+    "אפריל",
+    "מאי",
+    "יוני",
+    "יולי",
+    "אוגוסט",
+    "ספטמבר",
+    "אוקטובר",
+    "נובמבר",
+    "דצמבר",
+];
+
+/// Largest number spelled out as a cardinal, longer digit runs are read digit by digit
+const MAX_CARDINAL: u64 = 999_999_999_999;
+
+/// Replace digits, times and dates in `text` with Hebrew words.
+///
+/// Digits next to a Latin letter, as in `mp3` or `4K`, belong to a name or code and are
+/// kept.
+pub fn normalize(text: &str, options: &NormalizeOptions) -> Normalized {
+    let mut result = String::with_capacity(text.len() * 2);
+    let mut alignments = Vec::new();
+    let mut prev_index = 0;
+
+    for captures in NUMBER_PATTERN.captures_iter(text) {
+        let original = captures.get(0).unwrap().range();
+        let before = text[..original.start].chars().next_back();
+        let after = text[original.end..].chars().next();
+        if before.is_some_and(|ch| ch.is_ascii_alphabetic())
+            || after.is_some_and(|ch| ch.is_ascii_alphabetic())
+        {
+            continue;
+        }
+        let Some(words) = expand(&captures, options) else {
+            continue;
+        };
+        result.push_str(&text[prev_index..original.start]);
+        let start = result.len();
+        result.push_str(&words);
+        alignments.push(Alignment {
+            normalized: start..result.len(),
+            original: original.clone(),
+        });
+        prev_index = original.end;
+    }
+    result.push_str(&text[prev_index..]);
+
+    Normalized {
+        text: result,
+        alignments,
+    }
+}
+
+/// Replace the Hebrew presentation forms of `text` with the letters and marks they stand
+/// for, e.g. `שׁ` (U+FB2A) with a shin and its shin dot and `ﭏ` with alef lamed.
+///
+/// The model only knows the standard letters, so precomposed letters copied from PDFs or
+/// typeset documents are otherwise left without nikud. Each replaced char gets an
+/// alignment.
+pub fn normalize_presentation_forms(text: &str) -> Normalized {
+    let mut result = String::with_capacity(text.len());
+    let mut alignments = Vec::new();
+    for (idx, ch) in text.char_indices() {
+        if !is_presentation_form(ch) {
+            result.push(ch);
+            continue;
+        }
+        let start = result.len();
+        decompose_compatible(ch, |decomposed| result.push(decomposed));
+        if result[start..].chars().eq([ch]) {
+            continue;
+        }
+        alignments.push(Alignment {
+            original: idx..idx + ch.len_utf8(),
+            normalized: start..result.len(),
+        });
+    }
+
+    Normalized {
+        text: result,
+        alignments,
+    }
+}
+
+/// Chars written for a geresh: the ASCII apostrophe, the Hebrew geresh, typographic
+/// apostrophes and the accents some keyboards put in their place
+pub(crate) const GERESH_FORMS: &[char] = &[
+    '\'', '\u{05f3}', '\u{2019}', '\u{2018}', '`', '\u{00b4}', '\u{02bc}',
+];
+/// Chars written for gershayim, two apostrophes aside
+pub(crate) const GERSHAYIM_FORMS: &[char] = &['"', '\u{05f4}', '\u{201d}', '\u{201c}'];
+
+/// Whether `ch` may stand for a geresh or gershayim, as in `ז'אנר` or `צה"ל`
+#[cfg(feature = "model")]
+pub(crate) fn is_geresh_form(ch: char) -> bool {
+    GERESH_FORMS.contains(&ch) || GERSHAYIM_FORMS.contains(&ch)
+}
+
+/// Write the geresh and gershayim of the Hebrew words of `text` the way the model was
+/// trained on, as an ASCII apostrophe and double quote, e.g. `ז׳אנר` as `ז'אנר` and
+/// `צה’’ל` as `צה"ל`.
+///
+/// A geresh follows a letter, gershayim stand between two letters, other quotes are left
+/// alone. The lookalikes copied from word processors and typeset documents are
+/// otherwise split from the word by the tokenizer or unknown to it, leaving the letters
+/// around them with a worse prediction or none. Each replaced char gets an alignment.
+pub fn normalize_geresh(text: &str) -> Normalized {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let end_of = |i: usize| chars.get(i).map_or(text.len(), |&(idx, _)| idx);
+    let is_letter = |i: usize| chars.get(i).is_some_and(|&(_, ch)| is_hebrew_letter(ch));
+    let mut result = String::with_capacity(text.len());
+    let mut alignments = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (idx, ch) = chars[i];
+        let after_letter = i > 0 && is_letter(i - 1);
+        let apostrophes = GERESH_FORMS.contains(&ch)
+            && chars
+                .get(i + 1)
+                .is_some_and(|&(_, next)| GERESH_FORMS.contains(&next));
+        let (replacement, len) = if after_letter && apostrophes && is_letter(i + 2) {
+            ('"', 2)
+        } else if after_letter && GERSHAYIM_FORMS.contains(&ch) && is_letter(i + 1) {
+            ('"', 1)
+        } else if after_letter && GERESH_FORMS.contains(&ch) {
+            ('\'', 1)
+        } else {
+            (ch, 1)
+        };
+        let original = idx..end_of(i + len);
+        if text[original.clone()].chars().eq([replacement]) {
+            result.push(ch);
+        } else {
+            let start = result.len();
+            result.push(replacement);
+            alignments.push(Alignment {
+                original,
+                normalized: start..result.len(),
+            });
+        }
+        i += len;
+    }
+
+    Normalized {
+        text: result,
+        alignments,
+    }
+}
+
+fn expand(captures: &Captures, options: &NormalizeOptions) -> Option<String> {
+    let number = |name: &str| captures.name(name).map(|m| m.as_str());
+
+    if let Some(date) = number("date") {
+        let (day, month) = (
+            number("day")?.parse().ok()?,
+            number("month")?.parse::<usize>().ok()?,
+        );
+        if options.dates && (1..=31).contains(&day) && (1..=12).contains(&month) {
+            let year = number("year")?.parse().ok()?;
+            return Some(format!(
+                "{} ב{} {}",
+                number_to_words(day, Gender::Masculine),
+                MONTHS[month - 1],
+                number_to_words(year, Gender::Feminine)
+            ));
+        }
+        return Some(spell_digit_runs(date, options.gender));
+    }
+
+    if let Some(time) = number("time") {
+        let (hour, minute) = (
+            number("hour")?.parse().ok()?,
+            number("minute")?.parse().ok()?,
+        );
+        if !options.times || hour > 23 {
+            return Some(spell_digit_runs(time, options.gender));
+        }
+        let hour = number_to_words(hour, Gender::Feminine);
+        return Some(match minute {
+            0 => hour,
+            minute => format!("{hour} ו{}", number_to_words(minute, Gender::Feminine)),
+        });
+    }
+
+    if number("decimal").is_some() {
+        return Some(format!(
+            "{} נקודה {}",
+            digits_to_words(number("whole")?, options.gender),
+            digits_to_words(number("fraction")?, options.gender)
+        ));
+    }
+
+    let words = digits_to_words(&number("number")?.replace(',', ""), options.gender);
+    Some(match number("percent") {
+        Some("%") => format!("{words} אחוז"),
+        _ => words,
+    })
+}
+
+/// Spell out the digit runs of a date or time left unread, keeping the separators
+/// between them as written, e.g. `45/13/2024` as `ארבעים וחמש/שלוש עשרה/אלפיים עשרים וארבע`
+fn spell_digit_runs(text: &str, gender: Gender) -> String {
+    text.split_inclusive(|ch: char| !ch.is_ascii_digit())
+        .map(|part| {
+            let digits = part.trim_end_matches(|ch: char| !ch.is_ascii_digit());
+            format!("{}{}", digits_to_words(digits, gender), &part[digits.len()..])
+        })
+        .collect()
+}
+
+/// Spell out a run of digits, as a cardinal when it reads as one
+fn digits_to_words(digits: &str, gender: Gender) -> String {
+    match digits.parse::<u64>() {
+        Ok(number) if number <= MAX_CARDINAL && !(digits.len() > 1 && digits.starts_with('0')) => {
+            number_to_words(number, gender)
+        }
+        // Phone numbers, codes and the like
+        _ => digits
+            .chars()
+            .filter_map(|digit| digit.to_digit(10))
+            .map(|digit| number_to_words(digit.into(), gender))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Spell out `number` in Hebrew words, e.g. `2024` as `אלפיים עשרים וארבע`
+pub fn number_to_words(number: u64, gender: Gender) -> String {
+    if number == 0 {
+        return "אפס".to_string();
+    }
+
+    let mut parts = Vec::new();
+    push_parts(&mut parts, number, gender);
+    if parts.len() > 1 {
+        let last = parts.pop().unwrap();
+        parts.push(format!("ו{last}"));
+    }
+    parts.join(" ")
+}
+
+fn push_parts(parts: &mut Vec<String>, number: u64, gender: Gender) {
+    let scales = [
+        (1_000_000_000, "מיליארד", "מיליארד"),
+        (1_000_000, "מיליון", "מיליון"),
+    ];
+    let mut rest = number;
+    for (scale, one, many) in scales {
+        let count = rest / scale;
+        rest %= scale;
+        match count {
+            0 => {}
+            1 => parts.push(one.to_string()),
+            // Construct form, `שני מיליון` rather than `שניים מיליון`
+            2 => parts.push(format!("שני {many}")),
+            count => parts.push(format!(
+                "{} {many}",
+                number_to_words(count, Gender::Masculine)
+            )),
+        }
+    }
+
+    let thousands = rest / 1000;
+    rest %= 1000;
+    match thousands {
+        0 => {}
+        1 => parts.push("אלף".to_string()),
+        2 => parts.push("אלפיים".to_string()),
+        3..=10 => parts.push(format!(
+            "{} אלפים",
+            THOUSANDS_CONSTRUCT[thousands as usize - 3]
+        )),
+        count => parts.push(format!("{} אלף", number_to_words(count, Gender::Masculine))),
+    }
+
+    let hundreds = rest / 100;
+    rest %= 100;
+    match hundreds {
+        0 => {}
+        1 => parts.push("מאה".to_string()),
+        2 => parts.push("מאתיים".to_string()),
+        count => parts.push(format!(
+            "{} מאות",
+            UNITS[Gender::Feminine as usize][count as usize]
+        )),
+    }
+
+    let units = &UNITS[gender as usize];
+    match rest {
+        0 => {}
+        1..=10 => parts.push(units[rest as usize].to_string()),
+        11..=19 => parts.push(TEENS[gender as usize][rest as usize - 11].to_string()),
+        _ => {
+            parts.push(TENS[rest as usize / 10 - 2].to_string());
+            if !rest.is_multiple_of(10) {
+                parts.push(units[rest as usize % 10].to_string());
+            }
+        }
+    }
+}
+
+/// Indexed by `Gender as usize`, then by the digit
+const UNITS: [[&str; 11]; 2] = [
+    [
+        "",
+        "אחד",
+        "שניים",
+        "שלושה",
+        "ארבעה",
+        "חמישה",
+        "שישה",
+        "שבעה",
+        "שמונה",
+        "תשעה",
+        "עשרה",
+    ],
+    [
+        "",
+        "אחת",
+        "שתיים",
+        "שלוש",
+        "ארבע",
+        "חמש",
+        "שש",
+        "שבע",
+        "שמונה",
+        "תשע",
+        "עשר",
+    ],
+];
+
+const TEENS: [[&str; 9]; 2] = [
+    [
+        "אחד עשר",
+        "שנים עשר",
+        "שלושה עשר",
+        "ארבעה עשר",
+        "חמישה עשר",
+        "שישה עשר",
+        "שבעה עשר",
+        "שמונה עשר",
+        "תשעה עשר",
+    ],
+    [
+        "אחת עשרה",
+        "שתים עשרה",
+        "שלוש עשרה",
+        "ארבע עשרה",
+        "חמש עשרה",
+        "שש עשרה",
+        "שבע עשרה",
+        "שמונה עשרה",
+        "תשע עשרה",
+    ],
+];
+
+const TENS: [&str; 8] = [
+    "עשרים",
+    "שלושים",
+    "ארבעים",
+    "חמישים",
+    "שישים",
+    "שבעים",
+    "שמונים",
+    "תשעים",
+];
+
+/// Construct forms used before `אלפים`, from three to ten
+const THOUSANDS_CONSTRUCT: [&str; 8] = [
+    "שלושת",
+    "ארבעת",
+    "חמשת",
+    "ששת",
+    "שבעת",
+    "שמונת",
+    "תשעת",
+    "עשרת",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spelling::{SpellingOptions, respell};
+
+    #[test]
+    fn dates_and_times_left_unread_keep_their_separators() {
+        let options = NormalizeOptions::default();
+        assert_eq!(
+            normalize("45/13/2024", &options).text,
+            "ארבעים וחמש/שלוש עשרה/אלפיים עשרים וארבע"
+        );
+        assert_eq!(normalize("24:15", &options).text, "עשרים וארבע:חמש עשרה");
+
+        let options = NormalizeOptions {
+            dates: false,
+            times: false,
+            ..NormalizeOptions::default()
+        };
+        assert_eq!(normalize("1.5.2024", &options).text, "אחת.חמש.אלפיים עשרים וארבע");
+        assert_eq!(normalize("בשעה 10:30", &options).text, "בשעה עשר:שלושים");
+    }
+
+    #[test]
+    fn dates_and_times_are_read() {
+        let options = NormalizeOptions::default();
+        assert_eq!(
+            normalize("1/5/2024", &options).text,
+            "אחד במאי אלפיים עשרים וארבע"
+        );
+        assert_eq!(normalize("10:30", &options).text, "עשר ושלושים");
+        assert_eq!(normalize("10:00", &options).text, "עשר");
+    }
+
+    #[test]
+    fn millions_and_billions_are_counted() {
+        let words = |number| number_to_words(number, Gender::Feminine);
+        assert_eq!(words(1_000_000), "מיליון");
+        assert_eq!(words(2_000_000), "שני מיליון");
+        assert_eq!(words(2_000_000_000), "שני מיליארד");
+        assert_eq!(words(3_000_000), "שלושה מיליון");
+        assert_eq!(words(2_000_002), "שני מיליון ושתיים");
+        assert_eq!(words(12_000_000), "שנים עשר מיליון");
+    }
+
+    #[test]
+    fn digits_in_latin_words_are_kept() {
+        let options = NormalizeOptions::default();
+        for text in ["mp3", "4K", "a1b", "H2O", "x86"] {
+            let normalized = normalize(text, &options);
+            assert_eq!(normalized.text, text);
+            assert!(normalized.alignments.is_empty());
+        }
+        assert_eq!(normalize("שיר mp3 בן 3 דקות", &options).text, "שיר mp3 בן שלוש דקות");
+        assert_eq!(normalize("(3)", &options).text, "(שלוש)");
+    }
+
+    #[test]
+    fn offsets_map_back_through_the_replacements() {
+        let normalized = normalize("בשעה 14:30 היום", &NormalizeOptions::default());
+        let clock = "ארבע עשרה ושלושים";
+        assert_eq!(normalized.text, format!("בשעה {clock} היום"));
+        let time = "בשעה ".len();
+        assert_eq!(normalized.original_offset(0), 0);
+        assert_eq!(normalized.original_offset(time), time);
+        assert_eq!(normalized.original_offset(time + 3), time);
+        let after = time + clock.len();
+        assert_eq!(normalized.original_offset(after), time + "14:30".len());
+        assert_eq!(normalized.original_offset(normalized.text.len()), "בשעה 14:30 היום".len());
+
+        // Respelled texts map the same way, a fixed letter keeps its length
+        let respelled = respell("שלומ עולמ", &SpellingOptions::default());
+        assert_eq!(respelled.fixes.len(), 2);
+        assert_eq!(respelled.original_offset(6), 6);
+        assert_eq!(respelled.original_offset(7), 6);
+        assert_eq!(respelled.original_offset(respelled.text.len()), "שלומ עולמ".len());
+    }
+
+    #[test]
+    fn geresh_lookalikes_are_written_in_ascii() {
+        let cases = [
+            ("ז\u{05f3}אנר", "ז'אנר", 2..4),
+            ("צ\u{2019}יפס", "צ'יפס", 2..5),
+            ("צה\u{2019}\u{2019}ל", "צה\"ל", 4..10),
+        ];
+        for (text, expected, original) in cases {
+            let normalized = normalize_geresh(text);
+            assert_eq!(normalized.text, expected);
+            let normalized_range = original.start..original.start + 1;
+            assert_eq!(
+                normalized.alignments,
+                [Alignment {
+                    original: original.clone(),
+                    normalized: normalized_range.clone(),
+                }]
+            );
+            // The letters before and after the geresh map back to their place
+            assert_eq!(normalized.original_offset(0), 0);
+            assert_eq!(normalized.original_offset(normalized_range.start), original.start);
+            assert_eq!(normalized.original_offset(normalized_range.end), original.end);
+            assert_eq!(normalized.original_offset(expected.len()), text.len());
+        }
+
+        // A geresh ending a word, as in the numeral `ג׳`
+        assert_eq!(normalize_geresh("ג\u{05f3} ב").text, "ג' ב");
+        // Already in ASCII, or quotes around a word rather than inside it
+        for text in ["ג'ירפה", "צה\"ל", "'שלום'", "\u{201c}שלום\u{201d}", "\"צה\""] {
+            let normalized = normalize_geresh(text);
+            assert_eq!(normalized.text, text);
+            assert!(normalized.alignments.is_empty(), "{text}");
+        }
+    }
+}
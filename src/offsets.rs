@@ -0,0 +1,142 @@
+use std::ops::Range;
+
+use unicode_normalization::char::is_combining_mark;
+
+use crate::{
+    DiacriticsOptions, Phonikud, Result, Vocalization,
+    text::{CharOffsets, is_nikud},
+};
+
+/// A piece of the vocalized text and the part of the input it replaces.
+///
+/// A vocalized letter maps to the input letter along with the marks it had,
+/// other text maps char by char with its combining marks. Replacing every
+/// `input` range with the `output` text turns the input into the output,
+/// e.g. to apply the vocalization as edits in an editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OffsetMapping {
+    /// Byte range in the vocalized text
+    pub output: Range<usize>,
+    /// Byte range in the input text
+    pub input: Range<usize>,
+}
+
+impl Vocalization {
+    /// Map the vocalized text back to `input`, the text this vocalization was made from.
+    ///
+    /// Mappings are sorted and cover both texts without gaps. Text the output
+    /// doesn't copy char by char, like a lexicon replacement, maps as a whole.
+    pub fn offset_mapping(&self, input: &str) -> Vec<OffsetMapping> {
+        let mut mappings = Vec::with_capacity(self.text.len());
+        let (mut input_end, mut output_end) = (0, 0);
+        // Predictions of another text may fall inside its chars or past its end
+        let chars = CharOffsets::new(input);
+
+        for (prediction, span) in self.predictions.iter().zip(&self.spans) {
+            // Letters of an expanded acronym all point at the acronym
+            let start = chars.floor(prediction.start).max(input_end);
+            map_gap(
+                &input[input_end..start],
+                input_end,
+                &self.text[output_end..span.start],
+                output_end,
+                &mut mappings,
+            );
+            let end = cluster_end(input, start);
+            mappings.push(OffsetMapping {
+                output: span.clone(),
+                input: start..end,
+            });
+            (input_end, output_end) = (end, span.end);
+        }
+        map_gap(
+            &input[input_end..],
+            input_end,
+            &self.text[output_end..],
+            output_end,
+            &mut mappings,
+        );
+
+        mappings
+    }
+}
+
+impl Phonikud {
+    /// Vocalize `text` and map every piece of the output back to its byte range in `text`
+    pub fn add_diacritics_with_offsets(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<(String, Vec<OffsetMapping>)> {
+        let vocalization = self.vocalize(text, options)?;
+        let mappings = vocalization.offset_mapping(text);
+        Ok((vocalization.text, mappings))
+    }
+}
+
+/// Map text between two vocalized letters, cluster by cluster when both sides
+/// have the same base chars and as a single piece otherwise
+fn map_gap(
+    input: &str,
+    input_start: usize,
+    output: &str,
+    output_start: usize,
+    mappings: &mut Vec<OffsetMapping>,
+) {
+    if input.is_empty() && output.is_empty() {
+        return;
+    }
+    let input_clusters = clusters(input);
+    let output_clusters = clusters(output);
+    let aligned = input_clusters.len() == output_clusters.len()
+        && input_clusters
+            .iter()
+            .zip(&output_clusters)
+            .all(|(ours, theirs)| {
+                input[ours.clone()].chars().next() == output[theirs.clone()].chars().next()
+            });
+    if !aligned {
+        mappings.push(OffsetMapping {
+            output: output_start..output_start + output.len(),
+            input: input_start..input_start + input.len(),
+        });
+        return;
+    }
+    mappings.extend(
+        input_clusters
+            .into_iter()
+            .zip(output_clusters)
+            .map(|(input, output)| OffsetMapping {
+                output: output_start + output.start..output_start + output.end,
+                input: input_start + input.start..input_start + input.end,
+            }),
+    );
+}
+
+/// Byte ranges of every char of `text` with the marks following it
+fn clusters(text: &str) -> Vec<Range<usize>> {
+    let mut clusters: Vec<Range<usize>> = Vec::new();
+    for (idx, ch) in text.char_indices() {
+        let end = idx + ch.len_utf8();
+        match clusters.last_mut() {
+            Some(cluster) if is_cluster_mark(ch) => cluster.end = end,
+            _ => clusters.push(idx..end),
+        }
+    }
+    clusters
+}
+
+/// End of the letter at `start` and the marks following it
+fn cluster_end(text: &str, start: usize) -> usize {
+    let mut chars = text[start..].char_indices();
+    let letter_len = chars.next().map_or(0, |(_, ch)| ch.len_utf8());
+    chars
+        .find(|&(_, ch)| !is_cluster_mark(ch))
+        .map_or(text.len(), |(idx, _)| start + idx)
+        .max(start + letter_len)
+}
+
+fn is_cluster_mark(ch: char) -> bool {
+    is_nikud(ch) || is_combining_mark(ch)
+}
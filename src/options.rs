@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use crate::{acronym::AcronymMode, lexicon::Lexicon, spelling::SpellingOptions, text::MarkOrder};
+
+/// The "ole" symbol marks stress
+pub const STRESS_MARK: &str = "\u{05ab}";
+/// The "meteg" symbol marks vocal shva
+pub const VOCAL_SHVA_MARK: &str = "\u{05bd}";
+pub const PREFIX_MARK: &str = "|";
+/// Written for a vocal shva with [`VocalShvaStyle::HatafSegol`]
+const HATAF_SEGOL: &str = "\u{05b1}";
+
+/// Controls how the predictions are rendered into the output string
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DiacriticsOptions {
+    /// Emit vowel points and dagesh
+    pub nikud: bool,
+    /// Emit the dagesh along with the vowels. When false, the dagesh is only found in
+    /// the predictions (see [`crate::TokenPrediction::has_dagesh`]), for typesetting
+    /// pipelines placing it themselves. The dot of shuruk is always emitted.
+    pub dagesh: bool,
+    /// Emit the shin/sin dot
+    pub shin_dot: bool,
+    /// Mark stressed letters with this string, `None` to omit stress
+    pub stress_mark: Option<String>,
+    /// Where the stress mark goes among the marks of the stressed letter
+    pub stress_placement: StressPlacement,
+    /// Mark vocal shva with this string, `None` to omit it. Only written with
+    /// [`VocalShvaStyle::Marked`].
+    pub vocal_shva_mark: Option<String>,
+    /// How the shva of the letters predicted to carry a vocal shva is written
+    pub vocal_shva_style: VocalShvaStyle,
+    /// Mark the end of a prefix with this string, `None` to omit prefix boundaries
+    pub prefix_mark: Option<String>,
+    /// Mark matres lectionis with this string, they are left unmarked when `None`
+    pub mark_matres_lectionis: Option<String>,
+    /// Drop the letters predicted to be matres lectionis, producing the deficient
+    /// spelling (ktiv haser). Holam male and shuruk are kept since they carry a vowel.
+    pub ktiv_haser: bool,
+    /// Order of the marks after each letter, see [`MarkOrder`]
+    pub mark_order: MarkOrder,
+    /// Keep words that already carry diacritics exactly as written
+    pub preserve_existing: bool,
+    /// Keep the cantillation marks (taamim) of the input, for biblical and liturgical
+    /// texts. They are merged with the predicted nikud of their letter in the conventional
+    /// Hebrew order, see [`MarkOrder::Hebrew`]. The ole and meteg are read as the stress
+    /// and vocal shva marks, like in the model output, so they aren't kept.
+    pub keep_taamim: bool,
+    /// Only send the runs of words containing Hebrew letters to the model, leaving out
+    /// English words, numbers and URLs between them. Faster on mixed text, and the
+    /// model isn't confused by scripts it was not trained on.
+    pub hebrew_runs_only: bool,
+    /// Probability cutoffs for the stress, vocal shva and prefix heads
+    pub thresholds: Thresholds,
+    /// Words found here are replaced verbatim instead of using the model prediction,
+    /// their letters get no [`crate::TokenPrediction`]
+    pub lexicon: Option<Arc<Lexicon>>,
+    /// How acronyms written with gershayim are handled
+    pub acronyms: AcronymMode,
+    /// Never predict marks the letter can't take, such as a dagesh in a guttural or a
+    /// vowel under a final mem, by picking the best class the letter allows instead
+    pub orthography_constraints: bool,
+    /// Send final letters written inside a word and regular letters ending one to the
+    /// model in their right form, it reads such mistyped words poorly. The output keeps
+    /// the letters as written, see [`crate::spelling::fix_final_forms`].
+    pub fix_final_forms: bool,
+    /// Fix the typing variants of these options, such as extra yods and vavs, before
+    /// inference. The output is written with the fixed spelling, see
+    /// [`crate::spelling::respell`].
+    pub respell: Option<SpellingOptions>,
+    /// Replace the Hebrew presentation forms of the input, such as `שׁ` (U+FB2A), with the
+    /// standard letters and marks before inference. The output is written with the
+    /// standard letters, see [`crate::normalize::normalize_presentation_forms`].
+    pub normalize_presentation_forms: bool,
+    /// How the qamats qatan is written, fonts and nakdan conventions disagree
+    pub qamats_qatan: QamatsQatan,
+}
+
+/// Position of the stress mark relative to the other marks of a letter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum StressPlacement {
+    /// Right after the nikud, before the vocal shva and prefix marks
+    #[default]
+    AfterVowel,
+    /// Between the consonant (letter, shin dot and dagesh) and its vowel
+    BeforeVowel,
+    /// After every mark of the letter, only followed by the prefix mark
+    AfterCluster,
+    /// After the nikud of the letter carrying the vowel, moving the stress of a
+    /// consonant onto the holam male or shuruk that follows it
+    VowelLetter,
+}
+
+/// Probability (after sigmoid) above which a binary head fires.
+///
+/// The default of 0.5 matches a logit threshold of 0. Raise a threshold for
+/// precision, lower it for recall.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Thresholds {
+    pub stress: f32,
+    pub vocal_shva: f32,
+    pub prefix: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            stress: 0.5,
+            vocal_shva: 0.5,
+            prefix: 0.5,
+        }
+    }
+}
+
+/// How a predicted qamats qatan (the short o vowel) is written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum QamatsQatan {
+    /// The dedicated U+05C7 codepoint, missing from some fonts
+    #[default]
+    Dedicated,
+    /// A plain qamats, which it looks like in most texts
+    Qamats,
+    /// A plain qamats followed by this marker
+    Marked(String),
+}
+
+/// How a predicted vocal shva is written, learners' materials show the vowel it's read with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum VocalShvaStyle {
+    /// The shva followed by [`DiacriticsOptions::vocal_shva_mark`], the meteg by default
+    #[default]
+    Marked,
+    /// A hataf segol instead of the shva, the vowel of a vocal shva read as e
+    HatafSegol,
+    /// This string instead of the shva, e.g. a superscript `ᵉ`. It follows the letter's
+    /// other marks when the letter has no shva to replace.
+    Hint(String),
+}
+
+impl VocalShvaStyle {
+    /// The string replacing the shva, `None` when it's kept and marked
+    pub fn replacement(&self) -> Option<&str> {
+        match self {
+            VocalShvaStyle::Marked => None,
+            VocalShvaStyle::HatafSegol => Some(HATAF_SEGOL),
+            VocalShvaStyle::Hint(hint) => Some(hint),
+        }
+    }
+}
+
+/// What happens to inputs longer than the truncation length of tokenizer.json.
+///
+/// The tokenizer's own truncation is always turned off, it drops the tail of long
+/// inputs without an error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TruncationPolicy {
+    /// Split the text into chunks short enough for the truncation length
+    #[default]
+    Chunk,
+    /// Fail with [`crate::PhonikudError::InputTooLong`] when a chunk is longer
+    Error,
+}
+
+/// How the logits of the checkpoints of an ensemble are combined before decoding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EnsembleMode {
+    /// Average the logits of every class
+    #[default]
+    Mean,
+    /// Keep the highest logit of every class
+    Max,
+}
+
+impl Default for DiacriticsOptions {
+    fn default() -> Self {
+        Self {
+            nikud: true,
+            dagesh: true,
+            shin_dot: true,
+            stress_mark: Some(STRESS_MARK.to_string()),
+            stress_placement: StressPlacement::AfterVowel,
+            vocal_shva_mark: Some(VOCAL_SHVA_MARK.to_string()),
+            vocal_shva_style: VocalShvaStyle::Marked,
+            prefix_mark: Some(PREFIX_MARK.to_string()),
+            mark_matres_lectionis: None,
+            ktiv_haser: false,
+            mark_order: MarkOrder::Model,
+            preserve_existing: false,
+            keep_taamim: false,
+            hebrew_runs_only: false,
+            thresholds: Thresholds::default(),
+            lexicon: None,
+            acronyms: AcronymMode::Vocalize,
+            orthography_constraints: false,
+            fix_final_forms: false,
+            respell: None,
+            normalize_presentation_forms: false,
+            qamats_qatan: QamatsQatan::Dedicated,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::{DiacriticsOptions, Phonikud, PhonikudModel, Result};
+
+impl Phonikud {
+    /// Vocalize `texts` on `n_workers` sessions in parallel, returning results in input order
+    pub fn add_diacritics_parallel(
+        &mut self,
+        texts: &[String],
+        n_workers: usize,
+    ) -> Result<Vec<String>> {
+        self.add_diacritics_parallel_with_options(texts, n_workers, &DiacriticsOptions::default())
+    }
+
+    /// Like [`Phonikud::add_diacritics_parallel`] with custom options.
+    ///
+    /// Extra sessions are created on first use and kept for later calls.
+    pub fn add_diacritics_parallel_with_options(
+        &mut self,
+        texts: &[String],
+        n_workers: usize,
+        options: &DiacriticsOptions,
+    ) -> Result<Vec<String>> {
+        let n_workers = n_workers.clamp(1, texts.len().max(1));
+        // The main session counts as the first worker
+        while self.workers.len() + 1 < n_workers {
+            self.workers.push(self.inner.spawn_worker()?);
+        }
+        let mut sessions: Vec<&mut PhonikudModel> = std::iter::once(&mut self.inner)
+            .chain(self.workers.iter_mut())
+            .take(n_workers)
+            .collect();
+
+        // Each worker pulls the next text from a shared counter, so slow texts don't stall the rest
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<(usize, Result<String>)> = sessions
+            .par_iter_mut()
+            .flat_map_iter(|model| {
+                let mut done = Vec::new();
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(text) = texts.get(idx) else {
+                        break;
+                    };
+                    done.push((idx, model.run_inference(text, options)));
+                }
+                done
+            })
+            .collect();
+
+        results.sort_unstable_by_key(|&(idx, _)| idx);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
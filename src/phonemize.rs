@@ -0,0 +1,314 @@
+//! Convert vocalized Hebrew (nikud, stress and vocal shva marks) into IPA phonemes
+
+use std::ops::Range;
+
+use crate::{
+    hebrew::is_hebrew_letter,
+    options::{STRESS_MARK, VOCAL_SHVA_MARK},
+};
+
+pub(crate) const DAGESH: &str = "\u{05bc}";
+pub(crate) const SHVA: &str = "\u{05b0}";
+pub(crate) const HOLAM: &str = "\u{05b9}";
+pub(crate) const SIN_DOT: &str = "\u{05c2}";
+const PATAH: &str = "\u{05b7}";
+const GERESH: &[char] = &['\'', '\u{05f3}'];
+const STRESS_IPA: &str = "ˈ";
+
+/// A Hebrew letter together with the marks that follow it, see [`pieces`]
+pub(crate) struct Cluster {
+    /// Byte range of the letter, its marks, its geresh and a prefix marker after it
+    pub(crate) range: Range<usize>,
+    pub(crate) letter: char,
+    pub(crate) marks: String,
+    /// Whether a geresh follows the letter, as in `ג'`
+    pub(crate) geresh: bool,
+}
+
+impl Cluster {
+    pub(crate) fn has(&self, mark: &str) -> bool {
+        self.marks.contains(mark)
+    }
+
+    fn vowel(&self) -> Option<&'static str> {
+        self.marks.chars().find_map(|mark| match mark {
+            '\u{05b2}' | '\u{05b7}' | '\u{05b8}' => Some("a"), // hataf patah, patah, qamats
+            '\u{05b1}' | '\u{05b5}' | '\u{05b6}' => Some("e"), // hataf segol, tsere, segol
+            '\u{05b3}' | '\u{05b9}' | '\u{05ba}' | '\u{05c7}' => Some("o"), // hataf qamats, holam, qamats qatan
+            '\u{05b4}' => Some("i"),                                        // hiriq
+            '\u{05bb}' => Some("u"),                                        // qubuts
+            '\u{05b0}' if self.has(VOCAL_SHVA_MARK) => Some("e"), // vocal shva
+            _ => None,
+        })
+    }
+
+    fn consonant(&self) -> &'static str {
+        let dagesh = self.has(DAGESH);
+        match self.letter {
+            'א' | 'ע' => "ʔ",
+            'ב' if dagesh => "b",
+            'ב' => "v",
+            'ג' if self.geresh => "dʒ",
+            'ג' => "g",
+            'ד' => "d",
+            'ה' => "h",
+            'ו' => "v",
+            'ז' if self.geresh => "ʒ",
+            'ז' => "z",
+            'ח' => "χ",
+            'ט' | 'ת' => "t",
+            'י' => "j",
+            'כ' | 'ך' if dagesh => "k",
+            'כ' | 'ך' => "χ",
+            'ל' => "l",
+            'מ' | 'ם' => "m",
+            'נ' | 'ן' => "n",
+            'ס' => "s",
+            'פ' | 'ף' if dagesh => "p",
+            'פ' | 'ף' => "f",
+            'צ' | 'ץ' if self.geresh => "tʃ",
+            'צ' | 'ץ' => "ts",
+            'ק' => "k",
+            'ר' => "ʁ",
+            'ש' if self.has(SIN_DOT) => "s",
+            'ש' => "ʃ",
+            _ => "",
+        }
+    }
+}
+
+/// Notation of the phonemes produced by [`phonemize_with_alphabet`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PhonemeAlphabet {
+    /// The International Phonetic Alphabet, e.g. `ʃaˈlom`
+    #[default]
+    Ipa,
+    /// X-SAMPA, the ASCII encoding of IPA used by many TTS engines, e.g. `Sa"lom`
+    XSampa,
+    /// Kirshenbaum, the ASCII notation of espeak-ng and the Piper voices trained on
+    /// it, e.g. `Sa'lom`
+    Kirshenbaum,
+}
+
+impl PhonemeAlphabet {
+    /// Write the IPA `phoneme` in this alphabet, the symbols are the same for the rest
+    pub(crate) fn symbol(self, phoneme: char) -> Option<&'static str> {
+        let symbol = match (self, phoneme) {
+            (Self::Ipa, _) => return None,
+            (_, 'ʔ') => "?",
+            (_, 'ʃ') => "S",
+            (_, 'ʒ') => "Z",
+            (_, 'χ') => "X",
+            (Self::XSampa, 'ʁ') => "R",
+            (Self::XSampa, 'ˈ') => "\"",
+            (Self::Kirshenbaum, 'ʁ') => "g\"",
+            (Self::Kirshenbaum, 'ˈ') => "'",
+            _ => return None,
+        };
+        Some(symbol)
+    }
+}
+
+/// A piece of vocalized text, see [`pieces`]
+pub(crate) enum Piece {
+    /// The letters of a Hebrew word with the marks that follow them
+    Word(Vec<Cluster>),
+    /// A char between the words
+    Other(char),
+}
+
+/// Split `vocalized` into its Hebrew words and the chars between them.
+///
+/// A word is a run of letters, each followed by its marks, a geresh and the prefix
+/// marker `|`. Marks and prefix markers outside the words are dropped.
+pub(crate) fn pieces(vocalized: &str) -> impl Iterator<Item = Piece> + '_ {
+    let mut chars = vocalized.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let mut word: Vec<Cluster> = Vec::new();
+        while let Some(&(idx, ch)) = chars.peek() {
+            let end = idx + ch.len_utf8();
+            let geresh = GERESH.contains(&ch);
+            if is_hebrew_letter(ch) {
+                word.push(Cluster {
+                    range: idx..end,
+                    letter: ch,
+                    marks: String::new(),
+                    geresh: false,
+                });
+            } else if let Some(cluster) = word.last_mut()
+                && (is_mark(ch) || ch == '|' || geresh)
+            {
+                if is_mark(ch) {
+                    cluster.marks.push(ch);
+                }
+                cluster.geresh |= geresh;
+                cluster.range.end = end;
+            } else if !word.is_empty() {
+                return Some(Piece::Word(word));
+            } else if !is_mark(ch) && ch != '|' {
+                chars.next();
+                return Some(Piece::Other(ch));
+            }
+            chars.next();
+        }
+        (!word.is_empty()).then_some(Piece::Word(word))
+    })
+}
+
+/// Convert vocalized Hebrew text into an IPA phoneme string.
+///
+/// Characters that are not Hebrew letters or marks (spaces, punctuation, Latin)
+/// are kept as is. The prefix marker `|` is dropped.
+pub fn phonemize(vocalized: &str) -> String {
+    phonemize_with_alphabet(vocalized, PhonemeAlphabet::Ipa)
+}
+
+/// Like [`phonemize`], writing the phonemes in `alphabet`. The text between the
+/// Hebrew words is kept as is in every alphabet.
+pub fn phonemize_with_alphabet(vocalized: &str, alphabet: PhonemeAlphabet) -> String {
+    let mut result = String::with_capacity(vocalized.len());
+    for piece in pieces(vocalized) {
+        match piece {
+            Piece::Word(word) => push_word(&mut result, &word, alphabet),
+            Piece::Other(ch) => result.push(ch),
+        }
+    }
+    result
+}
+
+fn push_word(result: &mut String, word: &[Cluster], alphabet: PhonemeAlphabet) {
+    for phoneme in phonemize_word(word).chars() {
+        match alphabet.symbol(phoneme) {
+            Some(symbol) => result.push_str(symbol),
+            None => result.push(phoneme),
+        }
+    }
+}
+
+fn phonemize_word(word: &[Cluster]) -> String {
+    let mut result = String::new();
+    let mut prev_vowel: Option<&str> = None;
+
+    for (idx, cluster) in word.iter().enumerate() {
+        let is_last = idx + 1 == word.len();
+        let stress = if cluster.has(STRESS_MARK) { STRESS_IPA } else { "" };
+        let vowel = cluster.vowel();
+
+        let (consonant, vowel) = match cluster.letter {
+            // Holam male and shuruk carry the vowel of the previous letter
+            'ו' if vowel.is_none() || vowel == Some("o") && cluster.has(HOLAM) => {
+                if cluster.has(HOLAM) {
+                    if prev_vowel.is_none() {
+                        ("", Some("o"))
+                    } else {
+                        ("v", Some("o"))
+                    }
+                } else if cluster.has(DAGESH) {
+                    if prev_vowel.is_none() {
+                        ("", Some("u"))
+                    } else {
+                        ("v", Some("u"))
+                    }
+                } else {
+                    // Silent mater lectionis
+                    ("", None)
+                }
+            }
+            // Alef and ayin are only pronounced with a vowel, possibly one carried by a vav
+            'א' | 'ע' if vowel.is_none() && !word.get(idx + 1).is_some_and(is_vowel_vav) => {
+                ("", None)
+            }
+            // A final he is silent unless it carries a mappiq
+            'ה' if vowel.is_none() && !(is_last && cluster.has(DAGESH)) => ("", None),
+            'י' if vowel.is_none() => match prev_vowel {
+                Some("a" | "o" | "u") if is_last => ("j", None),
+                _ => ("", None),
+            },
+            _ => (cluster.consonant(), vowel),
+        };
+
+        match vowel {
+            // Patah genuva is pronounced before the final guttural
+            Some("a")
+                if is_last && cluster.has(PATAH) && matches!(cluster.letter, 'ח' | 'ע' | 'ה') =>
+            {
+                result.push_str(stress);
+                result.push('a');
+                result.push_str(consonant);
+            }
+            Some(vowel) => {
+                result.push_str(consonant);
+                result.push_str(stress);
+                result.push_str(vowel);
+            }
+            None => {
+                result.push_str(stress);
+                result.push_str(consonant);
+            }
+        }
+
+        prev_vowel = match vowel {
+            Some(vowel) => Some(vowel),
+            // Silent letters keep the previous vowel open for matres lectionis
+            None if consonant.is_empty() => prev_vowel,
+            None => None,
+        };
+    }
+
+    result
+}
+
+/// Holam male or shuruk, a vav that only carries a vowel
+fn is_vowel_vav(cluster: &Cluster) -> bool {
+    cluster.letter == 'ו'
+        && (cluster.has(HOLAM) || cluster.has(DAGESH) && cluster.vowel().is_none())
+}
+
+pub(crate) fn is_mark(ch: char) -> bool {
+    ('\u{0591}'..='\u{05c7}').contains(&ch)
+        && !matches!(ch, '\u{05be}' | '\u{05c0}' | '\u{05c3}' | '\u{05c6}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(vocalized: &str) -> Vec<String> {
+        pieces(vocalized)
+            .map(|piece| match piece {
+                Piece::Word(word) => word
+                    .iter()
+                    .map(|cluster| {
+                        let geresh = if cluster.geresh { "'" } else { "" };
+                        let text = &vocalized[cluster.range.clone()];
+                        format!("{}{geresh}={text}", cluster.letter)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Piece::Other(ch) => format!("<{ch}>"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pieces_split_words_and_attach_marks() {
+        assert_eq!(
+            describe("ו|ג'וּ׳ק! \u{05b8}x"),
+            [
+                "ו=ו| ג'=ג' ו'=וּ׳ ק=ק",
+                "<!>",
+                "< >",
+                "<x>",
+            ]
+        );
+        // A geresh outside a word is text, marks and prefix markers there are dropped
+        assert_eq!(describe("'|\u{05bc}א"), ["<'>", "א=א"]);
+    }
+
+    #[test]
+    fn vocal_shva_reads_as_e() {
+        assert_eq!(phonemize("בְּ\u{05bd}רֵאשִׁית"), "beʁeʃit");
+    }
+}
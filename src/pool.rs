@@ -0,0 +1,171 @@
+use std::sync::{
+    Arc, Mutex, MutexGuard, PoisonError, RwLock, TryLockError,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{DiacriticsOptions, Phonikud, PhonikudError, Result, Vocalization};
+
+/// A fixed set of sessions of one model, shared by reference across threads.
+///
+/// Every call goes to the next idle session in turn, or waits for the next
+/// session in turn when all of them are busy. Use one session per physical core
+/// group, so concurrent requests stop contending for a single session.
+pub struct PhonikudPool {
+    sessions: Vec<Mutex<Phonikud>>,
+    next: AtomicUsize,
+    /// Calls waiting for a busy session, see [`PhonikudPool::with_max_pending`]
+    pending: AtomicUsize,
+    max_pending: Option<usize>,
+}
+
+impl PhonikudPool {
+    /// Load `n_sessions` sessions of the model, splitting the available cores between them
+    #[cfg(feature = "onnx")]
+    pub fn new(model_path: &str, tokenizer_path: &str, n_sessions: usize) -> Result<Self> {
+        let n_sessions = n_sessions.max(1);
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        let phonikud = Phonikud::builder(model_path, tokenizer_path)
+            .with_intra_threads((cores / n_sessions).max(1))
+            .build()?;
+        Self::from_phonikud(phonikud, n_sessions)
+    }
+
+    /// Use `phonikud` and `n_sessions - 1` workers cloned from it, with the same settings
+    pub fn from_phonikud(phonikud: Phonikud, n_sessions: usize) -> Result<Self> {
+        let mut sessions = Vec::with_capacity(n_sessions.max(1));
+        for _ in 1..n_sessions {
+            sessions.push(Mutex::new(phonikud.clone_worker()?));
+        }
+        sessions.push(Mutex::new(phonikud));
+        Ok(Self {
+            sessions,
+            next: AtomicUsize::new(0),
+            pending: AtomicUsize::new(0),
+            max_pending: None,
+        })
+    }
+
+    /// Let at most `max_pending` calls wait while every session is busy, the next ones
+    /// fail right away with [`crate::PhonikudError::Overloaded`]. By default calls
+    /// always wait.
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = Some(max_pending);
+        self
+    }
+
+    /// Number of sessions
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Always false, a pool has at least one session
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn add_diacritics(&self, text: &str) -> Result<String> {
+        self.session()?.add_diacritics(text)
+    }
+
+    pub fn add_diacritics_with_options(
+        &self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<String> {
+        self.session()?.add_diacritics_with_options(text, options)
+    }
+
+    /// Vocalize `text` and also return the predictions it was rendered from
+    pub fn vocalize(&self, text: &str, options: &DiacriticsOptions) -> Result<Vocalization> {
+        self.session()?.vocalize(text, options)
+    }
+
+    /// Load the model at `model_path` into a pool of the same size and settings
+    pub fn load_replacement(&self, model_path: &str) -> Result<Self> {
+        // Load from a worker of the same settings, the session serves calls meanwhile
+        let template = self.session()?.clone_worker()?;
+        let phonikud = template.load_replacement(model_path)?;
+        let pool = Self::from_phonikud(phonikud, self.sessions.len())?;
+        Ok(Self {
+            max_pending: self.max_pending,
+            ..pool
+        })
+    }
+
+    /// Lock the next idle session in turn, or wait for the next one when all are busy
+    fn session(&self) -> Result<MutexGuard<'_, Phonikud>> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let n_sessions = self.sessions.len();
+        for offset in 0..n_sessions {
+            match self.sessions[(start + offset) % n_sessions].try_lock() {
+                Ok(session) => return Ok(session),
+                // A panic during inference leaves the session usable
+                Err(TryLockError::Poisoned(poisoned)) => return Ok(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) => {}
+            }
+        }
+        let waiting = self.pending.fetch_add(1, Ordering::Relaxed);
+        if let Some(capacity) = self.max_pending
+            && waiting >= capacity
+        {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+            return Err(PhonikudError::Overloaded { capacity });
+        }
+        let session = self.sessions[start % n_sessions]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+        Ok(session)
+    }
+}
+
+/// A [`PhonikudPool`] whose model can be replaced while it serves requests.
+///
+/// Each call runs on the pool current when it started. [`ModelManager::reload_model`]
+/// loads the new model next to the old one and swaps it in, calls still running on
+/// the old pool finish there and the old sessions are dropped after the last one.
+pub struct ModelManager {
+    pool: RwLock<Arc<PhonikudPool>>,
+}
+
+impl ModelManager {
+    pub fn new(pool: PhonikudPool) -> Self {
+        Self {
+            pool: RwLock::new(Arc::new(pool)),
+        }
+    }
+
+    /// The pool serving new calls
+    pub fn current(&self) -> Arc<PhonikudPool> {
+        self.pool
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Load the model at `model_path` with the settings of the current one and swap it in.
+    ///
+    /// The current model keeps serving if the new one fails to load.
+    pub fn reload_model(&self, model_path: &str) -> Result<()> {
+        let pool = self.current().load_replacement(model_path)?;
+        *self.pool.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(pool);
+        Ok(())
+    }
+
+    pub fn add_diacritics(&self, text: &str) -> Result<String> {
+        self.current().add_diacritics(text)
+    }
+
+    pub fn add_diacritics_with_options(
+        &self,
+        text: &str,
+        options: &DiacriticsOptions,
+    ) -> Result<String> {
+        self.current().add_diacritics_with_options(text, options)
+    }
+
+    /// Vocalize `text` and also return the predictions it was rendered from
+    pub fn vocalize(&self, text: &str, options: &DiacriticsOptions) -> Result<Vocalization> {
+        self.current().vocalize(text, options)
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{DiacriticsOptions, TokenPrediction};
+
+/// A pass over the predictions of a text before they are rendered, see
+/// [`Phonikud::add_post_processor`](crate::Phonikud::add_post_processor).
+///
+/// Passes may change the marks of a prediction, such as a house style for the qamats
+/// qatan or the vocal shva, replace its letter, e.g. to mask words, or drop it to leave the
+/// letter bare. Implemented for closures taking the same arguments as
+/// [`PostProcessor::process`].
+pub trait PostProcessor: Send + Sync {
+    /// Edit the `predictions` made over `text`, the text they are rendered over, which is
+    /// the input without nikud unless `options` keeps some of its marks. Predictions are
+    /// sorted by their byte offset into `text`, the letters of lexicon words have none.
+    fn process(
+        &self,
+        text: &str,
+        predictions: &mut Vec<TokenPrediction>,
+        options: &DiacriticsOptions,
+    );
+}
+
+impl<F> PostProcessor for F
+where
+    F: Fn(&str, &mut Vec<TokenPrediction>, &DiacriticsOptions) + Send + Sync,
+{
+    fn process(
+        &self,
+        text: &str,
+        predictions: &mut Vec<TokenPrediction>,
+        options: &DiacriticsOptions,
+    ) {
+        self(text, predictions, options)
+    }
+}
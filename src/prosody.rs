@@ -0,0 +1,79 @@
+//! Prosody hints read from the punctuation of a text
+
+use std::ops::Range;
+
+/// Punctuation read as a short pause
+const SHORT_PAUSES: &[char] = &[',', ';', ':', '—', '–', '\u{05c0}'];
+/// Punctuation read as a long pause, including the sof pasuq
+const LONG_PAUSES: &[char] = &['.', '!', '…', '\u{05c3}'];
+const QUESTION: char = '?';
+/// Closing quotes and brackets, a pause before them still counts
+const CLOSING: &[char] = &['"', '\'', '”', '’', '\u{05f4}', ')', ']', '»'];
+
+/// What a punctuation mark tells a speaker, ordered from the weakest hint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ProsodyHint {
+    /// Comma, semicolon, colon, dash or paseq
+    ShortPause,
+    /// Period, exclamation mark, ellipsis or sof pasuq
+    LongPause,
+    /// Question mark, the intonation rises before a long pause
+    Rising,
+}
+
+/// A prosody hint and the punctuation it was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProsodyEvent {
+    /// Byte range of the punctuation, a run such as `?!` is one event
+    pub range: Range<usize>,
+    pub hint: ProsodyHint,
+}
+
+/// The prosody hints of `text`, in order.
+///
+/// Works on the input, the vocalized text or the phonemes alike since they keep the
+/// punctuation. Punctuation glued to the next word, as in `3.5` or `1,000`, isn't a
+/// pause. In a run the strongest hint wins, a question mark over a period over a comma.
+pub fn events(text: &str) -> Vec<ProsodyEvent> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let end_of = |i: usize| chars.get(i).map_or(text.len(), |&(idx, _)| idx);
+    let mut events = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if hint(chars[i].1).is_none() {
+            i += 1;
+            continue;
+        }
+        let run_start = i;
+        let mut strongest = None;
+        while let Some(hint) = chars.get(i).and_then(|&(_, ch)| hint(ch)) {
+            strongest = strongest.max(Some(hint));
+            i += 1;
+        }
+        let run_end = i;
+        while chars.get(i).is_some_and(|&(_, ch)| CLOSING.contains(&ch)) {
+            i += 1;
+        }
+        // Part of a number, time or URL
+        let glued = chars.get(i).is_some_and(|&(_, ch)| !ch.is_whitespace());
+        if let Some(hint) = strongest.filter(|_| !glued) {
+            events.push(ProsodyEvent {
+                range: chars[run_start].0..end_of(run_end),
+                hint,
+            });
+        }
+    }
+    events
+}
+
+fn hint(ch: char) -> Option<ProsodyHint> {
+    match ch {
+        QUESTION => Some(ProsodyHint::Rising),
+        ch if LONG_PAUSES.contains(&ch) => Some(ProsodyHint::LongPause),
+        ch if SHORT_PAUSES.contains(&ch) => Some(ProsodyHint::ShortPause),
+        _ => None,
+    }
+}
@@ -0,0 +1,134 @@
+//! Python bindings, built as the `phonikud_rs` extension module with maturin
+
+use std::{collections::HashMap, sync::Arc};
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+};
+
+use crate::{
+    DiacriticsOptions, Lexicon, PREFIX_MARK, Phonikud, PhonikudError, STRESS_MARK, Thresholds,
+    VOCAL_SHVA_MARK, VocalShvaStyle, spelling::SpellingOptions,
+};
+
+fn to_py_err(error: PhonikudError) -> PyErr {
+    match error {
+        PhonikudError::InputTooLong { .. } => PyValueError::new_err(error.to_string()),
+        error => PyRuntimeError::new_err(error.to_string()),
+    }
+}
+
+#[pyclass(name = "Phonikud")]
+struct PyPhonikud(Phonikud);
+
+#[pymethods]
+impl PyPhonikud {
+    #[new]
+    fn new(model_path: &str, tokenizer_path: &str) -> PyResult<Self> {
+        Ok(Self(Phonikud::new(model_path, tokenizer_path).map_err(to_py_err)?))
+    }
+
+    /// Vocalize `text`. The keyword arguments mirror `DiacriticsOptions`, a `None`
+    /// marker is omitted from the output.
+    #[pyo3(signature = (
+        text,
+        *,
+        nikud = true,
+        dagesh = true,
+        shin_dot = true,
+        stress_mark = Some(STRESS_MARK.to_string()),
+        vocal_shva_mark = Some(VOCAL_SHVA_MARK.to_string()),
+        vocal_shva_hint = None,
+        prefix_mark = Some(PREFIX_MARK.to_string()),
+        mark_matres_lectionis = None,
+        ktiv_haser = false,
+        preserve_existing = false,
+        hebrew_runs_only = false,
+        orthography_constraints = false,
+        fix_final_forms = false,
+        normalize_presentation_forms = false,
+        respell = false,
+        stress_threshold = 0.5,
+        vocal_shva_threshold = 0.5,
+        prefix_threshold = 0.5,
+        lexicon = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn add_diacritics(
+        &mut self,
+        py: Python<'_>,
+        text: &str,
+        nikud: bool,
+        dagesh: bool,
+        shin_dot: bool,
+        stress_mark: Option<String>,
+        vocal_shva_mark: Option<String>,
+        vocal_shva_hint: Option<String>,
+        prefix_mark: Option<String>,
+        mark_matres_lectionis: Option<String>,
+        ktiv_haser: bool,
+        preserve_existing: bool,
+        hebrew_runs_only: bool,
+        orthography_constraints: bool,
+        fix_final_forms: bool,
+        normalize_presentation_forms: bool,
+        respell: bool,
+        stress_threshold: f32,
+        vocal_shva_threshold: f32,
+        prefix_threshold: f32,
+        lexicon: Option<HashMap<String, String>>,
+    ) -> PyResult<String> {
+        let options = DiacriticsOptions {
+            nikud,
+            dagesh,
+            shin_dot,
+            stress_mark,
+            vocal_shva_mark,
+            vocal_shva_style: vocal_shva_hint.map_or(VocalShvaStyle::Marked, VocalShvaStyle::Hint),
+            prefix_mark,
+            mark_matres_lectionis,
+            ktiv_haser,
+            preserve_existing,
+            hebrew_runs_only,
+            orthography_constraints,
+            fix_final_forms,
+            normalize_presentation_forms,
+            respell: respell.then(SpellingOptions::default),
+            thresholds: Thresholds {
+                stress: stress_threshold,
+                vocal_shva: vocal_shva_threshold,
+                prefix: prefix_threshold,
+            },
+            lexicon: lexicon.map(|lexicon| Arc::new(lexicon.into_iter().collect::<Lexicon>())),
+            ..Default::default()
+        };
+        // Inference doesn't touch Python objects, let other threads run meanwhile
+        py.detach(|| self.0.add_diacritics_with_options(text, &options))
+            .map_err(to_py_err)
+    }
+
+    /// Vocalize `text` and convert it into IPA phonemes
+    fn phonemize(&mut self, py: Python<'_>, text: &str) -> PyResult<String> {
+        py.detach(|| self.0.phonemize(text)).map_err(to_py_err)
+    }
+}
+
+#[pyfunction]
+fn remove_nikud(text: &str) -> String {
+    crate::text::remove_nikud(text)
+}
+
+/// Convert already vocalized text into IPA phonemes
+#[pyfunction]
+fn phonemize(vocalized: &str) -> String {
+    crate::phonemize::phonemize(vocalized)
+}
+
+#[pymodule]
+fn phonikud_rs(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyPhonikud>()?;
+    module.add_function(wrap_pyfunction!(remove_nikud, module)?)?;
+    module.add_function(wrap_pyfunction!(phonemize, module)?)?;
+    Ok(())
+}
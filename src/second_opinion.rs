@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use crate::{DiacriticsOptions, PREFIX_MARK, Phonikud, Result, text::remove_nikud};
+
+/// Error of a [`SecondOpinion`], e.g. a failed request to a remote vocalizer
+pub type OpinionError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Another vocalizer asked about the words the model is unsure of, e.g. a client of a
+/// remote nakdan that is slower but more accurate.
+///
+/// Implemented for closures taking the word and its text, like [`SecondOpinion::vocalize`].
+pub trait SecondOpinion: Send + Sync {
+    /// Vocalize `word`, a word of `text` without nikud and maybe with its punctuation.
+    ///
+    /// `None` and errors leave the word to the model. The letters of the answer must be
+    /// those of `word`, other answers are ignored too.
+    fn vocalize(&self, word: &str, text: &str) -> Result<Option<Opinion>, OpinionError>;
+}
+
+impl<F> SecondOpinion for F
+where
+    F: Fn(&str, &str) -> Result<Option<Opinion>, OpinionError> + Send + Sync,
+{
+    fn vocalize(&self, word: &str, text: &str) -> Result<Option<Opinion>, OpinionError> {
+        self(word, text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Opinion {
+    /// The vocalized word
+    pub text: String,
+    /// Probability of the answer being right, on the scale of the model's letter confidence
+    pub confidence: f32,
+}
+
+/// Vocalized text with the words the second opinion was asked about
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HybridVocalization {
+    pub text: String,
+    /// Words whose model confidence fell below the threshold, in order
+    pub consulted: Vec<ConsultedWord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConsultedWord {
+    /// Byte range of the word in the vocalized text
+    pub range: Range<usize>,
+    /// Lowest letter confidence of the model in the word
+    pub confidence: f32,
+    /// Answer of the second opinion, `None` when it had none, failed or changed the letters
+    pub opinion: Option<Opinion>,
+    /// The answer was more confident than the model and replaced its vocalization
+    pub replaced: bool,
+}
+
+impl Phonikud {
+    /// Vocalize `text`, asking `second_opinion` about the words with a letter of confidence
+    /// below `threshold` and keeping its answer when it's more confident than the model.
+    pub fn add_diacritics_with_second_opinion(
+        &mut self,
+        text: &str,
+        options: &DiacriticsOptions,
+        threshold: f32,
+        second_opinion: &dyn SecondOpinion,
+    ) -> Result<HybridVocalization> {
+        let report = self.add_diacritics_with_confidence(text, options, threshold)?;
+        let clean_text = remove_nikud(text);
+
+        let mut result = String::with_capacity(report.text.len());
+        let mut consulted = Vec::with_capacity(report.low_confidence.len());
+        let mut prev_index = 0;
+        for word in report.low_confidence {
+            result.push_str(&report.text[prev_index..word.range.start]);
+            prev_index = word.range.end;
+            let vocalized = &report.text[word.range];
+            let bare = letters(vocalized);
+            let opinion = match second_opinion.vocalize(&bare, &clean_text) {
+                Ok(opinion) => opinion.filter(|opinion| letters(&opinion.text) == bare),
+                #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+                Err(e) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = %e, word = %bare, "second opinion failed");
+                    None
+                }
+            };
+            let replaced = opinion
+                .as_ref()
+                .is_some_and(|opinion| opinion.confidence > word.confidence);
+
+            let start = result.len();
+            match &opinion {
+                Some(opinion) if replaced => result.push_str(&opinion.text),
+                _ => result.push_str(vocalized),
+            }
+            consulted.push(ConsultedWord {
+                range: start..result.len(),
+                confidence: word.confidence,
+                opinion,
+                replaced,
+            });
+        }
+        result.push_str(&report.text[prev_index..]);
+
+        Ok(HybridVocalization {
+            text: result,
+            consulted,
+        })
+    }
+}
+
+/// `word` without nikud and prefix marks
+fn letters(word: &str) -> String {
+    remove_nikud(word).replace(PREFIX_MARK, "")
+}
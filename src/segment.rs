@@ -0,0 +1,115 @@
+//! Split Hebrew text into sentences
+
+use std::ops::Range;
+
+/// Chars ending a sentence, including the Hebrew sof pasuq
+const TERMINATORS: &[char] = &['.', '!', '?', '…', '\u{05c3}'];
+/// Closing quotes and brackets that stay with the sentence they follow
+const CLOSING: &[char] = &['"', '\'', '”', '’', '\u{05f4}', '\u{05f3}', ')', ']', '»'];
+/// Abbreviations written with a period, e.g. `מס. 5` or `רח. הרצל`
+const ABBREVIATIONS: &[&str] = &["גב", "טל", "מס", "עמ", "פרופ", "רח", "שד"];
+
+/// Split `text` into sentences, see [`sentence_ranges`]
+pub fn sentences(text: &str) -> Vec<&str> {
+    sentence_ranges(text)
+        .into_iter()
+        .map(|range| &text[range])
+        .collect()
+}
+
+/// Byte ranges of the sentences of `text`, covering it without gaps. The
+/// whitespace after a sentence belongs to it.
+///
+/// A sentence ends at a line break or after `.`, `!`, `?`, `…` or a sof pasuq
+/// followed by whitespace, along with the closing quotes and brackets after it.
+/// Runs such as `?!` end the sentence once. Periods don't end a sentence after
+/// an initial (`ש. עגנון`), a common abbreviation (`רח. הרצל`) or in a number,
+/// and neither does an ellipsis in the middle of a line. Gershayim inside a
+/// word, as in `ד"ר` or `צה"ל`, are never taken for a closing quote.
+pub fn sentence_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for end in sentence_ends(text) {
+        let end = end + whitespace_len(&text[end..]);
+        if start < end {
+            ranges.push(start..end);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        ranges.push(start..text.len());
+    }
+    ranges
+}
+
+/// Byte offset right after every sentence, before the whitespace following it
+pub(crate) fn sentence_ends(text: &str) -> Vec<usize> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let end_of = |i: usize| chars.get(i).map_or(text.len(), |&(idx, _)| idx);
+    let mut ends = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i].1;
+        if ch == '\n' {
+            ends.push(end_of(i + 1));
+            i += 1;
+            continue;
+        }
+        if !TERMINATORS.contains(&ch) {
+            i += 1;
+            continue;
+        }
+
+        let run_start = i;
+        while chars.get(i).is_some_and(|&(_, ch)| TERMINATORS.contains(&ch)) {
+            i += 1;
+        }
+        let run: String = chars[run_start..i].iter().map(|&(_, ch)| ch).collect();
+        while chars.get(i).is_some_and(|&(_, ch)| CLOSING.contains(&ch)) {
+            i += 1;
+        }
+
+        // A terminator glued to the next word is part of a number, URL or acronym
+        let next = chars.get(i).map(|&(_, ch)| ch);
+        if next.is_some_and(|ch| !ch.is_whitespace()) {
+            continue;
+        }
+        let line_end = text[end_of(i)..]
+            .chars()
+            .take_while(|ch| ch.is_whitespace())
+            .any(|ch| ch == '\n');
+        let ellipsis = run.contains('…') || run.matches('.').count() > 1;
+        let ends_sentence = if run.contains(['!', '?', '\u{05c3}']) {
+            true
+        } else if ellipsis {
+            next.is_none() || line_end
+        } else {
+            !is_abbreviation(word_before(text, chars[run_start].0))
+        };
+        if ends_sentence {
+            ends.push(end_of(i));
+        }
+    }
+    ends
+}
+
+/// The word right before byte offset `end`, without opening quotes and brackets
+fn word_before(text: &str, end: usize) -> &str {
+    let before = &text[..end];
+    let start = before
+        .char_indices()
+        .rev()
+        .find(|&(_, ch)| ch.is_whitespace())
+        .map_or(0, |(idx, ch)| idx + ch.len_utf8());
+    before[start..].trim_start_matches(['"', '\'', '“', '(', '[', '«'])
+}
+
+fn is_abbreviation(word: &str) -> bool {
+    let mut chars = word.chars();
+    let initial = matches!((chars.next(), chars.next()), (Some(ch), None) if ch.is_alphabetic());
+    initial || ABBREVIATIONS.contains(&word)
+}
+
+fn whitespace_len(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
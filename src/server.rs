@@ -0,0 +1,196 @@
+//! HTTP microservice exposing the diacritization model as JSON endpoints
+
+use std::net::SocketAddr;
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AsyncPhonikud, DecodeWarning, DiacriticsOptions, Phonikud, PhonikudError, QamatsQatan,
+    Thresholds, TokenPrediction, VocalShvaStyle, spelling::SpellingOptions,
+};
+
+/// Pending requests allowed before new ones are rejected
+pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+/// Body of `POST /vocalize`
+#[derive(Debug, Deserialize)]
+pub struct VocalizeRequest {
+    pub text: String,
+    #[serde(default)]
+    pub options: RequestOptions,
+}
+
+/// Options accepted over HTTP, anything omitted keeps the [`DiacriticsOptions`] default
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RequestOptions {
+    pub nikud: Option<bool>,
+    pub dagesh: Option<bool>,
+    pub shin_dot: Option<bool>,
+    pub stress: Option<bool>,
+    pub vocal_shva: Option<bool>,
+    pub prefix: Option<bool>,
+    pub mark_matres_lectionis: Option<String>,
+    pub ktiv_haser: Option<bool>,
+    pub preserve_existing: Option<bool>,
+    pub keep_taamim: Option<bool>,
+    pub hebrew_runs_only: Option<bool>,
+    pub orthography_constraints: Option<bool>,
+    pub fix_final_forms: Option<bool>,
+    pub normalize_presentation_forms: Option<bool>,
+    pub respell: Option<bool>,
+    pub qamats_qatan: Option<QamatsQatan>,
+    pub vocal_shva_style: Option<VocalShvaStyle>,
+    pub stress_threshold: Option<f32>,
+    pub vocal_shva_threshold: Option<f32>,
+    pub prefix_threshold: Option<f32>,
+}
+
+impl From<RequestOptions> for DiacriticsOptions {
+    fn from(request: RequestOptions) -> Self {
+        let mut options = DiacriticsOptions::default();
+        let defaults = Thresholds::default();
+
+        options.nikud = request.nikud.unwrap_or(options.nikud);
+        options.dagesh = request.dagesh.unwrap_or(options.dagesh);
+        options.shin_dot = request.shin_dot.unwrap_or(options.shin_dot);
+        if request.stress == Some(false) {
+            options.stress_mark = None;
+        }
+        if request.vocal_shva == Some(false) {
+            options.vocal_shva_mark = None;
+        }
+        if request.prefix == Some(false) {
+            options.prefix_mark = None;
+        }
+        options.mark_matres_lectionis = request.mark_matres_lectionis;
+        options.ktiv_haser = request.ktiv_haser.unwrap_or(false);
+        options.preserve_existing = request.preserve_existing.unwrap_or(false);
+        options.keep_taamim = request.keep_taamim.unwrap_or(false);
+        options.hebrew_runs_only = request.hebrew_runs_only.unwrap_or(false);
+        options.orthography_constraints = request.orthography_constraints.unwrap_or(false);
+        options.fix_final_forms = request.fix_final_forms.unwrap_or(false);
+        options.normalize_presentation_forms =
+            request.normalize_presentation_forms.unwrap_or(false);
+        options.respell = request
+            .respell
+            .unwrap_or(false)
+            .then(SpellingOptions::default);
+        options.qamats_qatan = request.qamats_qatan.unwrap_or_default();
+        options.vocal_shva_style = request.vocal_shva_style.unwrap_or_default();
+        options.thresholds = Thresholds {
+            stress: request.stress_threshold.unwrap_or(defaults.stress),
+            vocal_shva: request.vocal_shva_threshold.unwrap_or(defaults.vocal_shva),
+            prefix: request.prefix_threshold.unwrap_or(defaults.prefix),
+        };
+        options
+    }
+}
+
+/// Response of `POST /vocalize`
+#[derive(Debug, Serialize)]
+pub struct VocalizeResponse {
+    pub text: String,
+    pub predictions: Vec<PredictionResponse>,
+    /// Letters left without nikud, see [`crate::Vocalization::warnings`]
+    pub warnings: Vec<DecodeWarning>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PredictionResponse {
+    pub char: char,
+    pub start: usize,
+    pub end: usize,
+    pub nikud_class: usize,
+    pub nikud: String,
+    pub dagesh: bool,
+    pub confidence: f32,
+    pub shin_class: Option<usize>,
+    pub stress: bool,
+    pub vocal_shva: bool,
+    pub prefix: bool,
+}
+
+impl From<&TokenPrediction> for PredictionResponse {
+    fn from(prediction: &TokenPrediction) -> Self {
+        Self {
+            char: prediction.char,
+            start: prediction.start,
+            end: prediction.end,
+            nikud_class: prediction.nikud_class,
+            nikud: prediction.nikud.clone(),
+            dagesh: prediction.has_dagesh(),
+            confidence: prediction.confidence,
+            shin_class: prediction.shin_class,
+            stress: prediction.stress,
+            vocal_shva: prediction.vocal_shva,
+            prefix: prediction.prefix,
+        }
+    }
+}
+
+struct ErrorResponse(PhonikudError);
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            PhonikudError::InputTooLong { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            PhonikudError::WorkerStopped => StatusCode::SERVICE_UNAVAILABLE,
+            PhonikudError::Overloaded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            PhonikudError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = serde_json::json!({ "error": self.0.to_string() });
+        (status, Json(body)).into_response()
+    }
+}
+
+async fn vocalize(
+    State(phonikud): State<AsyncPhonikud>,
+    Json(request): Json<VocalizeRequest>,
+) -> Result<Json<VocalizeResponse>, ErrorResponse> {
+    let vocalization = phonikud
+        .vocalize_async(request.text, request.options.into())
+        .await
+        .map_err(ErrorResponse)?;
+
+    Ok(Json(VocalizeResponse {
+        predictions: vocalization.predictions.iter().map(Into::into).collect(),
+        warnings: vocalization.warnings,
+        text: vocalization.text,
+    }))
+}
+
+/// Routes: `POST /vocalize` and `GET /health`
+pub fn router(phonikud: AsyncPhonikud) -> Router {
+    Router::new()
+        .route("/vocalize", post(vocalize))
+        .route("/health", get(|| async { "ok" }))
+        .with_state(phonikud)
+}
+
+/// Serve `phonikud` over HTTP on `addr` until the process is stopped, see
+/// [`serve_with_queue_capacity`]
+pub async fn serve(phonikud: Phonikud, addr: SocketAddr) -> std::io::Result<()> {
+    serve_with_queue_capacity(phonikud, addr, DEFAULT_QUEUE_CAPACITY).await
+}
+
+/// Serve `phonikud` over HTTP on `addr` with up to `queue_capacity` requests waiting
+/// for the model. Requests past it get `429 Too Many Requests` right away, so a load
+/// spike doesn't grow the memory of the server.
+pub async fn serve_with_queue_capacity(
+    phonikud: Phonikud,
+    addr: SocketAddr,
+    queue_capacity: usize,
+) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let phonikud = phonikud.into_async(queue_capacity).reject_when_full();
+    axum::serve(listener, router(phonikud)).await
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Cursor},
+    sync::Arc,
+};
+
+use ndarray::ArrayD;
+use ort::{
+    execution_providers::{CPUExecutionProvider, ExecutionProviderDispatch},
+    session::{
+        RunOptions, Session,
+        builder::{GraphOptimizationLevel, PrepackedWeights, SessionBuilder},
+    },
+    value::{Value, ValueType},
+};
+
+use crate::{
+    Precision, Provider,
+    error::{PhonikudError, Result},
+    info::ModelHeader,
+    model::{ModelInputs, TensorInfo},
+};
+
+/// Where the ONNX model is loaded from, kept so more sessions can be created later
+#[derive(Clone)]
+pub(crate) enum ModelSource {
+    File(String),
+    Bytes(Arc<[u8]>),
+    /// The model file mapped into memory, its pages are shared with other processes
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<memmap2::Mmap>),
+}
+
+impl ModelSource {
+    pub(crate) fn commit(&self, session_builder: SessionBuilder) -> ort::Result<Session> {
+        match self {
+            ModelSource::File(path) => session_builder.commit_from_file(path),
+            ModelSource::Bytes(bytes) => session_builder.commit_from_memory(bytes),
+            // ORT format models keep their initializers in the mapping instead of copying
+            // them, the mapping outlives the session since the source is stored next to it
+            #[cfg(feature = "mmap")]
+            ModelSource::Mapped(mmap) => session_builder
+                .with_config_entry("session.use_ort_model_bytes_directly", "1")?
+                .with_config_entry("session.use_ort_model_bytes_for_initializers", "1")?
+                .commit_from_memory(mmap),
+        }
+    }
+
+    /// Read the opset, weight types and metadata of the model again
+    pub(crate) fn header(&self) -> std::io::Result<ModelHeader> {
+        match self {
+            ModelSource::File(path) => ModelHeader::read(BufReader::new(File::open(path)?)),
+            ModelSource::Bytes(bytes) => ModelHeader::read(Cursor::new(bytes)),
+            #[cfg(feature = "mmap")]
+            ModelSource::Mapped(mmap) => ModelHeader::read(Cursor::new(&mmap[..])),
+        }
+    }
+
+    /// Precision named by the file name of the model, [`Precision::Auto`] when it's
+    /// loaded from memory
+    fn precision(&self) -> Precision {
+        match self {
+            ModelSource::File(path) => Precision::of_file(path),
+            _ => Precision::Auto,
+        }
+    }
+
+    /// SHA-256 digest (hex) of the model
+    #[cfg(feature = "disk-cache")]
+    pub(crate) fn sha256(&self) -> Result<String> {
+        use crate::checksum::{sha256_bytes, sha256_file};
+        match self {
+            ModelSource::File(path) => sha256_file(std::path::Path::new(path)),
+            ModelSource::Bytes(bytes) => Ok(sha256_bytes(bytes)),
+            #[cfg(feature = "mmap")]
+            ModelSource::Mapped(mmap) => Ok(sha256_bytes(mmap)),
+        }
+    }
+
+    /// Map the model file at `path` into memory
+    #[cfg(feature = "mmap")]
+    pub(crate) fn map(path: &str) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // Safety: the model file must not be modified or truncated while it is mapped
+        let mmap = unsafe { memmap2::Mmap::map(&file) }?;
+        Ok(ModelSource::Mapped(Arc::new(mmap)))
+    }
+}
+
+/// An ORT session along with what is needed to create more like it
+pub(crate) struct OnnxSession {
+    /// `None` between runs with [`SessionOptions::unload_between_runs`]
+    session: Option<Session>,
+    /// Inputs and outputs of the model, read once since the session may be unloaded
+    signature: (Vec<TensorInfo>, Vec<TensorInfo>),
+    source: ModelSource,
+    options: SessionOptions,
+}
+
+impl OnnxSession {
+    pub(crate) fn load(source: ModelSource, mut options: SessionOptions) -> Result<Self> {
+        // Sessions spawned from this one get a clone of the options, and with it the container.
+        // The container outlives the sessions, an unloaded model would keep its weights.
+        if !options.unload_between_runs {
+            options.prepacked_weights.get_or_insert_with(SharedWeights::new);
+        }
+        options
+            .intra_threads
+            .get_or_insert_with(|| source.precision().default_intra_threads());
+        let session = Self::commit(&source, &options)?;
+        let signature = Self::read_signature(&session);
+
+        Ok(Self {
+            session: (!options.unload_between_runs).then_some(session),
+            signature,
+            source,
+            options,
+        })
+    }
+
+    fn commit(source: &ModelSource, options: &SessionOptions) -> Result<Session> {
+        options
+            .session_builder()
+            .and_then(|session_builder| source.commit(session_builder))
+            .map_err(PhonikudError::ModelLoad)
+    }
+
+    /// Create another session of the same model with the same settings
+    pub(crate) fn spawn(&self) -> Result<Self> {
+        Self::load(self.source.clone(), self.options.clone())
+    }
+
+    /// Load another model with the same settings, its weights are pre-packed separately
+    pub(crate) fn load_other(&self, source: ModelSource) -> Result<Self> {
+        let options = SessionOptions {
+            prepacked_weights: None,
+            ..self.options.clone()
+        };
+        Self::load(source, options)
+    }
+
+    /// Names and shapes of the model inputs and outputs
+    pub(crate) fn signature(&self) -> (Vec<TensorInfo>, Vec<TensorInfo>) {
+        self.signature.clone()
+    }
+
+    fn read_signature(session: &Session) -> (Vec<TensorInfo>, Vec<TensorInfo>) {
+        let info = |name: &str, value_type: &ValueType| TensorInfo {
+            name: name.to_string(),
+            shape: value_type
+                .tensor_shape()
+                .map(|shape| shape.iter().map(|&dim| usize::try_from(dim).ok()).collect())
+                .unwrap_or_default(),
+        };
+        let inputs = session
+            .inputs
+            .iter()
+            .map(|input| info(&input.name, &input.input_type))
+            .collect();
+        let outputs = session
+            .outputs
+            .iter()
+            .map(|output| info(&output.name, &output.output_type))
+            .collect();
+        (inputs, outputs)
+    }
+
+    /// Custom metadata entry `key` of the model, if any. An unloaded model is loaded
+    /// again to read it.
+    pub(crate) fn metadata(&self, key: &str) -> Option<String> {
+        let loaded;
+        let session = match &self.session {
+            Some(session) => session,
+            None => {
+                loaded = Self::commit(&self.source, &self.options).ok()?;
+                &loaded
+            }
+        };
+        session.metadata().ok()?.custom(key).ok()?
+    }
+
+    /// Opset, weight types and metadata, read from the source of the session
+    pub(crate) fn header(&self) -> Option<ModelHeader> {
+        self.source.header().ok()
+    }
+
+    /// Execution providers the session was registered with and ONNX Runtime supports,
+    /// followed by the CPU fallback
+    pub(crate) fn providers(&self) -> Vec<String> {
+        let mut providers: Vec<String> = self
+            .options
+            .execution_providers
+            .iter()
+            // The dispatch only exposes the name of its provider through `Debug`
+            .filter_map(|provider| format!("{provider:?}").split(' ').next().map(str::to_string))
+            .filter(|name| Provider::from_ort_name(name).is_none_or(Provider::is_available))
+            .collect();
+        let cpu = Provider::Cpu.ort_name();
+        providers.retain(|name| name != cpu);
+        providers.push(cpu.to_string());
+        providers
+    }
+
+    /// Options shrinking the CPU arena back after every run with
+    /// [`SessionOptions::arena_shrinkage`], `None` when runs need no options
+    pub(crate) fn run_options(&self) -> Result<Option<RunOptions>> {
+        // Without the arena there is nothing to shrink, and ORT rejects the entry
+        if !self.options.arena_shrinkage || self.options.cpu_arena == Some(false) {
+            return Ok(None);
+        }
+        let mut run_options = RunOptions::new().map_err(PhonikudError::Inference)?;
+        run_options
+            .add_config_entry("memory.enable_memory_arena_shrinkage", "cpu:0")
+            .map_err(PhonikudError::Inference)?;
+        Ok(Some(run_options))
+    }
+
+    /// Run the model, terminated early through `run_options` if given. An unloaded
+    /// model is loaded for the run and unloaded again after it.
+    pub(crate) fn run(
+        &mut self,
+        inputs: ModelInputs,
+        run_options: Option<&RunOptions>,
+    ) -> Result<Vec<ArrayD<f32>>> {
+        let shape = [inputs.batch_size, inputs.seq_len()];
+        let to_tensor =
+            |values: Vec<i64>| Value::from_array((shape, values)).map_err(PhonikudError::Inference);
+        let mut session_inputs = HashMap::new();
+        session_inputs.insert("input_ids".to_string(), to_tensor(inputs.input_ids)?);
+        session_inputs.insert("attention_mask".to_string(), to_tensor(inputs.attention_mask)?);
+        session_inputs.insert("token_type_ids".to_string(), to_tensor(inputs.token_type_ids)?);
+
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => self.session.insert(Self::commit(&self.source, &self.options)?),
+        };
+        let outputs = match run_options {
+            Some(run_options) => session.run_with_options(session_inputs, run_options),
+            None => session.run(session_inputs),
+        }
+        .map_err(PhonikudError::Inference)?;
+
+        // In the order of the model outputs, the caller finds the heads by name with
+        // its `output_order`
+        let logits = (0..outputs.len())
+            .map(|idx| {
+                outputs[idx]
+                    .try_extract_array::<f32>()
+                    .map(|logits| logits.to_owned())
+                    .map_err(|e| PhonikudError::InvalidModelOutputs(format!("output {idx}: {e}")))
+            })
+            .collect();
+        drop(outputs);
+        if self.options.unload_between_runs {
+            self.session = None;
+        }
+        logits
+    }
+}
+
+/// ORT session settings, shared by every session created for a model
+#[derive(Clone)]
+pub(crate) struct SessionOptions {
+    pub(crate) execution_providers: Vec<ExecutionProviderDispatch>,
+    /// `None` until the model is loaded, see [`Precision::default_intra_threads`]
+    pub(crate) intra_threads: Option<usize>,
+    pub(crate) inter_threads: Option<usize>,
+    /// Logical cores the intra-op threads are pinned to, one thread each
+    pub(crate) thread_affinity: Option<Vec<usize>>,
+    pub(crate) optimization_level: OptimizationLevel,
+    pub(crate) memory_pattern: Option<bool>,
+    pub(crate) cpu_arena: Option<bool>,
+    /// Return the memory of the CPU arena to the system after every run
+    pub(crate) arena_shrinkage: bool,
+    /// Drop the session after every run and load it again for the next one
+    pub(crate) unload_between_runs: bool,
+    /// Weights pre-packed for the CPU kernels, shared by the sessions of a model
+    pub(crate) prepacked_weights: Option<SharedWeights>,
+}
+
+/// A [`PrepackedWeights`] container that can be moved across threads
+#[derive(Clone)]
+pub(crate) struct SharedWeights(PrepackedWeights);
+
+// Safety: ORT guards the container with a mutex, sessions of any thread may use it
+unsafe impl Send for SharedWeights {}
+unsafe impl Sync for SharedWeights {}
+
+impl SharedWeights {
+    fn new() -> Self {
+        Self(PrepackedWeights::new())
+    }
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            execution_providers: Vec::new(),
+            intra_threads: None,
+            inter_threads: None,
+            thread_affinity: None,
+            optimization_level: OptimizationLevel::Level3,
+            memory_pattern: None,
+            cpu_arena: None,
+            arena_shrinkage: false,
+            unload_between_runs: false,
+            prepacked_weights: None,
+        }
+    }
+}
+
+impl SessionOptions {
+    pub(crate) fn session_builder(&self) -> ort::Result<SessionBuilder> {
+        let intra_threads = match &self.thread_affinity {
+            Some(cores) => cores.len().max(1),
+            None => self
+                .intra_threads
+                .unwrap_or_else(|| Precision::Auto.default_intra_threads()),
+        };
+        let mut session_builder = SessionBuilder::new()?
+            .with_optimization_level(self.optimization_level.into())?
+            .with_intra_threads(intra_threads)?;
+        if let Some(cores) = self.thread_affinity.as_deref().filter(|cores| cores.len() > 1) {
+            session_builder = session_builder
+                .with_config_entry("session.intra_op_thread_affinities", affinities(cores))?;
+        }
+        if let Some(threads) = self.inter_threads {
+            session_builder = session_builder
+                .with_parallel_execution(true)?
+                .with_inter_threads(threads)?;
+        }
+        if let Some(enable) = self.memory_pattern {
+            session_builder = session_builder.with_memory_pattern(enable)?;
+        }
+        if let Some(SharedWeights(weights)) = &self.prepacked_weights {
+            session_builder = session_builder.with_prepacked_weights(weights)?;
+        }
+
+        let mut execution_providers = self.execution_providers.clone();
+        if let Some(enable) = self.cpu_arena {
+            execution_providers.push(
+                CPUExecutionProvider::default()
+                    .with_arena_allocator(enable)
+                    .build(),
+            );
+        }
+        session_builder.with_execution_providers(execution_providers)
+    }
+}
+
+/// The value of `session.intra_op_thread_affinities` pinning the intra-op threads to
+/// `cores`. ORT numbers the logical processors from 1 and leaves out the calling
+/// thread, which takes the first core.
+fn affinities(cores: &[usize]) -> String {
+    cores[1..]
+        .iter()
+        .map(|core| (core + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Copyable mirror of [`GraphOptimizationLevel`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OptimizationLevel {
+    Disable,
+    Level1,
+    Level2,
+    Level3,
+}
+
+impl From<GraphOptimizationLevel> for OptimizationLevel {
+    fn from(level: GraphOptimizationLevel) -> Self {
+        match level {
+            GraphOptimizationLevel::Disable => OptimizationLevel::Disable,
+            GraphOptimizationLevel::Level1 => OptimizationLevel::Level1,
+            GraphOptimizationLevel::Level2 => OptimizationLevel::Level2,
+            GraphOptimizationLevel::Level3 => OptimizationLevel::Level3,
+        }
+    }
+}
+
+impl From<OptimizationLevel> for GraphOptimizationLevel {
+    fn from(level: OptimizationLevel) -> Self {
+        match level {
+            OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+            OptimizationLevel::Level1 => GraphOptimizationLevel::Level1,
+            OptimizationLevel::Level2 => GraphOptimizationLevel::Level2,
+            OptimizationLevel::Level3 => GraphOptimizationLevel::Level3,
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Normalize common typing variants of user-generated text before diacritization
+
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    hebrew::{has_final_form, is_final_form, is_hebrew_letter, to_final_form, to_regular_form},
+    lexicon::Lexicon,
+    normalize::{Alignment, original_offset},
+    text::is_nikud,
+};
+
+/// Ending an abbreviation or acronym, after which a letter keeps its regular form
+const ABBREVIATION_MARKS: &[char] = &['\'', '"', '\u{05f3}', '\u{05f4}'];
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SpellingOptions {
+    /// Fix final letters written in the middle of a word and regular forms ending one
+    pub final_forms: bool,
+    /// Shorten runs of three or more yods or vavs to two
+    pub repeated_letters: bool,
+    /// Known spellings. A word missing from it is replaced by the one spelling found in
+    /// it with a yod or vav added or dropped, words with several such spellings are kept.
+    pub lexicon: Option<Arc<Lexicon>>,
+}
+
+impl Default for SpellingOptions {
+    fn default() -> Self {
+        Self {
+            final_forms: true,
+            repeated_letters: true,
+            lexicon: None,
+        }
+    }
+}
+
+/// Kind of a [`SpellingFix`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// A final letter inside a word, replaced by its regular form
+    FinalFormInWord,
+    /// A regular letter ending a word, replaced by its final form
+    MissingFinalForm,
+    ExtraYod,
+    ExtraVav,
+    MissingYod,
+    MissingVav,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpellingFix {
+    pub variant: Variant,
+    /// The letter in the input text, empty for an added letter, and its replacement in
+    /// the normalized text, empty for a dropped letter
+    pub alignment: Alignment,
+}
+
+/// Normalized text with the fixes applied to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Respelled {
+    pub text: String,
+    /// Fixes in order, text outside them is unchanged
+    pub fixes: Vec<SpellingFix>,
+}
+
+impl Respelled {
+    /// Map a byte offset in the normalized text back to the input text.
+    ///
+    /// Offsets inside a replaced letter map to the start of the original letter.
+    pub fn original_offset(&self, offset: usize) -> usize {
+        original_offset(self.fixes.iter().map(|fix| &fix.alignment), offset)
+    }
+}
+
+/// A letter of a word as it will be written, `text` is empty once it's dropped
+#[derive(Debug, Clone)]
+struct Letter {
+    original: Range<usize>,
+    text: String,
+    variant: Option<Variant>,
+}
+
+/// Fix misplaced final letters and missing or extra yods and vavs in the Hebrew words of
+/// `text`, leaving the rest of it as written.
+///
+/// Words found in the lexicon of `options` are never changed. One letter words and
+/// letters followed by a geresh or gershayim, as in `מ'` or `מנכ"ל`, keep their form.
+pub fn respell(text: &str, options: &SpellingOptions) -> Respelled {
+    let mut result = String::with_capacity(text.len());
+    let mut fixes = Vec::new();
+    let mut prev_index = 0;
+
+    for word in words(text) {
+        let Some(letters) = respell_word(text, word.clone(), options) else {
+            continue;
+        };
+        result.push_str(&text[prev_index..word.start]);
+        prev_index = word.start;
+        for letter in letters {
+            result.push_str(&text[prev_index..letter.original.start]);
+            let start = result.len();
+            result.push_str(&letter.text);
+            if let Some(variant) = letter.variant {
+                let alignment = Alignment {
+                    original: letter.original.clone(),
+                    normalized: start..result.len(),
+                };
+                fixes.push(SpellingFix { variant, alignment });
+            }
+            prev_index = letter.original.end;
+        }
+    }
+    result.push_str(&text[prev_index..]);
+
+    Respelled {
+        text: result,
+        fixes,
+    }
+}
+
+/// Fix final letters written inside a word and regular letters ending one, leaving the
+/// yods and vavs as written. Every letter is replaced by one of the same length, so the
+/// fixes have the same range in the input and the normalized text.
+pub fn fix_final_forms(text: &str) -> Respelled {
+    let options = SpellingOptions {
+        final_forms: true,
+        repeated_letters: false,
+        lexicon: None,
+    };
+    respell(text, &options)
+}
+
+/// Letters written in the wrong form for their place in the word, see [`fix_final_forms`]
+pub fn misplaced_final_forms(text: &str) -> Vec<SpellingFix> {
+    fix_final_forms(text).fixes
+}
+
+/// The letters of `word` with their fixes, `None` when it's kept as written
+fn respell_word(text: &str, word: Range<usize>, options: &SpellingOptions) -> Option<Vec<Letter>> {
+    let written = &text[word.clone()];
+    let bare = !written.chars().any(is_nikud);
+    let lexicon = options.lexicon.as_deref().filter(|_| bare);
+    if lexicon.is_some_and(|lexicon| lexicon.get(written).is_some()) {
+        return None;
+    }
+
+    let mut letters: Vec<Letter> = written
+        .char_indices()
+        .filter(|&(_, ch)| is_hebrew_letter(ch))
+        .map(|(idx, ch)| Letter {
+            original: word.start + idx..word.start + idx + ch.len_utf8(),
+            text: ch.to_string(),
+            variant: None,
+        })
+        .collect();
+    if letters.len() < 2 {
+        return None;
+    }
+
+    if options.final_forms {
+        let last = letters.len() - 1;
+        for letter in &mut letters[..last] {
+            let ch = first_char(&letter.text);
+            if is_final_form(ch) {
+                letter.text = to_regular_form(ch).to_string();
+                letter.variant = Some(Variant::FinalFormInWord);
+            }
+        }
+        let abbreviation = text[word.end..].starts_with(ABBREVIATION_MARKS);
+        let letter = &mut letters[last];
+        let ch = first_char(&letter.text);
+        if has_final_form(ch) && !abbreviation {
+            letter.text = to_final_form(ch).to_string();
+            letter.variant = Some(Variant::MissingFinalForm);
+        }
+    }
+
+    if options.repeated_letters {
+        let mut run = 0;
+        for idx in 0..letters.len() {
+            let ch = first_char(&letters[idx].text);
+            let repeated = idx > 0 && first_char(&text[letters[idx - 1].original.clone()]) == ch;
+            run = if repeated { run + 1 } else { 1 };
+            let variant = match ch {
+                'י' => Variant::ExtraYod,
+                'ו' => Variant::ExtraVav,
+                _ => continue,
+            };
+            if run > 2 {
+                letters[idx].text.clear();
+                letters[idx].variant = Some(variant);
+            }
+        }
+    }
+
+    if let Some(candidate) = lexicon.and_then(|lexicon| lexicon_candidate(&letters, lexicon)) {
+        letters = candidate;
+    }
+
+    letters
+        .iter()
+        .any(|letter| letter.variant.is_some())
+        .then_some(letters)
+}
+
+/// The only spelling of `letters` in `lexicon` with one yod or vav added or dropped,
+/// unless the word is already spelled as it lists it
+fn lexicon_candidate(letters: &[Letter], lexicon: &Lexicon) -> Option<Vec<Letter>> {
+    let spelled = |letters: &[Letter]| letters.iter().map(|l| l.text.as_str()).collect::<String>();
+    if lexicon.get(&spelled(letters)).is_some() {
+        return None;
+    }
+
+    let mut found: Option<(String, Vec<Letter>)> = None;
+    let mut candidates = Vec::new();
+    // A leading vav is the conjunction, letters are only added or dropped after it
+    for idx in 1..letters.len() {
+        if letters[idx].variant.is_none() {
+            let variant = match first_char(&letters[idx].text) {
+                'י' => Some(Variant::ExtraYod),
+                'ו' => Some(Variant::ExtraVav),
+                _ => None,
+            };
+            if let Some(variant) = variant {
+                let mut candidate = letters.to_vec();
+                candidate[idx].text.clear();
+                candidate[idx].variant = Some(variant);
+                candidates.push(candidate);
+            }
+        }
+        let at = letters[idx].original.start;
+        for (ch, variant) in [('י', Variant::MissingYod), ('ו', Variant::MissingVav)] {
+            let mut candidate = letters.to_vec();
+            let added = Letter {
+                original: at..at,
+                text: ch.to_string(),
+                variant: Some(variant),
+            };
+            candidate.insert(idx, added);
+            candidates.push(candidate);
+        }
+    }
+    for candidate in candidates {
+        let word = spelled(&candidate);
+        if lexicon.get(&word).is_none() {
+            continue;
+        }
+        match &found {
+            Some((other, _)) if *other != word => return None,
+            Some(_) => {}
+            None => found = Some((word, candidate)),
+        }
+    }
+    found.map(|(_, candidate)| candidate)
+}
+
+/// Byte ranges of the runs of Hebrew letters and their marks in `text`
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (idx, ch) in text.char_indices() {
+        match start {
+            _ if is_hebrew_letter(ch) => {
+                start.get_or_insert(idx);
+            }
+            Some(_) if is_nikud(ch) && ch != '|' => {}
+            Some(word_start) => {
+                words.push(word_start..idx);
+                start = None;
+            }
+            None => {}
+        }
+    }
+    if let Some(word_start) = start {
+        words.push(word_start..text.len());
+    }
+    words
+}
+
+fn first_char(text: &str) -> char {
+    text.chars().next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lexicon(words: &[&str]) -> Option<Arc<Lexicon>> {
+        let entries = words
+            .iter()
+            .map(|word| (word.to_string(), word.to_string()));
+        Some(Arc::new(entries.collect()))
+    }
+
+    #[test]
+    fn runs_of_yods_and_vavs_are_shortened_to_two() {
+        let respelled = respell("גדוווול היייתה", &SpellingOptions::default());
+        assert_eq!(respelled.text, "גדוול הייתה");
+        let variants: Vec<Variant> = respelled.fixes.iter().map(|fix| fix.variant).collect();
+        assert_eq!(
+            variants,
+            [Variant::ExtraVav, Variant::ExtraVav, Variant::ExtraYod]
+        );
+        assert!(
+            respelled
+                .fixes
+                .iter()
+                .all(|fix| fix.alignment.normalized.is_empty())
+        );
+
+        let kept = respell("גדוול הייתה", &SpellingOptions::default());
+        assert_eq!(kept.text, "גדוול הייתה");
+        assert!(kept.fixes.is_empty());
+
+        let options = SpellingOptions {
+            repeated_letters: false,
+            ..SpellingOptions::default()
+        };
+        assert_eq!(respell("גדוווול", &options).text, "גדוווול");
+    }
+
+    #[test]
+    fn lexicon_spelling_is_used_when_it_is_the_only_one() {
+        let options = SpellingOptions {
+            lexicon: lexicon(&["שלום"]),
+            ..SpellingOptions::default()
+        };
+        let respelled = respell("שלם", &options);
+        assert_eq!(respelled.text, "שלום");
+        assert_eq!(respelled.fixes.len(), 1);
+        assert_eq!(respelled.fixes[0].variant, Variant::MissingVav);
+        assert_eq!(respelled.fixes[0].alignment.original, 4..4);
+
+        // Adding a vav or a yod both give a known word
+        let options = SpellingOptions {
+            lexicon: lexicon(&["שלום", "שלים"]),
+            ..SpellingOptions::default()
+        };
+        assert_eq!(respell("שלם", &options).text, "שלם");
+
+        // Words the lexicon knows are kept as written
+        let options = SpellingOptions {
+            lexicon: lexicon(&["שלום", "שלם"]),
+            ..SpellingOptions::default()
+        };
+        assert_eq!(respell("שלם", &options).text, "שלם");
+    }
+}
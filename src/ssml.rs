@@ -0,0 +1,118 @@
+//! Turn vocalized Hebrew into SSML for text to speech engines
+
+use crate::{
+    hebrew::is_hebrew_letter,
+    phonemize::{is_mark, phonemize},
+    text::{remove_nikud, strip_phonikud_markers},
+};
+
+/// How the pronunciation of a Hebrew word is passed to the engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SsmlTarget {
+    /// `<phoneme alphabet="ipa">` with the IPA from [`phonemize`]
+    #[default]
+    Azure,
+    /// `<phoneme alphabet="ipa">` with the IPA from [`phonemize`]
+    Google,
+    /// `<sub alias>` with the standard nikud, which espeak-ng reads with its Hebrew rules
+    Espeak,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SsmlOptions {
+    pub target: SsmlTarget,
+    /// `xml:lang` of the document
+    pub lang: String,
+    /// Wrap the content in `<voice name="...">`, required by Azure
+    pub voice: Option<String>,
+}
+
+impl Default for SsmlOptions {
+    fn default() -> Self {
+        Self {
+            target: SsmlTarget::default(),
+            lang: "he-IL".to_string(),
+            voice: None,
+        }
+    }
+}
+
+/// Build an SSML document from the output of [`crate::Phonikud::add_diacritics`].
+///
+/// Every Hebrew word is written without nikud and tagged with its pronunciation,
+/// the stress and vocal shva marks end up in the IPA (or nikud) passed to the engine.
+/// Other text is escaped and kept as is.
+pub fn to_ssml(vocalized: &str, options: &SsmlOptions) -> String {
+    let mut body = String::with_capacity(vocalized.len() * 4);
+    let mut word_start = None;
+
+    for (idx, ch) in vocalized
+        .char_indices()
+        .chain(std::iter::once((vocalized.len(), ' ')))
+    {
+        // A geresh belongs to the word, it changes the pronunciation of ג, ז and צ
+        let in_word = is_hebrew_letter(ch)
+            || word_start.is_some() && (is_mark(ch) || matches!(ch, '|' | '\'' | '\u{05f3}'));
+        if in_word {
+            word_start.get_or_insert(idx);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            push_word(&mut body, &vocalized[start..idx], options.target);
+        }
+        if idx < vocalized.len() {
+            body.push_str(&escape(&vocalized[idx..idx + ch.len_utf8()]));
+        }
+    }
+
+    let mut ssml = format!(
+        r#"<speak version="1.0" xmlns="http://www.w3.org/2001/10/synthesis" xml:lang="{}">"#,
+        escape(&options.lang)
+    );
+    match &options.voice {
+        Some(voice) => {
+            ssml.push_str(&format!(
+                r#"<voice name="{}">{body}</voice>"#,
+                escape(voice)
+            ));
+        }
+        None => ssml.push_str(&body),
+    }
+    ssml.push_str("</speak>");
+    ssml
+}
+
+fn push_word(ssml: &mut String, word: &str, target: SsmlTarget) {
+    let text = escape(&remove_nikud(word));
+    match target {
+        SsmlTarget::Azure | SsmlTarget::Google => {
+            let ipa = escape(&phonemize(word));
+            ssml.push_str(&format!(
+                r#"<phoneme alphabet="ipa" ph="{ipa}">{text}</phoneme>"#
+            ));
+        }
+        SsmlTarget::Espeak => {
+            let alias = escape(&strip_phonikud_markers(word));
+            ssml.push_str(&format!(r#"<sub alias="{alias}">{text}</sub>"#));
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
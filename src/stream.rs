@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use crate::{DiacriticsOptions, Phonikud, Result};
+
+impl Phonikud {
+    /// Vocalize lines lazily, yielding one result per input line in order.
+    ///
+    /// Consecutive short lines are joined into a single model call, so only about
+    /// one chunk of input is held in memory at a time.
+    pub fn add_diacritics_stream<I>(&mut self, lines: I) -> DiacriticsStream<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.add_diacritics_stream_with_options(lines, DiacriticsOptions::default())
+    }
+
+    /// Like [`Phonikud::add_diacritics_stream`] with custom options
+    pub fn add_diacritics_stream_with_options<I>(
+        &mut self,
+        lines: I,
+        options: DiacriticsOptions,
+    ) -> DiacriticsStream<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        DiacriticsStream {
+            phonikud: self,
+            options,
+            lines: lines.into_iter(),
+            next_line: None,
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+/// Iterator returned by [`Phonikud::add_diacritics_stream`]
+pub struct DiacriticsStream<'a, I> {
+    phonikud: &'a mut Phonikud,
+    options: DiacriticsOptions,
+    lines: I,
+    /// A line read ahead that didn't fit in the previous batch
+    next_line: Option<String>,
+    ready: VecDeque<Result<String>>,
+}
+
+impl<I: Iterator<Item = String>> DiacriticsStream<'_, I> {
+    /// Read lines until the batch would exceed one model chunk
+    fn next_batch(&mut self) -> Vec<String> {
+        let max_chars = self.phonikud.inner.max_chunk_chars();
+        let mut batch = Vec::new();
+        let mut chars = 0;
+        while let Some(line) = self.next_line.take().or_else(|| self.lines.next()) {
+            let line_chars = line.chars().count() + 1;
+            // Lines with their own newlines would be split apart, keep them alone
+            if !batch.is_empty() && (chars + line_chars > max_chars || line.contains('\n')) {
+                self.next_line = Some(line);
+                break;
+            }
+            chars += line_chars;
+            let alone = line.contains('\n');
+            batch.push(line);
+            if alone || chars >= max_chars {
+                break;
+            }
+        }
+        batch
+    }
+
+    fn vocalize_batch(&mut self, batch: Vec<String>) {
+        if let [line] = batch.as_slice() {
+            let result = self
+                .phonikud
+                .add_diacritics_with_options(line, &self.options);
+            self.ready.push_back(result);
+            return;
+        }
+
+        match self
+            .phonikud
+            .add_diacritics_with_options(&batch.join("\n"), &self.options)
+        {
+            // Text outside Hebrew letters is copied as is, so the newlines line up
+            Ok(vocalized) if vocalized.matches('\n').count() + 1 == batch.len() => {
+                self.ready
+                    .extend(vocalized.split('\n').map(|line| Ok(line.to_string())));
+            }
+            // Retry line by line so an error only affects the line that caused it
+            _ => {
+                for line in &batch {
+                    let result = self
+                        .phonikud
+                        .add_diacritics_with_options(line, &self.options);
+                    self.ready.push_back(result);
+                }
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for DiacriticsStream<'_, I> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() {
+            let batch = self.next_batch();
+            if batch.is_empty() {
+                return None;
+            }
+            self.vocalize_batch(batch);
+        }
+        self.ready.pop_front()
+    }
+}
@@ -0,0 +1,68 @@
+//! Vocalize SRT and WebVTT subtitles, e.g. to dub them with a Hebrew voice
+
+use std::ops::Range;
+
+/// Byte ranges of the cue text of an SRT or WebVTT file.
+///
+/// Cue numbers, identifiers and timings are left out, and so are the WebVTT
+/// header, `NOTE`, `STYLE` and `REGION` blocks, which have no timing line.
+/// Formatting tags (`<i>`, `<c.yellow>`, `<00:00:01.000>`) and SSA override
+/// codes (`{\an8}`) inside the text are left out as well.
+pub fn cue_text_ranges(subtitles: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut in_cue_text = false;
+    let mut line_start = 0;
+
+    for line in subtitles.split_inclusive('\n') {
+        let range = line_start..line_start + line.len();
+        line_start = range.end;
+        let content = line.trim_end_matches(['\n', '\r']);
+
+        if content.trim().is_empty() {
+            in_cue_text = false;
+        } else if content.contains("-->") {
+            in_cue_text = true;
+        } else if in_cue_text {
+            text_ranges(content, range.start, &mut ranges);
+        }
+    }
+
+    ranges
+}
+
+/// Collect the text of a cue line starting at `offset`, without its tags
+fn text_ranges(line: &str, offset: usize, ranges: &mut Vec<Range<usize>>) {
+    let mut text_start = 0;
+    let mut idx = 0;
+    while let Some(pos) = line[idx..].find(['<', '{']) {
+        let start = idx + pos;
+        let close = if line[start..].starts_with('<') {
+            '>'
+        } else {
+            '}'
+        };
+        let Some(end) = line[start..].find(close).map(|end| start + end + 1) else {
+            break;
+        };
+        if text_start < start {
+            ranges.push(offset + text_start..offset + start);
+        }
+        text_start = end;
+        idx = end;
+    }
+    if text_start < line.len() {
+        ranges.push(offset + text_start..offset + line.len());
+    }
+}
+
+#[cfg(feature = "model")]
+impl crate::Phonikud {
+    /// Vocalize the cue text of an SRT or WebVTT file, keeping timings and tags as they are
+    pub fn add_diacritics_subtitles(
+        &mut self,
+        subtitles: &str,
+        options: &crate::DiacriticsOptions,
+    ) -> crate::Result<String> {
+        self.add_diacritics_in_ranges(subtitles, &cue_text_ranges(subtitles), options)
+    }
+}
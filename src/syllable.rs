@@ -0,0 +1,91 @@
+//! Split vocalized Hebrew words into syllables and locate the stress
+
+use std::ops::Range;
+
+use crate::{
+    options::{STRESS_MARK, VOCAL_SHVA_MARK},
+    phonemize::{Cluster, DAGESH, HOLAM, Piece, SHVA, pieces},
+};
+
+/// A vocalized word split into syllables
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Word {
+    /// Byte range of the word in the vocalized text
+    pub range: Range<usize>,
+    /// Syllables with their marks, concatenating them yields the word
+    pub syllables: Vec<String>,
+    /// Index of the stressed syllable, the last one when the word has no stress mark
+    pub stress_index: usize,
+}
+
+/// Whether a vowel point (or a vocal shva) sits on the letter
+fn has_vowel(cluster: &Cluster) -> bool {
+    cluster.marks.chars().any(is_vowel_point)
+        || cluster.has(SHVA) && cluster.has(VOCAL_SHVA_MARK)
+}
+
+/// Holam male or shuruk, a vav that only carries a vowel
+fn is_vowel_vav(cluster: &Cluster) -> bool {
+    let vowels = cluster.marks.chars().filter(|&mark| is_vowel_point(mark)).count();
+    cluster.letter == 'ו'
+        && (cluster.has(HOLAM) && vowels == 1 || cluster.has(DAGESH) && vowels == 0)
+}
+
+fn is_nucleus(cluster: &Cluster) -> bool {
+    has_vowel(cluster) || is_vowel_vav(cluster)
+}
+
+/// Split every Hebrew word of `vocalized` into syllables.
+///
+/// A syllable starts at each letter carrying a vowel, and consonants without
+/// one close the syllable before them. The stress is read from the phonikud
+/// stress mark (U+05AB). Text other than Hebrew letters and marks separates
+/// words and is not returned.
+pub fn syllabify(vocalized: &str) -> Vec<Word> {
+    pieces(vocalized)
+        .filter_map(|piece| match piece {
+            Piece::Word(clusters) => Some(split_word(vocalized, &clusters)),
+            Piece::Other(_) => None,
+        })
+        .collect()
+}
+
+fn split_word(vocalized: &str, clusters: &[Cluster]) -> Word {
+    // A vowel vav after a consonant without a vowel is that consonant's vowel
+    let starts: Vec<usize> = (0..clusters.len())
+        .filter(|&idx| {
+            let cluster = &clusters[idx];
+            let carries_vowel_vav =
+                !is_nucleus(cluster) && clusters.get(idx + 1).is_some_and(is_vowel_vav);
+            let attached_vav = is_vowel_vav(cluster) && idx > 0 && !is_nucleus(&clusters[idx - 1]);
+            is_nucleus(cluster) && !attached_vav || carries_vowel_vav
+        })
+        .collect();
+
+    // Consonants before the first vowel belong to the first syllable
+    let mut bounds: Vec<usize> = starts.iter().skip(1).copied().collect();
+    bounds.insert(0, 0);
+    bounds.push(clusters.len());
+
+    let mut syllables = Vec::with_capacity(bounds.len() - 1);
+    let mut stress_index = None;
+    for (syllable, window) in bounds.windows(2).enumerate() {
+        let group = &clusters[window[0]..window[1]];
+        if group.iter().any(|cluster| cluster.has(STRESS_MARK)) {
+            stress_index.get_or_insert(syllable);
+        }
+        let range = group[0].range.start..group[group.len() - 1].range.end;
+        syllables.push(vocalized[range].to_string());
+    }
+
+    Word {
+        range: clusters[0].range.start..clusters[clusters.len() - 1].range.end,
+        stress_index: stress_index.unwrap_or(syllables.len() - 1),
+        syllables,
+    }
+}
+
+fn is_vowel_point(mark: char) -> bool {
+    matches!(mark, '\u{05b1}'..='\u{05bb}' | '\u{05c7}')
+}
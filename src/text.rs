@@ -0,0 +1,420 @@
+//! Helpers for working with vocalized Hebrew text
+
+use std::{collections::BTreeMap, ops::Range};
+
+use unicode_normalization::char::canonical_combining_class;
+
+use crate::{
+    hebrew::is_hebrew_letter,
+    options::STRESS_MARK,
+    phonemize::is_mark,
+};
+
+/// Categories of the Hebrew marks, to choose what [`strip_marks`] removes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MarkCategories {
+    /// Vowel points, shva and the hataf vowels included (U+05B0 to U+05BB, U+05C7)
+    pub vowels: bool,
+    /// Dagesh or mappiq (U+05BC) and rafe (U+05BF)
+    pub dagesh: bool,
+    /// Shin and sin dots (U+05C1, U+05C2)
+    pub shin_dots: bool,
+    /// Cantillation marks (U+0591 to U+05AF) and the upper and lower dots, except ole
+    pub taamim: bool,
+    /// The phonikud stress (ole, U+05AB) and vocal shva (meteg, U+05BD) marks, and
+    /// the `|` prefix marker when it's between the letters of a word
+    pub phonikud_markers: bool,
+}
+
+impl MarkCategories {
+    /// Every mark, what [`remove_nikud`] strips
+    pub const ALL: Self = Self {
+        vowels: true,
+        dagesh: true,
+        shin_dots: true,
+        taamim: true,
+        phonikud_markers: true,
+    };
+    /// The marks of standard nikud: vowels, dagesh and shin dots
+    pub const NIKUD: Self = Self {
+        taamim: false,
+        phonikud_markers: false,
+        ..Self::ALL
+    };
+    /// No marks, [`strip_marks`] keeps the text as is
+    pub const NONE: Self = Self {
+        vowels: false,
+        dagesh: false,
+        shin_dots: false,
+        taamim: false,
+        phonikud_markers: false,
+    };
+
+    /// Whether the mark `ch` is in one of the categories, `|` never is on its own
+    pub fn contains(&self, ch: char) -> bool {
+        match ch {
+            '\u{05b0}'..='\u{05bb}' | '\u{05c7}' => self.vowels,
+            '\u{05bc}' | '\u{05bf}' => self.dagesh,
+            '\u{05c1}' | '\u{05c2}' => self.shin_dots,
+            '\u{05ab}' | '\u{05bd}' => self.phonikud_markers,
+            '\u{0591}'..='\u{05af}' | '\u{05c4}' | '\u{05c5}' => self.taamim,
+            _ => false,
+        }
+    }
+}
+
+impl Default for MarkCategories {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Remove nikud, cantillation and phonikud marks, leaving the bare letters.
+///
+/// Everything else is kept byte for byte, including Hebrew punctuation such as
+/// the maqaf and sof pasuq, whitespace, emoji, joiners and bidi controls. A `|`
+/// is only removed as a prefix marker, between the letters of a word.
+pub fn remove_nikud(text: &str) -> String {
+    strip_marks(text, MarkCategories::ALL)
+}
+
+/// Remove the marks of `categories` from `text`, e.g. [`MarkCategories::NIKUD`] to
+/// keep the taamim of a biblical text
+pub fn strip_marks(text: &str, categories: MarkCategories) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut prev_index = 0;
+    for range in mark_ranges(text, categories) {
+        stripped.push_str(&text[prev_index..range.start]);
+        prev_index = range.end;
+    }
+    stripped.push_str(&text[prev_index..]);
+    stripped
+}
+
+/// Whether `text` contains any nikud, cantillation or phonikud marks
+pub fn has_nikud(text: &str) -> bool {
+    mark_ranges(text, MarkCategories::ALL).next().is_some()
+}
+
+/// The letters of `text` without their marks, and the marks following every char by its
+/// byte offset in the letters. Marks before the first char are dropped.
+pub(crate) fn split_marks(text: &str) -> (String, BTreeMap<usize, String>) {
+    let mut letters = String::with_capacity(text.len());
+    let mut marks: BTreeMap<usize, String> = BTreeMap::new();
+    let mut prev_index = 0;
+    for range in mark_ranges(text, MarkCategories::ALL) {
+        letters.push_str(&text[prev_index..range.start]);
+        prev_index = range.end;
+        let Some((idx, _)) = letters.char_indices().next_back() else {
+            continue;
+        };
+        marks.entry(idx).or_default().push_str(&text[range]);
+    }
+    letters.push_str(&text[prev_index..]);
+    (letters, marks)
+}
+
+/// Whether `text` contains any of the marks of `categories`
+#[cfg(feature = "model")]
+pub(crate) fn has_marks(text: &str, categories: MarkCategories) -> bool {
+    mark_ranges(text, categories).next().is_some()
+}
+
+/// Byte ranges of the marks of `categories` in `text`
+fn mark_ranges(
+    text: &str,
+    categories: MarkCategories,
+) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut chars = text.char_indices();
+    // The last char kept, the text stripped once is stripped the same way again
+    let mut prev = None;
+    std::iter::from_fn(move || {
+        while let Some((idx, ch)) = chars.next() {
+            let strip = if ch == '|' {
+                // Marks stripped along with it don't separate it from the next letter
+                let next = chars.clone().map(|(_, ch)| ch).find(|&ch| !categories.contains(ch));
+                categories.phonikud_markers
+                    && prev.is_some_and(|prev| is_hebrew_letter(prev) || is_mark(prev))
+                    && next.is_some_and(is_hebrew_letter)
+            } else {
+                categories.contains(ch)
+            };
+            if strip {
+                return Some(idx..idx + ch.len_utf8());
+            }
+            prev = Some(ch);
+        }
+        None
+    })
+}
+
+/// Remove the stress marks added by phonikud, keeping the rest of the nikud
+pub fn strip_stress_marks(text: &str) -> String {
+    text.replace(STRESS_MARK, "")
+}
+
+/// Remove the phonikud specific stress, vocal shva and prefix markers, leaving standard nikud.
+///
+/// Like [`remove_nikud`], a `|` is only removed between the letters of a word.
+pub fn strip_phonikud_markers(text: &str) -> String {
+    let markers = MarkCategories {
+        phonikud_markers: true,
+        ..MarkCategories::NONE
+    };
+    strip_marks(text, markers)
+}
+
+/// Order of the marks following each Hebrew letter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MarkOrder {
+    /// The order the marks are predicted in: shin dot, nikud, stress, vocal shva
+    #[default]
+    Model,
+    /// Unicode canonical order, as produced by NFC. Vowels come before dagesh
+    /// and the shin dot, which some fonts render poorly.
+    Nfc,
+    /// The conventional Hebrew order: shin/sin dot, dagesh, vowel, meteg, then accents
+    Hebrew,
+}
+
+/// Reorder the marks after every Hebrew letter of `text`, the marks themselves
+/// and everything else are left untouched so byte offsets stay valid
+pub fn reorder_marks(text: &str, order: MarkOrder) -> String {
+    if order == MarkOrder::Model {
+        return text.to_string();
+    }
+    let rank = |mark: char| match order {
+        MarkOrder::Nfc => canonical_combining_class(mark),
+        _ => hebrew_mark_rank(mark),
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut marks: Vec<char> = Vec::new();
+    let mut after_letter = false;
+    for ch in text.chars() {
+        if after_letter && is_mark(ch) {
+            marks.push(ch);
+            continue;
+        }
+        // Stable sort, marks of the same rank keep their order
+        marks.sort_by_key(|&mark| rank(mark));
+        result.extend(marks.drain(..));
+        result.push(ch);
+        after_letter = is_hebrew_letter(ch);
+    }
+    marks.sort_by_key(|&mark| rank(mark));
+    result.extend(marks);
+    result
+}
+
+fn hebrew_mark_rank(mark: char) -> u8 {
+    match mark {
+        '\u{05c1}' | '\u{05c2}' => 0,              // shin and sin dot
+        '\u{05bc}' => 1,                           // dagesh or mappiq
+        '\u{05bf}' => 2,                           // rafe
+        '\u{05b0}'..='\u{05bb}' | '\u{05c7}' => 3, // vowels
+        '\u{05bd}' => 4,                           // meteg, the vocal shva mark
+        '\u{0591}'..='\u{05af}' => 5,              // accents, including the stress mark
+        _ => 6,
+    }
+}
+
+/// Whether `ch` is a Hebrew mark or the prefix marker, the characters `remove_nikud` strips
+pub(crate) fn is_nikud(ch: char) -> bool {
+    is_mark(ch) || ch == '|'
+}
+
+/// Like [`remove_nikud`], reusing the allocation of `clean_text`
+#[cfg(feature = "model")]
+pub(crate) fn remove_nikud_into(text: &str, clean_text: &mut String) {
+    clean_text.clear();
+    let mut prev_index = 0;
+    for range in mark_ranges(text, MarkCategories::ALL) {
+        clean_text.push_str(&text[prev_index..range.start]);
+        prev_index = range.end;
+    }
+    clean_text.push_str(&text[prev_index..]);
+}
+
+/// Remove nikud and phonikud marks, also returning the original byte offset of every byte kept
+#[cfg(feature = "model")]
+pub(crate) fn remove_nikud_with_offsets(text: &str) -> (String, Vec<usize>) {
+    strip_marks_with_offsets(text, MarkCategories::ALL)
+}
+
+/// Like [`strip_marks`], also returning the original byte offset of every byte kept
+#[cfg(feature = "model")]
+pub(crate) fn strip_marks_with_offsets(
+    text: &str,
+    categories: MarkCategories,
+) -> (String, Vec<usize>) {
+    let mut clean_text = String::with_capacity(text.len());
+    let mut offset_map = Vec::with_capacity(text.len() + 1);
+    let mut keep = |from: usize, to: usize| {
+        clean_text.push_str(&text[from..to]);
+        offset_map.extend(from..to);
+    };
+
+    let mut prev_index = 0;
+    for range in mark_ranges(text, categories) {
+        keep(prev_index, range.start);
+        prev_index = range.end;
+    }
+    keep(prev_index, text.len());
+    offset_map.push(text.len());
+
+    (clean_text, offset_map)
+}
+
+/// Start of every char of a text, to bring byte offsets from elsewhere onto its char
+/// boundaries before slicing it, such as the offsets of byte level tokenizers which
+/// can fall inside a multi-byte char
+#[cfg(feature = "model")]
+pub(crate) struct CharOffsets {
+    starts: Vec<usize>,
+    len: usize,
+}
+
+#[cfg(feature = "model")]
+impl CharOffsets {
+    pub(crate) fn new(text: &str) -> Self {
+        Self {
+            starts: text.char_indices().map(|(idx, _)| idx).collect(),
+            len: text.len(),
+        }
+    }
+
+    /// Start of the char holding byte `offset`, the end of the text for offsets past it
+    pub(crate) fn floor(&self, offset: usize) -> usize {
+        if offset >= self.len {
+            return self.len;
+        }
+        let idx = self.starts.partition_point(|&start| start <= offset);
+        self.starts[idx - 1]
+    }
+
+    /// Start of the first char from byte `offset` on, the end of the text after the last
+    pub(crate) fn ceil(&self, offset: usize) -> usize {
+        let idx = self.starts.partition_point(|&start| start < offset);
+        self.starts.get(idx).copied().unwrap_or(self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Code points of the Hebrew block each mark category strips
+    fn category(ch: char) -> Option<&'static str> {
+        match ch {
+            '\u{05b0}'..='\u{05bb}' | '\u{05c7}' => Some("vowels"),
+            '\u{05bc}' | '\u{05bf}' => Some("dagesh"),
+            '\u{05c1}' | '\u{05c2}' => Some("shin_dots"),
+            '\u{05ab}' | '\u{05bd}' => Some("phonikud_markers"),
+            '\u{0591}'..='\u{05af}' | '\u{05c4}' | '\u{05c5}' => Some("taamim"),
+            _ => None,
+        }
+    }
+
+    fn only(name: &str) -> MarkCategories {
+        MarkCategories {
+            vowels: name == "vowels",
+            dagesh: name == "dagesh",
+            shin_dots: name == "shin_dots",
+            taamim: name == "taamim",
+            phonikud_markers: name == "phonikud_markers",
+        }
+    }
+
+    #[test]
+    fn helpers_over_the_hebrew_block() {
+        let names = ["vowels", "dagesh", "shin_dots", "taamim", "phonikud_markers"];
+        for ch in '\u{0590}'..='\u{05ff}' {
+            let text = format!("ב{ch}ג");
+            let stripped = "בג";
+            let expected = |strips: bool| if strips { stripped } else { text.as_str() };
+            let category = category(ch);
+            let code = format!("U+{:04X}", ch as u32);
+
+            assert_eq!(remove_nikud(&text), expected(category.is_some()), "{code}");
+            assert_eq!(has_nikud(&text), category.is_some(), "{code}");
+            assert_eq!(strip_marks(&text, MarkCategories::ALL), remove_nikud(&text), "{code}");
+            assert_eq!(strip_marks(&text, MarkCategories::NONE), text, "{code}");
+            let nikud = matches!(category, Some("vowels" | "dagesh" | "shin_dots"));
+            assert_eq!(strip_marks(&text, MarkCategories::NIKUD), expected(nikud), "{code}");
+            assert_eq!(MarkCategories::ALL.contains(ch), category.is_some(), "{code}");
+            for name in names {
+                let strips = category == Some(name);
+                assert_eq!(strip_marks(&text, only(name)), expected(strips), "{code} {name}");
+                assert_eq!(only(name).contains(ch), strips, "{code} {name}");
+            }
+            assert_eq!(strip_stress_marks(&text), expected(ch == '\u{05ab}'), "{code}");
+            let marker = matches!(ch, '\u{05ab}' | '\u{05bd}');
+            assert_eq!(strip_phonikud_markers(&text), expected(marker), "{code}");
+        }
+    }
+
+    #[test]
+    fn phonikud_markers_are_stripped_between_letters_only() {
+        assert_eq!(strip_phonikud_markers("הַ|בַּ\u{05ab}יִת"), "הַבַּיִת");
+        assert_eq!(strip_phonikud_markers("וְ\u{05bd}|שָׁלוֹם"), "וְשָׁלוֹם");
+        assert_eq!(strip_phonikud_markers("cat a.txt | grep ש"), "cat a.txt | grep ש");
+        assert_eq!(strip_phonikud_markers("א | ב"), "א | ב");
+    }
+
+    #[test]
+    fn punctuation_of_the_block_is_kept() {
+        // Maqaf, paseq, sof pasuq, nun hafukha, geresh and gershayim
+        let text = "א־ב׀ג׃ד׆ה׳ו״";
+        assert_eq!(remove_nikud(text), text);
+        assert!(!has_nikud(text));
+    }
+
+    #[test]
+    fn nfc_order_matches_unicode_normalization() {
+        use unicode_normalization::UnicodeNormalization;
+
+        let letters: Vec<char> = ('\u{05d0}'..='\u{05ea}').collect();
+        let marks: Vec<char> = ('\u{0591}'..='\u{05c7}').filter(|&ch| is_mark(ch)).collect();
+        // xorshift, so every run checks the same strings
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        for _ in 0..100_000 {
+            let mut text = String::new();
+            for _ in 0..1 + next(4) {
+                text.push(letters[next(letters.len())]);
+                for _ in 0..next(5) {
+                    text.push(marks[next(marks.len())]);
+                }
+            }
+            let nfc: String = text.nfc().collect();
+            assert_eq!(reorder_marks(&text, MarkOrder::Nfc), nfc, "{text:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "model")]
+    fn char_offsets_widen_to_the_char_boundaries() {
+        // Letters of 2 bytes around an emoji of 4
+        let chars = CharOffsets::new("ש😀ל");
+        let floors = [0, 0, 2, 2, 2, 2, 6, 6, 8, 8];
+        let ceils = [0, 2, 2, 6, 6, 6, 6, 8, 8, 8];
+        for (offset, (floor, ceil)) in floors.into_iter().zip(ceils).enumerate() {
+            assert_eq!(chars.floor(offset), floor, "floor({offset})");
+            assert_eq!(chars.ceil(offset), ceil, "ceil({offset})");
+        }
+
+        let chars = CharOffsets::new("");
+        assert_eq!((chars.floor(0), chars.ceil(0)), (0, 0));
+        assert_eq!((chars.floor(3), chars.ceil(3)), (0, 0));
+    }
+}
@@ -0,0 +1,166 @@
+//! Pure Rust inference with tract, used instead of ONNX Runtime when the `tract` feature is enabled
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Cursor},
+    path::Path,
+    sync::Arc,
+};
+
+use ndarray::{ArrayD, IxDyn};
+use tract_onnx::prelude::*;
+
+use crate::{
+    error::{PhonikudError, Result},
+    info::ModelHeader,
+    model::{ModelInputs, TensorInfo},
+};
+
+/// An optimized tract plan, cheap to clone and shared by every worker
+#[derive(Clone)]
+pub(crate) struct TractSession {
+    plan: Arc<TypedRunnableModel>,
+    input_names: Vec<String>,
+    inputs: Vec<TensorInfo>,
+    outputs: Vec<TensorInfo>,
+    /// `metadata_props` of the ONNX model
+    metadata: Arc<HashMap<String, String>>,
+    /// Read along with the model, tract doesn't keep the opset and weight types
+    header: Option<Arc<ModelHeader>>,
+}
+
+impl TractSession {
+    pub(crate) fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let model = tract_onnx::onnx()
+            .model_for_path(&path)
+            .map_err(|e| PhonikudError::Tract(format!("failed to load model: {e:#}")))?;
+        let header = File::open(path).map(BufReader::new).and_then(ModelHeader::read);
+        Self::optimize(model, header.ok())
+    }
+
+    pub(crate) fn from_bytes(model: &[u8]) -> Result<Self> {
+        let header = ModelHeader::read(Cursor::new(model)).ok();
+        let model = tract_onnx::onnx()
+            .model_for_read(&mut Cursor::new(model))
+            .map_err(|e| PhonikudError::Tract(format!("failed to load model: {e:#}")))?;
+        Self::optimize(model, header)
+    }
+
+    /// Fix the inputs to `[1, sequence_length]` i64 tensors and optimize for that shape
+    fn optimize(mut model: InferenceModel, header: Option<ModelHeader>) -> Result<Self> {
+        let session = (|| {
+            let sequence_length = model.symbols.sym("sequence_length");
+            let fact = InferenceFact::dt_shape(
+                i64::datum_type(),
+                tvec!(1.to_dim(), sequence_length.to_dim()),
+            );
+            for idx in 0..model.inputs.len() {
+                model.set_input_fact(idx, fact.clone())?;
+            }
+            let input_names: Vec<String> = model
+                .input_outlets()?
+                .iter()
+                .map(|outlet| model.node(outlet.node).name.clone())
+                .collect();
+            let metadata = model
+                .properties
+                .iter()
+                .filter_map(|(key, value)| {
+                    let key = key.strip_prefix("onnx.metadata_props.")?;
+                    let value = value.try_as_plain_ram().ok()?.to_scalar::<String>().ok()?.clone();
+                    Some((key.to_string(), value))
+                })
+                .collect();
+
+            let model = model.into_optimized()?;
+            let info = |name: String, fact: &TypedFact| TensorInfo {
+                name,
+                shape: fact
+                    .shape
+                    .iter()
+                    .map(|dim| dim.as_i64().map(|dim| dim as usize))
+                    .collect(),
+            };
+            let inputs = (0..model.inputs.len())
+                .map(|idx| Ok(info(input_names[idx].clone(), model.input_fact(idx)?)))
+                .collect::<TractResult<_>>()?;
+            let outputs = model
+                .output_outlets()?
+                .iter()
+                .enumerate()
+                .map(|(idx, &outlet)| {
+                    let name = model
+                        .outlet_label(outlet)
+                        .unwrap_or(&model.node(outlet.node).name)
+                        .to_string();
+                    Ok(info(name, model.output_fact(idx)?))
+                })
+                .collect::<TractResult<_>>()?;
+
+            Ok::<_, TractError>(Self {
+                plan: model.into_runnable()?,
+                input_names,
+                inputs,
+                outputs,
+                metadata: Arc::new(metadata),
+                header: header.map(Arc::new),
+            })
+        })();
+
+        session.map_err(|e| PhonikudError::Tract(format!("failed to optimize model: {e:#}")))
+    }
+
+    /// Names and shapes of the model inputs and outputs
+    pub(crate) fn signature(&self) -> (Vec<TensorInfo>, Vec<TensorInfo>) {
+        (self.inputs.clone(), self.outputs.clone())
+    }
+
+    /// Custom metadata entry `key` of the model, if any
+    pub(crate) fn metadata(&self, key: &str) -> Option<String> {
+        self.metadata.get(key).cloned()
+    }
+
+    /// Opset, weight types and metadata of the model file
+    pub(crate) fn header(&self) -> Option<ModelHeader> {
+        self.header.as_deref().cloned()
+    }
+
+    pub(crate) fn run(&self, inputs: ModelInputs) -> Result<Vec<ArrayD<f32>>> {
+        let shape = [inputs.batch_size, inputs.seq_len()];
+        let mut values = TVec::new();
+        for name in &self.input_names {
+            let data = match name.as_str() {
+                "input_ids" => &inputs.input_ids,
+                "attention_mask" => &inputs.attention_mask,
+                "token_type_ids" => &inputs.token_type_ids,
+                name => {
+                    return Err(PhonikudError::Tract(format!(
+                        "unexpected model input {name}"
+                    )));
+                }
+            };
+            let tensor = Tensor::from_shape(&shape, data)
+                .map_err(|e| PhonikudError::Tract(e.to_string()))?;
+            values.push(tensor.into_tvalue());
+        }
+
+        let outputs = self
+            .plan
+            .run(values)
+            .map_err(|e| PhonikudError::Tract(format!("inference failed: {e:#}")))?;
+
+        outputs
+            .iter()
+            .enumerate()
+            .map(|(idx, output)| {
+                let logits = output.to_plain_array_view::<f32>().map_err(|e| {
+                    PhonikudError::InvalidModelOutputs(format!("output {idx}: {e}"))
+                })?;
+                let shape = logits.shape().to_vec();
+                ArrayD::from_shape_vec(IxDyn(&shape), logits.iter().copied().collect())
+                    .map_err(|e| PhonikudError::InvalidModelOutputs(format!("output {idx}: {e}")))
+            })
+            .collect()
+    }
+}
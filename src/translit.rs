@@ -0,0 +1,344 @@
+//! Transliterate vocalized Hebrew (nikud, stress and vocal shva marks) into Latin letters
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    options::{STRESS_MARK, VOCAL_SHVA_MARK},
+    phonemize::{Cluster, DAGESH, HOLAM, Piece, SIN_DOT, pieces},
+};
+
+/// Combining acute accent, written after the stressed vowel
+const ACUTE: char = '\u{0301}';
+
+/// Latin spelling produced by [`transliterate`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TranslitScheme {
+    /// The academic style of the SBL Handbook of Style, e.g. `šālôm`: every consonant
+    /// including alef (`ʾ`) and ayin (`ʿ`), the spirants with a line below or above
+    /// (`ḇ`, `ḵ`, `p̄`), long vowels with a macron, vowel letters as a circumflex and a
+    /// consonant doubled for a dagesh forte
+    #[default]
+    Sbl,
+    /// Plain Latin letters read in the modern Israeli pronunciation, e.g. `shalom`, as on
+    /// road signs. Alef, ayin and the vowel letters aren't written.
+    Simplified,
+}
+
+/// Options of [`transliterate`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TranslitOptions {
+    pub scheme: TranslitScheme,
+    /// Put an acute accent on the stressed vowel, e.g. `šālốm`
+    pub stress: bool,
+    /// Written for a vocal shva instead of the `ə` of [`TranslitScheme::Sbl`] or the `e`
+    /// of [`TranslitScheme::Simplified`], e.g. a superscript `ᵉ` for learners
+    pub vocal_shva: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Vowel {
+    Patah,
+    Qamats,
+    QamatsQatan,
+    Segol,
+    Tsere,
+    Hiriq,
+    Holam,
+    Qubuts,
+    HatafPatah,
+    HatafSegol,
+    HatafQamats,
+    VocalShva,
+    /// Vav with a dagesh
+    Shuruk,
+    /// Holam followed by a vav or a final he
+    HolamMale,
+    /// Hiriq followed by a yod
+    HiriqYod,
+    /// Tsere or segol followed by a yod
+    EYod,
+    /// Qamats followed by a final he
+    QamatsHe,
+}
+
+impl Vowel {
+    fn latin(self, scheme: TranslitScheme) -> &'static str {
+        match scheme {
+            TranslitScheme::Sbl => match self {
+                Vowel::Patah => "a",
+                Vowel::Qamats => "ā",
+                Vowel::QamatsQatan => "o",
+                Vowel::Segol => "e",
+                Vowel::Tsere => "ē",
+                Vowel::Hiriq => "i",
+                Vowel::Holam => "ō",
+                Vowel::Qubuts => "u",
+                Vowel::HatafPatah => "ă",
+                Vowel::HatafSegol => "ĕ",
+                Vowel::HatafQamats => "ŏ",
+                Vowel::VocalShva => "ə",
+                Vowel::Shuruk => "û",
+                Vowel::HolamMale => "ô",
+                Vowel::HiriqYod => "î",
+                Vowel::EYod => "ê",
+                Vowel::QamatsHe => "â",
+            },
+            TranslitScheme::Simplified => match self {
+                Vowel::Patah | Vowel::Qamats | Vowel::HatafPatah | Vowel::QamatsHe => "a",
+                Vowel::Segol | Vowel::Tsere | Vowel::HatafSegol | Vowel::VocalShva => "e",
+                Vowel::EYod => "e",
+                Vowel::Hiriq | Vowel::HiriqYod => "i",
+                Vowel::QamatsQatan | Vowel::Holam | Vowel::HatafQamats | Vowel::HolamMale => "o",
+                Vowel::Qubuts | Vowel::Shuruk => "u",
+            },
+        }
+    }
+
+    /// The vowel spelled with the unmarked vowel letter `letter` after it, `last` when the
+    /// letter ends the word
+    fn with_vowel_letter(self, letter: char, last: bool) -> Option<Vowel> {
+        match (self, letter) {
+            (Vowel::Hiriq, 'י') => Some(Vowel::HiriqYod),
+            (Vowel::Tsere | Vowel::Segol, 'י') => Some(Vowel::EYod),
+            (Vowel::Holam, 'ו') => Some(Vowel::HolamMale),
+            (Vowel::Qubuts, 'ו') => Some(Vowel::Shuruk),
+            (Vowel::Holam, 'ה') if last => Some(Vowel::HolamMale),
+            (Vowel::Qamats, 'ה') if last => Some(Vowel::QamatsHe),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the letter has no marks but the stress, e.g. a vowel letter
+fn is_bare(cluster: &Cluster) -> bool {
+    cluster.marks.split(STRESS_MARK).all(str::is_empty) && !cluster.geresh
+}
+
+fn vowel_of(cluster: &Cluster) -> Option<Vowel> {
+    cluster.marks.chars().find_map(|mark| match mark {
+        '\u{05b1}' => Some(Vowel::HatafSegol),
+        '\u{05b2}' => Some(Vowel::HatafPatah),
+        '\u{05b3}' => Some(Vowel::HatafQamats),
+        '\u{05b4}' => Some(Vowel::Hiriq),
+        '\u{05b5}' => Some(Vowel::Tsere),
+        '\u{05b6}' => Some(Vowel::Segol),
+        '\u{05b7}' => Some(Vowel::Patah),
+        '\u{05b8}' => Some(Vowel::Qamats),
+        '\u{05b9}' | '\u{05ba}' => Some(Vowel::Holam),
+        '\u{05bb}' => Some(Vowel::Qubuts),
+        '\u{05c7}' => Some(Vowel::QamatsQatan),
+        '\u{05b0}' if cluster.has(VOCAL_SHVA_MARK) => Some(Vowel::VocalShva),
+        _ => None,
+    })
+}
+
+fn consonant_of(cluster: &Cluster, scheme: TranslitScheme) -> &'static str {
+    match scheme {
+        TranslitScheme::Sbl => sbl_consonant(cluster),
+        TranslitScheme::Simplified => simplified_consonant(cluster),
+    }
+}
+
+fn sbl_consonant(cluster: &Cluster) -> &'static str {
+    let dagesh = cluster.has(DAGESH);
+    match cluster.letter {
+        'א' => "ʾ",
+        'ב' if dagesh => "b",
+        'ב' => "ḇ",
+        'ג' if cluster.geresh => "ǧ",
+        'ג' if dagesh => "g",
+        'ג' => "ḡ",
+        'ד' if dagesh => "d",
+        'ד' => "ḏ",
+        'ה' => "h",
+        'ו' => "w",
+        'ז' if cluster.geresh => "ž",
+        'ז' => "z",
+        'ח' => "ḥ",
+        'ט' => "ṭ",
+        'י' => "y",
+        'כ' | 'ך' if dagesh => "k",
+        'כ' | 'ך' => "ḵ",
+        'ל' => "l",
+        'מ' | 'ם' => "m",
+        'נ' | 'ן' => "n",
+        'ס' => "s",
+        'ע' => "ʿ",
+        'פ' | 'ף' if dagesh => "p",
+        'פ' | 'ף' => "p̄",
+        'צ' | 'ץ' if cluster.geresh => "č",
+        'צ' | 'ץ' => "ṣ",
+        'ק' => "q",
+        'ר' => "r",
+        'ש' if cluster.has(SIN_DOT) => "ś",
+        'ש' => "š",
+        'ת' if dagesh => "t",
+        'ת' => "ṯ",
+        _ => "",
+    }
+}
+
+fn simplified_consonant(cluster: &Cluster) -> &'static str {
+    let dagesh = cluster.has(DAGESH);
+    match cluster.letter {
+        'א' | 'ע' => "",
+        'ב' if dagesh => "b",
+        'ב' | 'ו' => "v",
+        'ג' if cluster.geresh => "j",
+        'ג' => "g",
+        'ד' => "d",
+        'ה' => "h",
+        'ז' if cluster.geresh => "zh",
+        'ז' => "z",
+        'ח' => "kh",
+        'ט' | 'ת' => "t",
+        'י' => "y",
+        'כ' | 'ך' if dagesh => "k",
+        'כ' | 'ך' => "kh",
+        'ל' => "l",
+        'מ' | 'ם' => "m",
+        'נ' | 'ן' => "n",
+        'ס' => "s",
+        'פ' | 'ף' if dagesh => "p",
+        'פ' | 'ף' => "f",
+        'צ' | 'ץ' if cluster.geresh => "ch",
+        'צ' | 'ץ' => "ts",
+        'ק' => "k",
+        'ר' => "r",
+        'ש' if cluster.has(SIN_DOT) => "s",
+        'ש' => "sh",
+        _ => "",
+    }
+}
+
+/// Transliterate vocalized Hebrew text into Latin letters with the scheme of `options`.
+///
+/// The text is read like by [`crate::phonemize::phonemize`], with the ole and meteg as
+/// the stress and vocal shva marks. Characters that are not Hebrew letters or marks are
+/// kept as is, the prefix marker `|` is dropped. The output is in NFC.
+pub fn transliterate(vocalized: &str, options: &TranslitOptions) -> String {
+    let mut result = String::with_capacity(vocalized.len());
+    for piece in pieces(vocalized) {
+        match piece {
+            Piece::Word(word) => transliterate_word(&word, options, &mut result),
+            Piece::Other(ch) => result.push(ch),
+        }
+    }
+    result.nfc().collect()
+}
+
+fn transliterate_word(word: &[Cluster], options: &TranslitOptions, result: &mut String) {
+    let scheme = options.scheme;
+    let sbl = scheme == TranslitScheme::Sbl;
+    // Whether the previous letter was read with a vowel
+    let mut prev_vowel = false;
+    // A stress mark on a letter without a vowel goes on the next one, e.g. before a
+    // holam male
+    let mut stress = false;
+    let mut idx = 0;
+
+    while idx < word.len() {
+        let cluster = &word[idx];
+        let is_last = idx + 1 == word.len();
+        stress |= cluster.has(STRESS_MARK);
+        let mut vowel = vowel_of(cluster);
+        let mut consonant = consonant_of(cluster, scheme);
+
+        if cluster.letter == 'ו' && vowel.is_none_or(|vowel| vowel == Vowel::Holam) {
+            let holam = cluster.has(HOLAM);
+            if holam || cluster.has(DAGESH) {
+                // The vav is the o or u of the letter before, and only a consonant itself
+                // when that letter has a vowel of its own
+                vowel = Some(if holam {
+                    Vowel::HolamMale
+                } else {
+                    Vowel::Shuruk
+                });
+                if !prev_vowel {
+                    consonant = "";
+                }
+            } else if idx > 0 {
+                // Silent mater lectionis
+                consonant = "";
+            }
+        } else if !sbl && cluster.letter == 'ה' && is_last && is_bare(cluster) {
+            // A final he is silent unless it carries a mappiq
+            consonant = "";
+        }
+
+        // The unmarked vowel letter after the vowel is part of it
+        let next = word.get(idx + 1);
+        let vowel_letter = vowel
+            .zip(next.filter(|next| is_bare(next)))
+            .and_then(|(vowel, next)| vowel.with_vowel_letter(next.letter, idx + 2 == word.len()));
+        if let Some(long) = vowel_letter {
+            vowel = Some(long);
+            stress |= next.is_some_and(|next| next.has(STRESS_MARK));
+        }
+
+        // A dagesh after a vowel doubles the consonant, apart from the mappiq and shuruk
+        let forte =
+            sbl && prev_vowel && cluster.has(DAGESH) && !matches!(cluster.letter, 'ה' | 'ו');
+        if forte {
+            result.push_str(consonant);
+        }
+
+        // A patah under a final guttural is read before it, e.g. ruaḥ
+        let genuva =
+            is_last && vowel == Some(Vowel::Patah) && matches!(cluster.letter, 'ח' | 'ע' | 'ה');
+        if !genuva {
+            result.push_str(consonant);
+        }
+        if let Some(vowel) = vowel {
+            match (vowel, &options.vocal_shva) {
+                (Vowel::VocalShva, Some(vocal_shva)) => result.push_str(vocal_shva),
+                _ => result.push_str(vowel.latin(scheme)),
+            }
+            if std::mem::take(&mut stress) && options.stress {
+                result.push(ACUTE);
+            }
+        }
+        if genuva {
+            result.push_str(consonant);
+        }
+
+        prev_vowel = vowel.is_some_and(|vowel| vowel != Vowel::VocalShva);
+        idx += if vowel_letter.is_some() { 2 } else { 1 };
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simplified() -> TranslitOptions {
+        TranslitOptions {
+            scheme: TranslitScheme::Simplified,
+            stress: true,
+            vocal_shva: None,
+        }
+    }
+
+    #[test]
+    fn sbl() {
+        let options = TranslitOptions::default();
+        assert_eq!(transliterate("שָׁל\u{05ab}וֹם", &options), "šālôm");
+        assert_eq!(transliterate("רוּחַ", &options), "rûaḥ");
+        assert_eq!(transliterate("בְּ\u{05bd}רֵאשִׁית", &options), "bərēʾšîṯ");
+        assert_eq!(transliterate("הַמֶּלֶךְ", &options), "hammeleḵ");
+    }
+
+    #[test]
+    fn simplified_with_stress() {
+        let options = simplified();
+        assert_eq!(transliterate("שָׁל\u{05ab}וֹם", &options), "shalóm");
+        assert_eq!(transliterate("ג'\u{05ab}וּק", &options), "júk");
+        assert_eq!(transliterate("רוּחַ", &options), "ruakh");
+        assert_eq!(transliterate("הַ|בַּ\u{05ab}יִת!", &options), "habáyit!");
+    }
+}
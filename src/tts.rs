@@ -0,0 +1,124 @@
+//! Glue for feeding the phonemes to TTS engines such as Piper
+
+use std::ops::Range;
+
+use crate::{
+    Phonikud, Result,
+    phonemize::{PhonemeAlphabet, phonemize},
+    prosody::{self, ProsodyEvent},
+    segment,
+};
+
+/// Punctuation kept in the phonemes, the Piper voices read it as a pause
+const PAUSES: &[char] = &['.', ',', '!', '?', ';', ':'];
+const STRESS_IPA: char = 'ˈ';
+
+/// How the phonemes handed to a TTS engine are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TtsOptions {
+    /// IPA for the Piper voices, which phonemize their training data with espeak-ng
+    pub alphabet: PhonemeAlphabet,
+    /// Keep the stress marks, voices trained without them read them as noise
+    pub stress: bool,
+    /// Fill [`PhonemeSentence::prosody`] with the pauses and intonation of the sentence
+    pub prosody: bool,
+}
+
+impl Default for TtsOptions {
+    fn default() -> Self {
+        Self {
+            alphabet: PhonemeAlphabet::Ipa,
+            stress: true,
+            prosody: false,
+        }
+    }
+}
+
+/// A sentence of the input and its phonemes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhonemeSentence {
+    /// Byte range of the sentence in the input text
+    pub range: Range<usize>,
+    pub phonemes: String,
+    /// Prosody hints with byte ranges into `phonemes`, see [`prosody::events`].
+    /// Empty unless [`TtsOptions::prosody`] is set.
+    pub prosody: Vec<ProsodyEvent>,
+}
+
+/// A frontend turning text into phonemes sentence by sentence, so synthesis loops
+/// can swap phonikud for another one
+pub trait PhonemeProvider {
+    /// Phonemes of every sentence of `text` that has any, see [`tts_phonemes`]
+    fn phonemize_sentences(
+        &mut self,
+        text: &str,
+        options: &TtsOptions,
+    ) -> Result<Vec<PhonemeSentence>>;
+}
+
+impl PhonemeProvider for Phonikud {
+    fn phonemize_sentences(
+        &mut self,
+        text: &str,
+        options: &TtsOptions,
+    ) -> Result<Vec<PhonemeSentence>> {
+        let mut sentences = Vec::new();
+        for range in segment::sentence_ranges(text) {
+            let vocalized = self.add_diacritics(&text[range.clone()])?;
+            let phonemes = tts_phonemes(&phonemize(&vocalized), options);
+            if phonemes.is_empty() {
+                continue;
+            }
+            let prosody = if options.prosody {
+                prosody::events(&phonemes)
+            } else {
+                Vec::new()
+            };
+            sentences.push(PhonemeSentence {
+                range,
+                phonemes,
+                prosody,
+            });
+        }
+        Ok(sentences)
+    }
+}
+
+/// Prepare the IPA from [`phonemize`] for a TTS engine.
+///
+/// Punctuation becomes one of `.,!?;:`, which the voices pause at: a sof pasuq or an
+/// ellipsis ends a sentence, dashes and the paseq are a comma and the maqaf joins words
+/// like a space. Quotes, brackets and other symbols are dropped and whitespace is
+/// collapsed. The phonemes are then written in `options.alphabet`.
+pub fn tts_phonemes(ipa: &str, options: &TtsOptions) -> String {
+    let mut result = String::with_capacity(ipa.len());
+    let mut space = false;
+    for ch in ipa.chars() {
+        let ch = match ch {
+            '\u{05c3}' | '…' => '.',
+            '—' | '–' | '\u{05c0}' => ',',
+            '\u{05be}' | '-' => ' ',
+            STRESS_IPA if !options.stress => continue,
+            // The IPA symbols, the stress mark included, are letters
+            ch if ch.is_alphanumeric() || ch.is_whitespace() || PAUSES.contains(&ch) => ch,
+            _ => continue,
+        };
+        if ch.is_whitespace() {
+            space = !result.is_empty();
+            continue;
+        }
+        // No space before a pause
+        if space && !PAUSES.contains(&ch) {
+            result.push(' ');
+        }
+        space = false;
+        match options.alphabet.symbol(ch) {
+            Some(symbol) => result.push_str(symbol),
+            None => result.push(ch),
+        }
+    }
+    result
+}
@@ -0,0 +1,56 @@
+//! JavaScript bindings, the model itself is only available with the `tract` backend
+
+use wasm_bindgen::prelude::*;
+
+use crate::normalize::NormalizeOptions;
+
+#[wasm_bindgen(js_name = removeNikud)]
+pub fn remove_nikud(text: &str) -> String {
+    crate::text::remove_nikud(text)
+}
+
+#[wasm_bindgen(js_name = hasNikud)]
+pub fn has_nikud(text: &str) -> bool {
+    crate::text::has_nikud(text)
+}
+
+#[wasm_bindgen(js_name = stripPhonikudMarkers)]
+pub fn strip_phonikud_markers(text: &str) -> String {
+    crate::text::strip_phonikud_markers(text)
+}
+
+/// Convert vocalized Hebrew into IPA phonemes
+#[wasm_bindgen]
+pub fn phonemize(vocalized: &str) -> String {
+    crate::phonemize::phonemize(vocalized)
+}
+
+/// Spell out numbers, times and dates in Hebrew words
+#[wasm_bindgen(js_name = normalizeNumbers)]
+pub fn normalize_numbers(text: &str) -> String {
+    crate::normalize::normalize(text, &NormalizeOptions::default()).text
+}
+
+/// Diacritization model running in the browser on the tract backend
+#[cfg(feature = "tract")]
+#[wasm_bindgen(js_name = Phonikud)]
+pub struct WasmPhonikud(crate::Phonikud);
+
+#[cfg(feature = "tract")]
+#[wasm_bindgen(js_class = Phonikud)]
+impl WasmPhonikud {
+    /// Load the model and tokenizer from the bytes of `phonikud.onnx` and `tokenizer.json`
+    #[wasm_bindgen(constructor)]
+    pub fn new(model: &[u8], tokenizer_json: &[u8]) -> Result<WasmPhonikud, JsError> {
+        Ok(Self(crate::Phonikud::from_bytes_tract(model, tokenizer_json)?))
+    }
+
+    #[wasm_bindgen(js_name = addDiacritics)]
+    pub fn add_diacritics(&mut self, text: &str) -> Result<String, JsError> {
+        Ok(self.0.add_diacritics(text)?)
+    }
+
+    pub fn phonemize(&mut self, text: &str) -> Result<String, JsError> {
+        Ok(self.0.phonemize(text)?)
+    }
+}